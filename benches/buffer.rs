@@ -0,0 +1,95 @@
+//! Times the buffer operations every key press goes through, and drawing a frame, on a large
+//! buffer. Run with `cargo bench --bench buffer`, a name filters the benchmarks:
+//! `cargo bench --bench buffer -- insert`
+
+use std::io;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use tte_core::{display::Display, status_line::StatusLine, Buffer, FileType, Position};
+
+/// Lines of the benchmarked buffer
+const LINES: usize = 20_000;
+
+/// An edit benchmarked by its name
+type Edit = (&'static str, fn(&mut Buffer));
+
+/// Size of the terminal drawn to
+const WIDTH: u16 = 120;
+const HEIGHT: u16 = 40;
+
+fn text() -> String {
+    (0..LINES)
+        .map(|i| format!("    let value_{i} = compute({i}, \"some text\");\n"))
+        .collect()
+}
+
+fn buffer(text: &str) -> Buffer {
+    let mut buffer = Buffer::scratch("bench", text, 0, 0, WIDTH as usize, HEIGHT as usize - 1);
+    buffer.file_type = FileType::Rust;
+    buffer
+}
+
+/// Each edit runs on a buffer of its own, made before it is timed
+fn edits(c: &mut Criterion) {
+    let text = text();
+    let edits: [Edit; 8] = [
+        ("recalculate_lines", |b| b.recalculate_lines()),
+        // What each frame without an edit costs, e.g. while idle
+        ("update_lines", |b| b.update_lines()),
+        ("insert_start", |b| insert_at(b, 0)),
+        ("insert_middle", |b| insert_at(b, LINES / 2)),
+        ("insert_end", |b| insert_at(b, LINES)),
+        ("delete_start", |b| delete_at(b, 0)),
+        ("delete_middle", |b| delete_at(b, LINES / 2)),
+        ("delete_end", |b| delete_at(b, LINES - 1)),
+    ];
+
+    for (name, edit) in edits {
+        c.bench_function(name, |b| {
+            b.iter_batched(
+                || buffer(&text),
+                |mut buffer| {
+                    edit(&mut buffer);
+                    buffer
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+/// Types a character on `line`, as a key press does
+fn insert_at(buffer: &mut Buffer, line: usize) {
+    buffer.move_cursor_to_position(Position::new(line, 4));
+    buffer.insert_ch('x');
+    buffer.commit_history();
+    buffer.recalculate_lines();
+}
+
+/// Deletes the character after the cursor on `line`
+fn delete_at(buffer: &mut Buffer, line: usize) {
+    buffer.move_cursor_to_position(Position::new(line, 4));
+    buffer.delete();
+    buffer.commit_history();
+    buffer.recalculate_lines();
+}
+
+/// Draws the buffer scrolled to its middle and the status line, as the editor draws a frame
+fn render(c: &mut Criterion) {
+    let mut buffer = buffer(&text());
+    buffer.move_cursor_to_position(Position::new(LINES / 2, 4));
+    let status_line = StatusLine::new(0, HEIGHT - 1, WIDTH as usize, 1, "bench");
+    let mut display = Display::headless(io::sink(), WIDTH, HEIGHT);
+
+    c.bench_function("render_frame", |b| {
+        b.iter(|| {
+            display.begin_draw().unwrap();
+            display.draw_status_line(&status_line).unwrap();
+            display.draw_buffer(&buffer).unwrap();
+            display.end_draw().unwrap();
+        })
+    });
+}
+
+criterion_group!(benches, edits, render);
+criterion_main!(benches);
@@ -0,0 +1,64 @@
+//! Applies the edits, moves and resizes read from the input to a buffer, checking that it stays
+//! consistent after each one like `random_edits_keep_the_buffer_consistent` does.
+//! Run with `cargo +nightly fuzz run buffer_edits` from the repository.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tte_core::{Buffer, Position};
+
+/// Characters typed by the edits: line breaks, tabs, wide characters and joiners
+const EDIT_CHARS: [char; 8] = ['a', ' ', '\n', '\t', 'é', '日', '\u{301}', '\u{200D}'];
+
+fuzz_target!(|data: &[u8]| {
+    let mut buffer = Buffer::scratch("fuzz", "fn main() {\n\tlet x = 1;\n}\n", 0, 0, 20, 5);
+
+    // Each edit is read from two bytes, what to do and with what
+    for edit in data.chunks_exact(2) {
+        apply_edit(&mut buffer, edit[0], edit[1]);
+        buffer.recalculate_lines();
+        buffer.scroll();
+        if let Err(e) = buffer.check_invariants() {
+            panic!("{e}");
+        }
+    }
+});
+
+/// Applies the edit or move numbered `op`, with `arg` picking its details
+fn apply_edit(buffer: &mut Buffer, op: u8, arg: u8) {
+    let ch = EDIT_CHARS[arg as usize % EDIT_CHARS.len()];
+    let count = arg as usize % 4;
+    match op % 20 {
+        0 => buffer.insert_ch(ch),
+        1 => buffer.insert_str(&format!("{ch}x\n{ch}")),
+        2 => buffer.backspace(),
+        3 => buffer.delete(),
+        4 => buffer.move_cursor_right(count),
+        5 => buffer.move_cursor_left(count),
+        6 => buffer.move_cursor_up(count),
+        7 => buffer.move_cursor_down(count),
+        8 if arg < 128 => buffer.move_cursor_word_left(count),
+        8 => buffer.move_cursor_word_right(count),
+        9 if arg < 128 => buffer.select(),
+        9 => buffer.clear_selection(),
+        10 => {
+            buffer.delete_selection();
+        }
+        11 => {
+            buffer.commit_history();
+            buffer.undo();
+        }
+        12 => {
+            buffer.redo();
+        }
+        13 => buffer.resize(arg as usize % 30, arg as usize / 30),
+        14 => buffer.overwrite_ch(ch),
+        15 => buffer.virtual_space = !buffer.virtual_space,
+        16 => {
+            buffer.toggle_fold();
+        }
+        17 => buffer.toggle_bookmark(),
+        18 => buffer.move_cursor_to_position(Position::new(arg as usize % 8, arg as usize / 8)),
+        _ => buffer.scroll_view(arg as i8 as isize % 5),
+    }
+}
@@ -0,0 +1,44 @@
+use crate::config::Config;
+
+/// How the editor helps people using a screen reader, set in the `[accessibility]` section of
+/// the config
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Accessibility {
+    /// Draw without decorative characters and keep the terminal cursor still while drawing,
+    /// with the messages on a line of their own below the status line
+    pub screen_reader: bool,
+    /// Ring the terminal bell on errors
+    pub bell: bool,
+    /// Send errors as desktop notifications, with the OSC 9 escape sequence
+    pub notify: bool,
+}
+
+impl Accessibility {
+    pub fn from_config(config: &Config) -> Self {
+        let enabled = |key| config.get_bool("accessibility", key) == Some(true);
+        Self {
+            screen_reader: enabled("screen_reader"),
+            bell: enabled("bell"),
+            notify: enabled("notify"),
+        }
+    }
+
+    /// Rows taken by the message line on a terminal `height` rows high, it is only shown to
+    /// screen readers and when the buffer keeps a row besides the status line
+    pub fn message_line_height(&self, height: u16) -> u16 {
+        if self.screen_reader && height > 2 {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+/// Makes `message` fit in a notification: on a single line and without control characters,
+/// which would end the escape sequence early
+pub fn notification_text(message: &str) -> String {
+    message
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
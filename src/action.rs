@@ -0,0 +1,161 @@
+use crate::{command::Command, script::Direction};
+
+/// Something the editor can do, what key presses are turned into before being applied
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Remembers the marks of the open buffers and leaves the editor, asking first whether to
+    /// save the buffers with unsaved changes
+    Quit,
+    /// Same as `Quit` without asking, the unsaved changes are lost
+    ForceQuit,
+    /// Writes the active buffer to its file
+    Save,
+    /// Moves the cursor, dropping the selection
+    MoveCursor(Direction, usize),
+    /// Moves the cursor, selecting the text it goes over
+    ExtendSelection(Direction, usize),
+    /// Moves the cursor over words, selecting the text it goes over if `select` is set
+    MoveWord {
+        forward: bool,
+        count: usize,
+        select: bool,
+    },
+    /// Moves the cursor to the start of its line, selecting the text it goes over if `select` is set
+    LineStart {
+        select: bool,
+    },
+    /// Moves the cursor to the end of its line, selecting the text it goes over if `select` is set
+    LineEnd {
+        select: bool,
+    },
+    /// Moves the cursor to the start of the buffer, selecting the text it goes over if `select` is set
+    BufferStart {
+        select: bool,
+    },
+    /// Moves the cursor to the end of the buffer, selecting the text it goes over if `select` is set
+    BufferEnd {
+        select: bool,
+    },
+    /// Moves the cursor past blocks of text, dropping the selection
+    MoveBlock {
+        block: Block,
+        forward: bool,
+        count: usize,
+    },
+    /// Scrolls the view by rows, down if positive, keeping the cursor in it
+    ScrollView(isize),
+    ClearSelection,
+    /// Sets how many times the next movement is repeated
+    Repeat(usize),
+    InsertChar(char),
+    /// Inserts text as a single edit, as pasted or composed by an input method
+    InsertText(String),
+    /// Inserts a tab, or spaces up to the next tab stop, as the settings of the buffer say
+    Indent,
+    /// Removes a level of indentation from the selected lines, or from the cursor line
+    Dedent,
+    Backspace,
+    Delete,
+    Undo,
+    Redo,
+    /// Copies the selection to the clipboard
+    Copy,
+    /// Applies the last edit again at the cursor, that many times
+    RepeatEdit(usize),
+    ToggleBookmark,
+    NextBookmark,
+    PreviousBookmark,
+    /// Waits for the letter naming the mark to set at the cursor
+    SetMark,
+    /// Waits for the letter naming the mark to jump to
+    JumpToMark,
+    ToggleFold,
+    FoldAtCursor,
+    UnfoldAtCursor,
+    /// Makes the other pane of the split the active one
+    SwitchPane,
+    /// Opens the command prompt with some input already typed
+    CommandPrompt(String),
+    /// Clears the terminal and draws everything again
+    Redraw,
+    /// Switches between inserting typed characters and replacing the ones under the cursor
+    ToggleOverwrite,
+    /// Shows or hides the overlay with the cursor, line and view of the buffer, for debugging
+    ToggleDebug,
+    /// Opens the prompt asking for the path of a file to open
+    OpenPrompt,
+    /// Opens the prompt asking for the codepoint of a character to insert
+    CodepointPrompt,
+    /// Runs a command, as if it was entered in the prompt
+    Run(Command),
+    /// Runs the command or script named by the text
+    RunText(String),
+    /// Opens the completion popup for the word before the cursor
+    Complete,
+    /// Replaces the misspelled word under the cursor with the next suggestion
+    NextSuggestion,
+    /// Adds the word under the cursor to the personal dictionary
+    AddToDictionary,
+    GotoDefinition,
+    Hover,
+    /// Opens the file the link under the cursor points to
+    FollowLink,
+    /// Jumps to the location mentioned on the line of the build output under the cursor
+    JumpToLocation,
+    /// Jumps to the entry on the line under the cursor in the quickfix list
+    JumpToEntry,
+    /// Jumps to the next entry of the quickfix list, or the previous one if not `forward`
+    StepEntry {
+        forward: bool,
+    },
+    /// Brings back the state under the cursor in the undo tree buffer
+    JumpInUndoTree,
+    NextBuffer,
+    PreviousBuffer,
+    /// Closes the active buffer, asking first if it has unsaved changes
+    CloseBuffer,
+    /// Switches to the buffer on the line under the cursor in the buffer list
+    JumpToListedBuffer,
+    /// Closes the buffer on the line under the cursor in the buffer list
+    CloseListedBuffer,
+    /// Shows how the version on the line under the cursor in the file history differs from
+    /// the buffer
+    CompareSnapshot,
+    /// Brings the buffer back to the version on the line under the cursor in the file history
+    RestoreSnapshot,
+}
+
+impl Action {
+    /// Whether the action changes the text of the buffer, and so can be repeated with `RepeatEdit`
+    pub fn is_edit(&self) -> bool {
+        matches!(
+            self,
+            Self::InsertChar(_)
+                | Self::InsertText(_)
+                | Self::Indent
+                | Self::Dedent
+                | Self::Backspace
+                | Self::Delete
+                | Self::Run(
+                    Command::Case(_)
+                        | Command::Sort { .. }
+                        | Command::Unique
+                        | Command::Reindent
+                        | Command::Reflow
+                        | Command::Json { .. }
+                        | Command::Insert(_)
+                )
+        )
+    }
+}
+
+/// The kinds of blocks of text the cursor can move past
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Block {
+    /// Lines of text between blank lines
+    Paragraph,
+    /// Lines indented at least as much as the line of the cursor
+    Indentation,
+    /// Text between matching `{` and `}`
+    Braces,
+}
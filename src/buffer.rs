@@ -1,8 +1,46 @@
 #![allow(dead_code)]
-use std::{ffi::OsStr, fs, path::{Path, PathBuf}};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
 
 use crossterm::style::Color;
 
+use crate::{
+    completion::{is_word_char_with, DEFAULT_WORD_SEPARATORS},
+    filetype::FileType,
+    grapheme,
+    highlight::{Highlight, HighlightKind},
+    lsp::{Diagnostic, Severity},
+    position::Position,
+    undo::{Edit, UndoTree},
+    width,
+};
+
+/// Number of columns between tab stops, unless the settings say otherwise
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// Drawn at the start of the rows a wrapped line goes on over, unless the settings say otherwise
+pub const DEFAULT_WRAP_MARKER: &str = "↪ ";
+
+/// Lines of more characters than this are long lines. The columns along them are noted every
+/// `COLUMN_CHUNK` characters, so that drawing or moving far into them starts from the nearest
+/// note rather than from the start of the line.
+pub const LONG_LINE: usize = 4096;
+const COLUMN_CHUNK: usize = 1024;
+
+/// The UTF-8 byte order mark some editors write at the start of files, and its encoding
+const BOM: char = '\u{FEFF}';
+const BOM_BYTES: &[u8] = b"\xEF\xBB\xBF";
+
+/// Id given to the next buffer created
+static NEXT_BUFFER_ID: AtomicUsize = AtomicUsize::new(0);
+
 /*
     Color theme default
     :root {
@@ -25,11 +63,25 @@ pub struct Line {
 
 impl Line {
     pub fn len(&self) -> usize {
-        self.end - self.start + 1
+        (self.end + 1).saturating_sub(self.start)
+    }
+
+    /// Returns true for the line after a final `\n`, which holds no characters
+    pub fn is_empty(&self) -> bool {
+        self.end < self.start
     }
 }
 
+/// Where the chunks of the long lines of a buffer start, as `(index, column)` sorted by line.
+/// Only good for the tab width they were worked out with.
+struct ColumnChunks {
+    tab_width: usize,
+    lines: HashMap<usize, Vec<(usize, usize)>>,
+}
+
 pub struct Buffer {
+    /// Unique id of the buffer
+    pub id: usize,
     /// The actual data in the buffer
     pub data: Vec<char>,
     /// Indexes into the lines in the buffer
@@ -48,9 +100,78 @@ pub struct Buffer {
     pub offset_x: usize,
     /// Cursor position in the data
     pub cursor_pos: usize,
-    /// Remember the previous x offset of the cursor in line
-    previous_offset: Option<usize>, // TODO: Remember to reset this when an update is made in the buffer
+    /// The other end of the selection, the selection goes from here to `cursor_pos`
+    pub selection_anchor: Option<usize>,
+    /// Column up and down keep to through shorter lines, forgotten once the cursor moves otherwise
+    goal_column: Option<usize>,
     pub file_path: Option<PathBuf>,
+    /// Name shown for buffers that are not backed by a file
+    pub name: Option<String>,
+    /// Id of the buffer a scratch buffer was made from, e.g. for the undo tree browser
+    pub source: Option<usize>,
+    /// Kind of content, detected from `file_path`
+    pub file_type: FileType,
+    /// Incremented on every change to `data`
+    pub version: usize,
+    /// Every change made to `data`, for undo and redo
+    pub history: UndoTree,
+    /// Whether there are unsaved changes
+    pub modified: bool,
+    /// Whether the file does not exist yet, it is created by the first save
+    pub new_file: bool,
+    /// Problems reported by the language server
+    pub diagnostics: Vec<Diagnostic>,
+    /// `[start, end)` ranges of the words unknown to the spell checker
+    pub misspelled: Vec<(usize, usize)>,
+    /// `[start, end)` ranges of the other occurrences of the word under the cursor in view
+    pub occurrences: Vec<(usize, usize)>,
+    /// Syntax highlighting of `data`, sorted by start
+    pub highlights: Vec<Highlight>,
+    /// Width of the column on the left used for marks, the text starts after it
+    pub gutter_width: usize,
+    /// Number of columns between tab stops, a tab takes the columns up to the next one
+    pub tab_width: usize,
+    /// Whether the cursor can move past the end of lines, typing there pads the line with spaces
+    pub virtual_space: bool,
+    /// Whether up and down keep to the column on screen, where tabs and wide characters take
+    /// their width, rather than to the number of characters from the start of the line
+    pub screen_columns: bool,
+    /// Whether Home and End stop at the first and last non-blank characters of the line before
+    /// going to its start and end
+    pub smart_home: bool,
+    /// Whether lines wider than the view go on over the next rows rather than past its right edge
+    pub wrap: bool,
+    /// Drawn at the start of the rows a wrapped line goes on over, e.g. `↪ `
+    pub wrap_marker: String,
+    /// Whether faint vertical lines are drawn at each tab stop of the indentation
+    pub indent_guides: bool,
+    /// Characters splitting words besides whitespace, for word motions and the word under the
+    /// cursor
+    pub word_separators: String,
+    /// Whether the file started with a UTF-8 byte order mark, it is left out of `data`
+    pub bom: bool,
+    /// Whether the byte order mark is written back on save, otherwise saving removes it
+    pub keep_bom: bool,
+    /// When the file was last modified as far as the buffer knows, when it was read or saved
+    disk_modified: Option<SystemTime>,
+    /// Columns the cursor is past the end of its line, as `(cursor_pos, columns)`.
+    /// Dropped once the cursor moves elsewhere.
+    virtual_cursor: Option<(usize, usize)>,
+    /// Chunks of the long lines worked out so far, dropped whenever the lines change
+    column_chunks: RefCell<Option<ColumnChunks>>,
+    /// `version` the lines were last worked out for
+    lines_version: Option<usize>,
+    /// Settings set for this buffer only with the `set` command, as `(name, value)`
+    pub settings: Vec<(String, String)>,
+    /// Indexes into `data` of the bookmarked lines, kept in place through edits
+    pub bookmarks: Vec<usize>,
+    /// Marks set by the user under a letter, as indexes into `data`, kept in place through edits
+    pub named_marks: Vec<(char, usize)>,
+    /// Folded blocks, as indexes into `data` of the header line and of the last hidden line.
+    /// Kept in place through edits like the marks.
+    pub folds: Vec<(usize, usize)>,
+    /// Lines `[start, end)` that differ from the other side, when comparing two buffers
+    pub changed_lines: Vec<(usize, usize)>,
     /// Background color
     pub bg_color: Color,
     /// Foreground color
@@ -62,6 +183,7 @@ impl Buffer {
     /// Returns a new empty `Buffer`
     pub fn new(x: u16, y: u16, width: usize, height: usize) -> Self {
         let mut buffer = Self {
+            id: NEXT_BUFFER_ID.fetch_add(1, Ordering::Relaxed),
             data: vec![],
             lines: vec![],
             x,
@@ -71,8 +193,40 @@ impl Buffer {
             offset_y: 0,
             offset_x: 0,
             cursor_pos: 0,
+            selection_anchor: None,
             file_path: None,
-            previous_offset: None,
+            name: None,
+            source: None,
+            file_type: FileType::Unknown,
+            version: 0,
+            history: UndoTree::new(),
+            modified: false,
+            new_file: false,
+            diagnostics: vec![],
+            misspelled: vec![],
+            occurrences: vec![],
+            highlights: vec![],
+            gutter_width: 0,
+            tab_width: DEFAULT_TAB_WIDTH,
+            virtual_space: false,
+            screen_columns: true,
+            smart_home: true,
+            wrap: false,
+            wrap_marker: DEFAULT_WRAP_MARKER.to_string(),
+            indent_guides: false,
+            word_separators: DEFAULT_WORD_SEPARATORS.to_string(),
+            bom: false,
+            keep_bom: true,
+            disk_modified: None,
+            virtual_cursor: None,
+            column_chunks: RefCell::new(None),
+            lines_version: None,
+            settings: vec![],
+            bookmarks: vec![],
+            named_marks: vec![],
+            folds: vec![],
+            changed_lines: vec![],
+            goal_column: None,
             bg_color: Color::Rgb {
                 r: 30,
                 g: 30,
@@ -97,29 +251,26 @@ impl Buffer {
     /// For now we replace CRLF to LF
     pub fn from_file(filename: &str, x: u16, y: u16, width: usize, height: usize) -> Self {
         let path = Path::new(filename);
-        let (data, file_path) = if path.is_file() {
+        let ((data, bom), file_path) = if path.is_file() {
             // If the path is a valid file, read its content
-            match fs::read(&path) {
-                Ok(bytes) => (
-                    bytes
-                        .into_iter()
-                        .map(|b| b as char)
-                        .filter(|c| *c != '\r') // Convert CRLF to LF
-                        .collect(),
-                    Some(path.to_path_buf()),
-                ),
-                Err(_) => (vec![], Some(path.to_path_buf())),
+            match read_data(path) {
+                Ok(read) => (read, Some(path.to_path_buf())),
+                Err(_) => ((vec![], false), Some(path.to_path_buf())),
             }
         } else if path.is_dir() {
             // If no filename or it's a directory, set empty data and None for file_path
-            (vec![], None)
+            ((vec![], false), None)
         } else {
             // If the path is invalid for some reason (file, but not readable)
-            (vec![], Some(path.to_path_buf()))
+            ((vec![], false), Some(path.to_path_buf()))
         };
 
+        let file_type = FileType::from_path(path);
+        let new_file = !path.exists();
+
         // Initialize the buffer
         let mut buffer = Self {
+            id: NEXT_BUFFER_ID.fetch_add(1, Ordering::Relaxed),
             data,
             lines: vec![],
             x,
@@ -129,8 +280,40 @@ impl Buffer {
             offset_y: 0,
             offset_x: 0,
             cursor_pos: 0,
+            selection_anchor: None,
             file_path,
-            previous_offset: None,
+            name: None,
+            source: None,
+            file_type,
+            version: 0,
+            history: UndoTree::new(),
+            modified: false,
+            new_file,
+            diagnostics: vec![],
+            misspelled: vec![],
+            occurrences: vec![],
+            highlights: vec![],
+            gutter_width: 0,
+            tab_width: DEFAULT_TAB_WIDTH,
+            virtual_space: false,
+            screen_columns: true,
+            smart_home: true,
+            wrap: false,
+            wrap_marker: DEFAULT_WRAP_MARKER.to_string(),
+            indent_guides: false,
+            word_separators: DEFAULT_WORD_SEPARATORS.to_string(),
+            bom,
+            keep_bom: true,
+            disk_modified: modified_time(path),
+            virtual_cursor: None,
+            column_chunks: RefCell::new(None),
+            lines_version: None,
+            settings: vec![],
+            bookmarks: vec![],
+            named_marks: vec![],
+            folds: vec![],
+            changed_lines: vec![],
+            goal_column: None,
             bg_color: Color::Rgb {
                 r: 30,
                 g: 30,
@@ -147,15 +330,26 @@ impl Buffer {
         buffer
     }
 
+    /// Returns a new `Buffer` holding `text`, not backed by any file
+    pub fn scratch(name: &str, text: &str, x: u16, y: u16, width: usize, height: usize) -> Self {
+        let mut buffer = Self::new(x, y, width, height);
+        buffer.name = Some(name.to_string());
+        buffer.data = text.chars().filter(|c| *c != '\r').collect();
+        buffer.recalculate_lines();
+
+        buffer
+    }
+
     pub fn file_name(&self) -> String {
-        match &self.file_path {
-            Some(path) => path
+        match (&self.file_path, &self.name) {
+            (Some(path), _) => path
                 .file_name()
                 .unwrap_or(OsStr::new("NO NAME"))
                 .to_str()
                 .unwrap_or("NO NAME")
                 .to_string(),
-            None => "NO NAME".to_string(),
+            (None, Some(name)) => name.clone(),
+            (None, None) => "NO NAME".to_string(),
         }
     }
 
@@ -164,14 +358,38 @@ impl Buffer {
         self.y = y;
     }
 
+    /// Resizes the view. Its top line stays where it is unless the cursor would end up out of it.
     pub fn resize(&mut self, w: usize, h: usize) {
         self.width = w;
         self.height = h;
+
+        // Not laid out yet, e.g. the panes of a split before their layout
+        if self.text_width() == 0 || h == 0 {
+            return;
+        }
+
+        // Show more of the cursor line when the view got wider
+        let column = self.display_column(self.cursor_pos);
+        self.offset_x = self
+            .offset_x
+            .min(column.saturating_sub(self.text_width() - 1));
+
+        self.scroll();
+    }
+
+    /// Works out the lines again if `data` changed since they last were, otherwise keeps them
+    /// and the chunks of the long ones
+    pub fn update_lines(&mut self) {
+        if self.lines_version != Some(self.version) {
+            self.recalculate_lines();
+        }
     }
 
     pub fn recalculate_lines(&mut self) {
         let mut previous_begining = 0;
         self.lines.clear();
+        *self.column_chunks.get_mut() = None;
+        self.lines_version = Some(self.version);
 
         for (i, ch) in self.data.iter().enumerate() {
             if *ch == '\n' {
@@ -183,7 +401,7 @@ impl Buffer {
             }
         }
 
-        let end = if self.data.len() < 1 {
+        let end = if self.data.is_empty() {
             0
         } else {
             self.data.len() - 1
@@ -195,119 +413,792 @@ impl Buffer {
         });
     }
 
+    /// Checks what the rest of the buffer relies on: the lines cover `data` from start to end,
+    /// and the cursor, selection and marks point into it. Lines are only checked to be up to date
+    /// with `data`, so call it after `recalculate_lines`.
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let len = self.data.len();
+        let (Some(first), Some(last)) = (self.lines.first(), self.lines.last()) else {
+            return Err("No lines".to_string());
+        };
+        if first.start != 0 {
+            return Err(format!("First line starts at {}", first.start));
+        }
+        for (i, pair) in self.lines.windows(2).enumerate() {
+            if self.data.get(pair[0].end) != Some(&'\n') {
+                return Err(format!(
+                    "Line {i} does not end with a line break at {}",
+                    pair[0].end
+                ));
+            }
+            if pair[1].start != pair[0].end + 1 {
+                let (end, start) = (pair[0].end, pair[1].start);
+                return Err(format!(
+                    "Line {} starts at {start} after a line ending at {end}",
+                    i + 1
+                ));
+            }
+        }
+        if !self.data.is_empty() && last.end + 1 != len {
+            return Err(format!(
+                "Last line ends at {} in {len} characters",
+                last.end
+            ));
+        }
+        if self.data[last.start.min(len)..].contains(&'\n') {
+            return Err("Last line holds a line break".to_string());
+        }
+
+        if self.cursor_pos > len {
+            return Err(format!("Cursor at {} in {len} characters", self.cursor_pos));
+        }
+        if let Some(anchor) = self.selection_anchor.filter(|anchor| *anchor > len) {
+            return Err(format!("Selection anchor at {anchor} in {len} characters"));
+        }
+        let last_row = self.last_row();
+        if self.offset_y > last_row {
+            return Err(format!(
+                "View starts at row {} of {}",
+                self.offset_y,
+                last_row + 1
+            ));
+        }
+
+        let marks = self
+            .bookmarks
+            .iter()
+            .chain(self.named_marks.iter().map(|(_, idx)| idx));
+        let folds = self.folds.iter().flat_map(|(start, end)| [start, end]);
+        if let Some(idx) = marks.chain(folds).find(|idx| **idx > len) {
+            return Err(format!("Mark at {idx} in {len} characters"));
+        }
+
+        Ok(())
+    }
+
     /// Returns the cursor x, y position on Terminal
     /// Position can be negative, which usually means cursor is currently outside the displayable bounds
     #[allow(unused_assignments)]
     pub fn cursor_xy(&self) -> (isize, isize) {
-        let mut x = 0isize;
-        let mut y = 0isize;
+        let line = self.cursor_position().line;
+        let column = self.cursor_column();
+        // A wrapped line has the cursor on the row its column goes on
+        let segment = match self.text_width() {
+            width if self.wrap && width > 0 => {
+                self.row_of_column(column).min(self.line_rows(line) - 1)
+            }
+            _ => 0,
+        };
 
-        for Line { start, end } in self.lines.iter() {
-            if *start <= self.cursor_pos && *end >= self.cursor_pos {
-                x = self.cursor_pos as isize - *start as isize - self.offset_x as isize;
+        // The wrap marker comes before the text of the rows a line wraps to
+        let x = (column + self.row_marker_width(segment) - self.row_start_column(segment)) as isize
+            - self.offset_x as isize;
+        let y = (self.visible_row(line) + segment) as isize - self.offset_y as isize;
 
-                return (
-                    x + self.x as isize,
-                    y - self.offset_y as isize + self.y as isize,
-                );
-            } else {
-                y += 1;
-            }
+        (
+            x + self.x as isize + self.gutter_width as isize,
+            y + self.y as isize,
+        )
+    }
+
+    /// Number of lines of the buffer, as drawn and as the cursor can reach them. Text ending
+    /// with a line break has an empty last line after it, where typing goes on a new line.
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Width available for the text, i.e., without the gutter
+    pub fn text_width(&self) -> usize {
+        self.width.saturating_sub(self.gutter_width)
+    }
+
+    /// Converts a position into an index into `data`.
+    /// Positions past the end of a line or of the buffer are clamped.
+    pub fn position_to_index(&self, position: Position) -> usize {
+        match self.lines.get(position.line) {
+            Some(l) => (l.start + position.column).min(self.line_end(position.line)),
+            None => self.data.len(),
         }
+    }
+
+    /// Returns the column on screen of the character at `idx` from the start of its line,
+    /// where tabs take the columns up to the next tab stop
+    pub fn display_column(&self, idx: usize) -> usize {
+        self.column_in_line(self.index_to_position(idx).line, idx)
+    }
 
-        let last_line = self
+    /// Returns the column on screen of the character at `idx` of `line`
+    fn column_in_line(&self, line: usize, idx: usize) -> usize {
+        let (from, column) = self.chunk_start(line, |i, _| i <= idx);
+
+        (from..idx.min(self.data.len())).fold(column, |column, i| column + self.width_at(i, column))
+    }
+
+    /// Returns the `(index, column)` the furthest along `line` where drawing or counting columns
+    /// can start from, while `before` holds for them. That is the start of a chunk of a long
+    /// line, or the start of any other line.
+    pub fn chunk_start(
+        &self,
+        line: usize,
+        before: impl Fn(usize, usize) -> bool,
+    ) -> (usize, usize) {
+        let Line { start, end } = self.lines[line];
+        if end.saturating_sub(start) < LONG_LINE {
+            return (start, 0);
+        }
+
+        let mut cache = self.column_chunks.borrow_mut();
+        if cache
+            .as_ref()
+            .is_some_and(|chunks| chunks.tab_width != self.tab_width)
+        {
+            *cache = None;
+        }
+        let chunks = cache
+            .get_or_insert_with(|| ColumnChunks {
+                tab_width: self.tab_width,
+                lines: HashMap::new(),
+            })
             .lines
-            .last()
-            .expect("Buffer should always have atleast one line");
+            .entry(line)
+            .or_insert_with(|| self.line_chunks(line));
 
-        (
-            last_line.end as isize - last_line.start as isize + 1 + self.x as isize,
-            y - 1 - self.offset_y as isize + self.y as isize,
-        )
+        match chunks.partition_point(|(idx, column)| before(*idx, *column)) {
+            0 => (start, 0),
+            after => chunks[after - 1],
+        }
     }
 
-    pub fn current_line(&self) -> usize {
-        let mut current_line = 0;
+    /// Works out where the chunks of `line` start, each at the start of a grapheme cluster
+    fn line_chunks(&self, line: usize) -> Vec<(usize, usize)> {
+        let (start, end) = (self.lines[line].start, self.line_end(line));
+        let mut chunks = vec![];
+        let mut column = 0;
+        let mut next = start + COLUMN_CHUNK;
+        for idx in start..end {
+            if idx >= next && grapheme::is_boundary(&self.data, idx) {
+                chunks.push((idx, column));
+                next = idx + COLUMN_CHUNK;
+            }
+            column += self.width_at(idx, column);
+        }
 
-        for Line { start, end } in self.lines.iter() {
-            if *start <= self.cursor_pos && *end >= self.cursor_pos {
-                return current_line;
-            } else {
-                current_line += 1;
+        chunks
+    }
+
+    /// Whether any line is longer than `LONG_LINE` characters
+    pub fn has_long_lines(&self) -> bool {
+        self.lines.iter().any(|line| line.len() > LONG_LINE)
+    }
+
+    /// Number of rows `line` is drawn on: one, or as many as it wraps to when `wrap` is on.
+    /// A line filling its last row has one more for the cursor at its end.
+    pub fn line_rows(&self, line: usize) -> usize {
+        let width = self.text_width();
+        if !self.wrap || width == 0 {
+            return 1;
+        }
+
+        // No character takes more columns than a tab or a wide character
+        if self.lines[line].len() * self.tab_width.max(2) < width {
+            return 1;
+        }
+        self.row_of_column(self.column_in_line(line, self.line_end(line))) + 1
+    }
+
+    /// Columns taken by the wrap marker at the start of the rows a line wraps to. It is left
+    /// out when it would not leave room for the text.
+    pub fn wrap_marker_width(&self) -> usize {
+        let marker: usize = self.wrap_marker.chars().map(width::char_width).sum();
+        if marker < self.text_width() {
+            marker
+        } else {
+            0
+        }
+    }
+
+    /// Columns taken by the wrap marker on the row `row` of a wrapped line, none on its first
+    pub fn row_marker_width(&self, row: usize) -> usize {
+        match row {
+            0 => 0,
+            _ => self.wrap_marker_width(),
+        }
+    }
+
+    /// Returns the column of a wrapped line its row `row` starts at. The first row has the
+    /// whole width of the text, the others what the wrap marker leaves.
+    pub fn row_start_column(&self, row: usize) -> usize {
+        match row {
+            0 => 0,
+            _ => self.text_width() + (row - 1) * (self.text_width() - self.wrap_marker_width()),
+        }
+    }
+
+    /// Returns the row of a wrapped line the column `column` of it is drawn on
+    pub fn row_of_column(&self, column: usize) -> usize {
+        let width = self.text_width();
+        if column < width {
+            0
+        } else {
+            1 + (column - width) / (width - self.wrap_marker_width())
+        }
+    }
+
+    /// Columns taken by the character at `idx` drawn at `column`. Only the first character of
+    /// a grapheme cluster takes any, e.g. the others of an emoji sequence are drawn with it.
+    pub fn width_at(&self, idx: usize, column: usize) -> usize {
+        if grapheme::is_boundary(&self.data, idx) {
+            self.char_width(self.data[idx], column)
+        } else {
+            0
+        }
+    }
+
+    /// Returns the column on screen of the cursor, past the end of its line in virtual space
+    pub fn cursor_column(&self) -> usize {
+        self.display_column(self.cursor_pos) + self.virtual_columns()
+    }
+
+    /// Returns how many columns the cursor is past the end of its line
+    pub fn virtual_columns(&self) -> usize {
+        match self.virtual_cursor {
+            Some((pos, columns)) if self.virtual_space && pos == self.cursor_pos => columns,
+            _ => 0,
+        }
+    }
+
+    fn set_virtual_columns(&mut self, columns: usize) {
+        self.virtual_cursor = (columns > 0).then_some((self.cursor_pos, columns));
+    }
+
+    /// Number of characters on `line`, without its line break. The cursor can be on each of them
+    /// and after the last one.
+    pub fn line_length(&self, line: usize) -> usize {
+        self.line_end(line) - self.lines[line].start
+    }
+
+    /// Characters of `line`, without its line break
+    fn line_chars(&self, line: usize) -> &[char] {
+        &self.data[self.lines[line].start..self.line_end(line)]
+    }
+
+    /// Text of `line`, without its line break
+    pub fn line_text(&self, line: usize) -> String {
+        self.data[self.lines[line].start..self.line_end(line)]
+            .iter()
+            .collect()
+    }
+
+    /// Returns the index of the line break ending `line`, or the end of `data` for the last line
+    fn line_end(&self, line: usize) -> usize {
+        let Line { start, end } = self.lines[line];
+        if end >= start && self.data.get(end) == Some(&'\n') {
+            end
+        } else {
+            self.data.len()
+        }
+    }
+
+    /// Columns taken by `ch` drawn at `column`
+    pub fn char_width(&self, ch: char, column: usize) -> usize {
+        if ch == '\t' {
+            let tab_width = self.tab_width.max(1);
+            tab_width - column % tab_width
+        } else {
+            width::char_width(ch)
+        }
+    }
+
+    /// Converts an index into `data` into a position
+    pub fn index_to_position(&self, idx: usize) -> Position {
+        for (i, Line { start, end }) in self.lines.iter().enumerate() {
+            if *start <= idx && *end >= idx {
+                return Position::new(i, idx - start);
             }
         }
 
-        unreachable!("Should never end up here.");
+        let last = self.lines.len() - 1;
+        Position::new(last, idx - self.lines[last].start)
+    }
+
+    /// Returns the position of the cursor
+    pub fn cursor_position(&self) -> Position {
+        self.index_to_position(self.cursor_pos)
+    }
+
+    /// Moves the cursor to `position`, clamped to its line
+    pub fn move_cursor_to_position(&mut self, position: Position) {
+        self.cursor_pos = self.position_to_index(position);
+        self.goal_column = None;
+    }
+
+    /// Converts a position into an offset in bytes into the UTF-8 text, e.g. for tools working
+    /// on the saved file. Positions past the end of a line or of the buffer are clamped.
+    pub fn byte_offset(&self, position: Position) -> usize {
+        let idx = self.position_to_index(position);
+        self.data[..idx].iter().map(|ch| ch.len_utf8()).sum()
+    }
+
+    /// Number of bytes the file is saved with before the text, those of the byte order mark
+    /// when it is kept
+    pub fn header_bytes(&self) -> usize {
+        if self.bom && self.keep_bom {
+            BOM_BYTES.len()
+        } else {
+            0
+        }
+    }
+
+    /// Converts an offset in bytes into the UTF-8 text into a position.
+    /// Offsets inside a character are moved back to its start.
+    pub fn byte_offset_to_position(&self, offset: usize) -> Position {
+        let mut bytes = 0;
+        let idx = self
+            .data
+            .iter()
+            .position(|ch| {
+                bytes += ch.len_utf8();
+                bytes > offset
+            })
+            .unwrap_or(self.data.len());
+        self.index_to_position(idx)
+    }
+
+    /// Returns the `[start, end)` ranges in `data` covered by each diagnostic.
+    /// Zero width diagnostics still cover one character.
+    pub fn diagnostic_ranges(&self) -> Vec<(usize, usize, Severity)> {
+        self.diagnostics
+            .iter()
+            .map(|d| {
+                let start = self.position_to_index(d.start);
+                let end = self.position_to_index(d.end);
+                (start, end.max(start + 1), d.severity)
+            })
+            .collect()
+    }
+
+    /// Returns the kind of highlighting applied to the character at `idx`, if any
+    pub fn highlight_at(&self, idx: usize) -> Option<HighlightKind> {
+        let after = self.highlights.partition_point(|h| h.start <= idx);
+
+        self.highlights[..after]
+            .iter()
+            .rev()
+            .find(|h| idx < h.end)
+            .map(|h| h.kind)
+    }
+
+    /// Returns the most severe diagnostic covering `idx`, if any
+    pub fn diagnostic_at(&self, idx: usize) -> Option<&Diagnostic> {
+        self.diagnostics
+            .iter()
+            .zip(self.diagnostic_ranges())
+            .filter(|(_, (start, end, _))| *start <= idx && idx < *end)
+            .map(|(d, _)| d)
+            .min_by_key(|d| d.severity)
+    }
+
+    /// Returns the most severe diagnostic reported on `line`, if any
+    pub fn line_diagnostic(&self, line: usize) -> Option<&Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.start.line <= line && d.end.line >= line)
+            .min_by_key(|d| d.severity)
+    }
+
+    /// Message of the diagnostic under the cursor, or on the cursor line
+    pub fn diagnostic_message_at_cursor(&self) -> Option<String> {
+        let line = self.cursor_position().line;
+
+        self.diagnostic_at(self.cursor_pos)
+            .or_else(|| self.line_diagnostic(line))
+            .map(|d| {
+                let message = d.message.lines().next().unwrap_or("");
+                format!("{}: {message}", d.severity.name())
+            })
+    }
+
+    pub fn current_line(&self) -> usize {
+        self.cursor_position().line
     }
 
     pub fn move_cursor_right(&mut self, dx: usize) {
-        if self.cursor_pos + dx <= self.data.len() {
-            self.cursor_pos += dx;
+        // In virtual space, stay on the line going past its end
+        let end = if self.virtual_space {
+            self.line_end(self.current_line())
+        } else {
+            self.data.len()
+        };
+        let columns = self.virtual_columns();
+
+        let mut steps = dx;
+        while steps > 0 && self.cursor_pos < end {
+            self.cursor_pos = grapheme::next_boundary(&self.data, self.cursor_pos).min(end);
+            steps -= 1;
+        }
+
+        if self.virtual_space {
+            self.set_virtual_columns(columns + steps);
+            self.goal_column = None;
+            return;
         }
 
-        self.previous_offset = None;
+        self.goal_column = None;
+        self.skip_folded(true);
     }
 
     pub fn move_cursor_left(&mut self, dx: usize) {
-        if self.cursor_pos >= dx {
-            self.cursor_pos -= dx;
+        let columns = self.virtual_columns();
+        if columns > 0 {
+            self.set_virtual_columns(columns.saturating_sub(dx));
+            self.goal_column = None;
+            if dx <= columns {
+                return;
+            }
+        }
+
+        for _ in 0..dx - columns.min(dx) {
+            if self.cursor_pos == 0 {
+                break;
+            }
+            self.cursor_pos = grapheme::previous_boundary(&self.data, self.cursor_pos);
         }
 
-        self.previous_offset = None;
+        self.goal_column = None;
+        self.skip_folded(false);
     }
 
-    pub fn move_cursor_up(&mut self, dy: usize) {
-        let mut current_line = self.current_line();
+    /// Moves the cursor to the start of its line. With `smart_home`, it goes to the first
+    /// non-blank character of the line first, and to the start from there.
+    pub fn move_cursor_home(&mut self) {
+        let Position { line, column } = self.cursor_position();
+        let first_non_blank = self.line_indent(line);
+
+        let column = if self.smart_home && column != first_non_blank {
+            first_non_blank
+        } else {
+            0
+        };
+        self.move_cursor_to_position(Position::new(line, column));
+        self.virtual_cursor = None;
+    }
+
+    /// Moves the cursor to the end of its line. With `smart_home`, it stops after the last
+    /// non-blank character of the line first, and goes to the end from there.
+    pub fn move_cursor_end(&mut self) {
+        let Position { line, column } = self.cursor_position();
+        let chars = self.line_chars(line);
+        let blanks = chars.iter().rev().take_while(|c| matches!(c, ' ' | '\t'));
+        let last_non_blank = chars.len() - blanks.count();
+
+        let column = if self.smart_home && column != last_non_blank {
+            last_non_blank
+        } else {
+            chars.len()
+        };
+        self.move_cursor_to_position(Position::new(line, column));
+        self.virtual_cursor = None;
+    }
+
+    /// Moves the cursor to the start of the buffer, and the view to the top of it
+    pub fn move_cursor_to_start(&mut self) {
+        self.cursor_pos = 0;
+        self.virtual_cursor = None;
+        self.goal_column = None;
+        self.offset_x = 0;
+        self.offset_y = 0;
+    }
+
+    /// Moves the cursor to the end of the buffer, and the view to the last page of it, so that
+    /// the last line is on the last row rather than leaving rows below it blank
+    pub fn move_cursor_to_end(&mut self) {
+        self.cursor_pos = self.data.len();
+        self.virtual_cursor = None;
+        self.goal_column = None;
+        self.skip_folded(false);
+
+        self.offset_y = (self.last_row() + 1).saturating_sub(self.height);
+    }
+
+    /// Whether `line` holds nothing but whitespace
+    fn is_blank_line(&self, line: usize) -> bool {
+        let Line { start, end } = self.lines[line];
+        let text = self.data.get(start..=end).unwrap_or_default();
+        text.iter().all(|c| c.is_whitespace())
+    }
+
+    /// Number of spaces and tabs at the start of `line`
+    fn line_indent(&self, line: usize) -> usize {
+        let Line { start, end } = self.lines[line];
+        let text = self.data.get(start..=end).unwrap_or_default();
+        text.iter()
+            .take_while(|c| **c == ' ' || **c == '\t')
+            .count()
+    }
+
+    /// Moves the cursor to the next blank line after a paragraph, or to the previous one before
+    /// it, and to the end or the start of the buffer when there are none
+    pub fn move_cursor_paragraph(&mut self, forward: bool) {
+        let current = self.current_line();
+        let last = self.lines.len() - 1;
+        let target = if forward {
+            (current + 1..=last)
+                .skip_while(|l| self.is_blank_line(*l))
+                .find(|l| self.is_blank_line(*l))
+        } else {
+            (0..current)
+                .rev()
+                .skip_while(|l| self.is_blank_line(*l))
+                .find(|l| self.is_blank_line(*l))
+        };
+
+        let position = match target {
+            Some(line) => Position::line_start(line),
+            None if forward => Position::new(last, self.line_length(last)),
+            None => Position::default(),
+        };
+        self.move_cursor_to_position(position);
+        self.virtual_cursor = None;
+        self.skip_folded(forward);
+    }
+
+    /// Moves the cursor to the first line of the block of lines indented at least as much as
+    /// its line, or to the last one if `forward`. From there, it moves on to the line
+    /// introducing the block, or to the line after it.
+    pub fn move_cursor_indent_block(&mut self, forward: bool) {
+        let current = self.current_line();
+        let last = self.lines.len() - 1;
+        let level = self.line_indent(current);
+        let in_block = |l: &usize| self.is_blank_line(*l) || self.line_indent(*l) >= level;
+
+        let mut target = if forward {
+            (current + 1..=last).take_while(in_block).last()
+        } else {
+            (0..current).rev().take_while(in_block).last()
+        }
+        .unwrap_or(current);
 
-        if current_line >= dy {
-            let line = &self.lines[current_line];
-            let mut x_offset = match self.previous_offset {
-                Some(offset) => offset,
-                None => self.cursor_pos - line.start,
+        // Blank lines around the block are not part of it
+        while target != current && self.is_blank_line(target) {
+            target = if forward { target - 1 } else { target + 1 };
+        }
+
+        if target == current {
+            let next = if forward {
+                (current + 1..=last).find(|l| !self.is_blank_line(*l))
+            } else {
+                (0..current).rev().find(|l| !self.is_blank_line(*l))
+            };
+            let Some(next) = next else {
+                return;
             };
+            target = next;
+        }
+
+        self.move_cursor_to_position(Position::new(target, self.line_indent(target)));
+        self.virtual_cursor = None;
+        self.skip_folded(forward);
+    }
 
-            current_line -= dy;
+    /// Moves the cursor to the `{` opening the block of braces around it, or to the `}` closing
+    /// it if `forward`. Returns false if the cursor is not in such a block.
+    pub fn move_cursor_brace_block(&mut self, forward: bool) -> bool {
+        let mut depth = 0usize;
+        let (open, close) = if forward { ('{', '}') } else { ('}', '{') };
+        let found = if forward {
+            (self.cursor_pos + 1..self.data.len()).find(|i| match self.data[*i] {
+                ch if ch == open => {
+                    depth += 1;
+                    false
+                }
+                ch if ch == close => depth.checked_sub(1).map(|d| depth = d).is_none(),
+                _ => false,
+            })
+        } else {
+            (0..self.cursor_pos).rev().find(|i| match self.data[*i] {
+                ch if ch == open => {
+                    depth += 1;
+                    false
+                }
+                ch if ch == close => depth.checked_sub(1).map(|d| depth = d).is_none(),
+                _ => false,
+            })
+        };
 
-            let line = &self.lines[current_line];
+        let Some(idx) = found else {
+            return false;
+        };
+        self.cursor_pos = idx;
+        self.virtual_cursor = None;
+        self.goal_column = None;
+        self.skip_folded(forward);
+        true
+    }
 
-            if x_offset >= line.len() {
-                self.previous_offset = Some(x_offset);
-                x_offset = line.len() - 1;
+    /// Moves the cursor to the start of the word `count` words before it
+    pub fn move_cursor_word_left(&mut self, count: usize) {
+        for _ in 0..count {
+            if self.cursor_pos == 0 {
+                break;
+            }
+            while self.cursor_pos > 0 && !self.is_word_char(self.data[self.cursor_pos - 1]) {
+                self.cursor_pos -= 1;
             }
+            while self.cursor_pos > 0 && self.is_word_char(self.data[self.cursor_pos - 1]) {
+                self.cursor_pos -= 1;
+            }
+        }
+
+        self.goal_column = None;
+        self.skip_folded(false);
+    }
 
-            self.cursor_pos = line.start + x_offset;
+    /// Moves the cursor past the end of the word `count` words after it, stopping at the end of
+    /// the buffer
+    pub fn move_cursor_word_right(&mut self, count: usize) {
+        let end = self.data.len();
+
+        for _ in 0..count {
+            if self.cursor_pos >= end {
+                break;
+            }
+            while self.cursor_pos < end && !self.is_word_char(self.data[self.cursor_pos]) {
+                self.cursor_pos += 1;
+            }
+            while self.cursor_pos < end && self.is_word_char(self.data[self.cursor_pos]) {
+                self.cursor_pos += 1;
+            }
+        }
+
+        self.goal_column = None;
+        self.skip_folded(true);
+    }
+
+    pub fn move_cursor_up(&mut self, dy: usize) {
+        let current_line = self.current_line();
+        let dy = isize::try_from(dy).unwrap_or(isize::MAX);
+
+        if let Some(target_line) = self.visible_line_from(current_line, -dy) {
+            self.move_cursor_to_line(target_line);
         }
     }
 
     pub fn move_cursor_down(&mut self, dy: usize) {
-        let mut current_line = self.current_line();
+        let current_line = self.current_line();
+        let dy = isize::try_from(dy).unwrap_or(isize::MAX);
 
-        if current_line + dy < self.lines.len() {
-            let line = &self.lines[current_line];
-            let mut x_offset = match self.previous_offset {
-                Some(offset) => offset,
-                None => self.cursor_pos - line.start,
-            };
+        if let Some(target_line) = self.visible_line_from(current_line, dy) {
+            self.move_cursor_to_line(target_line);
+        }
+    }
+
+    /// Moves the cursor to `line`, keeping to the column it was in before going up or down.
+    /// Lines too short for it leave the cursor at their end, or past it in virtual space.
+    fn move_cursor_to_line(&mut self, line: usize) {
+        let goal = self
+            .goal_column
+            .unwrap_or_else(|| self.cursor_goal_column());
+        let (idx, past_end) = self.index_at_column(line, goal);
+
+        self.cursor_pos = idx;
+        self.snap_to_cluster();
+        if self.virtual_space {
+            self.set_virtual_columns(past_end);
+        }
+        self.goal_column = Some(goal);
+    }
+
+    /// Column of the cursor that up and down keep to: on screen, or counted in characters
+    /// when `screen_columns` is off
+    fn cursor_goal_column(&self) -> usize {
+        if self.screen_columns {
+            self.cursor_column()
+        } else {
+            self.cursor_position().column + self.virtual_columns()
+        }
+    }
+
+    /// Returns the index of the character of `line` at `column`, as counted by
+    /// `cursor_goal_column`, or of its end with how many columns `column` is past it
+    fn index_at_column(&self, line: usize, column: usize) -> (usize, usize) {
+        let start = self.lines[line].start;
+        let end = self.line_end(line);
+
+        if !self.screen_columns {
+            let idx = self.position_to_index(Position::new(line, column));
+            return (idx, start + column - idx);
+        }
+
+        let (from, mut at) = self.chunk_start(line, |_, at| at <= column);
+        for idx in from..end {
+            let width = self.width_at(idx, at);
+            if at + width > column {
+                return (idx, 0);
+            }
+            at += width;
+        }
+        (end, column - at)
+    }
 
-            current_line += dy;
+    /// Returns the index of the character drawn at the cell `(column, row)` of the terminal, or
+    /// of the end of the line if the cell is past it. None if the cell is not on a line.
+    pub fn index_at_cell(&self, column: u16, row: u16) -> Option<usize> {
+        let text_x = self.x as usize + self.gutter_width;
+        let (column, row) = (column as usize, row as usize);
+        if column < text_x || row < self.y as usize || row >= self.y as usize + self.height {
+            return None;
+        }
 
-            let line = &self.lines[current_line];
+        let (line, segment) = *self.view_rows().get(row - self.y as usize)?;
 
-            if x_offset >= line.len() {
-                self.previous_offset = Some(x_offset);
-                x_offset = line.len() - 1;
+        // A click on the wrap marker goes to the first character after it
+        let column = (column - text_x).saturating_sub(self.row_marker_width(segment))
+            + self.offset_x
+            + self.row_start_column(segment);
+        let end = self.line_end(line);
+        let (from, mut at) = self.chunk_start(line, |_, at| at <= column);
+        for idx in from..end {
+            at += self.width_at(idx, at);
+            if at > column {
+                return Some(idx);
             }
+        }
+        Some(end)
+    }
 
-            self.cursor_pos = line.start + x_offset;
+    /// Moves the cursor back to the start of the grapheme cluster it is in
+    fn snap_to_cluster(&mut self) {
+        while !grapheme::is_boundary(&self.data, self.cursor_pos) {
+            self.cursor_pos -= 1;
+        }
+    }
+
+    /// Scrolls the view `dy` rows down, or up if negative, moving the cursor along if it would leave the view
+    pub fn scroll_view(&mut self, dy: isize) {
+        let last_row = self.last_row();
+        self.offset_y = self.offset_y.saturating_add_signed(dy).min(last_row);
+
+        let row = self.visible_row(self.current_line());
+        if row < self.offset_y {
+            self.move_cursor_down(self.offset_y - row);
+        } else if self.height > 0 && row >= self.offset_y + self.height {
+            self.move_cursor_up(row + 1 - self.offset_y - self.height);
         }
     }
 
     pub fn scroll(&mut self) {
         let (x, y) = self.cursor_xy();
-        let (w, h) = (self.width, self.height);
+        let (w, h) = (self.text_width(), self.height);
+
+        // Nothing can be brought into view of a pane with no room for text, it only has to stay
+        // within the buffer through edits
+        if w == 0 || h == 0 {
+            self.offset_y = self.offset_y.min(self.last_row());
+            return;
+        }
 
         let y = y - self.y as isize;
-        let x = x - self.x as isize;
+        let x = x - self.x as isize - self.gutter_width as isize;
 
         if y < 0 {
             let dy = (-y) as usize;
@@ -318,37 +1209,970 @@ impl Buffer {
             self.offset_y += dy as usize;
         }
 
+        // Wrapped lines are all in view across
+        if self.wrap {
+            self.offset_x = 0;
+            return;
+        }
+
+        // The whole of a wide character under the cursor is brought into view
+        let cursor_width = match self.data.get(self.cursor_pos) {
+            Some(ch) if *ch != '\t' && self.virtual_columns() == 0 => width::char_width(*ch).max(1),
+            _ => 1,
+        } as isize;
+
         if x < 0 {
             let dx = (-x) as usize;
             self.offset_x -= dx;
-        } else if x >= w as isize {
-            let dx = x - w as isize + 1;
+        } else if x + cursor_width > w as isize {
+            let dx = x + cursor_width - w as isize;
             self.offset_x += dx as usize;
         }
     }
 
+    /// Returns the lines hidden by folds, as sorted and merged inclusive `(first, last)` ranges
+    pub fn hidden_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = self
+            .folds
+            .iter()
+            .map(|(header, last)| {
+                (
+                    self.index_to_position(*header).line + 1,
+                    self.index_to_position(*last).line,
+                )
+            })
+            .filter(|(first, last)| first <= last)
+            .collect();
+        ranges.sort();
+
+        let mut merged: Vec<(usize, usize)> = vec![];
+        for (first, last) in ranges {
+            match merged.last_mut() {
+                Some((_, previous_last)) if first <= *previous_last + 1 => {
+                    *previous_last = (*previous_last).max(last)
+                }
+                _ => merged.push((first, last)),
+            }
+        }
+
+        merged
+    }
+
+    pub fn is_line_hidden(&self, line: usize) -> bool {
+        self.hidden_ranges()
+            .iter()
+            .any(|(first, last)| *first <= line && line <= *last)
+    }
+
+    /// Returns the row of `line` among the visible lines, i.e. not counting the folded ones.
+    /// With `wrap` on, that is the row its first part is drawn on.
+    pub fn visible_row(&self, line: usize) -> usize {
+        if self.wrap {
+            let hidden = self.hidden_ranges();
+            return (0..line)
+                .filter(|l| !hidden.iter().any(|(first, last)| first <= l && l <= last))
+                .map(|l| self.line_rows(l))
+                .sum();
+        }
+
+        let hidden_before: usize = self
+            .hidden_ranges()
+            .iter()
+            .filter(|(first, _)| *first < line)
+            .map(|(first, last)| (*last).min(line - 1) - first + 1)
+            .sum();
+
+        line - hidden_before
+    }
+
+    /// Returns the last row of the visible lines, the furthest down the view can start
+    pub fn last_row(&self) -> usize {
+        let last = self.lines.len() - 1;
+        self.visible_row(last) + self.line_rows(last) - 1
+    }
+
+    /// Returns the rows in view from the top, as the line drawn on each with which of its rows
+    /// it is, the first one unless the line is wrapped
+    pub fn view_rows(&self) -> Vec<(usize, usize)> {
+        let hidden = self.hidden_ranges();
+        (0..self.lines.len())
+            .filter(|l| !hidden.iter().any(|(first, last)| first <= l && l <= last))
+            .flat_map(|line| (0..self.line_rows(line)).map(move |segment| (line, segment)))
+            .skip(self.offset_y)
+            .take(self.height)
+            .collect()
+    }
+
+    /// Returns the visible line `count` visible lines away from `line`, or the furthest one in
+    /// that direction if there are fewer. Returns None if there is none at all.
+    fn visible_line_from(&self, line: usize, count: isize) -> Option<usize> {
+        let hidden = self.hidden_ranges();
+        let is_hidden = |l: usize| hidden.iter().any(|(first, last)| *first <= l && l <= *last);
+
+        // Stop at the first or last line if there are fewer lines than `count`
+        let mut target = None;
+        for _ in 0..count.unsigned_abs() {
+            let from = target.unwrap_or(line);
+            let next = if count > 0 {
+                (from + 1..self.lines.len()).find(|l| !is_hidden(*l))
+            } else {
+                (0..from).rev().find(|l| !is_hidden(*l))
+            };
+
+            match next {
+                Some(next) => target = Some(next),
+                None => break,
+            }
+        }
+
+        target
+    }
+
+    /// Moves a cursor that went into folded lines out of them, after them if `forward`,
+    /// otherwise at the end of the fold header
+    fn skip_folded(&mut self, forward: bool) {
+        let current = self.current_line();
+        if !self.is_line_hidden(current) {
+            return;
+        }
+
+        let target = if forward {
+            self.visible_line_from(current, 1)
+                .map(|l| self.lines[l].start)
+        } else {
+            self.visible_line_from(current, -1)
+                .map(|l| self.lines[l].end)
+        };
+
+        if let Some(idx) = target {
+            self.cursor_pos = idx.min(self.data.len());
+        }
+    }
+
+    /// Returns the `(header, last)` lines of the block that can be folded at `line`.
+    /// A line with an unclosed bracket folds up to the line before the closing bracket,
+    /// otherwise the more indented lines below it are folded. Lines inside a block fold their block.
+    pub fn foldable_range(&self, line: usize) -> Option<(usize, usize)> {
+        let line_text = |l: usize| -> &[char] {
+            let Line { start, end } = self.lines[l];
+            self.data.get(start..=end).unwrap_or_default()
+        };
+        let is_blank = |l: usize| self.is_blank_line(l);
+        let indent = |l: usize| self.line_indent(l);
+
+        // Brackets
+        let mut depth = 0isize;
+        for ch in line_text(line) {
+            match ch {
+                '{' | '[' | '(' => depth += 1,
+                '}' | ']' | ')' => depth -= 1,
+                _ => (),
+            }
+        }
+        if depth > 0 {
+            let mut depth = 0isize;
+            for (i, ch) in self.data.iter().enumerate().skip(self.lines[line].start) {
+                match ch {
+                    '{' | '[' | '(' => depth += 1,
+                    '}' | ']' | ')' => depth -= 1,
+                    _ => (),
+                }
+                if depth == 0 {
+                    let close_line = self.index_to_position(i).line;
+                    if close_line > line + 1 {
+                        return Some((line, close_line - 1));
+                    }
+                    break;
+                }
+            }
+        }
+
+        // Indentation
+        if !is_blank(line) {
+            let last = (line + 1..self.lines.len())
+                .take_while(|l| is_blank(*l) || indent(*l) > indent(line))
+                .filter(|l| !is_blank(*l))
+                .last();
+            if let Some(last) = last {
+                return Some((line, last));
+            }
+        }
+
+        // The block the line is in, starting at the closest less indented line above it
+        let level = if is_blank(line) {
+            usize::MAX
+        } else {
+            indent(line)
+        };
+        let header = (0..line)
+            .rev()
+            .find(|l| !is_blank(*l) && indent(*l) < level)?;
+        self.foldable_range(header)
+            .filter(|(_, last)| *last >= line)
+    }
+
+    /// Folds the block at the cursor, returns false if there is nothing to fold
+    pub fn fold_at_cursor(&mut self) -> bool {
+        let Some((header, last)) = self.foldable_range(self.current_line()) else {
+            return false;
+        };
+
+        self.folds
+            .push((self.lines[header].start, self.lines[last].start));
+        self.cursor_pos = self.lines[header].start
+            + self
+                .cursor_pos
+                .saturating_sub(self.lines[header].start)
+                .min(self.lines[header].len() - 1);
+        self.goal_column = None;
+        // Make room for the fold marks
+        self.gutter_width = self.gutter_width.max(3);
+
+        true
+    }
+
+    /// Unfolds the folds starting on the cursor line, returns false if there are none
+    pub fn unfold_at_cursor(&mut self) -> bool {
+        let line = self.current_line();
+        let count = self.folds.len();
+        let headers: Vec<usize> = self
+            .folds
+            .iter()
+            .map(|(header, _)| self.index_to_position(*header).line)
+            .collect();
+
+        let mut headers = headers.into_iter();
+        self.folds.retain(|_| headers.next() != Some(line));
+
+        self.folds.len() != count
+    }
+
+    pub fn toggle_fold(&mut self) -> bool {
+        self.unfold_at_cursor() || self.fold_at_cursor()
+    }
+
+    /// Unfolds the folds hiding the cursor, e.g. after jumping into them.
+    /// Returns true if anything was unfolded.
+    pub fn reveal_cursor(&mut self) -> bool {
+        let line = self.current_line();
+        let count = self.folds.len();
+        let ranges: Vec<(usize, usize)> = self
+            .folds
+            .iter()
+            .map(|(header, last)| {
+                (
+                    self.index_to_position(*header).line,
+                    self.index_to_position(*last).line,
+                )
+            })
+            .collect();
+
+        let mut ranges = ranges.into_iter();
+        self.folds.retain(|_| {
+            ranges
+                .next()
+                .is_some_and(|(header, last)| line <= header || line > last)
+        });
+
+        self.folds.len() != count
+    }
+
+    pub fn is_line_changed(&self, line: usize) -> bool {
+        self.changed_lines
+            .iter()
+            .any(|(start, end)| *start <= line && line < *end)
+    }
+
+    /// Returns the number of lines hidden under `line` if it is the header of a fold
+    pub fn folded_under(&self, line: usize) -> Option<usize> {
+        self.folds
+            .iter()
+            .filter(|(header, _)| self.index_to_position(*header).line == line)
+            .map(|(_, last)| self.index_to_position(*last).line.saturating_sub(line))
+            .max()
+    }
+
+    /// Returns true if `ch` can be a part of a word of this buffer
+    pub fn is_word_char(&self, ch: char) -> bool {
+        is_word_char_with(ch, &self.word_separators)
+    }
+
+    /// Returns the index where the word ending at the cursor begins.
+    /// If there is no word right before the cursor, returns `cursor_pos`.
+    pub fn word_start_before_cursor(&self) -> usize {
+        let mut start = self.cursor_pos;
+
+        while start > 0 && self.is_word_char(self.data[start - 1]) {
+            start -= 1;
+        }
+
+        start
+    }
+
+    /// Returns the `[start, end)` range of the word the cursor is on or right after
+    pub fn word_at_cursor(&self) -> Option<(usize, usize)> {
+        self.word_at(self.cursor_pos)
+    }
+
+    /// Returns the `[start, end)` range of the word `idx` is on or right after
+    pub fn word_at(&self, idx: usize) -> Option<(usize, usize)> {
+        let mut start = idx.min(self.data.len());
+        while start > 0 && self.is_word_char(self.data[start - 1]) {
+            start -= 1;
+        }
+
+        let mut end = idx;
+        while end < self.data.len() && self.is_word_char(self.data[end]) {
+            end += 1;
+        }
+
+        (start < end).then_some((start, end))
+    }
+
+    /// Returns the `[start, end)` range of the line `idx` is on, with its line break
+    pub fn line_range_at(&self, idx: usize) -> (usize, usize) {
+        let line = self.index_to_position(idx).line;
+        let end = (self.line_end(line) + 1).min(self.data.len());
+        (self.lines[line].start, end)
+    }
+
+    /// Returns the `[start, end)` ranges of the occurrences of the word under the cursor
+    /// shown in the viewport, besides the one under the cursor
+    pub fn word_occurrences_in_view(&self) -> Vec<(usize, usize)> {
+        let Some(word) = self.word_at_cursor() else {
+            return vec![];
+        };
+        let rows = self.view_rows();
+        let (Some((first, _)), Some((last, _))) = (rows.first(), rows.last()) else {
+            return vec![];
+        };
+        let view_start = self.lines[*first].start;
+        let view_end = self.lines[*last].end + 1;
+
+        self.find_word(word, view_start, view_end.min(self.data.len()))
+            .into_iter()
+            .filter(|range| *range != word)
+            .collect()
+    }
+
+    /// Returns the `[start, end)` ranges of all the occurrences of the word under the cursor
+    pub fn word_occurrences(&self) -> Vec<(usize, usize)> {
+        match self.word_at_cursor() {
+            Some(word) => self.find_word(word, 0, self.data.len()),
+            None => vec![],
+        }
+    }
+
+    /// Returns the `[start, end)` ranges of the whole word occurrences of `data[start..end]`
+    /// within `data[from..to]`
+    fn find_word(
+        &self,
+        (start, end): (usize, usize),
+        from: usize,
+        to: usize,
+    ) -> Vec<(usize, usize)> {
+        let word = &self.data[start..end];
+        let mut occurrences = vec![];
+        let mut i = from;
+
+        while i + word.len() <= to {
+            let whole_word = self.data[i..i + word.len()] == *word
+                && (i == 0 || !self.is_word_char(self.data[i - 1]))
+                && self
+                    .data
+                    .get(i + word.len())
+                    .is_none_or(|ch| !self.is_word_char(*ch));
+
+            if whole_word {
+                occurrences.push((i, i + word.len()));
+                i += word.len();
+            } else {
+                i += 1;
+            }
+        }
+
+        occurrences
+    }
+
+    /// Must be called after every change to `data`
+    fn mark_modified(&mut self) {
+        self.version += 1;
+        self.modified = true;
+    }
+
+    /// Moves the marks after an edit replacing `removed` characters at `start` with `inserted` ones.
+    /// Marks inside the removed text go to its start.
+    fn shift_marks(&mut self, start: usize, removed: usize, inserted: usize) {
+        let named = self.named_marks.iter_mut().map(|(_, idx)| idx);
+        let folds = self
+            .folds
+            .iter_mut()
+            .flat_map(|(header, last)| [header, last]);
+        for mark in self.bookmarks.iter_mut().chain(named).chain(folds) {
+            if *mark >= start + removed {
+                *mark = *mark - removed + inserted;
+            } else if *mark > start {
+                *mark = start;
+            }
+        }
+    }
+
+    /// Sets the mark `name` at `idx`, replacing the previous position of that mark
+    pub fn set_named_mark(&mut self, name: char, idx: usize) {
+        let idx = idx.min(self.data.len());
+        match self.named_marks.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, mark)) => *mark = idx,
+            None => self.named_marks.push((name, idx)),
+        }
+    }
+
+    pub fn named_mark(&self, name: char) -> Option<usize> {
+        self.named_marks
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, idx)| *idx)
+    }
+
+    /// Returns the lines holding a bookmark, sorted
+    pub fn bookmark_lines(&self) -> Vec<usize> {
+        let mut lines: Vec<usize> = self
+            .bookmarks
+            .iter()
+            .map(|idx| self.index_to_position(*idx).line)
+            .collect();
+        lines.sort();
+        lines.dedup();
+        lines
+    }
+
+    /// Adds a bookmark on the cursor line, or removes it if there is one already
+    pub fn toggle_bookmark(&mut self) {
+        let line = self.current_line();
+        let on_line: Vec<bool> = self
+            .bookmarks
+            .iter()
+            .map(|idx| self.index_to_position(*idx).line == line)
+            .collect();
+
+        if on_line.contains(&true) {
+            let mut on_line = on_line.into_iter();
+            self.bookmarks.retain(|_| !on_line.next().unwrap_or(false));
+        } else {
+            self.bookmarks.push(self.lines[line].start);
+            // Make room for the bookmark marks
+            self.gutter_width = self.gutter_width.max(2);
+        }
+    }
+
+    /// Moves the cursor to the start of the next bookmarked line, wrapping around to the first one.
+    /// Returns false if there are no bookmarks.
+    pub fn next_bookmark(&mut self) -> bool {
+        let lines = self.bookmark_lines();
+        let current = self.current_line();
+
+        match lines.iter().find(|l| **l > current).or(lines.first()) {
+            Some(line) => {
+                self.move_cursor_to_position(Position::line_start(*line));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the cursor to the start of the previous bookmarked line, wrapping around to the last one.
+    /// Returns false if there are no bookmarks.
+    pub fn previous_bookmark(&mut self) -> bool {
+        let lines = self.bookmark_lines();
+        let current = self.current_line();
+
+        match lines.iter().rev().find(|l| **l < current).or(lines.last()) {
+            Some(line) => {
+                self.move_cursor_to_position(Position::line_start(*line));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Starts selecting from the cursor, unless a selection is already in progress
+    pub fn select(&mut self) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.cursor_pos);
+        }
+    }
+
+    /// Selects from `anchor` to `cursor`, where the cursor goes, or only moves the cursor there
+    /// if they are the same
+    pub fn select_range(&mut self, anchor: usize, cursor: usize) {
+        self.selection_anchor = (anchor != cursor).then_some(anchor);
+        self.cursor_pos = cursor;
+        self.virtual_cursor = None;
+        self.goal_column = None;
+    }
+
+    /// Selects from `anchor` to `cursor` given as positions, see `select_range`
+    pub fn select_positions(&mut self, anchor: Position, cursor: Position) {
+        self.select_range(
+            self.position_to_index(anchor),
+            self.position_to_index(cursor),
+        );
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection_anchor = None;
+    }
+
+    /// Returns the `[start, end)` range of the selection, if anything is selected
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        let anchor = self.selection_anchor?;
+        let start = anchor.min(self.cursor_pos);
+        let end = anchor.max(self.cursor_pos).min(self.data.len());
+
+        (start < end).then_some((start, end))
+    }
+
+    /// Returns the start and end positions of the selection, if anything is selected
+    pub fn selection_positions(&self) -> Option<(Position, Position)> {
+        let (start, end) = self.selection()?;
+        Some((self.index_to_position(start), self.index_to_position(end)))
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        self.selection()
+            .map(|(start, end)| self.data[start..end].iter().collect())
+    }
+
+    /// Deletes the selected text, returns false if nothing was selected
+    pub fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                self.replace_range(start, end, "");
+                true
+            }
+            None => {
+                self.clear_selection();
+                false
+            }
+        }
+    }
+
+    /// Inserts `ch` at the cursor, replacing the selection if there is one.
+    /// In virtual space, the line is padded with spaces up to the cursor first.
     pub fn insert_ch(&mut self, ch: char) {
-        self.data.insert(self.cursor_pos, ch);
+        self.insert_str(ch.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Inserts `text` at the cursor as a single edit, replacing the selection if there is one.
+    /// In virtual space, the line is padded with spaces up to the cursor first, unless `text`
+    /// starts a new line.
+    pub fn insert_str(&mut self, text: &str) {
+        self.delete_selection();
+        let padding = if text.starts_with('\n') {
+            0
+        } else {
+            self.virtual_columns()
+        };
+        self.virtual_cursor = None;
+
+        let start = self.cursor_pos;
+        let mut inserted = vec![' '; padding];
+        inserted.extend(text.chars());
+        if inserted.is_empty() {
+            return;
+        }
+        self.history.record(
+            Edit {
+                start,
+                removed: vec![],
+                inserted: inserted.clone(),
+            },
+            start,
+        );
+        self.shift_marks(start, 0, inserted.len());
+        self.cursor_pos += inserted.len();
+        self.data.splice(start..start, inserted);
+        self.mark_modified();
+    }
+
+    /// Replaces the character under the cursor with `ch`, as typed in overwrite mode.
+    /// Line breaks are not replaced, `ch` goes before them.
+    pub fn overwrite_ch(&mut self, ch: char) {
+        if self.delete_selection()
+            || ch == '\n'
+            || matches!(self.data.get(self.cursor_pos), None | Some('\n'))
+        {
+            self.insert_ch(ch);
+            return;
+        }
+
+        // The whole grapheme cluster under the cursor is replaced
+        let end = grapheme::next_boundary(&self.data, self.cursor_pos);
+        let removed: Vec<char> = self.data.splice(self.cursor_pos..end, [ch]).collect();
+        self.shift_marks(self.cursor_pos, removed.len(), 1);
+        self.history.record(
+            Edit {
+                start: self.cursor_pos,
+                removed,
+                inserted: vec![ch],
+            },
+            self.cursor_pos,
+        );
         self.cursor_pos += 1;
+        self.mark_modified();
     }
 
     /// Same as backspace key pressed
     pub fn backspace(&mut self) {
-        self.cursor_pos -= 1;
-        self.data.remove(self.cursor_pos);
+        if self.delete_selection() {
+            return;
+        }
+
+        // Nothing to delete in virtual space, the cursor only comes back
+        if self.virtual_columns() > 0 {
+            self.move_cursor_left(1);
+            return;
+        }
+
+        if self.cursor_pos == 0 {
+            return;
+        }
+
+        // The whole grapheme cluster before the cursor goes
+        let cursor = self.cursor_pos;
+        self.cursor_pos = grapheme::previous_boundary(&self.data, cursor);
+        let removed: Vec<char> = self.data.drain(self.cursor_pos..cursor).collect();
+        self.shift_marks(self.cursor_pos, removed.len(), 0);
+        self.history.record(
+            Edit {
+                start: self.cursor_pos,
+                removed,
+                inserted: vec![],
+            },
+            cursor,
+        );
+        self.mark_modified();
     }
 
     /// Same as delete key pressed
     pub fn delete(&mut self) {
-        self.data.remove(self.cursor_pos);
+        if self.delete_selection() {
+            return;
+        }
+
+        if self.cursor_pos >= self.data.len() {
+            return;
+        }
+
+        // The whole grapheme cluster under the cursor goes
+        let end = grapheme::next_boundary(&self.data, self.cursor_pos);
+        let removed: Vec<char> = self.data.drain(self.cursor_pos..end).collect();
+        self.shift_marks(self.cursor_pos, removed.len(), 0);
+        self.history.record(
+            Edit {
+                start: self.cursor_pos,
+                removed,
+                inserted: vec![],
+            },
+            self.cursor_pos,
+        );
+        self.mark_modified();
+    }
+
+    /// Replaces the characters in `[start, end)` with `text`.
+    /// A cursor inside the replaced range ends up after the new text.
+    pub fn replace_range(&mut self, start: usize, end: usize, text: &str) {
+        let new: Vec<char> = text.chars().collect();
+        let new_len = new.len();
+        let removed = self.data.splice(start..end, new.clone()).collect();
+        self.shift_marks(start, end - start, new_len);
+        self.history.record(
+            Edit {
+                start,
+                removed,
+                inserted: new,
+            },
+            self.cursor_pos,
+        );
+
+        if self.cursor_pos > end {
+            self.cursor_pos = self.cursor_pos - (end - start) + new_len;
+        } else if self.cursor_pos >= start {
+            self.cursor_pos = start + new_len;
+        }
+
+        self.goal_column = None;
+        self.selection_anchor = None;
+        self.mark_modified();
+        self.recalculate_lines();
+    }
+
+    /// Replaces each of the `[start, end)` `ranges`, sorted and not overlapping, with `text`
+    pub fn replace_ranges(&mut self, ranges: &[(usize, usize)], text: &str) {
+        // Going backwards keeps the ranges still to replace in place
+        for (start, end) in ranges.iter().rev() {
+            self.replace_range(*start, *end, text);
+        }
+    }
+
+    /// Replaces the selection, or else the word under the cursor, with `transform` applied to
+    /// it. The selection stays over the new text. Returns false if there was nothing to change.
+    pub fn transform_selection_or_word(&mut self, transform: impl Fn(&str) -> String) -> bool {
+        let selection = self.selection();
+        let Some((start, end)) = selection.or_else(|| self.word_at_cursor()) else {
+            return false;
+        };
+
+        let text: String = self.data[start..end].iter().collect();
+        let transformed = transform(&text);
+        if transformed == text {
+            return true;
+        }
+
+        let cursor_at_end = self.cursor_pos == end;
+        let cursor_pos = self.cursor_pos;
+        self.replace_range(start, end, &transformed);
+
+        let new_end = start + transformed.chars().count();
+        self.cursor_pos = match (selection, cursor_at_end) {
+            (Some(_), true) => new_end,
+            (Some(_), false) => start,
+            (None, _) => cursor_pos.min(new_end),
+        };
+        if selection.is_some() {
+            self.selection_anchor = Some(if cursor_at_end { start } else { new_end });
+        }
+        self.goal_column = None;
+
+        true
+    }
+
+    /// Returns the first and last line touched by the selection, or all the lines if nothing
+    /// is selected
+    pub fn selected_lines(&self) -> (usize, usize) {
+        match self.selection_positions() {
+            // A selection ending at the start of a line does not include it
+            Some((start, end)) if end.column == 0 => (start.line, end.line - 1),
+            Some((start, end)) => (start.line, end.line),
+            None => (0, self.lines.len() - 1),
+        }
+    }
+
+    /// Replaces the selected lines, or all of them, with `transform` applied to them and
+    /// moves the cursor to the first one. Returns false if the lines did not change.
+    pub fn transform_lines(&mut self, transform: impl FnOnce(&mut Vec<String>)) -> bool {
+        let (first, last) = self.selected_lines();
+        self.transform_line_range(first, last, transform)
+    }
+
+    /// Replaces the lines from `first` to `last` with `transform` applied to them and moves
+    /// the cursor to the first one. Returns false if the lines did not change.
+    pub fn transform_line_range(
+        &mut self,
+        first: usize,
+        last: usize,
+        transform: impl FnOnce(&mut Vec<String>),
+    ) -> bool {
+        let start = self.lines[first].start;
+        let mut end = self.lines[last].end + 1;
+        if self.data.get(end - 1) == Some(&'\n') {
+            end -= 1;
+        }
+        let end = end.min(self.data.len()).max(start);
+
+        let text: String = self.data[start..end].iter().collect();
+        let mut lines: Vec<String> = text.split('\n').map(String::from).collect();
+        transform(&mut lines);
+        let transformed = lines.join("\n");
+
+        if transformed == text {
+            return false;
+        }
+
+        self.replace_range(start, end, &transformed);
+        self.move_cursor_to_position(Position::line_start(first));
+
+        true
+    }
+
+    /// Ends the current undo step, called once the edits of a command are done
+    pub fn commit_history(&mut self) {
+        self.history.commit(self.cursor_pos);
+    }
+
+    /// Reverts the last change, returns false if there is nothing to undo
+    pub fn undo(&mut self) -> bool {
+        self.commit_history();
+        let step = self.history.undo();
+        self.apply_history_step(step)
+    }
+
+    /// Applies the last undone change again, returns false if there is nothing to redo
+    pub fn redo(&mut self) -> bool {
+        self.commit_history();
+        let step = self.history.redo();
+        self.apply_history_step(step)
+    }
+
+    /// Brings the buffer back to the state `node` of the undo tree, returns false if it is already there
+    pub fn undo_jump(&mut self, node: usize) -> bool {
+        self.commit_history();
+        let step = self.history.jump(node);
+        self.apply_history_step(step)
+    }
+
+    /// Applies edits coming from the undo tree, without recording them again
+    fn apply_history_step(&mut self, step: Option<(Vec<Edit>, usize)>) -> bool {
+        let Some((edits, cursor)) = step else {
+            return false;
+        };
+
+        for Edit {
+            start,
+            removed,
+            inserted,
+        } in edits
+        {
+            let end = (start + removed.len()).min(self.data.len());
+            self.shift_marks(start, end - start, inserted.len());
+            self.data.splice(start..end, inserted);
+        }
+
+        self.cursor_pos = cursor.min(self.data.len());
+        self.goal_column = None;
+        self.selection_anchor = None;
+        self.mark_modified();
+        self.recalculate_lines();
+        self.scroll();
+
+        true
+    }
+
+    /// Returns the whole content of the buffer
+    pub fn text(&self) -> String {
+        self.data.iter().collect()
+    }
+
+    /// Returns the content of the file on disk, as it would be loaded into the buffer.
+    /// A file that does not exist yet is empty.
+    pub fn saved_text(&self) -> io::Result<String> {
+        match &self.file_path {
+            Some(path) if path.exists() => {
+                read_data(path).map(|(data, _)| data.into_iter().collect())
+            }
+            _ => Ok(String::new()),
+        }
+    }
+
+    /// Whether the file was changed by another program since the buffer read or saved it
+    pub fn changed_on_disk(&self) -> bool {
+        let Some(path) = &self.file_path else {
+            return false;
+        };
+        let modified = modified_time(path);
+        modified.is_some() && modified != self.disk_modified
+    }
+
+    /// Reads the file again, discarding the changes made in the buffer. Only the part that
+    /// differs is replaced, in one step that can be undone, so the cursor and the marks stay
+    /// where they are in the rest of the text.
+    pub fn reload(&mut self) -> io::Result<()> {
+        let Some(path) = self.file_path.clone() else {
+            return Ok(());
+        };
+        let (data, bom) = read_data(&path)?;
+        self.replace_text(&data);
+
+        self.bom = bom;
+        self.modified = false;
+        self.new_file = false;
+        self.disk_modified = modified_time(&path);
+        Ok(())
+    }
+
+    /// Replaces the text of the buffer with `data`. Only the part that differs is replaced, in
+    /// one step that can be undone. Returns false if there was no difference.
+    pub fn replace_text(&mut self, data: &[char]) -> bool {
+        let prefix = self
+            .data
+            .iter()
+            .zip(data)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = self.data[prefix..]
+            .iter()
+            .rev()
+            .zip(data[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if prefix + suffix >= self.data.len().max(data.len()) {
+            return false;
+        }
+
+        let text: String = data[prefix..data.len() - suffix].iter().collect();
+        self.replace_range(prefix, self.data.len() - suffix, &text);
+        self.commit_history();
+        self.scroll();
+        true
+    }
+
+    /// Directory the file of the buffer would be saved in, if it does not exist
+    pub fn missing_directory(&self) -> Option<&Path> {
+        let dir = self.file_path.as_deref()?.parent()?;
+        (!dir.as_os_str().is_empty() && !dir.exists()).then_some(dir)
     }
 
     /// Save the file if the buffer has a valid file_path
-    pub fn save(&self) {
+    pub fn save(&mut self) -> io::Result<()> {
         if let Some(path) = &self.file_path {
+            // Something else may have taken the place of the file since it was opened
+            if path.is_dir() {
+                return Err(io::Error::other(format!(
+                    "{} is a directory",
+                    path.display()
+                )));
+            }
+
             // save the data into the path
-            let content: String = self.data.iter().collect();
-            fs::write(path, content).expect("Failed to save file.");
+            let mut content = String::new();
+            if self.bom && self.keep_bom {
+                content.push(BOM);
+            }
+            content.extend(self.data.iter());
+            fs::write(path, content)?;
+            self.disk_modified = modified_time(path);
+            self.bom &= self.keep_bom;
+            self.modified = false;
+            self.new_file = false;
         }
+
+        Ok(())
     }
 }
+
+/// Returns when the file at `path` was last modified, if it can be told
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reads the file at `path` as buffer data, and whether it started with a byte order mark
+fn read_data(path: &Path) -> io::Result<(Vec<char>, bool)> {
+    Ok(decode(&fs::read(path)?))
+}
+
+/// Decodes the contents of a file as buffer data, and whether it started with a byte order mark.
+/// It is read as UTF-8, or byte by byte if it is not valid UTF-8.
+pub fn decode(bytes: &[u8]) -> (Vec<char>, bool) {
+    let (bytes, bom) = match bytes.strip_prefix(BOM_BYTES) {
+        Some(rest) => (rest, true),
+        None => (bytes, false),
+    };
+
+    let data = match std::str::from_utf8(bytes) {
+        Ok(text) => text.chars().collect(),
+        Err(_) => bytes.iter().map(|b| *b as char).collect::<Vec<_>>(),
+    };
+    (
+        data.into_iter().filter(|c| *c != '\r').collect(), // Convert CRLF to LF
+        bom,
+    )
+}
@@ -0,0 +1,41 @@
+use std::{
+    io,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use crate::shell::{self, ShellOutput};
+
+/// A build command running in the background
+pub struct Build {
+    pub command: String,
+    receiver: Receiver<io::Result<ShellOutput>>,
+}
+
+impl Build {
+    pub fn start(command: &str) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        let cmd = command.to_string();
+        thread::spawn(move || {
+            // The editor may have quit already, nobody to tell then
+            let _ = sender.send(shell::run(&cmd, None));
+        });
+
+        Self {
+            command: command.to_string(),
+            receiver,
+        }
+    }
+
+    /// Returns the result of the build once it is done
+    pub fn poll(&self) -> Option<io::Result<ShellOutput>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                Some(Err(io::Error::other("Build thread stopped unexpectedly")))
+            }
+        }
+    }
+}
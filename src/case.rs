@@ -0,0 +1,129 @@
+use crate::completion::is_word_char;
+
+/// A way of writing words, applied by the case commands to the selection or the word under
+/// the cursor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Case {
+    /// `UPPER CASE`
+    Upper,
+    /// `lower case`
+    Lower,
+    /// `Title Case`, every word starts with a capital and the rest is lower case
+    Title,
+    /// `snake_case`
+    Snake,
+    /// `camelCase`
+    Camel,
+}
+
+impl Case {
+    /// Returns the case of the command named `name`, e.g. `upper`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "upper" => Some(Self::Upper),
+            "lower" => Some(Self::Lower),
+            "title" => Some(Self::Title),
+            "snake" => Some(Self::Snake),
+            "camel" => Some(Self::Camel),
+            _ => None,
+        }
+    }
+
+    /// Rewrites every identifier in `text` in this case, leaving everything around them as is
+    pub fn apply(self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut identifier = String::new();
+
+        for ch in text.chars() {
+            if is_word_char(ch) {
+                identifier.push(ch);
+            } else {
+                result.push_str(&self.apply_to_identifier(&identifier));
+                identifier.clear();
+                result.push(ch);
+            }
+        }
+        result.push_str(&self.apply_to_identifier(&identifier));
+
+        result
+    }
+
+    fn apply_to_identifier(self, identifier: &str) -> String {
+        match self {
+            Self::Upper => identifier.to_uppercase(),
+            Self::Lower => identifier.to_lowercase(),
+            Self::Title => identifier
+                .split('_')
+                .map(capitalize)
+                .collect::<Vec<_>>()
+                .join("_"),
+            Self::Snake => split_words(identifier)
+                .iter()
+                .map(|w| w.to_lowercase())
+                .collect::<Vec<_>>()
+                .join("_"),
+            Self::Camel => split_words(identifier)
+                .iter()
+                .enumerate()
+                .map(|(i, w)| {
+                    if i == 0 {
+                        w.to_lowercase()
+                    } else {
+                        capitalize(w)
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Splits an identifier into its words, at underscores and at the humps of camel case.
+/// Acronyms stay together, e.g. `parseHTTPRequest` is `parse`, `HTTP` and `Request`.
+fn split_words(identifier: &str) -> Vec<String> {
+    let chars: Vec<char> = identifier.chars().collect();
+    let mut words = vec![];
+    let mut word = String::new();
+
+    for (i, ch) in chars.iter().enumerate() {
+        if *ch == '_' {
+            if !word.is_empty() {
+                words.push(word.clone());
+                word.clear();
+            }
+            continue;
+        }
+
+        let previous = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let hump = ch.is_uppercase()
+            && previous.is_some_and(|p| {
+                p.is_lowercase()
+                    || p.is_numeric()
+                    || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+            });
+
+        if hump && !word.is_empty() {
+            words.push(word.clone());
+            word.clear();
+        }
+        word.push(*ch);
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+/// Upper cases the first letter of `word` and lower cases the rest
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
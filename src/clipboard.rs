@@ -0,0 +1,237 @@
+use std::{
+    env,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
+
+/// Most text copied through the terminal by default. Terminals and multiplexers drop escape
+/// sequences longer than they are willing to hold, often around 100 kB once encoded.
+pub const DEFAULT_OSC52_LIMIT: usize = 74_000;
+
+/// Which of the desktop's selections to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selection {
+    /// The text selected last, pasted with the middle mouse button on X11 and Wayland
+    Primary,
+    /// What is copied and pasted with the keyboard
+    Clipboard,
+}
+
+/// A program sharing text with the other applications of the desktop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    /// `wl-copy` and `wl-paste` on Wayland
+    WlClipboard,
+    /// `xclip` on X11
+    Xclip,
+    /// `xsel` on X11
+    Xsel,
+    /// `pbcopy` on macOS, which has no primary selection
+    Pbcopy,
+}
+
+impl Tool {
+    /// Finds the first tool installed for the display server of the session
+    fn detect(selection: Selection) -> Option<Self> {
+        let candidates: &[(Self, &str)] = if env::var_os("WAYLAND_DISPLAY").is_some() {
+            &[
+                (Self::WlClipboard, "wl-copy"),
+                (Self::Xclip, "xclip"),
+                (Self::Xsel, "xsel"),
+            ]
+        } else if env::var_os("DISPLAY").is_some() {
+            &[(Self::Xclip, "xclip"), (Self::Xsel, "xsel")]
+        } else if cfg!(target_os = "macos") && selection == Selection::Clipboard {
+            &[(Self::Pbcopy, "pbcopy")]
+        } else {
+            &[]
+        };
+
+        candidates
+            .iter()
+            .find(|(_, program)| is_installed(program))
+            .map(|(tool, _)| *tool)
+    }
+
+    /// The command writing its input to `selection`
+    fn copy_command(self, selection: Selection) -> Command {
+        let primary = selection == Selection::Primary;
+        match self {
+            Self::WlClipboard if primary => with_args("wl-copy", &["--primary"]),
+            Self::WlClipboard => with_args("wl-copy", &[]),
+            Self::Xclip if primary => with_args("xclip", &["-selection", "primary", "-in"]),
+            Self::Xclip => with_args("xclip", &["-selection", "clipboard", "-in"]),
+            Self::Xsel if primary => with_args("xsel", &["--primary", "--input"]),
+            Self::Xsel => with_args("xsel", &["--clipboard", "--input"]),
+            Self::Pbcopy => with_args("pbcopy", &[]),
+        }
+    }
+
+    /// The command printing the primary selection
+    fn paste_command(self) -> Command {
+        match self {
+            Self::WlClipboard => with_args("wl-paste", &["--primary", "--no-newline"]),
+            Self::Xclip => with_args("xclip", &["-selection", "primary", "-out"]),
+            Self::Xsel => with_args("xsel", &["--primary", "--output"]),
+            Self::Pbcopy => with_args("pbpaste", &[]),
+        }
+    }
+
+    /// Writes `text` to `selection`
+    fn copy(self, selection: Selection, text: &str) -> std::io::Result<()> {
+        let mut child = self
+            .copy_command(selection)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        // The tools stay around to serve the selection, don't wait on them here
+        thread::spawn(move || child.wait());
+
+        Ok(())
+    }
+}
+
+/// The primary selection of X11 and Wayland: the text selected last, pasted with the middle
+/// mouse button. Without a display server or a tool to reach it, it is only kept in the editor.
+#[derive(Debug, Default)]
+pub struct PrimarySelection {
+    tool: Option<Tool>,
+    /// What was selected last in the editor
+    text: Option<String>,
+}
+
+impl PrimarySelection {
+    /// Shares the primary selection with the desktop if there is a tool for it
+    pub fn detect() -> Self {
+        Self {
+            tool: Tool::detect(Selection::Primary),
+            text: None,
+        }
+    }
+
+    /// Whether `text` is what was selected last in the editor
+    pub fn holds(&self, text: &str) -> bool {
+        self.text.as_deref() == Some(text)
+    }
+
+    /// Makes `text` the primary selection
+    pub fn set(&mut self, text: String) {
+        if let Some(tool) = self.tool {
+            // Only a convenience, nothing to tell if it fails
+            let _ = tool.copy(Selection::Primary, &text);
+        }
+
+        self.text = Some(text);
+    }
+
+    /// Returns the primary selection, which may come from another application
+    pub fn get(&self) -> Option<String> {
+        let from_tool = self.tool.and_then(|tool| {
+            let output = tool.paste_command().stderr(Stdio::null()).output().ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+        });
+
+        from_tool.or_else(|| self.text.clone())
+    }
+}
+
+/// The clipboard of the desktop, reached through a tool where the editor runs, or through the
+/// terminal with OSC 52 escape sequences when there is none, e.g. over SSH
+#[derive(Debug, Default)]
+pub struct Clipboard {
+    tool: Option<Tool>,
+    /// Whether text can be copied through the terminal
+    osc52: bool,
+    /// Most bytes of text copied through the terminal at once
+    osc52_limit: usize,
+    /// Text waiting to be written to the terminal by the display
+    pending: Option<String>,
+}
+
+impl Clipboard {
+    /// Uses the tool found for the session, or the terminal if there is none or the session
+    /// is over SSH, where the tool would copy on the remote machine. Dumb terminals are not
+    /// used. `osc52` forces using the terminal or not.
+    pub fn detect(osc52: Option<bool>, osc52_limit: usize) -> Self {
+        let remote = env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some();
+        let dumb = env::var("TERM").is_ok_and(|term| term == "dumb");
+        let tool = Tool::detect(Selection::Clipboard).filter(|_| !remote && osc52 != Some(true));
+
+        Self {
+            tool,
+            osc52: osc52.unwrap_or(tool.is_none() && !dumb),
+            osc52_limit,
+            pending: None,
+        }
+    }
+
+    /// Copies `text` to the clipboard, or tells why it could not be
+    pub fn copy(&mut self, text: String) -> Result<(), String> {
+        if let Some(tool) = self.tool {
+            return tool
+                .copy(Selection::Clipboard, &text)
+                .map_err(|e| format!("Failed to copy : {e}"));
+        }
+
+        if !self.osc52 {
+            return Err("No clipboard to copy to".to_string());
+        }
+        if text.len() > self.osc52_limit {
+            return Err(format!(
+                "Too much text to copy through the terminal : {} bytes, at most {}",
+                text.len(),
+                self.osc52_limit
+            ));
+        }
+        self.pending = Some(text);
+        Ok(())
+    }
+
+    /// Takes the text to write to the terminal with OSC 52
+    pub fn take_pending(&mut self) -> Option<String> {
+        self.pending.take()
+    }
+}
+
+/// Encodes `bytes` in base64, as OSC 52 expects them
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn with_args(program: &str, args: &[&str]) -> Command {
+    let mut command = Command::new(program);
+    command.args(args);
+    command
+}
+
+/// Whether `program` is found in one of the directories of `PATH`
+fn is_installed(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|dir| Path::new(&dir).join(program).is_file())
+    })
+}
@@ -0,0 +1,202 @@
+//! Dates and times as people read them, in the local time zone when the system can tell it.
+//!
+//! The time zone comes from the C library on Unix, elsewhere times are in UTC.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+#[cfg(unix)]
+mod unix {
+    use std::os::raw::{c_char, c_int, c_long};
+
+    /// `struct tm`, with the fields glibc, musl, macOS and the BSDs add at its end
+    #[repr(C)]
+    pub struct Tm {
+        pub tm_sec: c_int,
+        pub tm_min: c_int,
+        pub tm_hour: c_int,
+        pub tm_mday: c_int,
+        pub tm_mon: c_int,
+        pub tm_year: c_int,
+        pub tm_wday: c_int,
+        pub tm_yday: c_int,
+        pub tm_isdst: c_int,
+        pub tm_gmtoff: c_long,
+        pub tm_zone: *const c_char,
+    }
+
+    extern "C" {
+        pub fn tzset();
+        pub fn localtime_r(time: *const c_long, result: *mut Tm) -> *mut Tm;
+    }
+}
+
+/// A date and time of day, with the offset of its time zone from UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    /// From 1 to 12
+    pub month: u32,
+    /// From 1 to 31
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// From 0 for Sunday to 6 for Saturday
+    pub weekday: u32,
+    /// Seconds east of UTC
+    pub offset: i64,
+}
+
+impl DateTime {
+    /// The current date and time, in the local time zone
+    pub fn now() -> Self {
+        Self::local(SystemTime::now())
+    }
+
+    /// `time` in the local time zone, or in UTC if it cannot be told
+    pub fn local(time: SystemTime) -> Self {
+        #[cfg(unix)]
+        if let Some(local) = Self::from_c_library(time) {
+            return local;
+        }
+
+        Self::utc(time)
+    }
+
+    #[cfg(unix)]
+    fn from_c_library(time: SystemTime) -> Option<Self> {
+        let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let secs = std::os::raw::c_long::try_from(secs).ok()?;
+
+        // SAFETY: `tm` is only read once `localtime_r` filled it, it does not keep the pointers
+        let tm = unsafe {
+            let mut tm = std::mem::zeroed::<unix::Tm>();
+            unix::tzset();
+            if unix::localtime_r(&secs, &mut tm).is_null() {
+                return None;
+            }
+            tm
+        };
+
+        Some(Self {
+            year: i64::from(tm.tm_year) + 1900,
+            month: u32::try_from(tm.tm_mon + 1).ok()?,
+            day: u32::try_from(tm.tm_mday).ok()?,
+            hour: u32::try_from(tm.tm_hour).ok()?,
+            minute: u32::try_from(tm.tm_min).ok()?,
+            // A leap second is shown as the last second of its minute
+            second: u32::try_from(tm.tm_sec.min(59)).ok()?,
+            weekday: u32::try_from(tm.tm_wday).ok()?,
+            // `c_long` is narrower on 32 bit targets
+            #[allow(clippy::useless_conversion)]
+            offset: i64::from(tm.tm_gmtoff),
+        })
+    }
+
+    /// `time` in UTC
+    pub fn utc(time: SystemTime) -> Self {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+        // From the days since the epoch to the civil date, see
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+
+        Self {
+            year: yoe + era * 400 + i64::from(month <= 2),
+            month: month as u32,
+            day: day as u32,
+            hour: (secs / 3600) as u32,
+            minute: (secs / 60 % 60) as u32,
+            second: (secs % 60) as u32,
+            // The epoch was a Thursday
+            weekday: (days + 4).rem_euclid(7) as u32,
+            offset: 0,
+        }
+    }
+
+    /// Formats the date like `strftime` does, with `%Y` the year, `%m` the month, `%d` the day,
+    /// `%H`, `%M` and `%S` the time of day, `%F` for `%Y-%m-%d`, `%T` for `%H:%M:%S` and so on.
+    /// Unknown sequences are kept as they are.
+    pub fn format(&self, format: &str) -> String {
+        let mut text = String::new();
+        let mut chars = format.chars();
+
+        while let Some(ch) = chars.next() {
+            if ch != '%' {
+                text.push(ch);
+                continue;
+            }
+
+            let weekday = WEEKDAYS[self.weekday as usize % 7];
+            let month = MONTHS[(self.month as usize + 11) % 12];
+            match chars.next() {
+                Some('Y') => text.push_str(&format!("{:04}", self.year)),
+                Some('y') => text.push_str(&format!("{:02}", self.year.rem_euclid(100))),
+                Some('m') => text.push_str(&format!("{:02}", self.month)),
+                Some('d') => text.push_str(&format!("{:02}", self.day)),
+                Some('e') => text.push_str(&format!("{:>2}", self.day)),
+                Some('H') => text.push_str(&format!("{:02}", self.hour)),
+                Some('I') => text.push_str(&format!("{:02}", (self.hour + 11) % 12 + 1)),
+                Some('p') => text.push_str(if self.hour < 12 { "AM" } else { "PM" }),
+                Some('M') => text.push_str(&format!("{:02}", self.minute)),
+                Some('S') => text.push_str(&format!("{:02}", self.second)),
+                Some('A') => text.push_str(weekday),
+                Some('a') => text.push_str(&weekday[..3]),
+                Some('B') => text.push_str(month),
+                Some('b') => text.push_str(&month[..3]),
+                Some('F') => text.push_str(&self.format("%Y-%m-%d")),
+                Some('T') => text.push_str(&self.format("%H:%M:%S")),
+                Some('R') => text.push_str(&self.format("%H:%M")),
+                Some('z') => {
+                    let sign = if self.offset < 0 { '-' } else { '+' };
+                    let minutes = self.offset.abs() / 60;
+                    text.push_str(&format!("{sign}{:02}{:02}", minutes / 60, minutes % 60));
+                }
+                Some('%') => text.push('%'),
+                Some(other) => {
+                    text.push('%');
+                    text.push(other);
+                }
+                None => text.push('%'),
+            }
+        }
+
+        text
+    }
+}
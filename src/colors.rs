@@ -0,0 +1,126 @@
+use std::env;
+
+use crossterm::style::{Attribute, Color};
+
+/// Levels of each component in the 6x6x6 color cube of the 256-color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The 16 colors of basic terminals, with the values xterm gives them
+const BASIC_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (205, 0, 0)),
+    (Color::DarkGreen, (0, 205, 0)),
+    (Color::DarkYellow, (205, 205, 0)),
+    (Color::DarkBlue, (0, 0, 238)),
+    (Color::DarkMagenta, (205, 0, 205)),
+    (Color::DarkCyan, (0, 205, 205)),
+    (Color::Grey, (229, 229, 229)),
+    (Color::DarkGrey, (127, 127, 127)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (92, 92, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The colors a terminal can show. The theme is made of RGB colors, they are turned into the
+/// nearest ones of the palette the terminal has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSupport {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    /// Only the default colors, what stands out is shown in reverse video
+    NoColor,
+}
+
+impl ColorSupport {
+    /// Guesses what the terminal supports from the environment: `NO_COLOR`, `COLORTERM` and
+    /// the terminal name in `TERM`
+    pub fn detect() -> Self {
+        let var = |name| env::var(name).unwrap_or_default();
+        Self::from_env(&var("NO_COLOR"), &var("COLORTERM"), &var("TERM"))
+    }
+
+    /// Same as `detect`, given the values of `NO_COLOR`, `COLORTERM` and `TERM`
+    pub fn from_env(no_color: &str, color_term: &str, term: &str) -> Self {
+        if !no_color.is_empty() || term == "dumb" {
+            Self::NoColor
+        } else if matches!(color_term, "truecolor" | "24bit") || cfg!(windows) {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    /// Finds the support set as `colors` in the `[theme]` section: `truecolor`, `256`, `16`
+    /// or `none`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "truecolor" | "24bit" => Some(Self::TrueColor),
+            "256" => Some(Self::Ansi256),
+            "16" => Some(Self::Ansi16),
+            "none" => Some(Self::NoColor),
+            _ => None,
+        }
+    }
+
+    /// Returns the color of the palette nearest to `color`
+    pub fn convert(self, color: Color) -> Color {
+        match (self, color) {
+            (Self::NoColor, _) => Color::Reset,
+            (Self::Ansi256, Color::Rgb { r, g, b }) => Color::AnsiValue(nearest_ansi256(r, g, b)),
+            (Self::Ansi16, Color::Rgb { r, g, b }) => nearest_basic(r, g, b),
+            _ => color,
+        }
+    }
+
+    /// Reverse video for what would stand out by its background color, without colors
+    pub fn emphasis(self, emphasized: bool) -> Attribute {
+        if self == Self::NoColor && emphasized {
+            Attribute::Reverse
+        } else {
+            Attribute::NoReverse
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+/// Index of the nearest color of the color cube or of the gray ramp of the 256-color palette
+fn nearest_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |c: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|i| (CUBE_LEVELS[*i] as i32 - c as i32).abs())
+            .unwrap_or(0)
+    };
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+
+    // Grays go from 8 to 238 in steps of 10
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * gray_step;
+    let gray = (gray_level, gray_level, gray_level);
+
+    if distance(gray, (r, g, b)) < distance(cube, (r, g, b)) {
+        232 + gray_step
+    } else {
+        cube_index as u8
+    }
+}
+
+fn nearest_basic(r: u8, g: u8, b: u8) -> Color {
+    BASIC_COLORS
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
@@ -0,0 +1,267 @@
+use crate::{case::Case, encoding::Encoding};
+
+/// A command entered in the command prompt
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `!cmd` runs a shell command and shows its output in a scratch buffer
+    Shell(String),
+    /// `|cmd` pipes the selection, or the whole buffer, through a shell command and replaces it
+    /// with the output
+    Pipe(String),
+    /// `build` runs the build command from the config in the background
+    Build,
+    /// `grep <pattern>` searches the files under the working directory with the grep command
+    /// from the config and lists the matches in the quickfix list
+    Grep(String),
+    /// `diagnostics` lists the diagnostics of the open buffers in the quickfix list
+    Diagnostics,
+    /// `quickfix` or `copen` shows the quickfix list again
+    Quickfix,
+    /// `format` pipes the buffer through the formatter configured for its file type
+    Format,
+    /// `undotree` lists the states of the buffer's undo tree in a scratch buffer
+    UndoTree,
+    /// `diff` shows the unsaved changes of the buffer in a scratch buffer
+    Diff,
+    /// `history` lists the versions of the buffer's file kept each time it was saved
+    History,
+    /// `diffget` replaces the change under the cursor with the other side of the comparison
+    DiffGet,
+    /// `diffput` copies the change under the cursor to the other side of the comparison
+    DiffPut,
+    /// `rename` asks for a new name for the word under the cursor and replaces all its
+    /// occurrences in the buffer
+    Rename,
+    /// `upper`, `lower`, `title`, `snake` or `camel` rewrites the selection, or the word under
+    /// the cursor, in that case
+    Case(Case),
+    /// `sort` or `sort desc` sorts the selected lines, or all of them, with numbers in
+    /// numeric order
+    Sort { descending: bool },
+    /// `uniq` removes the selected lines, or any line, equal to the line before them
+    Unique,
+    /// `reindent` indents the selected lines, or all of them, after the nesting of their
+    /// brackets or with the indenter configured for the file type
+    Reindent,
+    /// `insert <template>` inserts a template at the cursor, e.g. `insert date`
+    Insert(String),
+    /// `reflow` rewraps the paragraph under the cursor, or the selected lines, to the text width
+    Reflow,
+    /// `json` pretty-prints the selected JSON, or the whole JSON buffer, with the indentation
+    /// of the buffer, and `json minify` puts it on a single line
+    Json { minify: bool },
+    /// `count` shows the number of lines, words and characters of the buffer and the selection
+    Count,
+    /// `offset` shows the offset in bytes of the cursor into the file, `offset <n>` moves the
+    /// cursor to the character at that offset, given in decimal or in hexadecimal like `0x1f`
+    Offset(Option<usize>),
+    /// `outline` opens the picker listing the definitions or headings of the buffer, to jump to one
+    Outline,
+    /// `char` opens the character picker to insert a character found by its name
+    Character,
+    /// `scratch` opens a new empty buffer without a file
+    Scratch,
+    /// `buffers` or `ls` lists the open buffers in a scratch buffer
+    Buffers,
+    /// `set <name> <value>` changes a setting of the buffer, `set` alone shows them
+    Set(Option<(String, String)>),
+    /// `init-config` writes the default config into the config directory and opens it
+    InitConfig,
+    /// `trust` lets the config of the current project run programs, like language servers
+    Trust,
+    /// `cd <path>` changes the working directory, `cd` alone goes to the home directory
+    ChangeDirectory(Option<String>),
+    /// `read <path>` or `r <path>` inserts the contents of a file at the cursor, decoded from
+    /// UTF-8 unless given another encoding like `r ++enc=latin1 <path>`
+    Read { path: String, encoding: Encoding },
+    /// `reload` or `e!` reads the file again, discarding the changes made in the buffer
+    Reload,
+    /// `wa` saves every buffer with unsaved changes, `wqa` then quits unless one failed
+    SaveAll { quit: bool },
+    /// `w <path>` writes the selection, or the whole buffer, to another file and
+    /// `10,50w <path>` writes lines 10 to 50. `w!` replaces a file that already exists.
+    Write {
+        /// First and last line, counted from 1
+        lines: Option<(usize, usize)>,
+        path: String,
+        overwrite: bool,
+    },
+}
+
+impl Command {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+
+        match text {
+            "build" => return Ok(Self::Build),
+            "format" => return Ok(Self::Format),
+            "diagnostics" => return Ok(Self::Diagnostics),
+            "quickfix" | "copen" => return Ok(Self::Quickfix),
+            "undotree" => return Ok(Self::UndoTree),
+            "diff" => return Ok(Self::Diff),
+            "history" => return Ok(Self::History),
+            "diffget" => return Ok(Self::DiffGet),
+            "diffput" => return Ok(Self::DiffPut),
+            "rename" => return Ok(Self::Rename),
+            "sort" => return Ok(Self::Sort { descending: false }),
+            "sort desc" => return Ok(Self::Sort { descending: true }),
+            "uniq" => return Ok(Self::Unique),
+            "reindent" => return Ok(Self::Reindent),
+            "reflow" => return Ok(Self::Reflow),
+            "json" | "json pretty" => return Ok(Self::Json { minify: false }),
+            "json minify" => return Ok(Self::Json { minify: true }),
+            "count" => return Ok(Self::Count),
+            "char" => return Ok(Self::Character),
+            "outline" => return Ok(Self::Outline),
+            "scratch" => return Ok(Self::Scratch),
+            "buffers" | "ls" => return Ok(Self::Buffers),
+            "reload" | "e!" => return Ok(Self::Reload),
+            "init-config" => return Ok(Self::InitConfig),
+            "trust" => return Ok(Self::Trust),
+            "wa" | "wall" => return Ok(Self::SaveAll { quit: false }),
+            "wqa" | "xa" => return Ok(Self::SaveAll { quit: true }),
+            _ => (),
+        }
+
+        if text == "set" {
+            return Ok(Self::Set(None));
+        }
+        if let Some(rest) = text.strip_prefix("set ") {
+            let mut words = rest.split_whitespace();
+            let name = words.next().unwrap_or_default();
+            let value = words.collect::<Vec<_>>().join(" ");
+            if value.is_empty() {
+                return Err(format!("No value given for {name}"));
+            }
+            return Ok(Self::Set(Some((name.to_string(), value))));
+        }
+
+        if text == "insert" {
+            return Err("No template given".to_string());
+        }
+        if let Some(name) = text.strip_prefix("insert ") {
+            return Ok(Self::Insert(name.trim().to_string()));
+        }
+
+        if text == "grep" {
+            return Err("No pattern given".to_string());
+        }
+        if let Some(pattern) = text.strip_prefix("grep ") {
+            return Ok(Self::Grep(pattern.trim().to_string()));
+        }
+
+        if text == "offset" {
+            return Ok(Self::Offset(None));
+        }
+        if let Some(rest) = text.strip_prefix("offset ") {
+            let rest = rest.trim();
+            let offset = match rest.strip_prefix("0x").or_else(|| rest.strip_prefix("0X")) {
+                Some(hex) => usize::from_str_radix(hex, 16),
+                None => rest.parse(),
+            };
+            return offset
+                .map(|offset| Self::Offset(Some(offset)))
+                .map_err(|_| format!("Invalid offset : {rest}"));
+        }
+
+        if text == "cd" {
+            return Ok(Self::ChangeDirectory(None));
+        }
+        if let Some(path) = text.strip_prefix("cd ") {
+            return Ok(Self::ChangeDirectory(Some(path.trim().to_string())));
+        }
+
+        if let Some(rest) = text
+            .strip_prefix("read ")
+            .or_else(|| text.strip_prefix("r "))
+        {
+            let rest = rest.trim_start();
+            let (encoding, path) = match rest.strip_prefix("++enc=") {
+                Some(rest) => {
+                    let (name, path) = rest.split_once(' ').unwrap_or((rest, ""));
+                    let encoding = Encoding::from_name(name)
+                        .ok_or_else(|| format!("Unknown encoding : {name}"))?;
+                    (encoding, path.trim())
+                }
+                None => (Encoding::default(), rest),
+            };
+            if path.is_empty() {
+                return Err("No file given".to_string());
+            }
+            return Ok(Self::Read {
+                path: path.to_string(),
+                encoding,
+            });
+        }
+
+        if let Some(write) = parse_write(text) {
+            return write;
+        }
+
+        if let Some(case) = Case::from_name(text) {
+            return Ok(Self::Case(case));
+        }
+
+        let (command, rest): (fn(String) -> Self, &str) = if let Some(rest) = text.strip_prefix('!')
+        {
+            (Self::Shell, rest)
+        } else if let Some(rest) = text.strip_prefix('|') {
+            (Self::Pipe, rest)
+        } else if text.is_empty() {
+            return Err("No command given".to_string());
+        } else {
+            return Err(format!("Unknown command : {text}"));
+        };
+
+        let rest = rest.trim();
+        if rest.is_empty() {
+            return Err("No shell command given".to_string());
+        }
+
+        Ok(command(rest.to_string()))
+    }
+}
+
+/// Parses `[first,last]w[!] <path>` or `write[!] <path>`, returns `None` for other commands
+fn parse_write(text: &str) -> Option<Result<Command, String>> {
+    let digits = text
+        .find(|c: char| !c.is_ascii_digit() && c != ',')
+        .unwrap_or(text.len());
+    let (range, rest) = text.split_at(digits);
+
+    let rest = rest
+        .strip_prefix("write")
+        .or_else(|| rest.strip_prefix('w'))?;
+    let (overwrite, rest) = match rest.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    // Some other command starting with a w
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+
+    let lines = if range.is_empty() {
+        None
+    } else {
+        let parsed = range
+            .split_once(',')
+            .and_then(|(first, last)| Some((first.parse().ok()?, last.parse().ok()?)))
+            .filter(|(first, last): &(usize, usize)| *first > 0 && first <= last);
+        match parsed {
+            Some(lines) => Some(lines),
+            None => return Some(Err(format!("Invalid line range : {range}"))),
+        }
+    };
+
+    let path = rest.trim();
+    if path.is_empty() {
+        return Some(Err("No file given".to_string()));
+    }
+
+    Some(Ok(Command::Write {
+        lines,
+        path: path.to_string(),
+        overwrite,
+    }))
+}
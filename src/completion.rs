@@ -0,0 +1,146 @@
+#![allow(dead_code)]
+use std::collections::HashMap;
+
+use crate::buffer::Buffer;
+
+/// Maximum number of suggestions shown in the popup at once
+pub const MAX_ITEMS: usize = 8;
+
+/// Characters splitting words unless set otherwise: the ASCII punctuation but `_`
+pub const DEFAULT_WORD_SEPARATORS: &str = "`~!@#$%^&*()-=+[{]}\\|;:'\",.<>/?";
+
+/// Returns true if `ch` can be a part of a word
+pub fn is_word_char(ch: char) -> bool {
+    is_word_char_with(ch, DEFAULT_WORD_SEPARATORS)
+}
+
+/// Returns true if `ch` can be a part of a word when the characters of `separators` split
+/// words: letters, digits and the ASCII punctuation left out of `separators` can. Whitespace and
+/// the other symbols always split words.
+pub fn is_word_char_with(ch: char, separators: &str) -> bool {
+    (ch.is_alphanumeric() || ch.is_ascii_punctuation()) && !separators.contains(ch)
+}
+
+/// Index of all the words present in a set of buffers, along with how often they occur.
+pub struct WordIndex {
+    words: HashMap<String, usize>,
+}
+
+impl WordIndex {
+    pub fn from_buffers(buffers: &[&Buffer]) -> Self {
+        let mut words = HashMap::new();
+        let mut word = String::new();
+
+        for buffer in buffers {
+            for ch in buffer.data.iter().chain(std::iter::once(&' ')) {
+                if buffer.is_word_char(*ch) {
+                    word.push(*ch);
+                } else if !word.is_empty() {
+                    // Single letters are never worth completing
+                    if word.chars().count() > 1 {
+                        *words.entry(word.clone()).or_insert(0) += 1;
+                    }
+                    word.clear();
+                }
+            }
+        }
+
+        Self { words }
+    }
+
+    /// Returns the words starting with `prefix`, most frequent first.
+    /// The prefix itself is never suggested.
+    pub fn matches(&self, prefix: &str) -> Vec<String> {
+        let mut matches: Vec<(&String, &usize)> = self
+            .words
+            .iter()
+            .filter(|(word, _)| word.starts_with(prefix) && word.as_str() != prefix)
+            .collect();
+
+        matches.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+        matches.into_iter().map(|(word, _)| word.clone()).collect()
+    }
+}
+
+/// State of an open completion popup
+pub struct Completion {
+    /// Index into the buffer data where the word being completed begins
+    pub start: usize,
+    /// The word typed so far
+    pub prefix: String,
+    /// All candidates matching `prefix`
+    pub items: Vec<String>,
+    /// Index of the highlighted candidate
+    pub selected: usize,
+    index: WordIndex,
+}
+
+impl Completion {
+    /// Opens a completion for the word before the cursor of `buffer`.
+    /// Returns `None` if there is nothing to complete.
+    pub fn new(buffer: &Buffer, buffers: &[&Buffer]) -> Option<Self> {
+        let start = buffer.word_start_before_cursor();
+        let index = WordIndex::from_buffers(buffers);
+
+        let mut completion = Self {
+            start,
+            prefix: String::new(),
+            items: vec![],
+            selected: 0,
+            index,
+        };
+        completion.update(buffer);
+
+        if completion.items.is_empty() {
+            None
+        } else {
+            Some(completion)
+        }
+    }
+
+    /// Refilters the candidates after the word before the cursor changed.
+    /// Returns false if the completion is no longer valid and should be closed.
+    pub fn update(&mut self, buffer: &Buffer) -> bool {
+        if buffer.cursor_pos < self.start || buffer.word_start_before_cursor() != self.start {
+            return false;
+        }
+
+        let prefix: String = buffer.data[self.start..buffer.cursor_pos].iter().collect();
+        if self.items.is_empty() || prefix != self.prefix {
+            self.items = self.index.matches(&prefix);
+            self.prefix = prefix;
+            self.selected = 0;
+        }
+
+        !self.items.is_empty()
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + 1) % self.items.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+        }
+    }
+
+    /// The candidates that fit in the popup, keeping `selected` visible
+    pub fn visible_items(&self) -> (&[String], usize) {
+        let first = self.selected.saturating_sub(MAX_ITEMS - 1);
+        let last = (first + MAX_ITEMS).min(self.items.len());
+        (&self.items[first..last], self.selected - first)
+    }
+
+    /// Replaces the typed prefix in `buffer` with the selected candidate
+    pub fn accept(&self, buffer: &mut Buffer) {
+        if let Some(word) = self.items.get(self.selected) {
+            for ch in word.chars().skip(self.prefix.chars().count()) {
+                buffer.insert_ch(ch);
+            }
+        }
+    }
+}
@@ -0,0 +1,400 @@
+#![allow(dead_code)]
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    paths::{get_config_dir, get_data_dir},
+    project,
+};
+
+/// Name of the user configuration file inside the config directory
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Name of the project configuration file, looked up at the root of the project
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".tte.toml";
+
+/// Name of the file in the data directory listing the roots of the projects whose config may
+/// run programs, one per line
+pub const TRUSTED_PROJECTS_FILE_NAME: &str = "trusted_projects";
+
+/// Sections of the project configuration naming programs to run, or where files are written,
+/// only read once the project is trusted: a cloned repository should not run anything or write
+/// anywhere just by opening one of its files
+pub const COMMAND_SECTIONS: [&str; 8] = [
+    "build", "format", "grep", "history", "hooks", "indent", "keys", "lsp",
+];
+
+/// The configuration written by `tte --init-config`, with every setting commented out
+pub const DEFAULT_CONFIG: &str = include_str!("default_config.toml");
+
+/// How often the configuration files are checked for changes
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A value in the configuration file
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<ConfigValue>),
+}
+
+/// User configuration, read from a small subset of TOML:
+/// `[section]` headers followed by `key = value` pairs, where a value is a
+/// string, an integer, a boolean or an array of those.
+///
+/// ```toml
+/// [lsp]
+/// rust = "rust-analyzer"
+/// ```
+///
+/// Keys before the first header belong to the section `""`.
+#[derive(Debug, Default)]
+pub struct Config {
+    sections: HashMap<String, HashMap<String, ConfigValue>>,
+    /// Sections of the project configuration left out because the project is not trusted
+    untrusted: Vec<String>,
+}
+
+impl Config {
+    /// Path of the user configuration file, if a config directory can be found
+    pub fn path() -> Option<PathBuf> {
+        get_config_dir().map(|dir| dir.join(CONFIG_FILE_NAME))
+    }
+
+    /// Path of the project configuration file: at the root of the project tte was started in,
+    /// found with the root markers set in `user`, or in the current directory outside of one
+    pub fn project_path(user: &Config) -> PathBuf {
+        Self::project_root(user).join(PROJECT_CONFIG_FILE_NAME)
+    }
+
+    /// Root of the project tte was started in, or the current directory outside of one
+    pub fn project_root(user: &Config) -> PathBuf {
+        project::current_root(user).unwrap_or_default()
+    }
+
+    /// Path of the list of trusted projects, if a data directory can be found
+    pub fn trusted_projects_path() -> Option<PathBuf> {
+        get_data_dir().map(|dir| dir.join(TRUSTED_PROJECTS_FILE_NAME))
+    }
+
+    /// Loads the user configuration file, then the project configuration file on top of it.
+    /// The sections of the project file running programs are left out unless the project is
+    /// trusted, see `trust`.
+    /// Missing files are not an error and result in the default configuration.
+    pub fn load() -> Result<Self, String> {
+        let mut config = match Self::path() {
+            Some(path) => Self::read(&path)?,
+            None => Self::default(),
+        };
+
+        let root = Self::project_root(&config);
+        let trusted = Self::trusted_projects_path().is_some_and(|list| is_trusted(&root, &list));
+        config.merge_project(Self::read(&Self::project_path(&config))?, trusted);
+
+        Ok(config)
+    }
+
+    /// Writes the default configuration into the config directory, creating it if needed.
+    /// Returns the path of the file, an existing one is left as it is.
+    pub fn init() -> Result<PathBuf, String> {
+        let path = Self::path().ok_or("No config directory")?;
+        Self::write_default(&path)?;
+        Ok(path)
+    }
+
+    /// Writes the default configuration to `path`, unless there is a file there already
+    pub fn write_default(path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {} : {e}", dir.display()))?;
+        }
+
+        let mut file = match File::create_new(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                return Err(format!("{} already exists", path.display()))
+            }
+            Err(e) => return Err(format!("Failed to create {} : {e}", path.display())),
+        };
+        file.write_all(DEFAULT_CONFIG.as_bytes())
+            .map_err(|e| format!("Failed to write {} : {e}", path.display()))
+    }
+
+    fn read(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map_err(|e| format!("{}: {e}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {e}", path.display())),
+        }
+    }
+
+    /// Adds the values of the project configuration `project`, replacing the ones already set.
+    /// Unless the project is `trusted`, the sections running programs are left out and listed
+    /// by `untrusted`.
+    pub fn merge_project(&mut self, mut project: Self, trusted: bool) {
+        if !trusted {
+            let mut left_out: Vec<String> = project
+                .sections
+                .keys()
+                .filter(|name| COMMAND_SECTIONS.contains(&name.as_str()))
+                .cloned()
+                .collect();
+            left_out.sort();
+            for name in &left_out {
+                project.sections.remove(name);
+            }
+            self.untrusted = left_out;
+        }
+        self.merge(project);
+    }
+
+    /// Sections of the project configuration left out because the project is not trusted
+    pub fn untrusted(&self) -> &[String] {
+        &self.untrusted
+    }
+
+    /// Adds the values of `other`, replacing the ones already set
+    pub fn merge(&mut self, other: Self) {
+        for (name, values) in other.sections {
+            self.sections.entry(name).or_default().extend(values);
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut section = String::new();
+
+        for (line_idx, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                let Some(name) = header.strip_suffix(']') else {
+                    return Err(format!(
+                        "line {}: unterminated section header",
+                        line_idx + 1
+                    ));
+                };
+                section = name.trim().to_string();
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected `key = value`", line_idx + 1));
+            };
+
+            let key = key.trim().trim_matches('"').to_string();
+            let value = parse_value(value.trim())
+                .ok_or_else(|| format!("line {}: invalid value for `{key}`", line_idx + 1))?;
+
+            config
+                .sections
+                .entry(section.clone())
+                .or_default()
+                .insert(key, value);
+        }
+
+        Ok(config)
+    }
+
+    pub fn section(&self, name: &str) -> Option<&HashMap<String, ConfigValue>> {
+        self.sections.get(name)
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&ConfigValue> {
+        self.sections.get(section).and_then(|s| s.get(key))
+    }
+
+    pub fn get_str(&self, section: &str, key: &str) -> Option<&str> {
+        match self.get(section, key) {
+            Some(ConfigValue::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn get_int(&self, section: &str, key: &str) -> Option<i64> {
+        match self.get(section, key) {
+            Some(ConfigValue::Integer(i)) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, section: &str, key: &str) -> Option<bool> {
+        match self.get(section, key) {
+            Some(ConfigValue::Boolean(b)) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the project at `root` is in the list of trusted projects at `list`
+pub fn is_trusted(root: &Path, list: &Path) -> bool {
+    fs::read_to_string(list).is_ok_and(|text| text.lines().any(|line| Path::new(line) == root))
+}
+
+/// Adds the project at `root` to the list of trusted projects at `list`, so that its
+/// configuration can run programs
+pub fn trust(root: &Path, list: &Path) -> Result<(), String> {
+    if is_trusted(root, list) {
+        return Ok(());
+    }
+    if let Some(dir) = list.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {} : {e}", dir.display()))?;
+    }
+
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(list)
+        .and_then(|mut file| writeln!(file, "{}", root.display()))
+        .map_err(|e| format!("Failed to write {} : {e}", list.display()))
+}
+
+/// Removes a `#` comment from the line, ignoring `#` inside strings
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, ch) in line.char_indices() {
+        match ch {
+            '\\' if in_string => escaped = !escaped,
+            '"' if !escaped => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => escaped = false,
+        }
+    }
+
+    line
+}
+
+fn parse_value(text: &str) -> Option<ConfigValue> {
+    if let Some(inner) = text.strip_prefix('"') {
+        return parse_string(inner).map(ConfigValue::String);
+    }
+
+    if let Some(inner) = text.strip_prefix('[') {
+        let inner = inner.strip_suffix(']')?.trim();
+        if inner.is_empty() {
+            return Some(ConfigValue::Array(vec![]));
+        }
+
+        return split_array_items(inner)
+            .into_iter()
+            .map(|item| parse_value(item.trim()))
+            .collect::<Option<Vec<_>>>()
+            .map(ConfigValue::Array);
+    }
+
+    match text {
+        "true" => Some(ConfigValue::Boolean(true)),
+        "false" => Some(ConfigValue::Boolean(false)),
+        _ => text.replace('_', "").parse().ok().map(ConfigValue::Integer),
+    }
+}
+
+/// Parses the rest of a basic string after its opening quote
+fn parse_string(text: &str) -> Option<String> {
+    let mut s = String::new();
+    let mut chars = text.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => return chars.as_str().trim().is_empty().then_some(s),
+            '\\' => match chars.next()? {
+                'n' => s.push('\n'),
+                't' => s.push('\t'),
+                'r' => s.push('\r'),
+                c => s.push(c),
+            },
+            c => s.push(c),
+        }
+    }
+
+    None
+}
+
+/// Splits the items of an array on commas that are not inside strings
+fn split_array_items(text: &str) -> Vec<&str> {
+    let mut items = vec![];
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (i, ch) in text.char_indices() {
+        match ch {
+            '\\' if in_string => escaped = !escaped,
+            '"' if !escaped => in_string = !in_string,
+            ',' if !in_string => {
+                items.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => escaped = false,
+        }
+    }
+
+    // Allow a trailing comma
+    if !text[start..].trim().is_empty() {
+        items.push(&text[start..]);
+    }
+
+    items
+}
+
+/// Notices when configuration files are changed, created or removed, to read them again
+pub struct ConfigWatch {
+    /// The files watched, with when they were last modified or `None` if they are missing
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    checked_at: Instant,
+}
+
+impl ConfigWatch {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self {
+            files: paths
+                .into_iter()
+                .map(|path| {
+                    let modified = modified_time(&path);
+                    (path, modified)
+                })
+                .collect(),
+            checked_at: Instant::now(),
+        }
+    }
+
+    /// Whether one of the files changed since the last check, checking at most once a second
+    pub fn poll(&mut self) -> bool {
+        if self.checked_at.elapsed() < CHECK_INTERVAL {
+            return false;
+        }
+        self.check()
+    }
+
+    /// Whether one of the files changed since the last check
+    pub fn check(&mut self) -> bool {
+        self.checked_at = Instant::now();
+
+        let mut changed = false;
+        for (path, modified) in &mut self.files {
+            let now = modified_time(path);
+            if now != *modified {
+                *modified = now;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// When the file at `path` was last modified, `None` if it cannot be told
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
@@ -0,0 +1,49 @@
+use std::fmt;
+
+/// Number of lines, words and characters of a text, words being separated by whitespace
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Counts {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+}
+
+impl Counts {
+    pub fn of(data: &[char]) -> Self {
+        let mut counts = Self {
+            chars: data.len(),
+            ..Self::default()
+        };
+        let mut in_word = false;
+
+        for ch in data {
+            if *ch == '\n' {
+                counts.lines += 1;
+            }
+
+            if ch.is_whitespace() {
+                in_word = false;
+            } else if !in_word {
+                in_word = true;
+                counts.words += 1;
+            }
+        }
+
+        // The last line does not need a newline to count
+        if data.last().is_some_and(|ch| *ch != '\n') {
+            counts.lines += 1;
+        }
+
+        counts
+    }
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} lines, {} words, {} characters",
+            self.lines, self.words, self.chars
+        )
+    }
+}
@@ -0,0 +1,117 @@
+use std::env;
+
+use crossterm::cursor::SetCursorStyle;
+
+use crate::config::Config;
+
+/// Shape of the terminal cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Bar,
+    Block,
+    Underline,
+}
+
+/// How the cursor is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorStyle {
+    pub shape: CursorShape,
+    pub blinking: bool,
+}
+
+impl CursorStyle {
+    pub const fn new(shape: CursorShape, blinking: bool) -> Self {
+        Self { shape, blinking }
+    }
+
+    /// Parses `bar`, `block` or `underline`, optionally prefixed by `blinking-` (the default)
+    /// or `steady-`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let (blinking, shape) = match name.split_once('-') {
+            Some(("blinking", shape)) => (true, shape),
+            Some(("steady", shape)) => (false, shape),
+            Some(_) => return None,
+            None => (true, name),
+        };
+
+        let shape = match shape {
+            "bar" => CursorShape::Bar,
+            "block" => CursorShape::Block,
+            "underline" => CursorShape::Underline,
+            _ => return None,
+        };
+
+        Some(Self::new(shape, blinking))
+    }
+
+    pub fn command(&self) -> SetCursorStyle {
+        match (self.shape, self.blinking) {
+            (CursorShape::Bar, true) => SetCursorStyle::BlinkingBar,
+            (CursorShape::Bar, false) => SetCursorStyle::SteadyBar,
+            (CursorShape::Block, true) => SetCursorStyle::BlinkingBlock,
+            (CursorShape::Block, false) => SetCursorStyle::SteadyBlock,
+            (CursorShape::Underline, true) => SetCursorStyle::BlinkingUnderScore,
+            (CursorShape::Underline, false) => SetCursorStyle::SteadyUnderScore,
+        }
+    }
+}
+
+/// The cursor style of each editing mode, set in the `[cursor]` section of the config, e.g.
+///
+/// ```toml
+/// [cursor]
+/// insert = "blinking-bar"
+/// overwrite = "steady-block"
+/// prompt = "underline"
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorStyles {
+    /// While typing inserts characters
+    pub insert: CursorStyle,
+    /// While typing replaces the characters under the cursor
+    pub overwrite: CursorStyle,
+    /// While a prompt is open
+    pub prompt: CursorStyle,
+}
+
+impl Default for CursorStyles {
+    fn default() -> Self {
+        Self {
+            insert: CursorStyle::new(CursorShape::Bar, true),
+            overwrite: CursorStyle::new(CursorShape::Block, true),
+            prompt: CursorStyle::new(CursorShape::Bar, true),
+        }
+    }
+}
+
+impl CursorStyles {
+    /// Reads the styles set in the config, the others keep their default
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let mut styles = Self::default();
+        for (mode, style) in [
+            ("insert", &mut styles.insert),
+            ("overwrite", &mut styles.overwrite),
+            ("prompt", &mut styles.prompt),
+        ] {
+            if let Some(name) = config.get_str("cursor", mode) {
+                *style = CursorStyle::from_name(name)
+                    .ok_or_else(|| format!("Unknown cursor style for {mode} : {name}"))?;
+            }
+        }
+
+        Ok(styles)
+    }
+}
+
+/// Whether the terminal understands the sequences changing the cursor style. The Linux console
+/// and dumb terminals print them instead.
+pub fn cursor_styles_supported() -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+
+    match env::var("TERM") {
+        Ok(term) => !matches!(term.as_str(), "" | "dumb" | "linux" | "cons25"),
+        Err(_) => false,
+    }
+}
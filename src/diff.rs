@@ -0,0 +1,204 @@
+#![allow(dead_code)]
+use crate::highlight::{Highlight, HighlightKind};
+
+/// Size of the largest LCS table we build, bigger changes are reported as a single hunk
+/// since the table grows with the product of both line counts
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// A changed region, lines `[old_start, old_end)` of the old text were replaced by
+/// lines `[new_start, new_end)` of the new text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_end: usize,
+    pub new_start: usize,
+    pub new_end: usize,
+}
+
+/// Returns the hunks turning the lines `old` into the lines `new`, in order.
+/// Based on the longest common subsequence of lines.
+pub fn diff_lines<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Hunk> {
+    // Formatters and edits usually touch a small part of the text, keep the table small
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    if old_mid.is_empty() && new_mid.is_empty() {
+        return vec![];
+    }
+
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_DIFF_CELLS {
+        return vec![Hunk {
+            old_start: prefix,
+            old_end: old.len() - suffix,
+            new_start: prefix,
+            new_end: new.len() - suffix,
+        }];
+    }
+
+    // lcs[i][j] is the length of the LCS of old_mid[i..] and new_mid[j..]
+    let mut lcs = vec![vec![0usize; new_mid.len() + 1]; old_mid.len() + 1];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i][j] = if old_mid[i] == new_mid[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut hunks = vec![];
+    let mut current: Option<Hunk> = None;
+    let (mut i, mut j) = (0, 0);
+
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            hunks.extend(current.take());
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        let hunk = current.get_or_insert(Hunk {
+            old_start: prefix + i,
+            old_end: prefix + i,
+            new_start: prefix + j,
+            new_end: prefix + j,
+        });
+
+        if j < new_mid.len() && (i == old_mid.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            j += 1;
+            hunk.new_end = prefix + j;
+        } else {
+            i += 1;
+            hunk.old_end = prefix + i;
+        }
+    }
+    hunks.extend(current);
+
+    hunks
+}
+
+/// Maps line `line` of the old text to the closest line of the new text
+pub fn map_line(hunks: &[Hunk], line: usize) -> usize {
+    let mut delta = 0isize;
+
+    for hunk in hunks {
+        if line < hunk.old_start {
+            break;
+        }
+
+        if line < hunk.old_end {
+            // Inside a change, stay at the same offset into the replacement if possible
+            let offset = line - hunk.old_start;
+            let new_len = hunk.new_end - hunk.new_start;
+            return hunk.new_start + offset.min(new_len.saturating_sub(1));
+        }
+
+        delta +=
+            (hunk.new_end - hunk.new_start) as isize - (hunk.old_end - hunk.old_start) as isize;
+    }
+
+    (line as isize + delta).max(0) as usize
+}
+
+/// Formats the changes from `old` to `new` as a unified diff, with `context` unchanged lines
+/// around each change
+pub fn unified(
+    old: &[&str],
+    new: &[&str],
+    old_name: &str,
+    new_name: &str,
+    context: usize,
+) -> String {
+    let hunks = diff_lines(old, new);
+    let mut text = format!("--- {old_name}\n+++ {new_name}\n");
+
+    // Changes close enough to share their context are shown together
+    let mut groups: Vec<Vec<Hunk>> = vec![];
+    for hunk in hunks {
+        match groups.last_mut() {
+            Some(group) if hunk.old_start <= group[group.len() - 1].old_end + 2 * context => {
+                group.push(hunk)
+            }
+            _ => groups.push(vec![hunk]),
+        }
+    }
+
+    for group in groups {
+        let (first, last) = (group[0], group[group.len() - 1]);
+        let old_start = first.old_start.saturating_sub(context);
+        let old_end = (last.old_end + context).min(old.len());
+        let new_start = first.new_start - (first.old_start - old_start);
+        let new_end = last.new_end + (old_end - last.old_end);
+
+        text.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + 1,
+            old_end - old_start,
+            new_start + 1,
+            new_end - new_start
+        ));
+
+        let mut line = old_start;
+        for hunk in &group {
+            for l in &old[line..hunk.old_start] {
+                text.push_str(&format!(" {l}\n"));
+            }
+            for l in &old[hunk.old_start..hunk.old_end] {
+                text.push_str(&format!("-{l}\n"));
+            }
+            for l in &new[hunk.new_start..hunk.new_end] {
+                text.push_str(&format!("+{l}\n"));
+            }
+            line = hunk.old_end;
+        }
+        for l in &old[line..old_end] {
+            text.push_str(&format!(" {l}\n"));
+        }
+    }
+
+    text
+}
+
+/// Highlights the added, removed and hunk header lines of a unified diff
+pub fn highlight(data: &[char]) -> Vec<Highlight> {
+    let mut highlights = vec![];
+    let mut line_start = 0;
+
+    while line_start < data.len() {
+        let line_end = data[line_start..]
+            .iter()
+            .position(|c| *c == '\n')
+            .map_or(data.len(), |i| line_start + i);
+        let line = &data[line_start..line_end];
+
+        let kind = match line {
+            ['+', '+', '+', ..] | ['-', '-', '-', ..] => None,
+            ['@', '@', ..] => Some(HighlightKind::HunkHeader),
+            ['+', ..] => Some(HighlightKind::Inserted),
+            ['-', ..] => Some(HighlightKind::Deleted),
+            _ => None,
+        };
+
+        if let Some(kind) = kind {
+            highlights.push(Highlight {
+                start: line_start,
+                end: line_end,
+                kind,
+            });
+        }
+
+        line_start = line_end + 1;
+    }
+
+    highlights
+}
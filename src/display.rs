@@ -1,28 +1,283 @@
 #![allow(dead_code)]
 use std::{
-    io::{self, Write},
+    env,
+    io::{self, IsTerminal, Write},
     process::exit,
 };
 
 use crossterm::{
     cursor::{Hide, MoveTo, SetCursorStyle, Show},
+    event::{
+        DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
     execute, queue,
-    style::{Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    style::{
+        Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor,
+    },
     terminal::{
-        self, disable_raw_mode, enable_raw_mode, Clear, DisableLineWrap, EnableLineWrap,
-        EnterAlternateScreen, LeaveAlternateScreen,
+        self, disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, Clear,
+        DisableLineWrap, EnableLineWrap, EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
     },
 };
 
 use crate::{
+    accessibility::notification_text,
     buffer::{Buffer, Line},
+    clipboard::base64,
+    colors::ColorSupport,
+    cursor_style::{cursor_styles_supported, CursorStyle},
+    indent,
+    lsp::Severity,
+    popup::Popup,
+    prompt::Prompt,
+    spans::{add_guides, line_spans, Layer, Paint, Span, Style},
+    start_screen::StartScreen,
     status_line::StatusLine,
+    tab_line::TabLine,
+};
+
+/*
+    Color theme default
+    :root {
+        --error-color: rgb(230, 90, 90);        /* Errors (soft red) */
+        --warning-color: rgb(255, 210, 85);     /* Warnings (warm yellow) */
+        --info-color: rgb(100, 160, 230);       /* Information (soft blue) */
+        --hint-color: rgb(150, 150, 150);       /* Hints (gray) */
+        --spell-color: rgb(230, 150, 90);       /* Misspelled words (soft orange) */
+        --selection-bg-color: rgb(70, 70, 90);  /* Selected text background (muted blue) */
+        --bookmark-color: rgb(100, 180, 230);   /* Bookmarks in the gutter (soft blue) */
+        --fold-color: rgb(150, 150, 150);       /* Fold marks and summaries (gray) */
+        --changed-bg-color: rgb(60, 50, 35);    /* Lines differing from the other side of a diff (dim amber) */
+        --occurrence-bg-color: rgb(50, 50, 60); /* Other occurrences of the word under the cursor (faint blue) */
+        --separator-color: rgb(70, 70, 70);     /* Line between split panes (dark gray) */
+        --end-of-buffer-color: rgb(90, 90, 90); /* `~` on the rows past the last line (dim gray) */
+        --guide-color: rgb(60, 60, 60);         /* Indent guides (faint gray) */
+        --mixed-indent-bg-color: rgb(90, 60, 30); /* Indentation mixing tabs and spaces (dim orange) */
+    }
+*/
+
+const SPELL_COLOR: Color = Color::Rgb {
+    r: 230,
+    g: 150,
+    b: 90,
+};
+
+const SELECTION_BG_COLOR: Color = Color::Rgb {
+    r: 70,
+    g: 70,
+    b: 90,
 };
 
+const BOOKMARK_COLOR: Color = Color::Rgb {
+    r: 100,
+    g: 180,
+    b: 230,
+};
+
+const FOLD_COLOR: Color = Color::Rgb {
+    r: 150,
+    g: 150,
+    b: 150,
+};
+
+const CHANGED_BG_COLOR: Color = Color::Rgb {
+    r: 60,
+    g: 50,
+    b: 35,
+};
+
+const OCCURRENCE_BG_COLOR: Color = Color::Rgb {
+    r: 50,
+    g: 50,
+    b: 60,
+};
+
+const SEPARATOR_COLOR: Color = Color::Rgb {
+    r: 70,
+    g: 70,
+    b: 70,
+};
+
+const END_OF_BUFFER_COLOR: Color = Color::Rgb {
+    r: 90,
+    g: 90,
+    b: 90,
+};
+
+const WRAP_MARKER_COLOR: Color = Color::Rgb {
+    r: 90,
+    g: 90,
+    b: 90,
+};
+
+const GUIDE_COLOR: Color = Color::Rgb {
+    r: 60,
+    g: 60,
+    b: 60,
+};
+
+const MIXED_INDENT_BG_COLOR: Color = Color::Rgb {
+    r: 90,
+    g: 60,
+    b: 30,
+};
+
+/// How many lines around a blank line are looked through for the indentation it is within
+const GUIDE_SEARCH_LINES: usize = 100;
+
+/// Saves the window title on the terminal's stack of titles, and brings it back
+const PUSH_TITLE: &str = "\x1b[22;0t";
+const POP_TITLE: &str = "\x1b[23;0t";
+
+/// Returns the width in columns of the indentation of `line`, or `None` if it is blank
+fn indent_width(buffer: &Buffer, line: usize) -> Option<usize> {
+    let Line { start, end } = buffer.lines[line];
+    let mut column = 0;
+    for idx in start..=end {
+        match buffer.data.get(idx) {
+            Some(' ' | '\t') => column += buffer.width_at(idx, column),
+            Some('\n') | None => return None,
+            Some(_) => return Some(column),
+        }
+    }
+    None
+}
+
+/// Returns the columns of the view where indent guides are drawn on `line`, one at each tab
+/// stop of its indentation. Blank lines take the indentation of the block they are in.
+fn guide_columns(buffer: &Buffer, line: usize) -> Vec<usize> {
+    let width = indent_width(buffer, line).unwrap_or_else(|| {
+        let before = (line.saturating_sub(GUIDE_SEARCH_LINES)..line)
+            .rev()
+            .find_map(|l| indent_width(buffer, l));
+        let after = (line + 1..buffer.lines.len().min(line + GUIDE_SEARCH_LINES))
+            .find_map(|l| indent_width(buffer, l));
+        before.min(after).unwrap_or(0)
+    });
+
+    (0..width)
+        .step_by(buffer.tab_width.max(1))
+        .filter(|column| *column >= buffer.offset_x)
+        .map(|column| column - buffer.offset_x)
+        .filter(|column| *column < buffer.text_width())
+        .collect()
+}
+
+/// The styles over the text of `buffer`, the most important last
+fn buffer_layers(buffer: &Buffer) -> Vec<Layer> {
+    let highlights = buffer
+        .highlights
+        .iter()
+        .map(|h| (h.start, h.end, Paint::Foreground(h.kind.color())))
+        .collect();
+
+    let mut layers = vec![
+        Layer::new(highlights),
+        Layer::uniform(
+            buffer.occurrences.iter().copied(),
+            Paint::Background(OCCURRENCE_BG_COLOR),
+        ),
+        Layer::uniform(buffer.selection(), Paint::Background(SELECTION_BG_COLOR)),
+        Layer::uniform(
+            buffer.misspelled.iter().copied(),
+            Paint::Underlined(SPELL_COLOR),
+        ),
+    ];
+
+    // Where the indentation is the syntax, mixing tabs and spaces in it is asking for trouble
+    if indent::is_significant(buffer.file_type) {
+        let mut lines: Vec<usize> = buffer
+            .view_rows()
+            .into_iter()
+            .map(|(line, _)| line)
+            .collect();
+        lines.dedup();
+        let mixed = lines.into_iter().filter_map(|line| {
+            let start = buffer.lines[line].start;
+            let text = buffer.data.get(start..=buffer.lines[line].end)?;
+            let len = text.iter().take_while(|c| matches!(c, ' ' | '\t')).count();
+            indent::has_mixed_indent(text).then_some((start, start + len))
+        });
+        layers.push(Layer::uniform(
+            mixed,
+            Paint::Background(MIXED_INDENT_BG_COLOR),
+        ));
+    }
+
+    // Where diagnostics overlap, the most severe one is shown
+    let diagnostics = buffer.diagnostic_ranges();
+    for severity in [
+        Severity::Hint,
+        Severity::Information,
+        Severity::Warning,
+        Severity::Error,
+    ] {
+        let ranges = diagnostics
+            .iter()
+            .filter(|(_, _, s)| *s == severity)
+            .map(|(start, end, _)| (*start, *end));
+        layers.push(Layer::uniform(
+            ranges,
+            Paint::Underlined(severity_color(severity)),
+        ));
+    }
+
+    layers
+}
+
+fn severity_color(severity: Severity) -> Color {
+    match severity {
+        Severity::Error => Color::Rgb {
+            r: 230,
+            g: 90,
+            b: 90,
+        },
+        Severity::Warning => Color::Rgb {
+            r: 255,
+            g: 210,
+            b: 85,
+        },
+        Severity::Information => Color::Rgb {
+            r: 100,
+            g: 160,
+            b: 230,
+        },
+        Severity::Hint => Color::Rgb {
+            r: 150,
+            g: 150,
+            b: 150,
+        },
+    }
+}
+
 pub struct Display<W: Write> {
     pub width: u16,
     pub height: u16,
     out: W,
+    /// Whether `out` is the terminal, which is set up for drawing and restored when dropped
+    terminal: bool,
+    /// Whether the terminal reports keys with the keyboard enhancement protocol
+    keyboard_enhanced: bool,
+    /// Whether the terminal can change the cursor style
+    cursor_styles: bool,
+    /// The cursor style last set, restored to the user's one when dropped
+    cursor_style: Option<CursorStyle>,
+    /// The colors the terminal can show
+    colors: ColorSupport,
+    /// Whether buffers are drawn on the default background of the terminal
+    terminal_background: bool,
+    /// The window title last set, the user's one is restored when dropped
+    title: Option<String>,
+    /// Whether the terminal is a dumb one, which prints the sequences setting the title or the
+    /// clipboard and asking for the keyboard protocol rather than understanding them
+    dumb: bool,
+    /// Whether colors were turned off, e.g. with `--no-color`, whatever the config says
+    no_color: bool,
+    /// Whether to draw for a screen reader: without decorative characters, and with the cursor
+    /// hidden until the frame is drawn
+    screen_reader: bool,
 }
 
 impl<W: Write> Display<W> {
@@ -35,13 +290,78 @@ impl<W: Write> Display<W> {
             width: size.0,
             height: size.1,
             out,
+            terminal: true,
+            keyboard_enhanced: false,
+            cursor_styles: cursor_styles_supported(),
+            cursor_style: None,
+            colors: ColorSupport::detect(),
+            terminal_background: false,
+            title: None,
+            dumb: false,
+            no_color: false,
+            screen_reader: false,
         };
+        if env::var("TERM").is_ok_and(|term| term == "dumb") {
+            display.set_dumb();
+        }
+
+        execute!(
+            display.out,
+            EnterAlternateScreen,
+            DisableLineWrap,
+            EnableMouseCapture,
+            EnableBracketedPaste
+        )?;
+        if !display.dumb {
+            // Focus changes are reported to save and to look for changes made to the files
+            execute!(display.out, Print(PUSH_TITLE), EnableFocusChange)?;
+        }
 
-        execute!(display.out, EnterAlternateScreen, DisableLineWrap)?;
+        // Where the terminal supports it, tell apart keys like Shift + Enter and Enter, and
+        // report when keys are repeated or released. The query may be written to stdout, which
+        // is not the terminal in filter mode.
+        if !display.dumb
+            && io::stdout().is_terminal()
+            && supports_keyboard_enhancement().unwrap_or(false)
+        {
+            execute!(
+                display.out,
+                PushKeyboardEnhancementFlags(
+                    KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                        | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                        | KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS
+                )
+            )?;
+            display.keyboard_enhanced = true;
+        }
 
         Ok(display)
     }
 
+    /// Returns a display of the given size drawing to `out`, leaving the terminal alone
+    pub fn headless(out: W, width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            out,
+            terminal: false,
+            keyboard_enhanced: false,
+            cursor_styles: false,
+            cursor_style: None,
+            colors: ColorSupport::TrueColor,
+            terminal_background: false,
+            title: None,
+            dumb: false,
+            no_color: false,
+            screen_reader: false,
+        }
+    }
+
+    /// Returns what the display draws to
+    pub fn output(&mut self) -> &mut W {
+        &mut self.out
+    }
+
     pub fn resize(&mut self, w: u16, h: u16) {
         self.width = w;
         self.height = h;
@@ -52,6 +372,10 @@ impl<W: Write> Display<W> {
     }
 
     pub fn begin_draw(&mut self) -> io::Result<()> {
+        // A screen reader follows the cursor, it should only see where it ends up
+        if self.screen_reader {
+            queue!(self.out, Hide)?;
+        }
         // We do not clear here because I'm not sure about our implementation of Display yet
         queue!(self.out, MoveTo(0, 0), ResetColor)
     }
@@ -60,18 +384,112 @@ impl<W: Write> Display<W> {
         self.flush()
     }
 
+    /// Draws with the colors of `colors` from now on, unless colors were turned off
+    pub fn set_color_support(&mut self, colors: ColorSupport) {
+        if !self.dumb && !self.no_color {
+            self.colors = colors;
+        }
+    }
+
+    /// Draws with the default colors of the terminal only, from now on
+    pub fn disable_colors(&mut self) {
+        self.no_color = true;
+        self.colors = ColorSupport::NoColor;
+    }
+
+    /// Leaves the background of buffers to the terminal instead of painting the theme's
+    pub fn set_terminal_background(&mut self, terminal_background: bool) {
+        self.terminal_background = terminal_background;
+    }
+
+    /// Draws for a screen reader from now on, see `Accessibility::screen_reader`
+    pub fn set_screen_reader(&mut self, screen_reader: bool) {
+        self.screen_reader = screen_reader;
+    }
+
+    /// The color to paint `color` with in `buffer`, the default one of the terminal for the
+    /// background of the buffer itself when it is left to the terminal
+    fn background(&self, buffer: &Buffer, color: Color) -> Color {
+        if self.terminal_background && color == buffer.bg_color {
+            Color::Reset
+        } else {
+            self.colors.convert(color)
+        }
+    }
+
     pub fn clear_all(&mut self) -> io::Result<()> {
         queue!(self.out, Clear(terminal::ClearType::All))
     }
 
-    pub fn set_cursor_style(&mut self, style: SetCursorStyle) -> io::Result<()> {
-        queue!(self.out, style)
+    /// Sets the terminal up again and clears it, for when another program messed with it.
+    /// Returns the size of the terminal, read again in case a resize was missed.
+    pub fn reset(&mut self) -> io::Result<(u16, u16)> {
+        if self.terminal {
+            enable_raw_mode()?;
+            queue!(
+                self.out,
+                EnterAlternateScreen,
+                DisableLineWrap,
+                EnableMouseCapture,
+                EnableBracketedPaste
+            )?;
+            if !self.dumb {
+                queue!(self.out, EnableFocusChange)?;
+            }
+            (self.width, self.height) = terminal::size()?;
+            // Set them again on the next frame
+            self.cursor_style = None;
+            self.title = None;
+        }
+
+        self.clear_all()?;
+        Ok((self.width, self.height))
+    }
+
+    /// Changes the cursor style, if the terminal can and it is not the current one already
+    pub fn set_cursor_style(&mut self, style: CursorStyle) -> io::Result<()> {
+        if !self.cursor_styles || self.cursor_style == Some(style) {
+            return Ok(());
+        }
+
+        self.cursor_style = Some(style);
+        queue!(self.out, style.command())
     }
 
     pub fn move_cursor_to(&mut self, x: u16, y: u16) -> io::Result<()> {
         queue!(self.out, MoveTo(x, y))
     }
 
+    /// Makes the display draw for a dumb terminal: without colors, cursor styles, window title
+    /// or clipboard
+    pub fn set_dumb(&mut self) {
+        self.dumb = true;
+        self.colors = ColorSupport::NoColor;
+        self.cursor_styles = false;
+    }
+
+    /// Sets the title of the terminal window, if it is not the current one already
+    pub fn set_title(&mut self, title: &str) -> io::Result<()> {
+        if self.dumb || self.title.as_deref() == Some(title) {
+            return Ok(());
+        }
+
+        self.title = Some(title.to_string());
+        queue!(self.out, SetTitle(title))
+    }
+
+    /// Copies `text` to the clipboard of the terminal with an OSC 52 escape sequence, which
+    /// reaches the machine the terminal runs on even over SSH
+    pub fn set_clipboard(&mut self, text: &str) -> io::Result<()> {
+        if self.dumb {
+            return Ok(());
+        }
+        queue!(
+            self.out,
+            Print(format!("\x1b]52;c;{}\x07", base64(text.as_bytes())))
+        )
+    }
+
     pub fn print(&mut self, string: String) -> io::Result<()> {
         queue!(self.out, Print(string))
     }
@@ -79,40 +497,206 @@ impl<W: Write> Display<W> {
     pub fn draw_buffer(&mut self, buffer: &Buffer) -> io::Result<()> {
         let mut display_buffer = String::with_capacity(buffer.width);
         let mut row_idx = buffer.y;
+        let layers = buffer_layers(buffer);
+        let bookmark_lines = buffer.bookmark_lines();
+        let bg_color = self.background(buffer, buffer.bg_color);
 
         queue!(
             self.out,
             Hide,
-            SetBackgroundColor(buffer.bg_color),
-            SetForegroundColor(buffer.fg_color),
+            SetBackgroundColor(bg_color),
+            SetForegroundColor(self.colors.convert(buffer.fg_color)),
         )?;
 
-        for Line { start, end } in buffer
-            .lines
-            .iter()
-            .skip(buffer.offset_y)
-            .take(buffer.height)
-        {
-            if let Some(data) = buffer.data.get(*start..=*end) {
-                display_buffer.clear();
-                for ch in data.iter().skip(buffer.offset_x).take(buffer.width) {
-                    if *ch != '\n' {
-                        display_buffer.push(*ch);
-                    }
-                }
-
-                // Fill rest with spaces
-                (0..(display_buffer.capacity() - display_buffer.len()))
-                    .for_each(|_| display_buffer.push(' '));
-
-                queue!(self.out, MoveTo(buffer.x, row_idx), Print(&display_buffer))?;
-                row_idx += 1;
+        for (line_idx, segment) in buffer.view_rows() {
+            queue!(self.out, MoveTo(buffer.x, row_idx))?;
+            let folded = buffer.folded_under(line_idx);
+
+            // The rows a line wraps to have blank marks
+            if buffer.gutter_width > 0 && segment > 0 {
+                let blank = " ".repeat(buffer.gutter_width.min(buffer.width));
+                queue!(self.out, Print(blank))?;
+            } else if buffer.gutter_width > 0 {
+                self.draw_gutter(
+                    buffer,
+                    line_idx,
+                    bookmark_lines.contains(&line_idx),
+                    folded.is_some(),
+                )?;
+            }
+
+            let line_bg_color = if buffer.is_line_changed(line_idx) {
+                CHANGED_BG_COLOR
+            } else {
+                buffer.bg_color
+            };
+
+            let base = Style::new(buffer.fg_color, line_bg_color);
+            let mut spans = line_spans(buffer, line_idx, segment, &layers, base);
+            // The rows a line wraps to start past its indentation
+            if buffer.indent_guides && segment == 0 {
+                let columns = guide_columns(buffer, line_idx);
+                spans = add_guides(spans, &columns, GUIDE_COLOR, base);
+            }
+
+            // The rows a line wraps to start with the wrap marker, before the text
+            let mut printed = 0;
+            if buffer.row_marker_width(segment) > 0 {
+                queue!(
+                    self.out,
+                    SetForegroundColor(self.colors.convert(WRAP_MARKER_COLOR)),
+                    Print(&buffer.wrap_marker),
+                    SetForegroundColor(self.colors.convert(buffer.fg_color))
+                )?;
+                printed += buffer.wrap_marker_width();
+            }
+            for span in spans {
+                self.print_span(buffer, &span)?;
+                printed += span.width;
+            }
+
+            // Summary of the folded lines after the fold header, on the last row of a wrapped one
+            if let Some(count) = folded.filter(|_| segment + 1 == buffer.line_rows(line_idx)) {
+                let s = if count == 1 { "" } else { "s" };
+                let summary: String = format!(" ... {count} line{s}")
+                    .chars()
+                    .take(buffer.text_width().saturating_sub(printed))
+                    .collect();
+                printed += summary.chars().count();
+                queue!(
+                    self.out,
+                    SetForegroundColor(self.colors.convert(FOLD_COLOR)),
+                    Print(summary),
+                    SetForegroundColor(self.colors.convert(buffer.fg_color))
+                )?;
+            }
+
+            // Fill rest with spaces
+            display_buffer.clear();
+            (printed..buffer.text_width()).for_each(|_| display_buffer.push(' '));
+            let line_bg_color = self.background(buffer, line_bg_color);
+
+            queue!(
+                self.out,
+                SetBackgroundColor(line_bg_color),
+                Print(&display_buffer),
+                SetBackgroundColor(bg_color)
+            )?;
+            row_idx += 1;
+        }
+
+        // Rows past the last line are marked, so nothing drawn before shows through. They are
+        // only cleared for screen readers, which would read the marks out.
+        let mark = if self.screen_reader { " " } else { "~" };
+        let end_of_buffer = format!("{:<1$}", mark, buffer.width);
+        for row in row_idx..buffer.y + buffer.height as u16 {
+            queue!(
+                self.out,
+                MoveTo(buffer.x, row),
+                SetForegroundColor(self.colors.convert(END_OF_BUFFER_COLOR)),
+                Print(&end_of_buffer),
+            )?;
+        }
+        queue!(
+            self.out,
+            SetForegroundColor(self.colors.convert(buffer.fg_color))
+        )?;
+
+        self.place_cursor(buffer)
+    }
+
+    /// Draws the gutter of `line_idx`: the diagnostic mark, the bookmark mark, then the fold mark
+    fn draw_gutter(
+        &mut self,
+        buffer: &Buffer,
+        line_idx: usize,
+        bookmarked: bool,
+        folded: bool,
+    ) -> io::Result<()> {
+        let mut marks = vec![match buffer.line_diagnostic(line_idx) {
+            Some(diagnostic) => {
+                let mark = match diagnostic.severity {
+                    Severity::Error => 'E',
+                    Severity::Warning => 'W',
+                    Severity::Information => 'I',
+                    Severity::Hint => 'H',
+                };
+                (mark, severity_color(diagnostic.severity))
             }
+            None => (' ', buffer.fg_color),
+        }];
+
+        if buffer.gutter_width > 1 {
+            marks.push((if bookmarked { '*' } else { ' ' }, BOOKMARK_COLOR));
+        }
+
+        if buffer.gutter_width > 2 {
+            marks.push((if folded { '+' } else { ' ' }, FOLD_COLOR));
+        }
+
+        // A pane narrower than the gutter only shows its first marks
+        marks.resize(buffer.gutter_width, (' ', buffer.fg_color));
+        for (mark, color) in marks.into_iter().take(buffer.width) {
+            queue!(
+                self.out,
+                SetForegroundColor(self.colors.convert(color)),
+                Print(mark)
+            )?;
+        }
+
+        queue!(
+            self.out,
+            SetForegroundColor(self.colors.convert(buffer.fg_color))
+        )
+    }
+
+    /// Prints the text of `span` in its style
+    fn print_span(&mut self, buffer: &Buffer, span: &Span) -> io::Result<()> {
+        let Style {
+            fg_color,
+            bg_color,
+            underlined,
+        } = span.style;
+        let text = &span.text;
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let (run_bg_color, buffer_bg_color) = (
+            self.background(buffer, bg_color),
+            self.background(buffer, buffer.bg_color),
+        );
+        queue!(
+            self.out,
+            SetForegroundColor(self.colors.convert(fg_color)),
+            SetBackgroundColor(run_bg_color),
+            SetAttribute(self.colors.emphasis(bg_color != buffer.bg_color))
+        )?;
+
+        if underlined {
+            queue!(
+                self.out,
+                SetAttribute(Attribute::Underlined),
+                Print(text),
+                SetAttribute(Attribute::NoUnderline),
+            )?;
+        } else {
+            queue!(self.out, Print(text))?;
         }
 
+        queue!(
+            self.out,
+            SetForegroundColor(self.colors.convert(buffer.fg_color)),
+            SetBackgroundColor(buffer_bg_color),
+            SetAttribute(self.colors.emphasis(false)),
+        )
+    }
+
+    /// Moves the terminal cursor to the cursor of `buffer`, if it is visible
+    pub fn place_cursor(&mut self, buffer: &Buffer) -> io::Result<()> {
         let (cursor_x, cursor_y) = buffer.cursor_xy();
 
-        if cursor_x >= buffer.x as isize
+        if cursor_x >= buffer.x as isize + buffer.gutter_width as isize
             && cursor_x < buffer.x as isize + buffer.width as isize
             && cursor_y >= buffer.y as isize
             && cursor_y < buffer.y as isize + buffer.height as isize
@@ -128,11 +712,168 @@ impl<W: Write> Display<W> {
         Ok(())
     }
 
+    pub fn draw_popup(&mut self, popup: &Popup) -> io::Result<()> {
+        queue!(self.out, Hide)?;
+
+        for idx in 0..popup.height {
+            let (bg_color, fg_color) = if popup.selected == Some(idx) {
+                (popup.selected_bg_color, popup.selected_fg_color)
+            } else {
+                (popup.bg_color, popup.fg_color)
+            };
+
+            queue!(
+                self.out,
+                SetBackgroundColor(self.colors.convert(bg_color)),
+                SetForegroundColor(self.colors.convert(fg_color)),
+                SetAttribute(self.colors.emphasis(popup.selected == Some(idx))),
+                MoveTo(popup.x, popup.y + idx as u16),
+                Print(popup.get_row_text(idx)),
+            )?;
+        }
+
+        queue!(
+            self.out,
+            SetAttribute(self.colors.emphasis(false)),
+            ResetColor
+        )
+    }
+
+    /// Draws the start screen over the view of `buffer`
+    pub fn draw_start_screen(&mut self, start: &StartScreen, buffer: &Buffer) -> io::Result<()> {
+        let title_row = start.title_row(buffer.height);
+        let bg_color = self.background(buffer, buffer.bg_color);
+        queue!(self.out, Hide, SetBackgroundColor(bg_color))?;
+
+        for (row, text) in start
+            .get_rows(buffer.width, buffer.height)
+            .into_iter()
+            .enumerate()
+        {
+            let color = if Some(row) == title_row {
+                start.title_color
+            } else {
+                start.fg_color
+            };
+
+            queue!(
+                self.out,
+                SetForegroundColor(self.colors.convert(color)),
+                MoveTo(buffer.x, buffer.y + row as u16),
+                Print(text),
+            )?;
+        }
+
+        queue!(self.out, ResetColor)
+    }
+
+    /// Draws the vertical line between two panes
+    pub fn draw_separator(&mut self, x: u16, y: u16, height: usize) -> io::Result<()> {
+        queue!(
+            self.out,
+            SetForegroundColor(self.colors.convert(SEPARATOR_COLOR))
+        )?;
+
+        let line = if self.screen_reader { '|' } else { '│' };
+        for row in 0..height {
+            queue!(self.out, MoveTo(x, y + row as u16), Print(line))?;
+        }
+
+        queue!(self.out, ResetColor)
+    }
+
+    /// Draws the prompt and moves the terminal cursor into it
+    pub fn draw_prompt(&mut self, prompt: &Prompt) -> io::Result<()> {
+        let (text, cursor_x) = prompt.get_text();
+
+        queue!(
+            self.out,
+            Hide,
+            SetBackgroundColor(self.colors.convert(prompt.bg_color)),
+            SetForegroundColor(self.colors.convert(prompt.fg_color)),
+            SetAttribute(self.colors.emphasis(true)),
+            MoveTo(prompt.x, prompt.y),
+            Print(text),
+            SetAttribute(self.colors.emphasis(false)),
+            ResetColor,
+            MoveTo(prompt.x + cursor_x as u16, prompt.y),
+            Show,
+        )
+    }
+
+    pub fn draw_tab_line(&mut self, tab_line: &TabLine) -> io::Result<()> {
+        queue!(
+            self.out,
+            SetBackgroundColor(self.colors.convert(tab_line.bg_color)),
+            MoveTo(0, tab_line.y),
+            Print(" ".repeat(tab_line.width)),
+        )?;
+
+        for (idx, column, text) in tab_line.visible_tabs() {
+            let (bg_color, fg_color) = if idx == tab_line.active {
+                (tab_line.active_bg_color, tab_line.active_fg_color)
+            } else {
+                (tab_line.bg_color, tab_line.fg_color)
+            };
+
+            queue!(
+                self.out,
+                SetBackgroundColor(self.colors.convert(bg_color)),
+                SetForegroundColor(self.colors.convert(fg_color)),
+                SetAttribute(self.colors.emphasis(idx == tab_line.active)),
+                MoveTo(column as u16, tab_line.y),
+                Print(text),
+            )?;
+        }
+
+        queue!(
+            self.out,
+            SetAttribute(self.colors.emphasis(false)),
+            ResetColor
+        )
+    }
+
+    /// Draws the message on the row below the status line, in the default colors of the
+    /// terminal, or clears the row when there is none
+    pub fn draw_message_line(
+        &mut self,
+        y: u16,
+        width: usize,
+        message: Option<&str>,
+    ) -> io::Result<()> {
+        let text: String = message.unwrap_or_default().chars().take(width).collect();
+
+        queue!(
+            self.out,
+            ResetColor,
+            MoveTo(0, y),
+            Print(format!("{text:<width$}")),
+        )
+    }
+
+    /// Rings the bell of the terminal
+    pub fn ring_bell(&mut self) -> io::Result<()> {
+        queue!(self.out, Print('\x07'))
+    }
+
+    /// Shows `message` as a desktop notification with an OSC 9 escape sequence, which terminals
+    /// not knowing it ignore
+    pub fn notify(&mut self, message: &str) -> io::Result<()> {
+        if self.dumb {
+            return Ok(());
+        }
+        queue!(
+            self.out,
+            Print(format!("\x1b]9;{}\x07", notification_text(message)))
+        )
+    }
+
     pub fn draw_status_line(&mut self, status_line: &StatusLine) -> io::Result<()> {
         queue!(
             self.out,
-            SetBackgroundColor(status_line.bg_color),
-            SetForegroundColor(status_line.fg_color),
+            SetBackgroundColor(self.colors.convert(status_line.bg_color)),
+            SetForegroundColor(self.colors.convert(status_line.fg_color)),
+            SetAttribute(self.colors.emphasis(true)),
         )?;
 
         let line = status_line.get_text();
@@ -141,6 +882,7 @@ impl<W: Write> Display<W> {
             self.out,
             MoveTo(status_line.x, status_line.y),
             Print(line),
+            SetAttribute(self.colors.emphasis(false)),
             ResetColor
         )
     }
@@ -148,17 +890,33 @@ impl<W: Write> Display<W> {
 
 impl<W: Write> Drop for Display<W> {
     fn drop(&mut self) {
+        if !self.terminal {
+            return;
+        }
+
+        if self.keyboard_enhanced {
+            let _ = execute!(self.out, PopKeyboardEnhancementFlags);
+        }
+
         if let Err(e) = disable_raw_mode() {
             eprintln!("ERROR : Failed to disable terminal raw mode : {e}");
             exit(1);
         }
 
+        if self.cursor_style.is_some() {
+            let _ = execute!(self.out, SetCursorStyle::DefaultUserShape);
+        }
+        if !self.dumb {
+            let _ = execute!(self.out, Print(POP_TITLE), DisableFocusChange);
+        }
+
         if let Err(e) = execute!(
             self.out,
             ResetColor,
+            DisableMouseCapture,
+            DisableBracketedPaste,
             LeaveAlternateScreen,
-            EnableLineWrap,
-            SetCursorStyle::BlinkingBlock
+            EnableLineWrap
         ) {
             eprintln!("ERROR : Failed to leave alternate screen : {e}");
             exit(1);
@@ -0,0 +1,4363 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    env, fs,
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+
+use crate::{
+    accessibility::Accessibility,
+    action::{Action, Block},
+    buffer::Buffer,
+    build::Build,
+    case::Case,
+    clipboard::{Clipboard, PrimarySelection, DEFAULT_OSC52_LIMIT},
+    clock::DateTime,
+    colors::ColorSupport,
+    command::Command,
+    completion::{Completion, MAX_ITEMS},
+    config::{self, Config, ConfigWatch, PROJECT_CONFIG_FILE_NAME},
+    count::Counts,
+    cursor_style::{CursorStyle, CursorStyles},
+    diff,
+    display::Display,
+    events::{EditorEvent, EventBus, EventContext},
+    filetype::FileType,
+    format,
+    highlight::{highlight, rainbow_brackets},
+    history::{FileHistory, Snapshot, HISTORY_BUFFER_PREFIX},
+    indent,
+    input::{self, ClickCounter},
+    json,
+    keymap::{Key, Keymap, Lookup},
+    lsp::{uri_to_path, LspClient, LspEvent},
+    markdown, modeline,
+    outline::{self, Symbol},
+    paths::{absolute, complete as complete_path, expand_home, shorten_home},
+    popup::Popup,
+    position::Position,
+    project,
+    prompt::{Prompt, PromptResult},
+    quickfix::{
+        self, location_for_line, BUILD_BUFFER_NAME, FIRST_ENTRY_LINE, QUICKFIX_BUFFER_NAME,
+    },
+    recovery,
+    reflow::{paragraph_around, reflow},
+    script::{load_scripts, Direction, Reply, Request, Script, ScriptOutcome, INIT_SCRIPT_NAME},
+    session::Session,
+    settings::Settings,
+    shell,
+    sort::sort_lines,
+    spell::{word_ranges, Dictionary, SuggestionCycle},
+    split::Split,
+    start_screen::StartScreen,
+    status_line::StatusLine,
+    tab_line::{Tab, TabLine},
+    template,
+    trash::{self, Trash},
+    undo::{format_age, UNDO_BUFFER_PREFIX},
+    unicode::{describe as describe_character, parse_codepoint, search as search_characters},
+};
+
+/// How long the cursor has to rest on a word before its other occurrences are highlighted
+const OCCURRENCES_DELAY: Duration = Duration::from_millis(500);
+
+/// How long to wait for the next key of a key sequence before giving up on it
+const KEY_SEQUENCE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Maximum number of lines of hover information shown at once
+const MAX_HOVER_LINES: usize = 10;
+
+/// Name of the scratch buffer listing the open buffers
+const BUFFER_LIST_NAME: &str = "[Buffers]";
+
+/// Command searching the files for `grep`, unless the config sets another one. The pattern is
+/// given after `-e` and the directory to search last.
+const DEFAULT_GREP_COMMAND: &str = "grep -rnI --exclude-dir=.git";
+
+/// How the clock in the status line shows the time when `[status] clock` is only turned on
+const DEFAULT_CLOCK_FORMAT: &str = "%H:%M";
+
+/// Number of lines scrolled by a turn of the mouse wheel
+const MOUSE_SCROLL_LINES: isize = 3;
+
+/// Longest description of the last event in the debugging overlay
+const MAX_DEBUG_EVENT_WIDTH: usize = 40;
+
+/// Largest count typed with Alt + digits, more would only keep the editor busy
+const MAX_COUNT: usize = 100_000;
+
+/// The id and version of a buffer, with its selection, that the word count is for
+type Counted = (usize, usize, Option<(usize, usize)>);
+
+/// What the input of the prompt is for
+enum PromptKind {
+    /// A command to run
+    Command,
+    /// The new name of the word at these ranges
+    Rename(Vec<(usize, usize)>),
+    /// The path of a file to open
+    OpenFile,
+    /// The hexadecimal codepoint of a character to insert
+    Codepoint,
+    /// Words of the name of a character to insert, with the index of the selected match
+    Character { selected: usize },
+    /// Letters of the symbol of the buffer to jump to, with the index of the selected match
+    Outline {
+        selected: usize,
+        symbols: Vec<Symbol>,
+    },
+    /// Whether to open the file dropped on the terminal, or to insert the pasted text naming it
+    OpenDropped { path: PathBuf, text: String },
+    /// Whether to create the missing directory of the file being saved
+    CreateDirectory,
+    /// Whether to save, or to throw away, the changes of the buffer being closed
+    CloseBuffer,
+    /// Whether to save, or to throw away, the changes of the open buffers before quitting
+    Quit,
+}
+
+/// What to do with the letter typed after a mark key
+#[derive(Clone, Copy)]
+enum MarkKey {
+    Set,
+    Jump,
+}
+
+/// The state of the editor, updated by the terminal events and drawn on a display
+pub struct Editor {
+    /// The active buffer
+    pub buffer: Buffer,
+    /// Buffers that are open but not shown, the next buffer is the first one
+    pub hidden_buffers: Vec<Buffer>,
+    /// The other pane when comparing two files
+    pub split: Option<Split>,
+    /// Set once the editor should be left
+    pub quit: bool,
+
+    width: u16,
+    height: u16,
+    config: Config,
+    keymap: Keymap,
+    scripts: HashMap<String, Script>,
+    // Keys bound by scripts, bound again over those of the config when it changes
+    script_keys: Vec<(String, String)>,
+    // Set while the commands hooks ran are run, which do not run the hooks again
+    running_hook_commands: bool,
+    /// What is kept for the next run, updated with the open buffers when quitting
+    pub session: Session,
+    events: EventBus,
+    status_line: StatusLine,
+    /// Start screen shown over the buffer with this id until it is edited or left
+    start_screen: Option<(usize, StartScreen)>,
+    // Message shown in the status line until the next key press
+    message: Option<String>,
+    // Error shown as the message, announced first with the bell or a notification if enabled
+    error: Option<String>,
+    // Error to announce with the next frame
+    alert: Option<String>,
+    // Message shown on the message line instead of the status line, for screen readers
+    message_line: Option<String>,
+    accessibility: Accessibility,
+
+    // Number typed with Alt + digits, the next movement is repeated that many times
+    count: Option<usize>,
+    // Keys typed so far of a sequence bound in the keymap, and when the last one was
+    pending_keys: Vec<Key>,
+    last_key_at: Instant,
+    pending_mark: Option<MarkKey>,
+
+    completion: Option<Completion>,
+    hover: Option<Vec<String>>,
+    prompt: Option<(Prompt, PromptKind)>,
+    build: Option<Build>,
+
+    language_servers: HashMap<FileType, LspClient>,
+    // Version of the buffer the language server last heard of
+    lsp_version: usize,
+
+    dictionary: Option<Dictionary>,
+    // Version of the buffer the misspelled words were found for
+    spell_version: Option<usize>,
+    spell_cycle: Option<SuggestionCycle>,
+
+    // Version of the buffer the highlights were computed for
+    highlight_version: Option<usize>,
+
+    // The word count in the status line, and the buffer and selection it was counted for
+    show_counts: bool,
+    counts_for: Option<Counted>,
+    // Whether the time the last frame took is shown in the status line
+    show_frame_time: bool,
+    // How the time of day is shown in the status line, if it is
+    clock_format: Option<String>,
+    // Whether the number of open buffers is shown in the status line
+    show_buffers: bool,
+    // The text selected last, shared with the desktop unless disabled in the config
+    primary: PrimarySelection,
+    clipboard: Clipboard,
+
+    // Where the cursor was last seen, and since when it has been resting there if the
+    // occurrences of the word under it were not highlighted yet
+    cursor_rest: (usize, usize, usize, usize),
+    cursor_rest_since: Option<Instant>,
+    // Where the cursor was at the last update
+    cursor_at: (usize, usize),
+
+    // Set when another buffer becomes the active one, and when it was just loaded
+    switched_buffer: bool,
+    opened_buffer: bool,
+    // Set when the screen has to be cleared before drawing the next frame
+    clear_screen: bool,
+    // Set when the terminal has to be set up again before drawing the next frame
+    redraw: bool,
+    // Set when something changed since the last frame was drawn
+    stale: bool,
+    // Whether the debugging overlay is shown, and the last event it describes
+    debug: bool,
+    // Whether the active buffer is printed to stdout when quitting, as with `--filter`
+    filter: bool,
+    last_event: Option<Event>,
+    // The actions of the last edit, made of the edits done in a row, and whether something else
+    // was done since so that the next edit replaces it
+    last_edit: Vec<Action>,
+    edit_finished: bool,
+    // Whether typed characters replace the ones under the cursor
+    overwrite: bool,
+    // The clicks made in a row, and while the button is held down how many they were and the
+    // range the last one selected, which dragging extends by the same units
+    clicks: ClickCounter,
+    drag: Option<(usize, (usize, usize))>,
+    cursor_styles: CursorStyles,
+    // The colors set in the config, instead of those detected from the terminal, and whether
+    // the display has yet to be set up for the config reloaded
+    color_support: Option<ColorSupport>,
+    config_changed: bool,
+    // The config files read again when they change, when they were loaded by the editor
+    config_watch: Option<ConfigWatch>,
+}
+
+impl Editor {
+    /// Creates the editor showing `buffer`, and `split` next to it, on a terminal of the given
+    /// size. The config, scripts and session are loaded from the config directory.
+    pub fn new(buffer: Buffer, split: Option<Split>, width: u16, height: u16) -> Self {
+        let mut message = None;
+
+        let config = match Config::load() {
+            Ok(config) => config,
+            Err(e) => {
+                message = Some(format!("Config error : {e}"));
+                Config::default()
+            }
+        };
+        let (scripts, errors) = load_scripts();
+        if !errors.is_empty() {
+            message = Some(format!("Script error : {}", errors.join(", ")));
+        }
+        let session = match Session::load() {
+            Ok(session) => session,
+            Err(e) => {
+                message = Some(format!("Session error : {e}"));
+                Session::default()
+            }
+        };
+
+        let config_files = Config::path()
+            .into_iter()
+            .chain([Config::project_path(&config)])
+            .collect();
+        let mut editor = Self::with_config(buffer, split, width, height, config, scripts, session);
+        if editor.message.is_none() {
+            editor.message = message.or_else(|| editor.untrusted_notice());
+        }
+        editor.config_watch = Some(ConfigWatch::new(config_files));
+
+        editor
+    }
+
+    /// Creates the editor like `new` does, with the given config, scripts and session instead of
+    /// the ones in the config directory
+    pub fn with_config(
+        buffer: Buffer,
+        split: Option<Split>,
+        width: u16,
+        height: u16,
+        config: Config,
+        scripts: HashMap<String, Script>,
+        session: Session,
+    ) -> Self {
+        let status_line = StatusLine::new(
+            0,
+            height.saturating_sub(1),
+            width as usize,
+            1,
+            &status_file_name(&buffer, &config),
+        );
+
+        let mut message = None;
+
+        let dictionary = match load_dictionary(&config, &buffer) {
+            Ok(dictionary) => dictionary,
+            Err(e) => {
+                message = Some(e);
+                None
+            }
+        };
+
+        // The parts of the editor reacting to what happens in it
+        let mut events = EventBus::default();
+        events.subscribe(resize_buffers);
+        events.subscribe(sync_session);
+        events.subscribe(apply_modelines);
+        events.subscribe(format_on_save);
+        events.subscribe(keep_history);
+        events.subscribe(run_script_hooks);
+        events.subscribe(notify_language_server);
+
+        let mut editor = Self {
+            // Read from the config by `apply_config`
+            keymap: Keymap::default(),
+            cursor_styles: CursorStyles::default(),
+            accessibility: Accessibility::default(),
+            show_counts: false,
+            show_frame_time: false,
+            clock_format: None,
+            show_buffers: false,
+            color_support: None,
+            config_changed: false,
+            config_watch: None,
+            primary: if config.get_bool("clipboard", "primary") == Some(false) {
+                PrimarySelection::default()
+            } else {
+                PrimarySelection::detect()
+            },
+            clipboard: Clipboard::detect(
+                config.get_bool("clipboard", "osc52"),
+                config
+                    .get_int("clipboard", "osc52_limit")
+                    .and_then(|limit| usize::try_from(limit).ok())
+                    .unwrap_or(DEFAULT_OSC52_LIMIT),
+            ),
+            lsp_version: buffer.version,
+            cursor_rest: (
+                buffer.id,
+                buffer.version,
+                buffer.cursor_pos,
+                buffer.offset_y,
+            ),
+            cursor_rest_since: Some(Instant::now()),
+            cursor_at: (buffer.id, buffer.cursor_pos),
+            buffer,
+            hidden_buffers: vec![],
+            split,
+            quit: false,
+            width,
+            height,
+            config,
+            scripts,
+            script_keys: vec![],
+            running_hook_commands: false,
+            session,
+            events,
+            status_line,
+            start_screen: None,
+            message,
+            count: None,
+            pending_keys: vec![],
+            last_key_at: Instant::now(),
+            pending_mark: None,
+            completion: None,
+            hover: None,
+            prompt: None,
+            build: None,
+            language_servers: HashMap::new(),
+            dictionary,
+            spell_version: None,
+            spell_cycle: None,
+            highlight_version: None,
+            counts_for: None,
+            switched_buffer: false,
+            opened_buffer: false,
+            clear_screen: false,
+            redraw: false,
+            stale: true,
+            debug: false,
+            filter: false,
+            last_event: None,
+            last_edit: vec![],
+            edit_finished: true,
+            overwrite: false,
+            clicks: ClickCounter::default(),
+            drag: None,
+            error: None,
+            alert: None,
+            message_line: None,
+        };
+        editor.apply_config();
+        // The display is set up with the config from the start
+        editor.config_changed = false;
+        editor.run_init_script();
+
+        if let Some(split) = &mut editor.split {
+            if let Err(e) = open_in_language_server(
+                &mut editor.language_servers,
+                &editor.config,
+                &mut split.other,
+            ) {
+                editor.message = Some(e);
+            }
+        }
+        if editor.buffer.file_path.is_some() {
+            editor.emit(EditorEvent::BufferOpened);
+        }
+        editor.suggest_wrap();
+
+        editor
+    }
+
+    /// Applies the options of the config the editor keeps track of: the key bindings, the
+    /// cursor styles, the accessibility options, the segments of the status line and the theme.
+    /// The others are read from the config when they are needed.
+    /// Key bindings or cursor styles with errors leave those there were.
+    fn apply_config(&mut self) {
+        match Keymap::from_config(&self.config) {
+            Ok(keymap) => self.keymap = keymap,
+            Err(e) => self.error = Some(format!("Config error : {e}")),
+        }
+        self.bind_script_keys();
+        match CursorStyles::from_config(&self.config) {
+            Ok(styles) => self.cursor_styles = styles,
+            Err(e) => self.error = Some(format!("Config error : {e}")),
+        }
+
+        let accessibility = Accessibility::from_config(&self.config);
+        let resized = accessibility.message_line_height(self.height)
+            != self.accessibility.message_line_height(self.height);
+        self.accessibility = accessibility;
+        self.status_line.plain = accessibility.screen_reader;
+        if !accessibility.screen_reader {
+            self.message_line = None;
+        }
+        // Make room for the message line, or give it back to the buffers
+        if resized {
+            self.resize(self.width, self.height);
+        }
+
+        self.show_counts = self.config.get_bool("status", "counts").unwrap_or(false);
+        self.show_frame_time = self
+            .config
+            .get_bool("status", "frame_time")
+            .unwrap_or(false);
+        self.clock_format = match self.config.get_str("status", "clock") {
+            Some(format) => Some(format.to_string()),
+            None => (self.config.get_bool("status", "clock") == Some(true))
+                .then(|| DEFAULT_CLOCK_FORMAT.to_string()),
+        };
+        self.show_buffers = self.config.get_bool("status", "buffers").unwrap_or(false);
+
+        // Those turned off are taken off the status line
+        self.counts_for = None;
+        self.status_line.counts = None;
+        self.status_line.frame_time = None;
+        self.show_directory();
+
+        self.color_support = match self.config.get_str("theme", "colors") {
+            Some(name) => ColorSupport::from_name(name).or_else(|| {
+                self.error = Some(format!("Config error : Unknown colors : {name}"));
+                None
+            }),
+            None => None,
+        };
+        self.config_changed = true;
+        self.highlight_version = None;
+        self.clear_screen = true;
+    }
+
+    /// Reads the config files again after one of them changed, the config there was is kept if
+    /// they have errors
+    fn reload_config(&mut self) {
+        match Config::load() {
+            Ok(config) => {
+                self.set_config(config);
+                self.message = Some(
+                    self.untrusted_notice()
+                        .unwrap_or_else(|| "Reloaded the config".to_string()),
+                );
+            }
+            Err(e) => self.error = Some(format!("Config error : {e}")),
+        }
+    }
+
+    /// Tells which sections of the project config were left out until the project is trusted
+    fn untrusted_notice(&self) -> Option<String> {
+        let untrusted = self.config.untrusted();
+        if untrusted.is_empty() {
+            return None;
+        }
+
+        let sections: Vec<String> = untrusted.iter().map(|name| format!("[{name}]")).collect();
+        Some(format!(
+            "{PROJECT_CONFIG_FILE_NAME} runs programs in {}, `trust` allows it",
+            sections.join(", ")
+        ))
+    }
+
+    /// Lets the config of the current project run programs, and reads it again
+    fn trust_project(&mut self) -> Result<(), String> {
+        let list = Config::trusted_projects_path().ok_or("No data directory")?;
+        let root = Config::project_root(&self.config);
+        config::trust(&root, &list)?;
+        self.reload_config();
+        self.message = Some(format!("Trusted {}", shorten_home(&root)));
+        Ok(())
+    }
+
+    /// Replaces the config, applying what changed in it
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+        self.apply_config();
+    }
+
+    /// Suggests wrapping the lines of a buffer that has very long ones, e.g. a minified file,
+    /// as those are easier to read wrapped than scrolled across
+    fn suggest_wrap(&mut self) {
+        if self.message.is_none()
+            && self.buffer.has_long_lines()
+            && !Settings::for_buffer(&self.config, &self.buffer).wrap
+        {
+            self.message = Some("Very long lines, `set wrap on` wraps them on screen".to_string());
+        }
+    }
+
+    /// Shows the working directory in the status line, if enabled in the config
+    fn show_directory(&mut self) {
+        self.status_line.directory = if self.config.get_bool("status", "directory") == Some(true) {
+            env::current_dir().ok().map(|dir| shorten_home(&dir))
+        } else {
+            None
+        };
+    }
+
+    /// Changes the working directory to `path`, or to the home directory
+    fn change_directory(&mut self, path: Option<&str>) -> Result<(), String> {
+        let dir = expand_home(path.unwrap_or("~"));
+
+        // Relative paths of open files would point into the new directory
+        let split = self.split.as_mut().map(|split| &mut split.other);
+        for buffer in std::iter::once(&mut self.buffer)
+            .chain(self.hidden_buffers.iter_mut())
+            .chain(split)
+        {
+            if let Some(file_path) = &mut buffer.file_path {
+                *file_path = absolute(file_path);
+            }
+        }
+
+        env::set_current_dir(&dir)
+            .map_err(|e| format!("Failed to change directory to {} : {e}", dir.display()))?;
+
+        let dir = env::current_dir().map_err(|e| format!("Failed to read the directory : {e}"))?;
+        self.message = Some(format!("Working directory : {}", shorten_home(&dir)));
+        self.status_line.filename = status_file_name(&self.buffer, &self.config);
+        self.show_directory();
+
+        Ok(())
+    }
+
+    /// Shows the start screen over the current buffer, for when tte starts without a file
+    pub fn show_start_screen(&mut self) {
+        self.start_screen = Some((self.buffer.id, StartScreen::new(&self.session.recent)));
+    }
+
+    /// Runs the handlers of an editor event with the current state
+    fn emit(&mut self, event: EditorEvent) {
+        let mut outcome = ScriptOutcome::default();
+        self.events.emit(
+            event,
+            &mut EventContext {
+                config: &self.config,
+                buffer: &mut self.buffer,
+                hidden: &mut self.hidden_buffers,
+                build: &mut self.build,
+                split: &mut self.split,
+                session: &mut self.session,
+                language_servers: &mut self.language_servers,
+                scripts: &self.scripts,
+                run_hooks: !self.running_hook_commands,
+                outcome: &mut outcome,
+                message: &mut self.message,
+            },
+        );
+        self.finish_script(outcome, true);
+    }
+
+    /// Runs the `init` script of the config directory, if there is one
+    fn run_init_script(&mut self) {
+        let Some(script) = self.scripts.get(INIT_SCRIPT_NAME) else {
+            return;
+        };
+
+        let mut outcome = ScriptOutcome::default();
+        if let Err(e) = run_script(
+            script,
+            &self.config,
+            &mut self.buffer,
+            &mut self.hidden_buffers,
+            &mut self.build,
+            &mut self.split,
+            &mut outcome,
+        ) {
+            self.error = Some(e);
+        }
+        self.finish_script(outcome, false);
+    }
+
+    /// Does what scripts left to the editor: shows their message, binds their keys and runs the
+    /// commands of the editor they ran. Hooks are not run again by the commands of a hook, so a
+    /// hook saving files does not start over on each save.
+    fn finish_script(&mut self, outcome: ScriptOutcome, hook: bool) {
+        self.switched_buffer |= outcome.switched;
+        if let Some(message) = outcome.message {
+            self.message = Some(message);
+        }
+        if !outcome.keys.is_empty() {
+            for (sequence, command) in outcome.keys {
+                self.script_keys.retain(|(bound, _)| *bound != sequence);
+                self.script_keys.push((sequence, command));
+            }
+            self.bind_script_keys();
+        }
+
+        if outcome.commands.is_empty() {
+            return;
+        }
+        let running_hook_commands = self.running_hook_commands;
+        self.running_hook_commands |= hook;
+        for command in outcome.commands {
+            self.run(command);
+        }
+        self.running_hook_commands = running_hook_commands;
+    }
+
+    /// Binds the keys scripts bound, over those of the config
+    fn bind_script_keys(&mut self) {
+        for (sequence, command) in &self.script_keys {
+            if let Err(e) = Keymap::parse_sequence(&self.config, sequence)
+                .and_then(|keys| self.keymap.bind(&keys, command))
+            {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /// Remembers `action` as part of the last edit if it is one, for `RepeatEdit`. Any other
+    /// action finishes the edit, the next one starts a new edit.
+    fn record_edit(&mut self, action: &Action) {
+        if !action.is_edit() {
+            if !matches!(action, Action::RepeatEdit(_)) {
+                self.edit_finished = true;
+            }
+            return;
+        }
+
+        if self.edit_finished {
+            self.last_edit.clear();
+            self.edit_finished = false;
+        }
+        self.last_edit.push(action.clone());
+    }
+
+    /// Handles an event of the terminal
+    pub fn handle_event(&mut self, event: Event) {
+        self.stale = true;
+        self.last_event = Some(event.clone());
+        let event = match event {
+            Event::Key(key) => Event::Key(input::normalize(key)),
+            event => event,
+        };
+
+        // Releasing the key that showed a message must not hide it
+        if let Event::Key(KeyEvent {
+            kind: KeyEventKind::Press,
+            ..
+        }) = event
+        {
+            self.message = None;
+            self.hover = None;
+            self.last_key_at = Instant::now();
+        }
+
+        // A count typed before a key press is used up by it
+        let prefix = match event {
+            Event::Key(KeyEvent {
+                kind: KeyEventKind::Press,
+                ..
+            }) => self.count.take(),
+            _ => None,
+        };
+
+        match event {
+            // While the prompt is open it takes all the keys
+            Event::Key(key) if self.prompt.is_some() => self.handle_prompt_key(&key),
+
+            // The key after a mark key names the mark
+            Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) if self.pending_mark.is_some() => self.handle_mark_key(code),
+
+            // The completion popup gets the first chance at handling keys
+            _ if handle_completion_key(&mut self.completion, &mut self.buffer, &event) => (),
+
+            Event::Key(key) => {
+                // Then the sequences of keys bound in the config
+                let mut command = None;
+                if handle_keymap_key(&self.keymap, &mut self.pending_keys, &key, &mut command) {
+                    if let Some(text) = command {
+                        self.apply(Action::RunText(text));
+                    }
+                } else if let Some(action) = self.action_for_key(&key, prefix) {
+                    self.record_edit(&action);
+                    self.apply(action);
+                }
+            }
+
+            // Pasted text, and what some input methods compose, comes in one piece
+            Event::Paste(text) => match &mut self.prompt {
+                Some((prompt, _)) => prompt.insert_text(&text),
+                None => match dropped_file(&text)
+                    .filter(|_| self.config.get_bool("editor", "open_dropped_files") == Some(true))
+                {
+                    Some(path) => {
+                        let label = format!("Open {} ? (y/n) ", path.display());
+                        self.prompt = Some((
+                            Prompt::new(
+                                0,
+                                self.height.saturating_sub(1),
+                                self.width as usize,
+                                &label,
+                                "",
+                            ),
+                            PromptKind::OpenDropped { path, text },
+                        ));
+                    }
+                    None => {
+                        let action = Action::InsertText(text);
+                        self.record_edit(&action);
+                        self.apply(action);
+                    }
+                },
+            },
+
+            Event::Resize(w, h) => self.resize(w, h),
+            Event::FocusLost => {
+                // Scratch buffers have nowhere to be saved to
+                if self.config.get_bool("editor", "save_on_focus_lost") == Some(true)
+                    && self.unsaved_buffers().any(|b| b.file_path.is_some())
+                {
+                    self.save_all();
+                }
+            }
+            Event::FocusGained => self.check_files_on_disk(),
+            Event::Mouse(mouse) => {
+                self.edit_finished = true;
+                self.handle_mouse(mouse);
+            }
+        }
+
+        self.buffer.commit_history();
+
+        // Keep the suggestions in sync with what is being typed
+        if let Some(c) = &mut self.completion {
+            if !c.update(&self.buffer) {
+                self.completion = None;
+            }
+        }
+    }
+
+    /// Handles a key typed in the open prompt
+    fn handle_prompt_key(&mut self, key: &KeyEvent) {
+        let result = match &mut self.prompt {
+            // The arrows go through the matches of the character and outline pickers
+            Some((p, kind))
+                if key.kind == KeyEventKind::Press
+                    && matches!(key.code, KeyCode::Up | KeyCode::Down)
+                    && matches!(
+                        kind,
+                        PromptKind::Character { .. } | PromptKind::Outline { .. }
+                    ) =>
+            {
+                let count = match kind {
+                    PromptKind::Outline { symbols, .. } => {
+                        outline::filter(symbols, &p.input()).len()
+                    }
+                    _ => search_characters(&p.input()).len(),
+                };
+                if let PromptKind::Character { selected } | PromptKind::Outline { selected, .. } =
+                    kind
+                {
+                    *selected = match key.code {
+                        KeyCode::Up => selected.saturating_sub(1),
+                        _ => (*selected + 1).min(count.saturating_sub(1)),
+                    };
+                }
+                PromptResult::Pending
+            }
+            // Tab completes the path of the file to open, the directory given to `cd` or the
+            // template to insert
+            Some((p, kind @ (PromptKind::Command | PromptKind::OpenFile)))
+                if key.kind == KeyEventKind::Press && key.code == KeyCode::Tab =>
+            {
+                let input = p.input();
+                let completed = match kind {
+                    PromptKind::OpenFile => Some(complete_path(&input, false).0),
+                    _ => input
+                        .strip_prefix("cd ")
+                        .map(|path| format!("cd {}", complete_path(path.trim_start(), true).0))
+                        .or_else(|| {
+                            let name = input.strip_prefix("insert ")?.trim_start();
+                            Some(format!("insert {}", template::complete(&self.config, name)))
+                        }),
+                };
+                if let Some(completed) = completed {
+                    p.input = completed.chars().collect();
+                    p.cursor_pos = p.input.len();
+                }
+                PromptResult::Pending
+            }
+            Some((p, kind)) => {
+                let input = p.input.clone();
+                let result = p.handle_key(key);
+                // The matches change with the input, start again from the first one
+                if let PromptKind::Character { selected } | PromptKind::Outline { selected, .. } =
+                    kind
+                {
+                    if p.input != input {
+                        *selected = 0;
+                    }
+                }
+                result
+            }
+            None => PromptResult::Pending,
+        };
+
+        match (result, self.prompt.as_ref().map(|(_, kind)| kind)) {
+            (PromptResult::Submit(text), Some(PromptKind::Command)) => {
+                self.prompt = None;
+                if let Ok(command) = Command::parse(&text) {
+                    self.record_edit(&Action::Run(command));
+                }
+                self.apply(Action::RunText(text));
+            }
+            (PromptResult::Submit(text), Some(PromptKind::Rename(ranges))) => {
+                let ranges = ranges.clone();
+                self.prompt = None;
+                self.buffer.occurrences.clear();
+                if text.is_empty() {
+                    self.message = Some("No name given".to_string());
+                } else {
+                    self.buffer.replace_ranges(&ranges, &text);
+                    self.message = Some(format!("Renamed {} occurrences", ranges.len()));
+                }
+            }
+            (PromptResult::Submit(text), Some(PromptKind::OpenFile)) => {
+                self.prompt = None;
+                let path = expand_home(text.trim());
+                if text.trim().is_empty() {
+                    self.message = Some("No path given".to_string());
+                } else if path.is_dir() {
+                    self.message = Some(format!("{} is a directory", path.display()));
+                } else {
+                    self.opened_buffer =
+                        open_buffer(&path, &mut self.buffer, &mut self.hidden_buffers);
+                    self.switched_buffer = true;
+                }
+            }
+            (PromptResult::Submit(answer), Some(PromptKind::OpenDropped { path, text })) => {
+                let (path, text) = (path.clone(), text.clone());
+                self.prompt = None;
+                if answer.trim().eq_ignore_ascii_case("y") {
+                    self.opened_buffer =
+                        open_buffer(&path, &mut self.buffer, &mut self.hidden_buffers);
+                    self.switched_buffer = true;
+                } else {
+                    let action = Action::InsertText(text);
+                    self.record_edit(&action);
+                    self.apply(action);
+                }
+            }
+            // Picked characters are typed, so that they are repeated and run the hooks
+            (PromptResult::Submit(text), Some(PromptKind::Codepoint)) => {
+                self.prompt = None;
+                match parse_codepoint(&text) {
+                    Ok(ch) => {
+                        let action = Action::InsertChar(ch);
+                        self.record_edit(&action);
+                        self.apply(action);
+                    }
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            (PromptResult::Submit(text), Some(PromptKind::Character { selected })) => {
+                let picked = search_characters(&text).get(*selected).map(|(ch, _)| *ch);
+                self.prompt = None;
+                match picked {
+                    Some(ch) => {
+                        let action = Action::InsertChar(ch);
+                        self.record_edit(&action);
+                        self.apply(action);
+                    }
+                    None => self.message = Some("No matching character".to_string()),
+                }
+            }
+            (PromptResult::Submit(text), Some(PromptKind::Outline { selected, symbols })) => {
+                match outline::filter(symbols, &text).get(*selected) {
+                    Some(symbol) => {
+                        let position = Position::new(symbol.line, symbol.column);
+                        self.buffer.move_cursor_to_position(position);
+                        self.buffer.scroll();
+                    }
+                    None => self.message = Some("No matching symbol".to_string()),
+                }
+                self.prompt = None;
+            }
+            (PromptResult::Submit(text), Some(PromptKind::CreateDirectory)) => {
+                self.prompt = None;
+                if !text.trim().eq_ignore_ascii_case("y") {
+                    self.message = Some("Not saved".to_string());
+                } else if let Some(dir) = self.buffer.missing_directory().map(Path::to_path_buf) {
+                    match fs::create_dir_all(&dir) {
+                        Ok(()) => self.save(),
+                        Err(e) => {
+                            self.error = Some(format!("Failed to create {} : {e}", dir.display()))
+                        }
+                    }
+                }
+            }
+            (PromptResult::Submit(text), Some(PromptKind::CloseBuffer)) => {
+                self.prompt = None;
+                let yes = text.trim().eq_ignore_ascii_case("y");
+                if self.buffer.file_path.is_none() {
+                    if yes {
+                        self.close_buffer();
+                    }
+                } else if !yes {
+                    self.close_buffer();
+                } else {
+                    self.save();
+                    if !self.buffer.modified {
+                        self.close_buffer();
+                    }
+                }
+            }
+            (PromptResult::Submit(text), Some(PromptKind::Quit)) => {
+                self.prompt = None;
+                // Anything but yes quits without saving, a failed save keeps the editor open
+                if !text.trim().eq_ignore_ascii_case("y") || self.save_all() {
+                    self.apply(Action::ForceQuit);
+                }
+            }
+            (PromptResult::Cancel, kind) => {
+                if let Some(PromptKind::Rename(_)) = kind {
+                    self.buffer.occurrences.clear();
+                }
+                self.prompt = None;
+            }
+            _ => (),
+        }
+    }
+
+    /// Saves the active buffer, running the save hooks around it
+    fn save(&mut self) {
+        self.emit(EditorEvent::BeforeSave);
+        match self.buffer.save() {
+            Ok(()) => {
+                self.status_line.filename = status_file_name(&self.buffer, &self.config);
+                self.emit(EditorEvent::AfterSave);
+            }
+            Err(e) => self.error = Some(format!("Failed to save : {e}")),
+        }
+    }
+
+    /// Whether quitting would lose the changes made to `buffer`, the active one if `active`.
+    /// In filter mode, what the active buffer holds is printed rather than lost.
+    fn loses_changes(&self, buffer: &Buffer, active: bool) -> bool {
+        buffer.modified && !(active && self.filter && buffer.file_path.is_none())
+    }
+
+    /// Returns the buffers with changes that would be lost by quitting, scratch buffers included
+    fn unsaved_buffers(&self) -> impl Iterator<Item = &Buffer> {
+        let others = self
+            .hidden_buffers
+            .iter()
+            .chain(self.split.as_ref().map(|split| &split.other));
+        std::iter::once(&self.buffer)
+            .filter(|b| self.loses_changes(b, true))
+            .chain(others.filter(|b| self.loses_changes(b, false)))
+    }
+
+    fn count_unsaved(&self) -> usize {
+        self.unsaved_buffers().count()
+    }
+
+    /// Writes copies of the buffers with unsaved changes into `dir`, before quitting without
+    /// asking. Returns where the copies went.
+    pub fn save_recovery_copies(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        recovery::save_copies(dir, self.unsaved_buffers())
+    }
+
+    /// Saves every buffer with unsaved changes that has a file, the hidden ones and the other
+    /// pane going through the same hooks as the active one. Reports the buffers that could
+    /// not be saved, scratch buffers among them, in the status line and returns whether all
+    /// were.
+    fn save_all(&mut self) -> bool {
+        let mut saved = 0;
+        let mut failed = vec![];
+
+        // Each buffer takes the place of the active one while it is saved
+        let others = self.hidden_buffers.len() + usize::from(self.split.is_some());
+        for i in 0..=others {
+            self.swap_active(i);
+            let unsaved = self.loses_changes(&self.buffer, i == 0);
+            if unsaved && self.buffer.file_path.is_none() {
+                failed.push(format!("{} (no file)", self.buffer.file_name()));
+            } else if unsaved {
+                self.message = None;
+                self.save();
+                if self.buffer.modified {
+                    let error = self
+                        .error
+                        .take()
+                        .or(self.message.take())
+                        .unwrap_or_default();
+                    failed.push(format!("{} ({error})", self.buffer.file_name()));
+                } else {
+                    saved += 1;
+                }
+            }
+            self.swap_active(i);
+        }
+        self.status_line.filename = status_file_name(&self.buffer, &self.config);
+
+        if failed.is_empty() {
+            let s = if saved == 1 { "" } else { "s" };
+            self.message = Some(format!("Saved {saved} buffer{s}"));
+        } else {
+            self.error = Some(format!("Not saved : {}", failed.join(", ")));
+        }
+        failed.is_empty()
+    }
+
+    /// Reloads the buffers whose file was changed by another program, unless they have unsaved
+    /// changes of their own, which are kept and reported in the status line
+    fn check_files_on_disk(&mut self) {
+        let mut reloaded = vec![];
+        let mut kept = vec![];
+        let buffers = std::iter::once(&mut self.buffer)
+            .chain(self.hidden_buffers.iter_mut())
+            .chain(self.split.as_mut().map(|split| &mut split.other));
+        for buffer in buffers.filter(|b| b.changed_on_disk()) {
+            if buffer.modified {
+                kept.push(buffer.file_name());
+            } else if buffer.reload().is_ok() {
+                reloaded.push(buffer.file_name());
+            }
+        }
+
+        self.status_line.filename = status_file_name(&self.buffer, &self.config);
+
+        if !kept.is_empty() {
+            self.message = Some(format!(
+                "Changed on disk, `reload` to discard your changes : {}",
+                kept.join(", ")
+            ));
+        } else if !reloaded.is_empty() {
+            self.message = Some(format!("Reloaded {}", reloaded.join(", ")));
+        }
+    }
+
+    /// Swaps the active buffer with the hidden buffer `i - 1`, or with the other pane past the
+    /// hidden buffers. Does nothing for 0.
+    fn swap_active(&mut self, i: usize) {
+        if i == 0 {
+            return;
+        }
+        match self.hidden_buffers.get_mut(i - 1) {
+            Some(hidden) => mem::swap(&mut self.buffer, hidden),
+            None => {
+                if let Some(split) = &mut self.split {
+                    mem::swap(&mut self.buffer, &mut split.other);
+                }
+            }
+        }
+    }
+
+    /// Handles the key typed after a mark key, naming the mark
+    fn handle_mark_key(&mut self, code: KeyCode) {
+        let buffer = &mut self.buffer;
+        match (self.pending_mark.take(), code) {
+            (Some(MarkKey::Set), KeyCode::Char(name)) if name.is_alphanumeric() => {
+                buffer.set_named_mark(name, buffer.cursor_pos);
+                self.session.update(buffer);
+                self.message = Some(match self.session.save() {
+                    Ok(()) => format!("Mark '{name}' set"),
+                    Err(e) => format!("Failed to save the session : {e}"),
+                });
+            }
+            (Some(MarkKey::Jump), KeyCode::Char(name)) => match buffer.named_mark(name) {
+                Some(idx) => {
+                    buffer.cursor_pos = idx.min(buffer.data.len());
+                    buffer.clear_selection();
+                    buffer.scroll();
+                }
+                None => self.message = Some(format!("No mark '{name}'")),
+            },
+            _ => self.message = Some("Cancelled".to_string()),
+        }
+    }
+
+    /// Lays the editor out on a terminal of the new size
+    pub fn resize(&mut self, w: u16, h: u16) {
+        self.width = w;
+        self.height = h;
+        self.layout_buffers();
+        self.status_line.resize(w as usize, 1);
+        let status_y = h.saturating_sub(1 + self.accessibility.message_line_height(h));
+        self.status_line.move_to(0, status_y);
+        if let Some((prompt, _)) = &mut self.prompt {
+            prompt.width = w as usize;
+            prompt.y = h.saturating_sub(1);
+        }
+    }
+
+    /// Places the buffers between the tab line, if there is one, and the status line
+    fn layout_buffers(&mut self) {
+        let y = self.tab_line_height();
+        let message_line = self.accessibility.message_line_height(self.height);
+        let (width, height) = buffer_size(self.width, self.height - y - message_line);
+        self.emit(EditorEvent::Resize { y, width, height });
+    }
+
+    /// Rows taken by the tab line, it is only shown with several buffers and room to spare
+    fn tab_line_height(&self) -> u16 {
+        if !self.hidden_buffers.is_empty() && self.height > 2 {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Returns the tab line listing the buffers in the order they were opened, if it is shown
+    fn tab_line(&self) -> Option<TabLine> {
+        if self.tab_line_height() == 0 {
+            return None;
+        }
+
+        let mut buffers: Vec<&Buffer> = std::iter::once(&self.buffer)
+            .chain(self.hidden_buffers.iter())
+            .collect();
+        buffers.sort_by_key(|b| b.id);
+
+        let active = buffers.iter().position(|b| b.id == self.buffer.id)?;
+        let tabs = buffers
+            .into_iter()
+            .map(|b| Tab {
+                id: b.id,
+                name: b.file_name(),
+                modified: b.modified,
+            })
+            .collect();
+
+        Some(TabLine::new(0, self.width as usize, tabs, active))
+    }
+
+    /// Makes the buffer with this id the active one
+    fn switch_to_buffer(&mut self, id: usize) {
+        if let Some(idx) = self.hidden_buffers.iter().position(|b| b.id == id) {
+            let next = self.hidden_buffers.remove(idx);
+            show_buffer(next, &mut self.buffer, &mut self.hidden_buffers);
+            self.switched_buffer = true;
+        }
+    }
+
+    /// Makes the buffer `step` tabs away from the active one the active one, going around
+    fn switch_buffer_by(&mut self, step: isize) {
+        let mut ids: Vec<usize> = self.hidden_buffers.iter().map(|b| b.id).collect();
+        ids.push(self.buffer.id);
+        ids.sort();
+
+        let active = ids.iter().position(|id| *id == self.buffer.id).unwrap_or(0);
+        let next = (active as isize + step).rem_euclid(ids.len() as isize);
+        self.switch_to_buffer(ids[next as usize]);
+    }
+
+    /// Closes the active buffer and shows the next one, or the other pane of a split.
+    /// Closing the last buffer quits the editor.
+    fn close_buffer(&mut self) {
+        self.emit(EditorEvent::BeforeClose);
+
+        let mut ids: Vec<usize> = self.hidden_buffers.iter().map(|b| b.id).collect();
+        ids.sort();
+        // The buffer opened after the closed one, or the first one if it was the last
+        let next = ids
+            .iter()
+            .find(|id| **id > self.buffer.id)
+            .or(ids.first())
+            .copied();
+
+        if let Some(id) = next {
+            self.switch_to_buffer(id);
+            self.hidden_buffers.pop();
+        } else if let Some(split) = self.split.take() {
+            self.buffer = split.other;
+            self.switched_buffer = true;
+            self.layout_buffers();
+        } else {
+            // The changes of the last buffer were dealt with when closing it
+            self.apply(Action::ForceQuit);
+        }
+    }
+
+    /// Handles a mouse event. Clicking a tab switches to its buffer, clicking the text moves the
+    /// cursor there, a double click selects a word and a triple click a line.
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let clicked = self
+                    .tab_line()
+                    .filter(|tab_line| tab_line.y == mouse.row)
+                    .and_then(|tab_line| tab_line.tab_at(mouse.column as usize));
+                if let Some(id) = clicked {
+                    self.switch_to_buffer(id);
+                    return;
+                }
+
+                let Some(idx) = self.buffer.index_at_cell(mouse.column, mouse.row) else {
+                    return;
+                };
+                let count = self.clicks.click(mouse.column, mouse.row, Instant::now());
+                let (start, end) = click_range(&self.buffer, count, idx);
+                self.buffer.select_range(start, end);
+                self.drag = Some((count, (start, end)));
+            }
+            // Extends the selection from where the button went down, a word or a line at a time
+            // after a double or a triple click
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let Some((count, (start, end))) = self.drag else {
+                    return;
+                };
+                let Some(idx) = self.buffer.index_at_cell(mouse.column, mouse.row) else {
+                    return;
+                };
+                let (to_start, to_end) = click_range(&self.buffer, count, idx);
+                if to_start < start {
+                    self.buffer.select_range(end, to_start);
+                } else {
+                    self.buffer.select_range(start, to_end.max(end));
+                }
+                self.buffer.scroll();
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.drag = None,
+            // Pastes the primary selection where it is clicked, like terminals do
+            MouseEventKind::Down(MouseButton::Middle) => {
+                let Some(idx) = self.buffer.index_at_cell(mouse.column, mouse.row) else {
+                    return;
+                };
+                if let Some(text) = self.primary.get() {
+                    self.buffer.clear_selection();
+                    self.buffer.cursor_pos = idx;
+                    self.apply(Action::InsertText(text));
+                }
+            }
+            MouseEventKind::ScrollDown => self.apply(Action::ScrollView(MOUSE_SCROLL_LINES)),
+            MouseEventKind::ScrollUp => self.apply(Action::ScrollView(-MOUSE_SCROLL_LINES)),
+            _ => (),
+        }
+    }
+
+    /// Translates a key press into the action it is bound to. `prefix` is the count typed
+    /// before it, if any.
+    pub fn action_for_key(&self, key: &KeyEvent, prefix: Option<usize>) -> Option<Action> {
+        let repeat = prefix.unwrap_or(1);
+
+        // Keys act when pressed, not again when released
+        if key.kind != KeyEventKind::Press {
+            return None;
+        }
+
+        let action = match *key {
+            KeyEvent {
+                code: KeyCode::Char('q'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::Quit,
+            // Reported as an upper case Q by some terminals
+            KeyEvent {
+                code: KeyCode::Char('q' | 'Q'),
+                modifiers,
+                ..
+            } if modifiers == KeyModifiers::CONTROL | KeyModifiers::SHIFT => Action::ForceQuit,
+            KeyEvent {
+                code: KeyCode::Char('l'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::Redraw,
+            KeyEvent {
+                code: KeyCode::Insert,
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => Action::ToggleOverwrite,
+            KeyEvent {
+                code: KeyCode::Char('s'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::Save,
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::Copy,
+
+            KeyEvent {
+                code: code @ (KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down),
+                modifiers: modifiers @ (KeyModifiers::NONE | KeyModifiers::SHIFT),
+                ..
+            } => {
+                let direction = match code {
+                    KeyCode::Left => Direction::Left,
+                    KeyCode::Right => Direction::Right,
+                    KeyCode::Up => Direction::Up,
+                    _ => Direction::Down,
+                };
+                if modifiers == KeyModifiers::SHIFT {
+                    Action::ExtendSelection(direction, repeat)
+                } else {
+                    Action::MoveCursor(direction, repeat)
+                }
+            }
+            KeyEvent {
+                code: code @ (KeyCode::Home | KeyCode::End),
+                modifiers: modifiers @ (KeyModifiers::NONE | KeyModifiers::SHIFT),
+                ..
+            } => {
+                let select = modifiers == KeyModifiers::SHIFT;
+                if code == KeyCode::Home {
+                    Action::LineStart { select }
+                } else {
+                    Action::LineEnd { select }
+                }
+            }
+            KeyEvent {
+                code: code @ (KeyCode::Home | KeyCode::End),
+                modifiers,
+                ..
+            } if modifiers - KeyModifiers::SHIFT == KeyModifiers::CONTROL => {
+                let select = modifiers.contains(KeyModifiers::SHIFT);
+                if code == KeyCode::Home {
+                    Action::BufferStart { select }
+                } else {
+                    Action::BufferEnd { select }
+                }
+            }
+            // Terminals differ in whether they send Shift along with the shifted characters
+            KeyEvent {
+                code: KeyCode::Char(ch @ ('{' | '}' | '(' | ')' | '<' | '>')),
+                modifiers,
+                ..
+            } if modifiers - KeyModifiers::SHIFT == KeyModifiers::ALT => Action::MoveBlock {
+                block: match ch {
+                    '{' | '}' => Block::Paragraph,
+                    '(' | ')' => Block::Indentation,
+                    _ => Block::Braces,
+                },
+                forward: matches!(ch, '}' | ')' | '>'),
+                count: repeat,
+            },
+            // Ctrl and Alt work the same on the arrows, terminals differ in which one they send
+            KeyEvent {
+                code: code @ (KeyCode::Left | KeyCode::Right),
+                modifiers,
+                ..
+            } if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                Action::MoveWord {
+                    forward: code == KeyCode::Right,
+                    count: repeat,
+                    select: modifiers.contains(KeyModifiers::SHIFT),
+                }
+            }
+            KeyEvent {
+                code: code @ (KeyCode::Up | KeyCode::Down),
+                modifiers,
+                ..
+            } if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                let rows = repeat as isize;
+                Action::ScrollView(if code == KeyCode::Up { -rows } else { rows })
+            }
+            KeyEvent {
+                code: KeyCode::Char(digit @ '0'..='9'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => {
+                let digit = digit as usize - '0' as usize;
+                let count = prefix.unwrap_or(0).saturating_mul(10).saturating_add(digit);
+                Action::Repeat(count.min(MAX_COUNT))
+            }
+            KeyEvent {
+                code: KeyCode::Esc,
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => Action::ClearSelection,
+
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } if is_buffer_list(&self.buffer) => Action::CloseListedBuffer,
+            KeyEvent {
+                code: KeyCode::Char('r'),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } if is_history_list(&self.buffer) => Action::RestoreSnapshot,
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => Action::InsertChar(c),
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => Action::InsertChar(c.to_ascii_uppercase()),
+            // Terminals telling Shift + Enter apart from Enter still break the line with it
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => Action::InsertChar('\n'),
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                let buffer = &self.buffer;
+                if buffer.file_path.is_none() && buffer.name.as_deref() == Some(BUILD_BUFFER_NAME) {
+                    Action::JumpToLocation
+                } else if is_quickfix_list(buffer) {
+                    Action::JumpToEntry
+                } else if buffer.source.is_some()
+                    && buffer
+                        .name
+                        .as_deref()
+                        .is_some_and(|n| n.starts_with(UNDO_BUFFER_PREFIX))
+                {
+                    Action::JumpInUndoTree
+                } else if is_buffer_list(buffer) {
+                    Action::JumpToListedBuffer
+                } else if is_history_list(buffer) {
+                    Action::CompareSnapshot
+                } else {
+                    Action::InsertChar('\n')
+                }
+            }
+            KeyEvent {
+                code: KeyCode::Tab,
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => Action::Indent,
+            // Terminals send Shift + Tab as a key of its own
+            KeyEvent {
+                code: KeyCode::BackTab,
+                ..
+            } => Action::Dedent,
+            KeyEvent {
+                code: KeyCode::Backspace,
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => Action::Backspace,
+            KeyEvent {
+                code: KeyCode::Delete,
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => Action::Delete,
+
+            KeyEvent {
+                code: KeyCode::Char('z'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::Undo,
+            KeyEvent {
+                code: KeyCode::Char('y'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::Redo,
+
+            KeyEvent {
+                code: KeyCode::Char('b'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::ToggleBookmark,
+            KeyEvent {
+                code: KeyCode::F(2),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => Action::NextBookmark,
+            KeyEvent {
+                code: KeyCode::F(2),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => Action::PreviousBookmark,
+
+            KeyEvent {
+                code: KeyCode::Char('m'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => Action::SetMark,
+            KeyEvent {
+                code: KeyCode::Char('\''),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => Action::JumpToMark,
+
+            KeyEvent {
+                code: KeyCode::Char('f'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => Action::ToggleFold,
+            KeyEvent {
+                code: KeyCode::Char(','),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => Action::FoldAtCursor,
+            KeyEvent {
+                code: KeyCode::Char('.'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => Action::UnfoldAtCursor,
+
+            KeyEvent {
+                code: KeyCode::Char('w'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => Action::SwitchPane,
+            KeyEvent {
+                code: KeyCode::Char('w'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::CloseBuffer,
+
+            KeyEvent {
+                code: KeyCode::Char('r'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => Action::RepeatEdit(repeat),
+
+            KeyEvent {
+                code: KeyCode::Char('q'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => Action::Run(Command::Reflow),
+            KeyEvent {
+                code: KeyCode::Char('o'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => Action::Run(Command::Outline),
+            KeyEvent {
+                code: KeyCode::Char('u'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => Action::Run(Command::Case(Case::Upper)),
+            KeyEvent {
+                code: KeyCode::Char('l'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => Action::Run(Command::Case(Case::Lower)),
+
+            KeyEvent {
+                code: KeyCode::F(5),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => Action::Run(Command::Build),
+            KeyEvent {
+                code: KeyCode::F(4),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => Action::StepEntry { forward: true },
+            KeyEvent {
+                code: KeyCode::F(4),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => Action::StepEntry { forward: false },
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers,
+                ..
+            } if modifiers == KeyModifiers::CONTROL | KeyModifiers::ALT => Action::ToggleDebug,
+            KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::CommandPrompt(String::new()),
+            KeyEvent {
+                code: KeyCode::Char('o'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::OpenPrompt,
+            KeyEvent {
+                code: KeyCode::Char('r'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => {
+                // Pipe the selection if there is one, otherwise just run a command
+                let input = if self.buffer.selection().is_some() {
+                    "|"
+                } else {
+                    "!"
+                };
+                Action::CommandPrompt(input.to_string())
+            }
+
+            // Terminals often cannot tell Ctrl + Shift + U from Ctrl + U, take both
+            KeyEvent {
+                code: KeyCode::Char('u' | 'U'),
+                modifiers,
+                ..
+            } if modifiers.contains(KeyModifiers::CONTROL) => Action::CodepointPrompt,
+
+            KeyEvent {
+                code: KeyCode::F(6),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => Action::Run(Command::Rename),
+
+            KeyEvent {
+                code: KeyCode::Char(' '),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::Complete,
+
+            KeyEvent {
+                code: KeyCode::F(7),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => Action::NextSuggestion,
+            KeyEvent {
+                code: KeyCode::F(7),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            } => Action::AddToDictionary,
+
+            KeyEvent {
+                code: KeyCode::F(12),
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => Action::GotoDefinition,
+            KeyEvent {
+                code: KeyCode::Char('k'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::Hover,
+
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => Action::FollowLink,
+            KeyEvent {
+                code: KeyCode::PageDown,
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::NextBuffer,
+            KeyEvent {
+                code: KeyCode::PageUp,
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => Action::PreviousBuffer,
+
+            _ => return None,
+        };
+
+        Some(action)
+    }
+
+    /// Applies an action to the state of the editor
+    pub fn apply(&mut self, action: Action) {
+        let buffer = &mut self.buffer;
+
+        match action {
+            Action::Quit => {
+                let unsaved = self.count_unsaved();
+                if unsaved == 0 {
+                    self.apply(Action::ForceQuit);
+                    return;
+                }
+
+                let label = format!(
+                    "Save changes to {unsaved} buffer{} before quitting ? (y/n) ",
+                    if unsaved == 1 { "" } else { "s" }
+                );
+                self.prompt = Some((
+                    Prompt::new(
+                        0,
+                        self.height.saturating_sub(1),
+                        self.width as usize,
+                        &label,
+                        "",
+                    ),
+                    PromptKind::Quit,
+                ));
+            }
+            Action::ForceQuit => {
+                // Marks move with the edits, remember where they ended up
+                let others = self.split.as_ref().map(|split| &split.other);
+                for b in std::iter::once(&*buffer)
+                    .chain(self.hidden_buffers.iter())
+                    .chain(others)
+                {
+                    self.session.update(b);
+                }
+                let _ = self.session.save();
+                self.quit = true;
+            }
+            Action::Save => {
+                // Ask before creating the directories of a new file
+                if let Some(dir) = self.buffer.missing_directory() {
+                    let label = format!("Create directory {} ? (y/n) ", dir.display());
+                    self.prompt = Some((
+                        Prompt::new(
+                            0,
+                            self.height.saturating_sub(1),
+                            self.width as usize,
+                            &label,
+                            "",
+                        ),
+                        PromptKind::CreateDirectory,
+                    ));
+                    return;
+                }
+                self.save();
+            }
+
+            Action::MoveCursor(direction, count) => {
+                buffer.clear_selection();
+                move_cursor(buffer, direction, count);
+                buffer.scroll();
+            }
+            Action::ExtendSelection(direction, count) => {
+                buffer.select();
+                move_cursor(buffer, direction, count);
+                buffer.scroll();
+            }
+            Action::MoveWord {
+                forward,
+                count,
+                select,
+            } => {
+                if select {
+                    buffer.select();
+                } else {
+                    buffer.clear_selection();
+                }
+                if forward {
+                    buffer.move_cursor_word_right(count);
+                } else {
+                    buffer.move_cursor_word_left(count);
+                }
+                buffer.scroll();
+            }
+            Action::LineStart { select } | Action::LineEnd { select } => {
+                if select {
+                    buffer.select();
+                } else {
+                    buffer.clear_selection();
+                }
+                if let Action::LineStart { .. } = action {
+                    buffer.move_cursor_home();
+                } else {
+                    buffer.move_cursor_end();
+                }
+                buffer.scroll();
+            }
+            Action::BufferStart { select } | Action::BufferEnd { select } => {
+                if select {
+                    buffer.select();
+                } else {
+                    buffer.clear_selection();
+                }
+                if let Action::BufferStart { .. } = action {
+                    buffer.move_cursor_to_start();
+                } else {
+                    buffer.move_cursor_to_end();
+                }
+                buffer.scroll();
+            }
+            Action::MoveBlock {
+                block,
+                forward,
+                count,
+            } => {
+                buffer.clear_selection();
+                for _ in 0..count {
+                    let before = buffer.cursor_pos;
+                    match block {
+                        Block::Paragraph => buffer.move_cursor_paragraph(forward),
+                        Block::Indentation => buffer.move_cursor_indent_block(forward),
+                        Block::Braces => {
+                            if !buffer.move_cursor_brace_block(forward) {
+                                self.message = Some("Not in a block of braces".to_string());
+                                break;
+                            }
+                        }
+                    }
+                    if buffer.cursor_pos == before {
+                        break;
+                    }
+                }
+                buffer.scroll();
+            }
+            Action::ScrollView(rows) => buffer.scroll_view(rows),
+            Action::ClearSelection => buffer.clear_selection(),
+            Action::Repeat(n) => self.count = Some(n),
+
+            Action::InsertChar(c) => {
+                if self.overwrite {
+                    buffer.overwrite_ch(c);
+                } else if c == '\n' && indent::is_significant(buffer.file_type) {
+                    // The new line starts where the block it is in does
+                    let unit = Settings::for_buffer(&self.config, buffer).indent_unit();
+                    let Position { line, column } = buffer.cursor_position();
+                    let before: String = buffer.line_text(line).chars().take(column).collect();
+                    let indent = indent::next_line_indent(&before, buffer.file_type, &unit);
+                    buffer.insert_str(&format!("\n{indent}"));
+                } else {
+                    buffer.insert_ch(c);
+                }
+                // Line breaks are not typed characters for the hooks
+                if c != '\n' {
+                    self.emit(EditorEvent::CharInserted(c));
+                }
+            }
+            Action::InsertText(text) => {
+                let text = text.replace("\r\n", "\n").replace('\r', "\n");
+                buffer.insert_str(&text);
+                buffer.recalculate_lines();
+                buffer.scroll();
+            }
+            // Selected lines are indented as a whole
+            Action::Indent
+                if buffer
+                    .selected_text()
+                    .is_some_and(|text| text.contains('\n')) =>
+            {
+                let unit = Settings::for_buffer(&self.config, buffer).indent_unit();
+                shift_selected_lines(buffer, |line| {
+                    if !line.trim().is_empty() {
+                        line.insert_str(0, &unit);
+                    }
+                });
+            }
+            Action::Indent => {
+                let settings = Settings::for_buffer(&self.config, buffer);
+                if settings.expand_tabs {
+                    // Up to the next tab stop
+                    let column = buffer.cursor_column();
+                    let spaces = settings.tab_width - column % settings.tab_width;
+                    (0..spaces).for_each(|_| buffer.insert_ch(' '));
+                } else {
+                    buffer.insert_ch('\t');
+                }
+            }
+            Action::Dedent => {
+                let unit = Settings::for_buffer(&self.config, buffer).indent_unit();
+                shift_selected_lines(buffer, |line| indent::dedent(line, &unit));
+            }
+            Action::Backspace => buffer.backspace(),
+            Action::Delete => buffer.delete(),
+
+            Action::Undo => {
+                if !buffer.undo() {
+                    self.message = Some("Nothing to undo".to_string());
+                }
+            }
+            Action::Redo => {
+                if !buffer.redo() {
+                    self.message = Some("Nothing to redo".to_string());
+                }
+            }
+
+            Action::Copy => match buffer.selected_text().filter(|text| !text.is_empty()) {
+                Some(text) => {
+                    let count = text.chars().count();
+                    self.message = Some(match self.clipboard.copy(text) {
+                        Ok(()) => format!("Copied {count} characters"),
+                        Err(e) => e,
+                    });
+                }
+                None => self.message = Some("Nothing selected to copy, Ctrl + q quits".to_string()),
+            },
+            Action::RepeatEdit(count) => {
+                if self.last_edit.is_empty() {
+                    self.message = Some("No edit to repeat".to_string());
+                    return;
+                }
+                let edits = self.last_edit.clone();
+                for _ in 0..count {
+                    let before = (self.buffer.id, self.buffer.version, self.buffer.cursor_pos);
+                    edits.iter().for_each(|edit| self.apply(edit.clone()));
+                    // An edit that stopped doing anything, e.g. at the end of the buffer
+                    if (self.buffer.id, self.buffer.version, self.buffer.cursor_pos) == before {
+                        break;
+                    }
+                }
+            }
+
+            Action::ToggleBookmark => buffer.toggle_bookmark(),
+            Action::NextBookmark | Action::PreviousBookmark => {
+                let moved = if action == Action::NextBookmark {
+                    buffer.next_bookmark()
+                } else {
+                    buffer.previous_bookmark()
+                };
+                if moved {
+                    buffer.scroll();
+                } else {
+                    self.message = Some("No bookmarks".to_string());
+                }
+            }
+
+            Action::SetMark => {
+                self.pending_mark = Some(MarkKey::Set);
+                self.message = Some("Set mark : press a letter".to_string());
+            }
+            Action::JumpToMark => {
+                self.pending_mark = Some(MarkKey::Jump);
+                self.message = Some("Jump to mark : press a letter".to_string());
+            }
+
+            Action::ToggleFold | Action::FoldAtCursor => {
+                let changed = if action == Action::ToggleFold {
+                    buffer.toggle_fold()
+                } else {
+                    buffer.fold_at_cursor()
+                };
+                if !changed {
+                    self.message = Some("Nothing to fold here".to_string());
+                }
+                buffer.scroll();
+            }
+            Action::UnfoldAtCursor => {
+                buffer.unfold_at_cursor();
+                buffer.scroll();
+            }
+
+            Action::SwitchPane => {
+                if let Some(split) = &mut self.split {
+                    split.switch(buffer);
+                    self.switched_buffer = true;
+                }
+            }
+
+            Action::CommandPrompt(input) => {
+                self.prompt = Some((
+                    Prompt::new(
+                        0,
+                        self.height.saturating_sub(1),
+                        self.width as usize,
+                        ":",
+                        &input,
+                    ),
+                    PromptKind::Command,
+                ));
+            }
+            Action::Redraw => self.redraw = true,
+            Action::ToggleDebug => self.set_debug(!self.debug),
+            Action::ToggleOverwrite => {
+                self.overwrite = !self.overwrite;
+                let mode = if self.overwrite {
+                    "Overwrite"
+                } else {
+                    "Insert"
+                };
+                self.message = Some(format!("{mode} mode"));
+            }
+            Action::OpenPrompt => {
+                self.prompt = Some((
+                    Prompt::new(
+                        0,
+                        self.height.saturating_sub(1),
+                        self.width as usize,
+                        "Open : ",
+                        "",
+                    ),
+                    PromptKind::OpenFile,
+                ));
+            }
+            Action::CodepointPrompt => {
+                self.prompt = Some((
+                    Prompt::new(
+                        0,
+                        self.height.saturating_sub(1),
+                        self.width as usize,
+                        "U+",
+                        "",
+                    ),
+                    PromptKind::Codepoint,
+                ));
+            }
+            Action::Run(command) => self.run(command),
+            Action::RunText(text) => match Command::parse(&text) {
+                Ok(command) => self.run(command),
+                Err(e) => match self.scripts.get(text.trim()) {
+                    Some(script) => {
+                        let mut outcome = ScriptOutcome::default();
+                        if let Err(e) = run_script(
+                            script,
+                            &self.config,
+                            buffer,
+                            &mut self.hidden_buffers,
+                            &mut self.build,
+                            &mut self.split,
+                            &mut outcome,
+                        ) {
+                            self.error = Some(e);
+                        }
+                        self.finish_script(outcome, false);
+                    }
+                    None => self.error = Some(e),
+                },
+            },
+
+            Action::Complete => {
+                // Words of every open buffer are candidates, hidden or in the other pane
+                let buffers: Vec<&Buffer> = std::iter::once(&*buffer)
+                    .chain(self.split.as_ref().map(|split| &split.other))
+                    .chain(self.hidden_buffers.iter())
+                    .collect();
+                self.completion = Completion::new(buffer, &buffers);
+            }
+
+            Action::NextSuggestion => {
+                if let Some(dictionary) = &self.dictionary {
+                    // Keep cycling if the cursor is still on the word we replaced last time
+                    if !self
+                        .spell_cycle
+                        .as_ref()
+                        .is_some_and(|c| c.is_current(buffer))
+                    {
+                        self.spell_cycle = SuggestionCycle::new(buffer, dictionary);
+                    }
+
+                    self.message = Some(match &mut self.spell_cycle {
+                        Some(cycle) if cycle.suggestions.is_empty() => {
+                            format!("No suggestions for \"{}\"", cycle.original)
+                        }
+                        Some(cycle) => {
+                            let word = cycle.next(buffer);
+                            match cycle.current {
+                                Some(i) => {
+                                    format!("{word} ({}/{})", i + 1, cycle.suggestions.len())
+                                }
+                                None => format!("{word} (original)"),
+                            }
+                        }
+                        None => "No misspelled word under the cursor".to_string(),
+                    });
+                }
+            }
+            Action::AddToDictionary => {
+                if let Some(dictionary) = &mut self.dictionary {
+                    let word = word_ranges(&buffer.data)
+                        .into_iter()
+                        .find(|(start, end)| {
+                            *start <= buffer.cursor_pos && buffer.cursor_pos <= *end
+                        })
+                        .map(|(start, end)| buffer.data[start..end].iter().collect::<String>());
+
+                    self.message = Some(match word {
+                        Some(word) => match dictionary.add_to_personal(&word) {
+                            Ok(()) => {
+                                self.spell_version = None;
+                                format!("Added \"{word}\" to the personal dictionary")
+                            }
+                            Err(e) => format!("Failed to add \"{word}\" : {e}"),
+                        },
+                        None => "No word under the cursor".to_string(),
+                    });
+                }
+            }
+
+            Action::GotoDefinition | Action::Hover => {
+                if let (Some(client), Some(path)) = (
+                    self.language_servers.get_mut(&buffer.file_type),
+                    &buffer.file_path,
+                ) {
+                    let position = buffer.cursor_position();
+                    let result = if action == Action::GotoDefinition {
+                        client.goto_definition(path, position)
+                    } else {
+                        client.hover(path, position)
+                    };
+                    if let Err(e) = result {
+                        self.message = Some(format!("Language server error : {e}"));
+                    }
+                }
+            }
+
+            Action::FollowLink => match resolve_link(buffer) {
+                Ok(path) => {
+                    self.opened_buffer = open_buffer(&path, buffer, &mut self.hidden_buffers);
+                    self.switched_buffer = true;
+                }
+                Err(e) => self.error = Some(e),
+            },
+            Action::JumpToLocation => match jump_to_location(buffer, &mut self.hidden_buffers) {
+                Ok(opened) => {
+                    self.opened_buffer = opened;
+                    self.switched_buffer = true;
+                }
+                Err(e) => self.error = Some(e),
+            },
+            Action::JumpToEntry => {
+                let entry = quickfix::Entry::parse(
+                    buffer
+                        .text()
+                        .lines()
+                        .nth(buffer.current_line())
+                        .unwrap_or(""),
+                );
+                let result = entry
+                    .ok_or_else(|| "No entry on this line".to_string())
+                    .and_then(|entry| go_to_entry(&entry, buffer, &mut self.hidden_buffers));
+                match result {
+                    Ok(opened) => {
+                        self.opened_buffer = opened;
+                        self.switched_buffer = true;
+                    }
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            Action::StepEntry { forward } => {
+                match step_entry(
+                    forward,
+                    buffer,
+                    &mut self.hidden_buffers,
+                    self.split.as_mut(),
+                ) {
+                    Ok((message, opened)) => {
+                        self.opened_buffer = opened;
+                        self.switched_buffer = true;
+                        self.message = Some(message);
+                    }
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            Action::JumpInUndoTree => match jump_in_undo_tree(buffer, &mut self.hidden_buffers) {
+                Ok(()) => self.switched_buffer = true,
+                Err(e) => self.error = Some(e),
+            },
+            Action::JumpToListedBuffer => match listed_buffer(buffer) {
+                Some(id) if self.hidden_buffers.iter().any(|b| b.id == id) => {
+                    // The list has done its job once another buffer is picked
+                    let list = buffer.id;
+                    self.switch_to_buffer(id);
+                    self.hidden_buffers.retain(|b| b.id != list);
+                }
+                Some(_) => self.message = Some("The buffer is closed".to_string()),
+                None => self.message = Some("No buffer on this line".to_string()),
+            },
+            Action::CloseListedBuffer => match listed_buffer(buffer) {
+                Some(id) => match self.hidden_buffers.iter().find(|b| b.id == id) {
+                    Some(listed) if listed.modified => {
+                        self.message = Some(format!(
+                            "{} has unsaved changes, switch to it to close it",
+                            listed.file_name()
+                        ));
+                    }
+                    Some(_) => {
+                        self.switch_to_buffer(id);
+                        self.close_buffer();
+                        // Show the list again, without the closed buffer
+                        self.apply(Action::Run(Command::Buffers));
+                    }
+                    None => self.message = Some("The buffer is closed".to_string()),
+                },
+                None => self.message = Some("No buffer on this line".to_string()),
+            },
+            Action::CompareSnapshot => {
+                match compare_snapshot(&self.config, buffer, &mut self.hidden_buffers) {
+                    Ok(()) => self.switched_buffer = true,
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            Action::RestoreSnapshot => {
+                match restore_snapshot(&self.config, buffer, &mut self.hidden_buffers) {
+                    Ok(message) => {
+                        self.switched_buffer = true;
+                        self.message = Some(message);
+                    }
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            Action::NextBuffer => self.switch_buffer_by(1),
+            Action::PreviousBuffer => self.switch_buffer_by(-1),
+            Action::CloseBuffer => {
+                if !buffer.modified {
+                    self.close_buffer();
+                    return;
+                }
+
+                // Buffers without a file can only lose their changes
+                let label = match &buffer.file_path {
+                    Some(_) => format!("Save changes to {} ? (y/n) ", buffer.file_name()),
+                    None => format!("Discard changes to {} ? (y/n) ", buffer.file_name()),
+                };
+                self.prompt = Some((
+                    Prompt::new(
+                        0,
+                        self.height.saturating_sub(1),
+                        self.width as usize,
+                        &label,
+                        "",
+                    ),
+                    PromptKind::CloseBuffer,
+                ));
+            }
+        }
+    }
+
+    /// Runs a command entered in the prompt, bound to keys or to a key sequence
+    fn run(&mut self, command: Command) {
+        match command {
+            Command::Count => self.message = Some(count_message(&self.buffer)),
+            Command::Offset(None) => self.message = Some(offset_message(&self.buffer)),
+            Command::Set(setting) => {
+                if let Some((name, value)) = setting {
+                    if let Err(e) = set_buffer_setting(&mut self.buffer, &name, &value) {
+                        self.error = Some(e);
+                        return;
+                    }
+                    self.highlight_version = None;
+                }
+
+                let settings = Settings::for_buffer(&self.config, &self.buffer);
+                self.message = Some(format!(
+                    "{} filetype={}",
+                    settings.describe(),
+                    self.buffer.file_type.name()
+                ));
+            }
+            Command::SaveAll { quit } => {
+                if self.save_all() && quit {
+                    self.apply(Action::ForceQuit);
+                }
+            }
+            Command::Write {
+                lines,
+                path,
+                overwrite,
+            } => match write_part(
+                &self.buffer,
+                lines,
+                &path,
+                overwrite,
+                self.config.get_bool("editor", "trash") != Some(false),
+                Trash::of_user(),
+            ) {
+                Ok(message) => self.message = Some(message),
+                Err(e) => self.error = Some(e),
+            },
+            Command::ChangeDirectory(path) => {
+                if let Err(e) = self.change_directory(path.as_deref()) {
+                    self.error = Some(e);
+                }
+            }
+            Command::Trust => {
+                if let Err(e) = self.trust_project() {
+                    self.error = Some(e);
+                }
+            }
+            Command::InitConfig => match Config::init() {
+                Ok(path) => {
+                    self.opened_buffer =
+                        open_buffer(&path, &mut self.buffer, &mut self.hidden_buffers);
+                    self.switched_buffer = true;
+                    self.message = Some(format!("Wrote {}", shorten_home(&path)));
+                }
+                Err(e) => self.error = Some(e),
+            },
+            Command::Rename => {
+                match start_rename(
+                    &mut self.buffer,
+                    self.height.saturating_sub(1),
+                    self.width as usize,
+                ) {
+                    Ok(opened) => self.prompt = Some(opened),
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            Command::Character => {
+                self.prompt = Some((
+                    Prompt::new(
+                        0,
+                        self.height.saturating_sub(1),
+                        self.width as usize,
+                        "Character : ",
+                        "",
+                    ),
+                    PromptKind::Character { selected: 0 },
+                ));
+            }
+            Command::Outline => {
+                let symbols = outline::symbols(&self.buffer.text(), self.buffer.file_type);
+                if symbols.is_empty() {
+                    self.message = Some("No symbols in this buffer".to_string());
+                    return;
+                }
+                self.prompt = Some((
+                    Prompt::new(
+                        0,
+                        self.height.saturating_sub(1),
+                        self.width as usize,
+                        "Symbol : ",
+                        "",
+                    ),
+                    PromptKind::Outline {
+                        selected: 0,
+                        symbols,
+                    },
+                ));
+            }
+            command => {
+                match run_command(
+                    command,
+                    &self.config,
+                    &mut self.buffer,
+                    &mut self.hidden_buffers,
+                    &mut self.build,
+                    &mut self.split,
+                ) {
+                    Ok(switched) => self.switched_buffer |= switched,
+                    Err(e) => self.error = Some(e),
+                }
+                // `reload` may have brought a byte order mark back
+                self.status_line.filename = status_file_name(&self.buffer, &self.config);
+            }
+        }
+    }
+
+    /// Keeps up with the background work and everything derived from the buffer.
+    /// Called after each event, and regularly while there are none.
+    pub fn update(&mut self) {
+        self.buffer.update_lines();
+
+        if self.config_watch.as_mut().is_some_and(ConfigWatch::poll) {
+            self.reload_config();
+        }
+
+        // Keep the server in sync with the buffer
+        if self.buffer.version != self.lsp_version {
+            if let (Some(client), Some(path)) = (
+                self.language_servers.get_mut(&self.buffer.file_type),
+                &self.buffer.file_path,
+            ) {
+                if let Err(e) = client.did_change(path, self.buffer.version, &self.buffer.text()) {
+                    self.message = Some(format!("Language server error : {e}"));
+                }
+            }
+            self.lsp_version = self.buffer.version;
+        }
+
+        let lsp_events: Vec<LspEvent> = self
+            .language_servers
+            .values_mut()
+            .flat_map(|client| client.poll())
+            .collect();
+        self.stale |= !lsp_events.is_empty();
+
+        for lsp_event in lsp_events {
+            match lsp_event {
+                LspEvent::Diagnostics { path, diagnostics } => {
+                    if let Some(target) = std::iter::once(&mut self.buffer)
+                        .chain(self.hidden_buffers.iter_mut())
+                        .find(|b| is_buffer_file(b, &path))
+                    {
+                        target.diagnostics = diagnostics;
+                    }
+                }
+                LspEvent::Definition { path, position } => {
+                    if !is_buffer_file(&self.buffer, &path) {
+                        self.opened_buffer |=
+                            open_buffer(&path, &mut self.buffer, &mut self.hidden_buffers);
+                        self.switched_buffer = true;
+                    }
+
+                    self.buffer.move_cursor_to_position(position);
+                    self.buffer.scroll();
+                }
+                LspEvent::Hover(text) => {
+                    self.hover = Some(
+                        text.lines()
+                            .take(MAX_HOVER_LINES)
+                            .map(String::from)
+                            .collect(),
+                    );
+                }
+                LspEvent::Message(text) => self.message = Some(text),
+            }
+        }
+
+        if let Some(result) = self.build.as_ref().and_then(|b| b.poll()) {
+            self.stale = true;
+            let command = self.build.take().map(|b| b.command).unwrap_or_default();
+            match result {
+                Ok(output) => {
+                    let text = format!("$ {command}\n{}\n[{}]\n", output.combined(), output.status);
+                    show_scratch(
+                        BUILD_BUFFER_NAME,
+                        &text,
+                        &mut self.buffer,
+                        &mut self.hidden_buffers,
+                    );
+                    self.switched_buffer = true;
+
+                    let result = if output.status.success() {
+                        "Build succeeded".to_string()
+                    } else {
+                        format!("Build failed ({})", output.status)
+                    };
+                    // The locations in the output go to the quickfix list, ready for F4
+                    let entries = quickfix::build_entries(&output.combined());
+                    self.message = Some(if entries.is_empty() {
+                        result
+                    } else {
+                        let list = quickfix::list_text(&format!("$ {command}"), &entries);
+                        fill_quickfix_list(&list, &self.buffer, &mut self.hidden_buffers);
+                        let count = entries.len();
+                        let s = if count == 1 { "" } else { "s" };
+                        format!("{result}, {count} location{s} in the quickfix list")
+                    });
+                }
+                Err(e) => self.error = Some(format!("Failed to run `{command}` : {e}")),
+            }
+        }
+
+        // Selecting text makes it the primary selection
+        if let Some(text) = self.buffer.selected_text() {
+            if !text.is_empty() && !self.primary.holds(&text) {
+                self.primary.set(text);
+            }
+        }
+
+        for buffer in
+            std::iter::once(&mut self.buffer).chain(self.split.as_mut().map(|s| &mut s.other))
+        {
+            let settings = Settings::for_buffer(&self.config, buffer);
+            buffer.tab_width = settings.tab_width;
+            buffer.virtual_space = settings.virtual_space;
+            buffer.screen_columns = settings.screen_columns;
+            buffer.smart_home = settings.smart_home;
+            buffer.keep_bom = settings.keep_bom;
+            buffer.indent_guides = settings.indent_guides;
+            buffer.word_separators = settings.word_separators;
+            if buffer.wrap != settings.wrap || buffer.wrap_marker != settings.wrap_marker {
+                buffer.wrap = settings.wrap;
+                buffer.wrap_marker = settings.wrap_marker;
+                buffer.offset_y = buffer.offset_y.min(buffer.last_row());
+                buffer.scroll();
+            }
+        }
+        // Whether the byte order mark will be saved is shown with the file name
+        if self.buffer.bom && self.status_line.filename.ends_with(" [BOM]") != self.buffer.keep_bom
+        {
+            self.status_line.filename = status_file_name(&self.buffer, &self.config);
+        }
+
+        // The tab line comes with the second buffer and goes away with it
+        if self.buffer.y != self.tab_line_height() {
+            self.layout_buffers();
+            self.clear_screen = true;
+        }
+
+        if mem::take(&mut self.switched_buffer) {
+            if mem::take(&mut self.opened_buffer) {
+                self.emit(EditorEvent::BufferOpened);
+                self.suggest_wrap();
+            }
+
+            if self.dictionary.is_none() {
+                match load_dictionary(&self.config, &self.buffer) {
+                    Ok(loaded) => self.dictionary = loaded,
+                    Err(e) => self.error = Some(e),
+                }
+            }
+
+            // The new buffer may not cover everything the previous one drew
+            self.clear_screen = true;
+
+            self.status_line.filename = status_file_name(&self.buffer, &self.config);
+            self.lsp_version = self.buffer.version;
+            self.spell_version = None;
+            self.highlight_version = None;
+            self.spell_cycle = None;
+            self.completion = None;
+            self.hover = None;
+        }
+
+        let buffer = &self.buffer;
+        if self.cursor_at != (buffer.id, buffer.cursor_pos) {
+            self.cursor_at = (buffer.id, buffer.cursor_pos);
+            self.emit(EditorEvent::CursorMoved);
+        }
+
+        // The start screen goes away once its buffer is edited or another one is shown
+        if let Some((id, _)) = self.start_screen {
+            if self.buffer.id != id || self.buffer.version != 0 {
+                self.start_screen = None;
+                self.clear_screen = true;
+            }
+        }
+
+        let buffer = &mut self.buffer;
+        if let Some(split) = &mut self.split {
+            split.update_diff(buffer);
+            split.sync_scroll(buffer);
+        }
+
+        // Jumps, e.g. to a mark or a definition, can land in folded lines
+        if buffer.reveal_cursor() {
+            buffer.scroll();
+        }
+
+        if self.highlight_version != Some(buffer.version) {
+            buffer.highlights = highlight(buffer.file_type, &buffer.data);
+            if Settings::for_buffer(&self.config, buffer).rainbow_brackets {
+                buffer
+                    .highlights
+                    .extend(rainbow_brackets(buffer.file_type, &buffer.data));
+                buffer.highlights.sort_by_key(|h| h.start);
+            }
+            self.highlight_version = Some(buffer.version);
+        }
+
+        if let Some(dictionary) = &self.dictionary {
+            if self.spell_version != Some(buffer.version) {
+                buffer.misspelled = if is_prose(buffer) {
+                    dictionary.misspelled_ranges(&buffer.data)
+                } else {
+                    vec![]
+                };
+                self.spell_version = Some(buffer.version);
+            }
+        }
+
+        let cursor_now = (
+            buffer.id,
+            buffer.version,
+            buffer.cursor_pos,
+            buffer.offset_y,
+        );
+        if cursor_now != self.cursor_rest {
+            self.cursor_rest = cursor_now;
+            self.cursor_rest_since = Some(Instant::now());
+            buffer.occurrences.clear();
+        } else if !matches!(self.prompt, Some((_, PromptKind::Rename(_))))
+            // Nothing changes on screen by itself for screen readers
+            && !self.accessibility.screen_reader
+            && self
+                .cursor_rest_since
+                .is_some_and(|since| since.elapsed() >= OCCURRENCES_DELAY)
+        {
+            self.cursor_rest_since = None;
+            buffer.occurrences = buffer.word_occurrences_in_view();
+            self.stale |= !buffer.occurrences.is_empty();
+        }
+
+        let counted = (buffer.id, buffer.version, buffer.selection());
+        if self.show_counts && self.counts_for != Some(counted) {
+            self.status_line.counts = Some(word_count(buffer));
+            self.counts_for = Some(counted);
+        }
+
+        // Checked on every tick of the main loop, drawn again only when they change
+        let clock = self
+            .clock_format
+            .as_ref()
+            .map(|format| DateTime::now().format(format));
+        let buffers = self.show_buffers.then(|| {
+            let open = std::iter::once(&*buffer)
+                .chain(&self.hidden_buffers)
+                .chain(self.split.as_ref().map(|split| &split.other))
+                .filter(|b| !is_buffer_list(b))
+                .count();
+            match open {
+                1 => "1 buffer".to_string(),
+                n => format!("{n} buffers"),
+            }
+        });
+        if clock != self.status_line.clock || buffers != self.status_line.buffers {
+            self.status_line.clock = clock;
+            self.status_line.buffers = buffers;
+            self.stale = true;
+        }
+
+        if !self.pending_keys.is_empty() && self.last_key_at.elapsed() >= KEY_SEQUENCE_TIMEOUT {
+            self.pending_keys.clear();
+            self.stale = true;
+        }
+
+        if let Some(error) = self.error.take() {
+            if self.accessibility.bell || self.accessibility.notify {
+                self.alert = Some(error.clone());
+            }
+            self.message = Some(error);
+        }
+
+        let message = self.status_line.message.take();
+        self.status_line.message = self
+            .message
+            .clone()
+            .or_else(|| {
+                self.build
+                    .as_ref()
+                    .map(|b| format!("Running `{}`...", b.command))
+            })
+            .or_else(|| self.buffer.diagnostic_message_at_cursor());
+        // The message goes on its own line, where a screen reader notices it changing
+        if self.accessibility.message_line_height(self.height) > 0 {
+            let announced = self.status_line.message.take();
+            self.stale |= announced != self.message_line;
+            self.message_line = announced;
+        }
+        let pending = self.pending_input();
+        let pending = mem::replace(&mut self.status_line.pending, pending);
+        self.stale |= self.status_line.message != message
+            || self.alert.is_some()
+            || self.status_line.pending != pending
+            || self.clear_screen
+            || self.redraw;
+
+        // Stop where the buffer breaks rather than where it makes something panic later on
+        if cfg!(debug_assertions) {
+            if let Err(e) = self.buffer.check_invariants() {
+                panic!("Broken buffer after {:?} : {e}", self.last_event);
+            }
+        }
+    }
+
+    /// Describes the input typed so far that the next key press goes on from: the count
+    /// typed before a command and the keys of an unfinished key sequence
+    fn pending_input(&self) -> Option<String> {
+        let (times, ellipsis) = if self.accessibility.screen_reader {
+            ("x", "...")
+        } else {
+            ("×", "…")
+        };
+        let count = self.count.map(|count| format!("{count}{times}"));
+        let keys = (!self.pending_keys.is_empty()).then(|| {
+            let keys: Vec<String> = self.pending_keys.iter().map(Key::to_string).collect();
+            format!("{} {ellipsis}", keys.join(" "))
+        });
+
+        match (count, keys) {
+            (Some(count), Some(keys)) => Some(format!("{count} {keys}")),
+            (count, keys) => count.or(keys),
+        }
+    }
+
+    /// Tells the editor the active buffer is printed to stdout when quitting, as with
+    /// `--filter`, so that its changes are not lost
+    pub fn set_filter(&mut self, filter: bool) {
+        self.filter = filter;
+    }
+
+    /// Shows or hides the debugging overlay
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+        // The overlay may cover more than the next frame draws
+        self.clear_screen = true;
+    }
+
+    /// Describes the cursor, its line and the view of the active buffer, and the last event
+    fn debug_lines(&self) -> Vec<String> {
+        let buffer = &self.buffer;
+        let line = buffer.current_line();
+        let (start, end) = (buffer.lines[line].start, buffer.lines[line].end);
+        let under_cursor = buffer.data.get(buffer.cursor_pos);
+        let event = self
+            .last_event
+            .as_ref()
+            .map(|event| format!("{event:?}"))
+            .unwrap_or_default();
+
+        vec![
+            format!("Cursor {} {:?}", buffer.cursor_pos, under_cursor),
+            format!("Screen {:?}", buffer.cursor_xy()),
+            format!("Line {line} : {start}..={end} of {}", buffer.line_count()),
+            format!("Offset x {} y {}", buffer.offset_x, buffer.offset_y),
+            format!(
+                "Event {}",
+                event
+                    .chars()
+                    .take(MAX_DEBUG_EVENT_WIDTH)
+                    .collect::<String>()
+            ),
+        ]
+    }
+
+    /// Whether the screen is behind the editor, e.g. after an event or an answer of the
+    /// language server, and the next frame has to be drawn
+    pub fn needs_draw(&self) -> bool {
+        self.stale
+    }
+
+    /// The colors the terminal supports according to the config, if it says
+    pub fn color_support(&self) -> Option<ColorSupport> {
+        self.color_support
+    }
+
+    /// How the config has the editor help screen readers
+    pub fn accessibility(&self) -> Accessibility {
+        self.accessibility
+    }
+
+    /// Whether the config leaves the background of buffers to the terminal
+    pub fn terminal_background(&self) -> bool {
+        self.config.get_bool("theme", "terminal_background") == Some(true)
+    }
+
+    /// The style of the cursor in the current editing mode
+    pub fn cursor_style(&self) -> CursorStyle {
+        if self.prompt.is_some() {
+            self.cursor_styles.prompt
+        } else if self.overwrite {
+            self.cursor_styles.overwrite
+        } else {
+            self.cursor_styles.insert
+        }
+    }
+
+    /// Draws the editor, the active buffer last so that the cursor ends up in it
+    pub fn draw<W: Write>(&mut self, display: &mut Display<W>) -> io::Result<()> {
+        self.stale = false;
+        let frame_start = Instant::now();
+        if mem::take(&mut self.redraw) {
+            let (width, height) = display.reset()?;
+            if (width, height) != (self.width, self.height) {
+                self.resize(width, height);
+            }
+            self.clear_screen = false;
+        } else if mem::take(&mut self.clear_screen) {
+            display.clear_all()?;
+        }
+        if mem::take(&mut self.config_changed) {
+            if let Some(colors) = self.color_support {
+                display.set_color_support(colors);
+            }
+            display.set_terminal_background(self.terminal_background());
+            display.set_screen_reader(self.accessibility.screen_reader);
+        }
+        display.set_cursor_style(self.cursor_style())?;
+        display.set_title(&window_title(&self.buffer))?;
+        if let Some(text) = self.clipboard.take_pending() {
+            display.set_clipboard(&text)?;
+        }
+
+        if let Some(error) = self.alert.take() {
+            if self.accessibility.bell {
+                display.ring_bell()?;
+            }
+            if self.accessibility.notify {
+                display.notify(&error)?;
+            }
+        }
+
+        // A single row is left to the buffer
+        if self.height > 1 {
+            display.draw_status_line(&self.status_line)?;
+        }
+        if self.accessibility.message_line_height(self.height) > 0 {
+            display.draw_message_line(
+                self.height - 1,
+                self.width as usize,
+                self.message_line.as_deref(),
+            )?;
+        }
+        if let Some(tab_line) = self.tab_line() {
+            display.draw_tab_line(&tab_line)?;
+        }
+
+        let buffer = &self.buffer;
+        if let Some(split) = &self.split {
+            display.draw_buffer(&split.other)?;
+            display.draw_separator(split.separator_x(buffer), buffer.y, buffer.height)?;
+        }
+
+        display.draw_buffer(buffer)?; // Make sure to draw the active buffer the last to get the correct cursor position
+
+        if let Some((_, start)) = &self.start_screen {
+            display.draw_start_screen(start, buffer)?;
+            display.place_cursor(buffer)?;
+        }
+
+        if let Some(lines) = &self.hover {
+            let (cursor_x, cursor_y) = buffer.cursor_xy();
+            let popup = Popup::new(
+                lines.clone(),
+                None,
+                (cursor_x.max(0) as u16, cursor_y.max(0) as u16),
+                display.width,
+                display.height,
+            );
+            display.draw_popup(&popup)?;
+            display.place_cursor(buffer)?;
+        }
+
+        if let Some(c) = &self.completion {
+            let (cursor_x, cursor_y) = buffer.cursor_xy();
+            let (items, selected) = c.visible_items();
+            let popup = Popup::new(
+                items.to_vec(),
+                Some(selected),
+                (cursor_x.max(0) as u16, cursor_y.max(0) as u16),
+                display.width,
+                display.height,
+            );
+            display.draw_popup(&popup)?;
+            display.place_cursor(buffer)?;
+        }
+
+        // Which keys can follow the ones typed so far
+        if let Lookup::Prefix(next) = self.keymap.lookup(&self.pending_keys) {
+            if !self.pending_keys.is_empty() {
+                let popup = Popup::new(
+                    next.continuations(),
+                    None,
+                    (0, display.height.saturating_sub(1)),
+                    display.width,
+                    display.height,
+                );
+                display.draw_popup(&popup)?;
+            }
+        }
+
+        // Over the buffers and popups at the top right, the prompt still goes over it
+        if self.debug {
+            let popup = Popup::new(
+                self.debug_lines(),
+                None,
+                (display.width, 0),
+                display.width,
+                display.height,
+            );
+            display.draw_popup(&popup)?;
+            display.place_cursor(buffer)?;
+        }
+
+        if let Some((prompt, kind)) = &self.prompt {
+            let picked = match kind {
+                PromptKind::Character { selected } => Some((
+                    *selected,
+                    search_characters(&prompt.input())
+                        .iter()
+                        .map(|(ch, name)| describe_character(*ch, name))
+                        .collect::<Vec<_>>(),
+                )),
+                PromptKind::Outline { selected, symbols } => Some((
+                    *selected,
+                    outline::filter(symbols, &prompt.input())
+                        .iter()
+                        .map(|symbol| symbol.describe())
+                        .collect(),
+                )),
+                _ => None,
+            };
+            if let Some((selected, matches)) = picked {
+                // Keep the selected match in the list
+                let first = (selected + 1).saturating_sub(MAX_ITEMS);
+                let items: Vec<String> = matches.into_iter().skip(first).take(MAX_ITEMS).collect();
+
+                if !items.is_empty() {
+                    let popup = Popup::new(
+                        items,
+                        Some(selected - first),
+                        (prompt.x, prompt.y),
+                        display.width,
+                        display.height,
+                    );
+                    display.draw_popup(&popup)?;
+                }
+            }
+
+            display.draw_prompt(prompt)?;
+        }
+
+        // Shown with the next frame, which this one does not make necessary
+        if self.show_frame_time {
+            self.status_line.frame_time = Some(format!("{:.1?}", frame_start.elapsed()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the range a click at `idx` selects: none for a single click, the word under it for a
+/// double click and its line for a triple click
+fn click_range(buffer: &Buffer, count: usize, idx: usize) -> (usize, usize) {
+    match count {
+        1 => (idx, idx),
+        2 => buffer.word_at(idx).unwrap_or((idx, idx)),
+        _ => buffer.line_range_at(idx),
+    }
+}
+
+/// Moves the cursor of `buffer` `count` times
+fn move_cursor(buffer: &mut Buffer, direction: Direction, count: usize) {
+    match direction {
+        Direction::Up => buffer.move_cursor_up(count),
+        Direction::Down => buffer.move_cursor_down(count),
+        Direction::Left => buffer.move_cursor_left(count),
+        Direction::Right => buffer.move_cursor_right(count),
+    }
+}
+
+/// Opens `buffer` in the language server configured for its file type, starting the server if needed.
+/// Does nothing if no server is configured.
+fn open_in_language_server(
+    language_servers: &mut HashMap<FileType, LspClient>,
+    config: &Config,
+    buffer: &mut Buffer,
+) -> Result<(), String> {
+    let Some(path) = &buffer.file_path else {
+        return Ok(());
+    };
+
+    let Some(command) = config.get_str("lsp", buffer.file_type.name()) else {
+        return Ok(());
+    };
+
+    let client = match language_servers.entry(buffer.file_type) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => {
+            // Servers are started once per file type, for the project of the first file
+            let root = project::find_root(path, &project::root_markers(config))
+                .or_else(|| project::current_root(config))
+                .ok_or_else(|| format!("Failed to start `{command}` : no project directory"))?;
+            let client = LspClient::start(command, &root)
+                .map_err(|e| format!("Failed to start `{command}` : {e}"))?;
+            entry.insert(client)
+        }
+    };
+
+    client
+        .did_open(
+            path,
+            buffer.file_type.language_id(),
+            buffer.version,
+            &buffer.text(),
+        )
+        .map_err(|e| format!("Language server error : {e}"))?;
+
+    // Make room for the diagnostic marks
+    buffer.gutter_width = buffer.gutter_width.max(2);
+
+    Ok(())
+}
+
+/// Makes the buffer of the file at `path` the active one, loading it if it is not open yet.
+/// The previously active buffer is kept at the back of `hidden`.
+/// Returns true if the file was loaded into a new buffer.
+fn open_buffer(path: &Path, buffer: &mut Buffer, hidden: &mut Vec<Buffer>) -> bool {
+    let (next, loaded) = match hidden.iter().position(|b| is_buffer_file(b, path)) {
+        Some(idx) => (hidden.remove(idx), false),
+        None => (
+            Buffer::from_file(
+                &path.to_string_lossy(),
+                buffer.x,
+                buffer.y,
+                buffer.width,
+                buffer.height,
+            ),
+            true,
+        ),
+    };
+
+    show_buffer(next, buffer, hidden);
+
+    loaded
+}
+
+/// Makes `next` the active buffer, keeping the previously active one at the back of `hidden`
+fn show_buffer(next: Buffer, buffer: &mut Buffer, hidden: &mut Vec<Buffer>) {
+    hidden.push(mem::replace(buffer, next));
+}
+
+/// Makes a scratch buffer named `name` holding `text` the active buffer.
+/// A scratch buffer with the same name is replaced, e.g. when running the same command again.
+fn show_scratch(name: &str, text: &str, buffer: &mut Buffer, hidden: &mut Vec<Buffer>) {
+    hidden.retain(|b| b.file_path.is_some() || b.name.as_deref() != Some(name));
+
+    let scratch = Buffer::scratch(name, text, buffer.x, buffer.y, buffer.width, buffer.height);
+    if buffer.file_path.is_none() && buffer.name.as_deref() == Some(name) {
+        *buffer = scratch;
+    } else {
+        show_buffer(scratch, buffer, hidden);
+    }
+}
+
+/// Runs a command entered in the prompt.
+/// Returns true if another buffer became the active one.
+fn run_command(
+    command: Command,
+    config: &Config,
+    buffer: &mut Buffer,
+    hidden: &mut Vec<Buffer>,
+    build: &mut Option<Build>,
+    split: &mut Option<Split>,
+) -> Result<bool, String> {
+    match command {
+        Command::Shell(cmd) => {
+            let output =
+                shell::run(&cmd, None).map_err(|e| format!("Failed to run `{cmd}` : {e}"))?;
+
+            let mut text = output.combined();
+            if !output.status.success() {
+                text.push_str(&format!("\n[{}]\n", output.status));
+            }
+
+            show_scratch(&format!("[Shell] {cmd}"), &text, buffer, hidden);
+
+            Ok(true)
+        }
+        Command::Build => {
+            if build.is_some() {
+                return Err("A build is already running".to_string());
+            }
+
+            let command = config.get_str("build", "command").ok_or_else(|| {
+                "No build command, set `command` in the `[build]` section of the config".to_string()
+            })?;
+            *build = Some(Build::start(command));
+
+            Ok(false)
+        }
+        Command::Grep(pattern) => {
+            let program = config
+                .get_str("grep", "command")
+                .unwrap_or(DEFAULT_GREP_COMMAND);
+            let cmd = format!("{program} -e {} .", shell::quote(&pattern));
+            let output =
+                shell::run(&cmd, None).map_err(|e| format!("Failed to run `{cmd}` : {e}"))?;
+
+            let mut entries = quickfix::grep_entries(&output.stdout);
+            // Matches are shown in the status line, as the files have them
+            for entry in &mut entries {
+                entry.message = shell::printable(&entry.message);
+            }
+            if entries.is_empty() {
+                return Err(match output.status.code() {
+                    Some(0 | 1) => format!("No matches for {pattern}"),
+                    _ => format!("Failed to run `{cmd}` : {}", output.stderr.trim()),
+                });
+            }
+
+            let count = entries.len();
+            let es = if count == 1 { "" } else { "es" };
+            let title = format!("{count} match{es} for {pattern}");
+            let text = quickfix::list_text(&title, &entries);
+            show_scratch(QUICKFIX_BUFFER_NAME, &text, buffer, hidden);
+
+            Ok(true)
+        }
+        Command::Diagnostics => {
+            let mut entries = vec![];
+            let others = split.as_ref().map(|s| &s.other);
+            for b in std::iter::once(&*buffer).chain(hidden.iter()).chain(others) {
+                let Some(path) = &b.file_path else {
+                    continue;
+                };
+                let mut diagnostics: Vec<_> = b.diagnostics.iter().collect();
+                diagnostics.sort_by_key(|d| (d.start.line, d.start.column));
+                entries.extend(diagnostics.into_iter().map(|d| quickfix::Entry {
+                    location: quickfix::Location {
+                        path: path.clone(),
+                        line: d.start.line,
+                        column: d.start.column,
+                    },
+                    message: format!(
+                        "{}: {}",
+                        d.severity.name(),
+                        d.message.lines().next().unwrap_or("")
+                    ),
+                }));
+            }
+            if entries.is_empty() {
+                return Err("No diagnostics".to_string());
+            }
+
+            let count = entries.len();
+            let title = format!("{count} diagnostic{}", if count == 1 { "" } else { "s" });
+            let text = quickfix::list_text(&title, &entries);
+            show_scratch(QUICKFIX_BUFFER_NAME, &text, buffer, hidden);
+
+            Ok(true)
+        }
+        Command::Quickfix => {
+            if is_quickfix_list(buffer) {
+                return Ok(false);
+            }
+            let idx = hidden
+                .iter()
+                .position(is_quickfix_list)
+                .ok_or_else(|| "No quickfix list".to_string())?;
+            let list = hidden.remove(idx);
+            show_buffer(list, buffer, hidden);
+
+            Ok(true)
+        }
+        Command::Format => format_buffer(config, buffer).map(|_| false),
+        Command::UndoTree => {
+            buffer.commit_history();
+
+            let mut text = format!(
+                "Undo tree of {}, press Enter on a state to go back to it\n\n",
+                buffer.file_name()
+            );
+            for line in buffer.history.describe() {
+                text.push_str(&line);
+                text.push('\n');
+            }
+
+            let source = buffer.id;
+            let name = format!("{UNDO_BUFFER_PREFIX} {}", buffer.file_name());
+            show_scratch(&name, &text, buffer, hidden);
+            buffer.source = Some(source);
+
+            Ok(true)
+        }
+        Command::Scratch => {
+            let scratch = Buffer::new(buffer.x, buffer.y, buffer.width, buffer.height);
+            show_buffer(scratch, buffer, hidden);
+
+            Ok(true)
+        }
+        Command::Buffers => {
+            let mut buffers: Vec<&Buffer> = std::iter::once(&*buffer)
+                .chain(hidden.iter())
+                .filter(|b| !is_buffer_list(b))
+                .collect();
+            buffers.sort_by_key(|b| b.id);
+
+            let markers = project::root_markers(config);
+            let mut text =
+                "Open buffers, press Enter on one to switch to it or d to close it\n\n".to_string();
+            for b in buffers {
+                let modified = if b.modified { '+' } else { ' ' };
+                let name = match &b.file_path {
+                    Some(path) => project::relative_path(path, &markers)
+                        .unwrap_or_else(|| path.display().to_string()),
+                    None => b.file_name(),
+                };
+                text.push_str(&format!("{:>4} {modified} {name}\n", b.id));
+            }
+
+            show_scratch(BUFFER_LIST_NAME, &text, buffer, hidden);
+            buffer.move_cursor_to_position(Position::line_start(2));
+
+            Ok(true)
+        }
+        Command::Rename
+        | Command::Count
+        | Command::Offset(None)
+        | Command::Character
+        | Command::Outline
+        | Command::Set(_)
+        | Command::ChangeDirectory(_)
+        | Command::InitConfig
+        | Command::Trust
+        | Command::SaveAll { .. }
+        | Command::Write { .. } => {
+            unreachable!("Handled before running commands, they need the prompt or the status line")
+        }
+        Command::Offset(Some(offset)) => {
+            let header = buffer.header_bytes();
+            let size = header + buffer.byte_offset(Position::new(buffer.line_count(), 0));
+            if offset > size {
+                return Err(format!(
+                    "Offset {offset} is past the end of the file at {size}"
+                ));
+            }
+
+            let position = buffer.byte_offset_to_position(offset.saturating_sub(header));
+            buffer.selection_anchor = None;
+            buffer.move_cursor_to_position(position);
+            buffer.scroll();
+            Ok(false)
+        }
+        Command::Reload => {
+            let Some(path) = buffer.file_path.clone() else {
+                return Err("The buffer is not saved to a file".to_string());
+            };
+            if !path.is_file() {
+                return Err(format!("{} does not exist", path.display()));
+            }
+
+            buffer.commit_history();
+            buffer
+                .reload()
+                .map_err(|e| format!("Failed to read {} : {e}", path.display()))?;
+
+            Ok(false)
+        }
+        Command::Read { path, encoding } => {
+            let path = expand_home(&path);
+            let bytes =
+                fs::read(&path).map_err(|e| format!("Failed to read {} : {e}", path.display()))?;
+            // Buffers hold LF line endings only
+            let text = encoding.decode(&bytes).replace("\r\n", "\n");
+
+            // One edit, undone at once
+            let cursor = buffer.cursor_pos;
+            buffer.commit_history();
+            buffer.replace_range(cursor, cursor, &text);
+            buffer.commit_history();
+
+            Ok(false)
+        }
+        Command::Sort { descending } => {
+            if !buffer.transform_lines(|lines| sort_lines(lines, descending)) {
+                return Err("Already sorted".to_string());
+            }
+            Ok(false)
+        }
+        Command::Unique => {
+            if !buffer.transform_lines(|lines| lines.dedup()) {
+                return Err("No duplicate lines".to_string());
+            }
+            Ok(false)
+        }
+        Command::Json { minify } => {
+            let selection = buffer.selection();
+            if selection.is_none() && buffer.file_type != FileType::Json {
+                return Err("Not a JSON buffer, select the JSON to format".to_string());
+            }
+
+            // Whitespace around the JSON stays, e.g. the line break ending the file
+            let (start, end) = selection.unwrap_or((0, buffer.data.len()));
+            let data = &buffer.data;
+            let start = (start..end)
+                .find(|i| !data[*i].is_whitespace())
+                .unwrap_or(end);
+            let end = (start..end)
+                .rev()
+                .find(|i| !data[*i].is_whitespace())
+                .map_or(start, |i| i + 1);
+            let text: String = data[start..end].iter().collect();
+
+            let formatted = if minify {
+                json::minify(&text)
+            } else {
+                let unit = Settings::for_buffer(config, buffer).indent_unit();
+                json::pretty(&text, &unit)
+            };
+
+            // The cursor goes where the JSON went wrong
+            let formatted = formatted.map_err(|e| {
+                let position = buffer.index_to_position(start + e.offset);
+                buffer.selection_anchor = None;
+                buffer.move_cursor_to_position(position);
+                buffer.scroll();
+                format!(
+                    "Invalid JSON : {} at line {}, column {}",
+                    e.message,
+                    position.line + 1,
+                    position.column + 1
+                )
+            })?;
+            if formatted == text {
+                return Err(if minify {
+                    "Already minified".to_string()
+                } else {
+                    "Already pretty-printed".to_string()
+                });
+            }
+
+            let cursor_pos = buffer.cursor_pos;
+            buffer.replace_range(start, end, &formatted);
+            if selection.is_none() {
+                buffer.cursor_pos = cursor_pos.min(buffer.data.len());
+            }
+            buffer.scroll();
+            Ok(false)
+        }
+        Command::Insert(name) => {
+            let text = template::expand(config, &name, buffer, DateTime::now())?;
+            buffer.insert_str(&text);
+            buffer.recalculate_lines();
+            buffer.scroll();
+            Ok(false)
+        }
+        Command::Reflow => {
+            let code = !is_prose(buffer);
+            let (first, last) = if buffer.selection().is_some() {
+                buffer.selected_lines()
+            } else {
+                let text = buffer.text();
+                let lines: Vec<&str> = text.split('\n').collect();
+                paragraph_around(&lines, buffer.current_line(), code)
+                    .ok_or_else(|| "No paragraph under the cursor".to_string())?
+            };
+
+            let settings = Settings::for_buffer(config, buffer);
+            if !buffer.transform_line_range(first, last, |lines| {
+                reflow(lines, settings.text_width, settings.tab_width, code)
+            }) {
+                return Err("Already within the text width".to_string());
+            }
+            buffer.scroll();
+            Ok(false)
+        }
+        Command::Reindent => {
+            if !reindent(config, buffer)? {
+                return Err("Already indented".to_string());
+            }
+            Ok(false)
+        }
+        Command::Case(case) => {
+            if buffer.transform_selection_or_word(|text| case.apply(text)) {
+                Ok(false)
+            } else {
+                Err("No word under the cursor".to_string())
+            }
+        }
+        Command::DiffGet | Command::DiffPut => match split {
+            Some(split) => split
+                .copy_hunk(buffer, matches!(command, Command::DiffPut))
+                .map(|_| false),
+            None => Err("Not comparing buffers".to_string()),
+        },
+        Command::Diff => {
+            let Some(path) = &buffer.file_path else {
+                return Err("The buffer is not saved to a file".to_string());
+            };
+
+            let saved = buffer
+                .saved_text()
+                .map_err(|e| format!("Failed to read {} : {e}", path.display()))?;
+            let current = buffer.text();
+            if saved == current {
+                return Err("No unsaved changes".to_string());
+            }
+
+            let old: Vec<&str> = saved.lines().collect();
+            let new: Vec<&str> = current.lines().collect();
+            if old == new {
+                return Err("Only the newline at the end of the file changed".to_string());
+            }
+            let name = path.display().to_string();
+            let text = diff::unified(
+                &old,
+                &new,
+                &format!("{name} (saved)"),
+                &format!("{name} (buffer)"),
+                3,
+            );
+
+            let source = buffer.id;
+            show_scratch(
+                &format!("[Diff] {}", buffer.file_name()),
+                &text,
+                buffer,
+                hidden,
+            );
+            buffer.file_type = FileType::Diff;
+            buffer.source = Some(source);
+
+            Ok(true)
+        }
+        Command::History => {
+            let Some(path) = &buffer.file_path else {
+                return Err("The buffer is not saved to a file".to_string());
+            };
+            let history = FileHistory::for_file(config, path)?.ok_or_else(|| {
+                "The history of files is turned off in the `[history]` section of the config"
+                    .to_string()
+            })?;
+            let snapshots = history
+                .snapshots()
+                .map_err(|e| format!("Failed to read the history : {e}"))?;
+            if snapshots.is_empty() {
+                return Err(format!("No history of {} yet", buffer.file_name()));
+            }
+
+            let mut text = format!(
+                "History of {}, press Enter on a version to compare it with the buffer \
+                 or r to restore it\n\n",
+                buffer.file_name()
+            );
+            let current = buffer.text();
+            let current: Vec<&str> = current.lines().collect();
+            for (i, snapshot) in snapshots.iter().enumerate() {
+                let (added, removed) = match snapshot.data() {
+                    Ok(data) => {
+                        let old: String = data.into_iter().collect();
+                        let old: Vec<&str> = old.lines().collect();
+                        diff::diff_lines(&old, &current)
+                            .iter()
+                            .fold((0, 0), |(a, r), h| {
+                                (a + h.new_end - h.new_start, r + h.old_end - h.old_start)
+                            })
+                    }
+                    Err(_) => (0, 0),
+                };
+                text.push_str(&format!(
+                    "{:>4}  {}  {:<8}  +{added} -{removed}\n",
+                    i + 1,
+                    snapshot.saved_at(),
+                    format_age(snapshot.time()),
+                ));
+            }
+
+            let source = buffer.id;
+            let name = format!("{HISTORY_BUFFER_PREFIX} {}", buffer.file_name());
+            show_scratch(&name, &text, buffer, hidden);
+            buffer.source = Some(source);
+            buffer.move_cursor_to_position(Position::line_start(2));
+
+            Ok(true)
+        }
+        Command::Pipe(cmd) => {
+            let selection = buffer.selection();
+            let (start, end) = selection.unwrap_or((0, buffer.data.len()));
+            let input: String = buffer.data[start..end].iter().collect();
+
+            let output = shell::run(&cmd, Some(&input))
+                .map_err(|e| format!("Failed to run `{cmd}` : {e}"))?;
+
+            if !output.status.success() {
+                return Err(match output.stderr.lines().next() {
+                    Some(error) => format!("`{cmd}` failed ({}) : {error}", output.status),
+                    None => format!("`{cmd}` failed ({})", output.status),
+                });
+            }
+
+            let cursor_pos = buffer.cursor_pos;
+            buffer.replace_range(start, end, &output.stdout);
+
+            // Stay around the same place when the whole buffer was replaced
+            if selection.is_none() {
+                buffer.cursor_pos = cursor_pos.min(buffer.data.len());
+            }
+            buffer.scroll();
+
+            Ok(false)
+        }
+    }
+}
+
+/// Re-indents the selected lines of `buffer`, or all of them, with the indenter configured for
+/// its file type or after the nesting of brackets. The cursor stays on its line.
+/// Returns true if the indentation changed.
+fn reindent(config: &Config, buffer: &mut Buffer) -> Result<bool, String> {
+    let position = buffer.cursor_position();
+    let selected = buffer.selection().is_some();
+
+    let changed = match config.get_str("indent", buffer.file_type.name()) {
+        Some(command) => {
+            let (first, last) = buffer.selected_lines();
+            let text = buffer.text();
+            let lines: Vec<&str> = text
+                .split('\n')
+                .skip(first)
+                .take(last - first + 1)
+                .collect();
+            let input = lines.join("\n");
+            let output = format::run_formatter(command, &input)?;
+            // Indenters end what they print with a line break
+            let output = if input.ends_with('\n') {
+                &output
+            } else {
+                output.strip_suffix('\n').unwrap_or(&output)
+            };
+            buffer.transform_lines(|lines| *lines = output.split('\n').map(String::from).collect())
+        }
+        None if indent::has_bracket_rules(buffer.file_type) => {
+            let unit = Settings::for_buffer(config, buffer).indent_unit();
+            buffer.transform_lines(|lines| indent::reindent(lines, &unit))
+        }
+        None => {
+            let name = buffer.file_type.name();
+            return Err(format!(
+                "No indentation rules for {name}, set `{name}` in the `[indent]` section of the config"
+            ));
+        }
+    };
+
+    if changed && !selected {
+        buffer.move_cursor_to_position(Position::line_start(position.line));
+        buffer.move_cursor_home();
+        buffer.scroll();
+    }
+
+    Ok(changed)
+}
+
+/// Pipes `buffer` through the formatter configured for its file type.
+/// Returns true if the formatter changed anything.
+fn format_buffer(config: &Config, buffer: &mut Buffer) -> Result<bool, String> {
+    let name = buffer.file_type.name();
+    let command = config.get_str("format", name).ok_or_else(|| {
+        format!("No formatter for {name}, set `{name}` in the `[format]` section of the config")
+    })?;
+
+    let formatted = format::run_formatter(command, &buffer.text())?;
+
+    Ok(format::apply(buffer, &formatted))
+}
+
+/// Runs `script` on the active buffer, answering what it asks. Commands of the editor itself,
+/// like `wa`, are left in `outcome` to run once it is done, along with the keys it bound.
+fn run_script(
+    script: &Script,
+    config: &Config,
+    buffer: &mut Buffer,
+    hidden: &mut Vec<Buffer>,
+    build: &mut Option<Build>,
+    split: &mut Option<Split>,
+    outcome: &mut ScriptOutcome,
+) -> Result<(), String> {
+    let result = script.run(|request| {
+        let reply = match request {
+            Request::LineCount => Reply::Number(buffer.line_count()),
+            Request::Line(line) if line < buffer.line_count() => {
+                Reply::Text(buffer.line_text(line))
+            }
+            Request::Line(line) => return Err(format!("There is no line {}", line + 1)),
+            Request::Lines => Reply::Lines(
+                (0..buffer.line_count())
+                    .map(|line| buffer.line_text(line))
+                    .collect(),
+            ),
+            Request::Cursor => Reply::Position(buffer.cursor_position()),
+            Request::Selection => Reply::Text(buffer.selected_text().unwrap_or_default()),
+            Request::Insert(text) => {
+                text.chars().for_each(|ch| buffer.insert_ch(ch));
+                Reply::Done
+            }
+            Request::Backspace(count) => {
+                for _ in 0..count {
+                    if buffer.cursor_pos == 0 && buffer.selection().is_none() {
+                        break;
+                    }
+                    buffer.backspace();
+                }
+                Reply::Done
+            }
+            Request::Delete(count) => {
+                for _ in 0..count {
+                    if buffer.cursor_pos >= buffer.data.len() && buffer.selection().is_none() {
+                        break;
+                    }
+                    buffer.delete();
+                }
+                Reply::Done
+            }
+            Request::Move(direction, count) => {
+                move_cursor(buffer, direction, count);
+                Reply::Done
+            }
+            Request::Goto(position) => {
+                buffer.move_cursor_to_position(position);
+                Reply::Done
+            }
+            Request::Message(text) => {
+                outcome.message = Some(text);
+                Reply::Done
+            }
+            Request::Run(text) => {
+                match Command::parse(&text)? {
+                    Command::Count => outcome.message = Some(count_message(buffer)),
+                    Command::Offset(None) => outcome.message = Some(offset_message(buffer)),
+                    Command::Rename | Command::Character | Command::Outline => {
+                        return Err(format!("`{text}` needs the prompt"))
+                    }
+                    command @ (Command::Set(_)
+                    | Command::ChangeDirectory(_)
+                    | Command::InitConfig
+                    | Command::Trust
+                    | Command::SaveAll { .. }
+                    | Command::Write { .. }) => outcome.commands.push(command),
+                    command => {
+                        outcome.switched |=
+                            run_command(command, config, buffer, hidden, build, split)?
+                    }
+                }
+                Reply::Done
+            }
+            Request::Bind { keys, command } => {
+                Keymap::parse_sequence(config, &keys)?;
+                outcome.keys.push((keys, command));
+                Reply::Done
+            }
+        };
+        Ok(reply)
+    });
+
+    buffer.scroll();
+
+    result
+}
+
+/// Title of the terminal window, naming the file of `buffer` and whether it has unsaved changes
+fn window_title(buffer: &Buffer) -> String {
+    if buffer.modified {
+        format!("{} + — tte", buffer.file_name())
+    } else {
+        format!("{} — tte", buffer.file_name())
+    }
+}
+
+/// Name of the file of `buffer` in the status line, relative to the root of its project
+fn status_file_name(buffer: &Buffer, config: &Config) -> String {
+    let name = buffer
+        .file_path
+        .as_deref()
+        .and_then(|path| project::relative_path(path, &project::root_markers(config)))
+        .unwrap_or_else(|| buffer.file_name());
+
+    if buffer.new_file {
+        format!("{name} [New File]")
+    } else if buffer.bom && buffer.keep_bom {
+        format!("{name} [BOM]")
+    } else {
+        name
+    }
+}
+
+/// Size left to the buffers on a terminal of the given size. A terminal too short for both
+/// hides the status line, and buffers never get smaller than one cell.
+pub fn buffer_size(width: u16, height: u16) -> (usize, usize) {
+    let status_height = if height > 1 { 1 } else { 0 };
+    (
+        (width as usize).max(1),
+        (height as usize).saturating_sub(status_height).max(1),
+    )
+}
+
+/// Keeps the buffers filling the terminal
+fn resize_buffers(event: &EditorEvent, cx: &mut EventContext) -> Result<(), String> {
+    let EditorEvent::Resize { y, width, height } = *event else {
+        return Ok(());
+    };
+
+    // Be sure to resize the buffer correctly or the rendering will messup.
+    match cx.split {
+        Some(split) => split.layout(cx.buffer, 0, y, width, height),
+        None => {
+            cx.buffer.move_to(0, y);
+            cx.buffer.resize(width, height);
+        }
+    }
+    for hidden in cx.hidden.iter_mut() {
+        hidden.move_to(0, y);
+        hidden.resize(width, height);
+    }
+
+    Ok(())
+}
+
+/// Restores the named marks of opened files and stores them when they are saved
+fn sync_session(event: &EditorEvent, cx: &mut EventContext) -> Result<(), String> {
+    match event {
+        EditorEvent::BufferOpened => {
+            cx.session.restore(cx.buffer);
+            cx.session.opened(cx.buffer);
+        }
+        EditorEvent::AfterSave => {
+            cx.session.update(cx.buffer);
+            cx.session
+                .save()
+                .map_err(|e| format!("Failed to save the session : {e}"))?;
+        }
+        // Marks move with the edits, remember where they ended up
+        EditorEvent::BeforeClose => cx.session.update(cx.buffer),
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// Applies the modelines of opened files, if `modelines` is on in the `[editor]` section.
+/// Off by default, a file should not change how it is edited unless asked to.
+fn apply_modelines(event: &EditorEvent, cx: &mut EventContext) -> Result<(), String> {
+    if *event != EditorEvent::BufferOpened
+        || cx.config.get_bool("editor", "modelines") != Some(true)
+    {
+        return Ok(());
+    }
+
+    // A bad modeline is not worth a message, the file opens as if it had none
+    for (name, value) in modeline::parse(&cx.buffer.text()) {
+        let _ = set_buffer_setting(cx.buffer, &name, &value);
+    }
+
+    Ok(())
+}
+
+/// Formats buffers before saving them, if their `format_on_save` setting is on and a formatter is
+/// configured
+fn format_on_save(event: &EditorEvent, cx: &mut EventContext) -> Result<(), String> {
+    if *event == EditorEvent::BeforeSave
+        && Settings::for_buffer(cx.config, cx.buffer).format_on_save
+        && cx
+            .config
+            .get_str("format", cx.buffer.file_type.name())
+            .is_some()
+    {
+        format_buffer(cx.config, cx.buffer)?;
+    }
+
+    Ok(())
+}
+
+/// Keeps the version of the file just saved in its history
+fn keep_history(event: &EditorEvent, cx: &mut EventContext) -> Result<(), String> {
+    let (EditorEvent::AfterSave, Some(path)) = (event, &cx.buffer.file_path) else {
+        return Ok(());
+    };
+    let Some(history) = FileHistory::for_file(cx.config, path)? else {
+        return Ok(());
+    };
+
+    history.record(path).map(|_| ()).map_err(|e| {
+        format!(
+            "Failed to keep the history of {} : {e}",
+            cx.buffer.file_name()
+        )
+    })
+}
+
+/// Runs the scripts set in the `[hooks]` section of the config
+fn run_script_hooks(event: &EditorEvent, cx: &mut EventContext) -> Result<(), String> {
+    let hook = match event {
+        EditorEvent::BufferOpened => "on_open",
+        EditorEvent::BeforeSave => "on_save",
+        EditorEvent::CharInserted(_) => "on_insert",
+        _ => return Ok(()),
+    };
+
+    let Some(name) = cx.config.get_str("hooks", hook).filter(|_| cx.run_hooks) else {
+        return Ok(());
+    };
+    let Some(script) = cx.scripts.get(name) else {
+        return Err(format!("No script `{name}` for the {hook} hook"));
+    };
+
+    run_script(
+        script, cx.config, cx.buffer, cx.hidden, cx.build, cx.split, cx.outcome,
+    )
+}
+
+/// Tells the language server of the buffer about it being opened, saved and closed
+fn notify_language_server(event: &EditorEvent, cx: &mut EventContext) -> Result<(), String> {
+    match event {
+        EditorEvent::BufferOpened => {
+            open_in_language_server(cx.language_servers, cx.config, cx.buffer)
+        }
+        EditorEvent::AfterSave => {
+            let (Some(client), Some(path)) = (
+                cx.language_servers.get_mut(&cx.buffer.file_type),
+                &cx.buffer.file_path,
+            ) else {
+                return Ok(());
+            };
+            client
+                .did_save(path)
+                .map_err(|e| format!("Language server error : {e}"))
+        }
+        EditorEvent::BeforeClose => {
+            let (Some(client), Some(path)) = (
+                cx.language_servers.get_mut(&cx.buffer.file_type),
+                &cx.buffer.file_path,
+            ) else {
+                return Ok(());
+            };
+            client
+                .did_close(path)
+                .map_err(|e| format!("Language server error : {e}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Describes the counts of the buffer, and of the selection if there is one
+fn count_message(buffer: &Buffer) -> String {
+    let mut message = format!("Buffer: {}", Counts::of(&buffer.data));
+    if let Some((start, end)) = buffer.selection() {
+        message.push_str(&format!(
+            " | Selection: {}",
+            Counts::of(&buffer.data[start..end])
+        ));
+    }
+    message
+}
+
+/// Describes where the cursor, and the selection if there is one, are in bytes into the file,
+/// in decimal and in hexadecimal
+fn offset_message(buffer: &Buffer) -> String {
+    let header = buffer.header_bytes();
+    let offset_of = |idx: usize| header + buffer.byte_offset(buffer.index_to_position(idx));
+
+    let offset = offset_of(buffer.cursor_pos);
+    let size = offset_of(buffer.data.len());
+    let mut message = format!("Offset {offset} (0x{offset:x}) of {size} bytes");
+    if let Some((start, end)) = buffer.selection() {
+        let (start, end) = (offset_of(start), offset_of(end));
+        message.push_str(&format!(
+            " | Selection: {start}-{end} (0x{start:x}-0x{end:x}), {} bytes",
+            end - start
+        ));
+    }
+    message
+}
+
+/// Writes the `lines` (counted from 1), the selection or the whole of `buffer` to the file at
+/// `path`, without changing the file of the buffer. Returns the message for the status line.
+/// A file replaced with `overwrite` is copied to `trash` first with `use_trash`, the message
+/// tells if there is no trash to copy it to.
+pub fn write_part(
+    buffer: &Buffer,
+    lines: Option<(usize, usize)>,
+    path: &str,
+    overwrite: bool,
+    use_trash: bool,
+    trash: Option<Trash>,
+) -> Result<String, String> {
+    let path = expand_home(path);
+    let exists = path.exists();
+    if exists && !overwrite {
+        return Err(format!("{} exists, use w! to replace it", path.display()));
+    }
+
+    let text = match (lines, buffer.selection()) {
+        (Some((first, last)), _) => {
+            let count = buffer.line_count();
+            if first > count {
+                return Err(format!("The buffer has {count} lines"));
+            }
+            let start = buffer.lines[first - 1].start;
+            let end = (buffer.lines[last.min(count) - 1].end + 1).min(buffer.data.len());
+            buffer.data[start..end].iter().collect()
+        }
+        (None, Some((start, end))) => buffer.data[start..end].iter().collect(),
+        (None, None) => buffer.text(),
+    };
+
+    // The file replaced can be brought back from the trash
+    let kept = trash::replace_file(&path, &text, trash.as_ref().filter(|_| use_trash))?;
+
+    let written = format!("Wrote {} lines to {}", text.lines().count(), path.display());
+    Ok(match kept {
+        Some(_) => format!("{written}, the file replaced is in the trash"),
+        None if exists && use_trash && trash.is_none() => {
+            format!("{written}, there is no trash to keep the file replaced")
+        }
+        None => written,
+    })
+}
+
+/// The word count for the status line, out of the buffer's if something is selected
+fn word_count(buffer: &Buffer) -> String {
+    let total = Counts::of(&buffer.data).words;
+    match buffer.selection() {
+        Some((start, end)) => {
+            let selected = Counts::of(&buffer.data[start..end]).words;
+            format!("{selected} of {total} words")
+        }
+        None => format!("{total} words"),
+    }
+}
+
+/// Highlights the occurrences of the word under the cursor and opens a prompt asking for
+/// their new name
+fn start_rename(buffer: &mut Buffer, y: u16, width: usize) -> Result<(Prompt, PromptKind), String> {
+    let Some((start, end)) = buffer.word_at_cursor() else {
+        return Err("No word under the cursor".to_string());
+    };
+    let word: String = buffer.data[start..end].iter().collect();
+    let ranges = buffer.word_occurrences();
+    buffer.occurrences = ranges.clone();
+
+    let prompt = Prompt::new(0, y, width, &format!("Rename {word} to : "), &word);
+
+    Ok((prompt, PromptKind::Rename(ranges)))
+}
+
+/// Brings the buffer browsed by the undo tree buffer back to the state under the cursor,
+/// and makes it the active buffer
+fn jump_in_undo_tree(buffer: &mut Buffer, hidden: &mut Vec<Buffer>) -> Result<(), String> {
+    let line = buffer.current_line();
+    let node: usize = buffer
+        .text()
+        .lines()
+        .nth(line)
+        .and_then(|l| l.split_whitespace().next())
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| "No state on this line".to_string())?;
+
+    let idx = hidden
+        .iter()
+        .position(|b| Some(b.id) == buffer.source)
+        .ok_or_else(|| "The buffer of this undo tree is closed".to_string())?;
+
+    let source = hidden.remove(idx);
+    show_buffer(source, buffer, hidden);
+    buffer.undo_jump(node);
+
+    Ok(())
+}
+
+/// Sets a setting of `buffer` only, the file type or one of the `Settings`
+fn set_buffer_setting(buffer: &mut Buffer, name: &str, value: &str) -> Result<(), String> {
+    if name == "filetype" {
+        buffer.file_type =
+            FileType::from_name(value).ok_or_else(|| format!("Unknown file type : {value}"))?;
+        return Ok(());
+    }
+
+    // Check the value before keeping it
+    Settings::default().set(name, value)?;
+    buffer.settings.retain(|(n, _)| n != name);
+    buffer.settings.push((name.to_string(), value.to_string()));
+
+    Ok(())
+}
+
+/// Whether `buffer` is the list of the open buffers
+fn is_buffer_list(buffer: &Buffer) -> bool {
+    buffer.file_path.is_none() && buffer.name.as_deref() == Some(BUFFER_LIST_NAME)
+}
+
+/// Whether `buffer` lists the history of the file of another buffer
+fn is_history_list(buffer: &Buffer) -> bool {
+    buffer.file_path.is_none()
+        && buffer.source.is_some()
+        && buffer
+            .name
+            .as_deref()
+            .is_some_and(|n| n.starts_with(HISTORY_BUFFER_PREFIX))
+}
+
+/// Returns the snapshot on the line under the cursor in the history list, and the position in
+/// `hidden` of the buffer whose history it is
+fn listed_snapshot(
+    config: &Config,
+    buffer: &Buffer,
+    hidden: &[Buffer],
+) -> Result<(usize, Snapshot), String> {
+    let number: usize = buffer
+        .text()
+        .lines()
+        .nth(buffer.current_line())
+        .and_then(|l| l.split_whitespace().next())
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| "No version on this line".to_string())?;
+
+    let idx = hidden
+        .iter()
+        .position(|b| Some(b.id) == buffer.source)
+        .ok_or_else(|| "The buffer of this history is closed".to_string())?;
+    let path = hidden[idx]
+        .file_path
+        .as_deref()
+        .ok_or_else(|| "The buffer is not saved to a file".to_string())?;
+
+    let snapshot = FileHistory::for_file(config, path)?
+        .and_then(|history| history.snapshots().ok())
+        .and_then(|snapshots| snapshots.into_iter().nth(number.checked_sub(1)?))
+        .ok_or_else(|| "The version is no longer kept".to_string())?;
+
+    Ok((idx, snapshot))
+}
+
+/// Shows how the version under the cursor in the history list differs from its buffer
+fn compare_snapshot(
+    config: &Config,
+    buffer: &mut Buffer,
+    hidden: &mut Vec<Buffer>,
+) -> Result<(), String> {
+    let (idx, snapshot) = listed_snapshot(config, buffer, hidden)?;
+    let source = &hidden[idx];
+    let old: String = snapshot
+        .data()
+        .map_err(|e| format!("Failed to read {} : {e}", snapshot.path.display()))?
+        .into_iter()
+        .collect();
+    let new = source.text();
+
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    if old == new {
+        return Err("This version is the same as the buffer".to_string());
+    }
+
+    let name = source.file_name();
+    let text = diff::unified(
+        &old,
+        &new,
+        &format!("{name} ({})", snapshot.saved_at()),
+        &format!("{name} (buffer)"),
+        3,
+    );
+
+    let source = source.id;
+    show_scratch(&format!("[Diff] {name}"), &text, buffer, hidden);
+    buffer.file_type = FileType::Diff;
+    buffer.source = Some(source);
+
+    Ok(())
+}
+
+/// Brings the buffer of the history list back to the version under the cursor, as one edit
+/// that can be undone, and makes it the active buffer
+fn restore_snapshot(
+    config: &Config,
+    buffer: &mut Buffer,
+    hidden: &mut Vec<Buffer>,
+) -> Result<String, String> {
+    let (idx, snapshot) = listed_snapshot(config, buffer, hidden)?;
+    let data = snapshot
+        .data()
+        .map_err(|e| format!("Failed to read {} : {e}", snapshot.path.display()))?;
+
+    // The list has done its job once a version is restored
+    let list = buffer.id;
+    let source = hidden.remove(idx);
+    show_buffer(source, buffer, hidden);
+    hidden.retain(|b| b.id != list);
+
+    let time = snapshot.saved_at();
+    buffer.commit_history();
+    if buffer.replace_text(&data) {
+        Ok(format!("Restored the version of {time}, save to keep it"))
+    } else {
+        Ok(format!("The buffer is already the version of {time}"))
+    }
+}
+
+/// Returns the id of the buffer on the line under the cursor in the buffer list
+fn listed_buffer(buffer: &Buffer) -> Option<usize> {
+    buffer
+        .text()
+        .lines()
+        .nth(buffer.current_line())?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Applies `shift` to each of the selected lines, or to the cursor line, keeping the lines
+/// selected, or the cursor on the same character of its line
+fn shift_selected_lines(buffer: &mut Buffer, shift: impl Fn(&mut String)) {
+    let cursor = buffer.cursor_position();
+    let selected = buffer.selection().is_some();
+    let (first, last) = if selected {
+        buffer.selected_lines()
+    } else {
+        (cursor.line, cursor.line)
+    };
+    let before = buffer.lines[first].len();
+
+    let changed =
+        buffer.transform_line_range(first, last, |lines| lines.iter_mut().for_each(&shift));
+    if !changed {
+        return;
+    }
+
+    if selected {
+        let end = Position::new(last, buffer.line_length(last));
+        buffer.select_positions(Position::line_start(first), end);
+    } else {
+        // Characters were added or removed before the cursor, in the indentation
+        let shifted = buffer.lines[first].len() as isize - before as isize;
+        let column = (cursor.column as isize + shifted).max(0) as usize;
+        buffer.move_cursor_to_position(Position::new(cursor.line, column));
+    }
+}
+
+/// Whether `buffer` is the quickfix list
+fn is_quickfix_list(buffer: &Buffer) -> bool {
+    buffer.file_path.is_none() && buffer.name.as_deref() == Some(QUICKFIX_BUFFER_NAME)
+}
+
+/// Puts `text` in the quickfix list without showing it, replacing the list there was
+fn fill_quickfix_list(text: &str, buffer: &Buffer, hidden: &mut Vec<Buffer>) {
+    hidden.retain(|b| !is_quickfix_list(b));
+    let list = Buffer::scratch(
+        QUICKFIX_BUFFER_NAME,
+        text,
+        buffer.x,
+        buffer.y,
+        buffer.width,
+        buffer.height,
+    );
+    hidden.push(list);
+}
+
+/// Moves the cursor to the location of `entry`, opening its file if needed.
+/// Returns true if the file was loaded into a new buffer.
+fn go_to_entry(
+    entry: &quickfix::Entry,
+    buffer: &mut Buffer,
+    hidden: &mut Vec<Buffer>,
+) -> Result<bool, String> {
+    let path = &entry.location.path;
+    let opened = if is_buffer_file(buffer, path) {
+        false
+    } else if path.is_file() {
+        open_buffer(path, buffer, hidden)
+    } else {
+        return Err(format!("No such file : {}", path.display()));
+    };
+
+    buffer.move_cursor_to_position(Position::new(entry.location.line, entry.location.column));
+    buffer.scroll();
+
+    Ok(opened)
+}
+
+/// Goes to the entry of the quickfix list after the one under its cursor, or before it if not
+/// `forward`, wherever the list is among the open buffers. The cursor of the list stays on the
+/// entry, so the next step goes on from there.
+/// Returns the message to show and true if the file of the entry was loaded into a new buffer.
+fn step_entry(
+    forward: bool,
+    buffer: &mut Buffer,
+    hidden: &mut Vec<Buffer>,
+    split: Option<&mut Split>,
+) -> Result<(String, bool), String> {
+    let list = if is_quickfix_list(buffer) {
+        &mut *buffer
+    } else if let Some(list) = hidden.iter_mut().find(|b| is_quickfix_list(b)) {
+        list
+    } else {
+        split
+            .map(|s| &mut s.other)
+            .filter(|b| is_quickfix_list(b))
+            .ok_or_else(|| "No quickfix list".to_string())?
+    };
+
+    let text = list.text();
+    let entries: Vec<(usize, quickfix::Entry)> = text
+        .lines()
+        .enumerate()
+        .skip(FIRST_ENTRY_LINE)
+        .filter_map(|(line, text)| Some((line, quickfix::Entry::parse(text)?)))
+        .collect();
+    let current = list.current_line();
+    let next = if forward {
+        entries.iter().position(|(line, _)| *line > current)
+    } else {
+        entries.iter().rposition(|(line, _)| *line < current)
+    };
+    let Some(idx) = next else {
+        return Err(match (entries.is_empty(), forward) {
+            (true, _) => "The quickfix list is empty".to_string(),
+            (false, true) => "No next entry in the quickfix list".to_string(),
+            (false, false) => "No previous entry in the quickfix list".to_string(),
+        });
+    };
+
+    let (line, entry) = &entries[idx];
+    list.move_cursor_to_position(Position::new(*line, 0));
+    list.scroll();
+
+    let opened = go_to_entry(entry, buffer, hidden)?;
+    let message = format!("Entry {} of {} : {}", idx + 1, entries.len(), entry.message);
+
+    Ok((message, opened))
+}
+
+/// Jumps from the line under the cursor in the build output to the location it mentions.
+/// Returns true if the file was loaded into a new buffer.
+fn jump_to_location(buffer: &mut Buffer, hidden: &mut Vec<Buffer>) -> Result<bool, String> {
+    let text = buffer.text();
+    let lines: Vec<&str> = text.lines().collect();
+    let location = location_for_line(&lines, buffer.current_line())
+        .ok_or_else(|| "No location on this line".to_string())?;
+
+    if !location.path.is_file() {
+        return Err(format!("No such file : {}", location.path.display()));
+    }
+
+    let opened = open_buffer(&location.path, buffer, hidden);
+    buffer.move_cursor_to_position(Position::new(location.line, location.column));
+    buffer.scroll();
+
+    Ok(opened)
+}
+
+/// Resolves the link or path under the cursor to an existing file.
+/// Relative paths are relative to the directory of the buffer's file.
+fn resolve_link(buffer: &Buffer) -> Result<PathBuf, String> {
+    let target = markdown::link_target_at(&buffer.data, buffer.cursor_pos)
+        .ok_or_else(|| "No link under the cursor".to_string())?;
+
+    if target.contains("://") {
+        return Err(format!("Cannot open {target}"));
+    }
+
+    // Drop anchors like `file.md#section`
+    let target = target.split('#').next().unwrap_or_default();
+    if target.is_empty() {
+        return Err("Link points inside the current file".to_string());
+    }
+
+    let base = buffer
+        .file_path
+        .as_ref()
+        .and_then(|p| p.parent())
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let path = base.join(target);
+
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(format!("No such file : {}", path.display()))
+    }
+}
+
+/// Returns the file named by pasted text that looks like what terminals paste when a file is
+/// dropped on them: an absolute path to an existing file, maybe quoted, with its spaces escaped
+/// by backslashes, or a `file://` URI
+fn dropped_file(text: &str) -> Option<PathBuf> {
+    let text = text.trim();
+    if text.contains('\n') {
+        return None;
+    }
+    let text = ['\'', '"']
+        .into_iter()
+        .find_map(|quote| text.strip_prefix(quote)?.strip_suffix(quote))
+        .unwrap_or(text);
+
+    let path = if text.starts_with("file://") {
+        uri_to_path(text)
+    } else if cfg!(windows) {
+        PathBuf::from(text)
+    } else {
+        let mut unescaped = String::with_capacity(text.len());
+        let mut chars = text.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => unescaped.extend(chars.next()),
+                _ => unescaped.push(ch),
+            }
+        }
+        PathBuf::from(unescaped)
+    };
+
+    (path.is_absolute() && path.is_file()).then_some(path)
+}
+
+/// Returns true if `buffer` holds prose that should be spell checked
+fn is_prose(buffer: &Buffer) -> bool {
+    matches!(buffer.file_type, FileType::Markdown | FileType::Text)
+}
+
+/// Loads the dictionary used to spell check `buffer`.
+/// Returns `None` if spell checking does not apply to the buffer or is disabled in the config.
+fn load_dictionary(config: &Config, buffer: &Buffer) -> Result<Option<Dictionary>, String> {
+    if !is_prose(buffer) || config.get_bool("spell", "enabled") == Some(false) {
+        return Ok(None);
+    }
+
+    let path = config.get_str("spell", "dictionary").map(Path::new);
+
+    Dictionary::load(path)
+        .map(Some)
+        .map_err(|e| format!("Spell checking disabled : {e}"))
+}
+
+/// Returns true if `path` points to the file open in `buffer`
+fn is_buffer_file(buffer: &Buffer, path: &Path) -> bool {
+    let Some(file_path) = &buffer.file_path else {
+        return false;
+    };
+
+    match (fs::canonicalize(file_path), fs::canonicalize(path)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => file_path == path,
+    }
+}
+
+/// Follows the sequences of keys bound in the keymap, setting the command to run once one is
+/// complete. Returns true if the key was part of a sequence.
+fn handle_keymap_key(
+    keymap: &Keymap,
+    pending: &mut Vec<Key>,
+    key: &KeyEvent,
+    entered_command: &mut Option<String>,
+) -> bool {
+    if key.kind != KeyEventKind::Press {
+        return !pending.is_empty();
+    }
+
+    pending.push(Key::from_event(key));
+    match keymap.lookup(pending) {
+        Lookup::Command(command) => {
+            *entered_command = Some(command.to_string());
+            pending.clear();
+            true
+        }
+        Lookup::Prefix(_) => true,
+        // A key that does not continue the sequence cancels it
+        Lookup::Unbound => {
+            let in_sequence = pending.len() > 1;
+            pending.clear();
+            in_sequence
+        }
+    }
+}
+
+/// Handles the keys that navigate an open completion popup.
+/// Returns true if the event was consumed.
+fn handle_completion_key(
+    completion: &mut Option<Completion>,
+    buffer: &mut Buffer,
+    event: &Event,
+) -> bool {
+    let Some(c) = completion else {
+        return false;
+    };
+
+    let Event::Key(KeyEvent {
+        code,
+        modifiers: KeyModifiers::NONE,
+        kind: KeyEventKind::Press,
+        ..
+    }) = event
+    else {
+        return false;
+    };
+
+    match code {
+        KeyCode::Up => c.select_previous(),
+        KeyCode::Down => c.select_next(),
+        KeyCode::Enter | KeyCode::Tab => {
+            c.accept(buffer);
+            buffer.scroll();
+            *completion = None;
+        }
+        KeyCode::Esc => *completion = None,
+        _ => return false,
+    }
+
+    true
+}
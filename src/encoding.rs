@@ -0,0 +1,48 @@
+/// Text encodings files can be read from, they are always saved as UTF-8
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// ISO 8859-1, every byte is the character with the same code
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    /// Finds an encoding by name, e.g. `utf-8`, `latin1` or `utf-16le`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Some(Self::Utf8),
+            "latin1" | "latin-1" | "iso-8859-1" => Some(Self::Latin1),
+            "utf-16le" | "utf16le" => Some(Self::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Self::Utf16Be),
+            _ => None,
+        }
+    }
+
+    /// Decodes `bytes` without a leading byte order mark. Invalid sequences become U+FFFD.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Utf8 => {
+                let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+                String::from_utf8_lossy(bytes).into_owned()
+            }
+            Self::Latin1 => bytes.iter().map(|b| *b as char).collect(),
+            Self::Utf16Le | Self::Utf16Be => {
+                let units: Vec<u16> = bytes
+                    .chunks(2)
+                    .map(|pair| {
+                        let pair = [pair[0], pair.get(1).copied().unwrap_or(0)];
+                        match self {
+                            Self::Utf16Le => u16::from_le_bytes(pair),
+                            _ => u16::from_be_bytes(pair),
+                        }
+                    })
+                    .collect();
+                let units = units.strip_prefix(&[0xFEFF]).unwrap_or(&units);
+                String::from_utf16_lossy(units)
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use crate::{
+    buffer::Buffer,
+    build::Build,
+    config::Config,
+    filetype::FileType,
+    lsp::LspClient,
+    script::{Script, ScriptOutcome},
+    session::Session,
+    split::Split,
+};
+
+/// Things happening in the editor that other parts of it can react to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditorEvent {
+    /// A file was loaded into the active buffer
+    BufferOpened,
+    /// The active buffer is about to be written to its file
+    BeforeSave,
+    /// The active buffer was written to its file
+    AfterSave,
+    /// The active buffer is about to be closed
+    BeforeClose,
+    /// A character was typed into the active buffer
+    CharInserted(char),
+    /// The cursor of the active buffer moved, or another buffer became the active one
+    CursorMoved,
+    /// The area of the buffers changed, e.g. the terminal was resized. It starts at row `y`,
+    /// the size is without the tab and status lines.
+    Resize { y: u16, width: usize, height: usize },
+}
+
+/// The editor state handlers can work with
+pub struct EventContext<'a> {
+    pub config: &'a Config,
+    pub buffer: &'a mut Buffer,
+    pub hidden: &'a mut Vec<Buffer>,
+    pub build: &'a mut Option<Build>,
+    pub split: &'a mut Option<Split>,
+    pub session: &'a mut Session,
+    pub language_servers: &'a mut HashMap<FileType, LspClient>,
+    pub scripts: &'a HashMap<String, Script>,
+    /// Whether the scripts set as hooks run, they do not while the commands of a hook run
+    pub run_hooks: bool,
+    /// What the scripts run as hooks leave to the editor
+    pub outcome: &'a mut ScriptOutcome,
+    /// Message for the status line
+    pub message: &'a mut Option<String>,
+}
+
+/// Reacts to an event, errors are shown in the status line
+pub type Handler = fn(&EditorEvent, &mut EventContext) -> Result<(), String>;
+
+/// Handlers subscribed to the editor events, run in the order they subscribed
+#[derive(Default)]
+pub struct EventBus {
+    handlers: Vec<Handler>,
+}
+
+impl EventBus {
+    pub fn subscribe(&mut self, handler: Handler) {
+        self.handlers.push(handler);
+    }
+
+    /// Runs every handler on `event`, even if one of them fails
+    pub fn emit(&self, event: EditorEvent, context: &mut EventContext) {
+        for handler in &self.handlers {
+            if let Err(e) = handler(&event, context) {
+                *context.message = Some(e);
+            }
+        }
+    }
+}
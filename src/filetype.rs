@@ -0,0 +1,111 @@
+#![allow(dead_code)]
+use std::path::Path;
+
+/// The kind of content in a buffer, detected from the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    Rust,
+    C,
+    Cpp,
+    Python,
+    JavaScript,
+    TypeScript,
+    Go,
+    Json,
+    Toml,
+    Yaml,
+    Markdown,
+    Diff,
+    Text,
+    Unknown,
+}
+
+impl FileType {
+    pub const ALL: [Self; 14] = [
+        Self::Rust,
+        Self::C,
+        Self::Cpp,
+        Self::Python,
+        Self::JavaScript,
+        Self::TypeScript,
+        Self::Go,
+        Self::Json,
+        Self::Toml,
+        Self::Yaml,
+        Self::Markdown,
+        Self::Diff,
+        Self::Text,
+        Self::Unknown,
+    ];
+
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            "rs" => Self::Rust,
+            "c" | "h" => Self::C,
+            "cpp" | "cc" | "cxx" | "hpp" | "hh" => Self::Cpp,
+            "py" | "pyw" => Self::Python,
+            "js" | "mjs" | "cjs" | "jsx" => Self::JavaScript,
+            "ts" | "tsx" => Self::TypeScript,
+            "go" => Self::Go,
+            "json" => Self::Json,
+            "toml" => Self::Toml,
+            "yaml" | "yml" => Self::Yaml,
+            "md" | "markdown" => Self::Markdown,
+            "diff" | "patch" => Self::Diff,
+            "txt" | "text" => Self::Text,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Name of the file type, as used in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::C => "c",
+            Self::Cpp => "cpp",
+            Self::Python => "python",
+            Self::JavaScript => "javascript",
+            Self::TypeScript => "typescript",
+            Self::Go => "go",
+            Self::Json => "json",
+            Self::Toml => "toml",
+            Self::Yaml => "yaml",
+            Self::Markdown => "markdown",
+            Self::Diff => "diff",
+            Self::Text => "text",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Returns the file type called `name` in the config file
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|file_type| file_type.name() == name)
+    }
+
+    /// Returns what starts a comment running to the end of the line, if the file type has one
+    pub fn line_comment(&self) -> Option<&'static str> {
+        match self {
+            Self::Rust | Self::C | Self::Cpp | Self::JavaScript | Self::TypeScript | Self::Go => {
+                Some("//")
+            }
+            Self::Python | Self::Toml | Self::Yaml => Some("#"),
+            _ => None,
+        }
+    }
+
+    /// Language identifier used by language servers
+    pub fn language_id(&self) -> &'static str {
+        match self {
+            Self::Text | Self::Unknown => "plaintext",
+            other => other.name(),
+        }
+    }
+}
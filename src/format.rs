@@ -0,0 +1,59 @@
+use crate::{buffer::Buffer, diff, shell};
+
+/// Pipes `text` through the formatter `command` and returns the formatted text
+pub fn run_formatter(command: &str, text: &str) -> Result<String, String> {
+    let output =
+        shell::run(command, Some(text)).map_err(|e| format!("Failed to run `{command}` : {e}"))?;
+
+    if !output.status.success() {
+        return Err(match output.stderr.lines().find(|l| !l.trim().is_empty()) {
+            Some(error) => format!("`{command}` failed : {error}"),
+            None => format!("`{command}` failed ({})", output.status),
+        });
+    }
+
+    Ok(output.stdout)
+}
+
+/// Replaces the content of `buffer` with `formatted`, only touching the lines that changed.
+/// The cursor stays on the same line, or the closest one if that line changed.
+/// Returns false if nothing changed.
+pub fn apply(buffer: &mut Buffer, formatted: &str) -> bool {
+    // The buffer keeps no CR, as when a file is opened, so CRLF line breaks change no line
+    let formatted: String = formatted.chars().filter(|c| *c != '\r').collect();
+    let text = buffer.text();
+    let old: Vec<&str> = text.split_inclusive('\n').collect();
+    let new: Vec<&str> = formatted.split_inclusive('\n').collect();
+
+    let hunks = diff::diff_lines(&old, &new);
+    if hunks.is_empty() {
+        return false;
+    }
+
+    let mut position = buffer.cursor_position();
+    position.line = diff::map_line(&hunks, position.line);
+
+    // Char index where each old line starts, with one past the end for the last line
+    let mut line_starts = Vec::with_capacity(old.len() + 1);
+    let mut idx = 0;
+    line_starts.push(idx);
+    for l in &old {
+        idx += l.chars().count();
+        line_starts.push(idx);
+    }
+
+    // Going backwards keeps the indexes of the earlier hunks valid
+    for hunk in hunks.iter().rev() {
+        let replacement: String = new[hunk.new_start..hunk.new_end].concat();
+        buffer.replace_range(
+            line_starts[hunk.old_start],
+            line_starts[hunk.old_end],
+            &replacement,
+        );
+    }
+
+    buffer.move_cursor_to_position(position);
+    buffer.scroll();
+
+    true
+}
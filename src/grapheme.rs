@@ -0,0 +1,52 @@
+use unicode_segmentation::GraphemeCursor;
+
+/// Returns whether a grapheme cluster, what is seen as a single character, starts at `idx` in
+/// `chars`, following the extended grapheme cluster rules of Unicode: combining marks, emoji
+/// sequences, pairs of regional indicators (flags), Hangul syllables made of jamo and Indic
+/// conjuncts each make a single cluster. A line break always starts a cluster of its own.
+pub fn is_boundary(chars: &[char], idx: usize) -> bool {
+    if idx == 0 || idx >= chars.len() || chars[idx - 1] == '\n' || chars[idx] == '\n' {
+        return true;
+    }
+    // Nothing below the combining marks joins, which covers most text quickly
+    if chars[idx - 1] < '\u{300}' && chars[idx] < '\u{300}' {
+        return true;
+    }
+
+    // Most pairs of characters are decided on their own, more of the text before them is only
+    // looked at when the rules ask for it, e.g. to count the regional indicators before a flag
+    let mut before = 1;
+    loop {
+        let start = idx - before;
+        let text: String = chars[start..=idx].iter().collect();
+        let at = text.len() - chars[idx].len_utf8();
+        // Text cut out of the middle of the buffer is placed after a byte standing for what comes
+        // before it, so the rules ask for more rather than take it as the start of the text
+        let offset = usize::from(start > 0);
+
+        let mut cursor = GraphemeCursor::new(offset + at, offset + text.len(), true);
+        match cursor.is_boundary(&text, offset) {
+            Ok(boundary) => return boundary,
+            Err(_) if start > 0 => before = (before * 2).min(idx),
+            Err(_) => return true,
+        }
+    }
+}
+
+/// Returns the start of the cluster after the one at `idx`, at most `chars.len()`
+pub fn next_boundary(chars: &[char], idx: usize) -> usize {
+    let mut next = idx + 1;
+    while next < chars.len() && !is_boundary(chars, next) {
+        next += 1;
+    }
+    next.min(chars.len())
+}
+
+/// Returns the start of the cluster before `idx`, or 0
+pub fn previous_boundary(chars: &[char], idx: usize) -> usize {
+    let mut previous = idx.saturating_sub(1);
+    while previous > 0 && !is_boundary(chars, previous) {
+        previous -= 1;
+    }
+    previous
+}
@@ -0,0 +1,188 @@
+#![allow(dead_code)]
+
+use crossterm::style::Color;
+
+use crate::{diff, filetype::FileType, markdown};
+
+/*
+    Color theme default
+    :root {
+        --heading-color: rgb(255, 210, 85);     /* Headings (warm yellow) */
+        --emphasis-color: rgb(200, 160, 230);   /* Bold and italic text (soft purple) */
+        --code-color: rgb(190, 230, 120);       /* Code spans and blocks (soft green) */
+        --list-color: rgb(255, 215, 85);        /* List bullets (soft yellow) */
+        --link-color: rgb(100, 180, 230);       /* Links (soft blue) */
+        --quote-color: rgb(120, 150, 120);      /* Block quotes (dim green) */
+        --inserted-color: rgb(120, 200, 120);   /* Added lines in diffs (green) */
+        --deleted-color: rgb(230, 90, 90);      /* Removed lines in diffs (soft red) */
+        --hunk-color: rgb(100, 160, 230);       /* Diff hunk headers (soft blue) */
+        --bracket-colors: rgb(255, 215, 0), rgb(218, 112, 214), rgb(23, 159, 255);
+                                                /* Nested brackets, cycling (gold, orchid, blue) */
+    }
+*/
+
+/// Colors of nested brackets, the outermost first, cycling for deeper ones
+const BRACKET_COLORS: [Color; 3] = [
+    Color::Rgb {
+        r: 255,
+        g: 215,
+        b: 0,
+    },
+    Color::Rgb {
+        r: 218,
+        g: 112,
+        b: 214,
+    },
+    Color::Rgb {
+        r: 23,
+        g: 159,
+        b: 255,
+    },
+];
+
+/// The kind of token a range of text was recognized as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightKind {
+    Heading,
+    Emphasis,
+    Code,
+    ListBullet,
+    Link,
+    Quote,
+    Inserted,
+    Deleted,
+    HunkHeader,
+    /// A bracket nested in that many others
+    Bracket(usize),
+}
+
+impl HighlightKind {
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Heading => Color::Rgb {
+                r: 255,
+                g: 210,
+                b: 85,
+            },
+            Self::Emphasis => Color::Rgb {
+                r: 200,
+                g: 160,
+                b: 230,
+            },
+            Self::Code => Color::Rgb {
+                r: 190,
+                g: 230,
+                b: 120,
+            },
+            Self::ListBullet => Color::Rgb {
+                r: 255,
+                g: 215,
+                b: 85,
+            },
+            Self::Link => Color::Rgb {
+                r: 100,
+                g: 180,
+                b: 230,
+            },
+            Self::Quote => Color::Rgb {
+                r: 120,
+                g: 150,
+                b: 120,
+            },
+            Self::Inserted => Color::Rgb {
+                r: 120,
+                g: 200,
+                b: 120,
+            },
+            Self::Deleted => Color::Rgb {
+                r: 230,
+                g: 90,
+                b: 90,
+            },
+            Self::HunkHeader => Color::Rgb {
+                r: 100,
+                g: 160,
+                b: 230,
+            },
+            Self::Bracket(depth) => BRACKET_COLORS[depth % BRACKET_COLORS.len()],
+        }
+    }
+}
+
+/// A highlighted `[start, end)` range of the buffer data
+#[derive(Debug, Clone, Copy)]
+pub struct Highlight {
+    pub start: usize,
+    pub end: usize,
+    pub kind: HighlightKind,
+}
+
+/// Returns the highlighted ranges of `data`, sorted by `start`, for the given file type
+pub fn highlight(file_type: FileType, data: &[char]) -> Vec<Highlight> {
+    match file_type {
+        FileType::Markdown => markdown::highlight(data),
+        FileType::Diff => diff::highlight(data),
+        _ => vec![],
+    }
+}
+
+/// Returns the brackets of `data` colored by how deeply they are nested, a closing bracket
+/// taking the color of the one it closes. Brackets in strings, character literals and the
+/// line comments of `file_type` are left out.
+pub fn rainbow_brackets(file_type: FileType, data: &[char]) -> Vec<Highlight> {
+    let comment: Vec<char> = file_type
+        .line_comment()
+        .unwrap_or_default()
+        .chars()
+        .collect();
+    let mut highlights = vec![];
+    let mut depth = 0;
+    let mut quote = None;
+    let mut i = 0;
+
+    while i < data.len() {
+        let c = data[i];
+        match (quote, c) {
+            (Some(_), '\\') => i += 1,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"' | '`') => quote = Some(c),
+            // A character literal like `'('`, not a lifetime like `'a`
+            (None, '\'') => {
+                let end = if data.get(i + 1) == Some(&'\\') {
+                    i + 3
+                } else {
+                    i + 2
+                };
+                if data.get(end) == Some(&'\'') {
+                    i = end;
+                }
+            }
+            (None, _) if !comment.is_empty() && data[i..].starts_with(&comment) => {
+                while i < data.len() && data[i] != '\n' {
+                    i += 1;
+                }
+            }
+            (None, '(' | '[' | '{') => {
+                highlights.push(Highlight {
+                    start: i,
+                    end: i + 1,
+                    kind: HighlightKind::Bracket(depth),
+                });
+                depth += 1;
+            }
+            (None, ')' | ']' | '}') => {
+                depth = depth.saturating_sub(1);
+                highlights.push(Highlight {
+                    start: i,
+                    end: i + 1,
+                    kind: HighlightKind::Bracket(depth),
+                });
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+
+    highlights
+}
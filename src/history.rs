@@ -0,0 +1,159 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    buffer,
+    clock::DateTime,
+    config::Config,
+    paths::{absolute, expand_home, get_data_dir},
+};
+
+/// Start of the name of the scratch buffers listing the history of a file
+pub const HISTORY_BUFFER_PREFIX: &str = "[History]";
+
+/// How many snapshots of a file are kept unless set in the config
+pub const DEFAULT_KEEP: usize = 50;
+
+/// The versions of a file as it was saved, each one kept in a directory named after its path
+pub struct FileHistory {
+    dir: PathBuf,
+    keep: usize,
+}
+
+/// A version of a file, named after the milliseconds since the epoch when it was saved
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub millis: u64,
+    pub path: PathBuf,
+}
+
+impl Snapshot {
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.millis)
+    }
+
+    /// When the snapshot was taken, e.g. `2024-01-31 13:45:00`
+    pub fn saved_at(&self) -> String {
+        DateTime::local(self.time()).format("%F %T")
+    }
+
+    /// Reads the snapshot as buffer data
+    pub fn data(&self) -> io::Result<Vec<char>> {
+        fs::read(&self.path).map(|bytes| buffer::decode(&bytes).0)
+    }
+}
+
+impl FileHistory {
+    /// The history of the file at `path`, `None` when turned off in the `[history]` section of
+    /// the config or without a data directory to keep it in
+    pub fn for_file(config: &Config, path: &Path) -> Result<Option<Self>, String> {
+        if config.get_bool("history", "enabled") == Some(false) {
+            return Ok(None);
+        }
+
+        let root = match (config.get_str("history", "directory"), get_data_dir()) {
+            (Some(dir), _) => expand_home(dir),
+            (None, Some(data)) => data.join("history"),
+            (None, None) => return Ok(None),
+        };
+        let keep = match config.get_int("history", "keep") {
+            None => DEFAULT_KEEP,
+            Some(keep) => usize::try_from(keep)
+                .ok()
+                .filter(|keep| *keep >= 1)
+                .ok_or_else(|| format!("history: `keep` must be at least 1, not {keep}"))?,
+        };
+
+        Ok(Some(Self {
+            dir: root.join(path_name(path)),
+            keep,
+        }))
+    }
+
+    /// Returns the snapshots of the file, the most recent first
+    pub fn snapshots(&self) -> io::Result<Vec<Snapshot>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+
+        let mut snapshots: Vec<Snapshot> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let millis = entry.file_name().to_str()?.parse().ok()?;
+                Some(Snapshot {
+                    millis,
+                    path: entry.path(),
+                })
+            })
+            .collect();
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.millis));
+
+        Ok(snapshots)
+    }
+
+    /// Keeps what the file at `file` holds as its latest version, unless it already is, and
+    /// removes the oldest snapshots past the number kept. Returns whether a snapshot was taken.
+    /// Snapshots are only readable by those who can read the file, in a directory only the
+    /// user can open.
+    pub fn record(&self, file: &Path) -> io::Result<bool> {
+        let contents = fs::read(file)?;
+        let permissions = fs::metadata(file)?.permissions();
+        let snapshots = self.snapshots()?;
+        if let Some(latest) = snapshots.first() {
+            if fs::read(&latest.path).is_ok_and(|latest| latest == contents) {
+                return Ok(false);
+            }
+        }
+
+        // Always after the latest one, even if the clock went back
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let millis = match snapshots.first() {
+            Some(latest) => now.max(latest.millis + 1),
+            None => now,
+        };
+
+        create_private_dir(&self.dir)?;
+        let snapshot = self.dir.join(millis.to_string());
+        fs::write(&snapshot, &contents)?;
+        fs::set_permissions(&snapshot, permissions)?;
+
+        for old in snapshots.iter().skip(self.keep - 1) {
+            fs::remove_file(&old.path)?;
+        }
+
+        Ok(true)
+    }
+}
+
+/// The whole path of a file in one name, like vim names its undo files
+pub fn path_name(path: &Path) -> String {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| absolute(path));
+    path.to_string_lossy()
+        .chars()
+        .map(|c| {
+            if matches!(c, '/' | '\\' | ':') {
+                '%'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Creates `dir` and the missing directories above it, only the user can open them on Unix
+pub fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)
+}
@@ -0,0 +1,169 @@
+use crate::filetype::FileType;
+
+/// Whether the indentation of `file_type` follows the nesting of its brackets, so that it can
+/// be re-indented without an external indenter
+pub fn has_bracket_rules(file_type: FileType) -> bool {
+    matches!(
+        file_type,
+        FileType::Rust
+            | FileType::C
+            | FileType::Cpp
+            | FileType::JavaScript
+            | FileType::TypeScript
+            | FileType::Go
+            | FileType::Json
+    )
+}
+
+/// Whether the indentation of `file_type` is part of its syntax, like the blocks of Python or
+/// the nesting of YAML, so that breaking a line keeps it
+pub fn is_significant(file_type: FileType) -> bool {
+    matches!(file_type, FileType::Python | FileType::Yaml)
+}
+
+/// Statements of Python after which the block they are in is done
+const BLOCK_ENDS: [&str; 5] = ["return", "pass", "break", "continue", "raise"];
+
+/// Returns the indentation of a new line started after `line`: that of `line`, one `unit`
+/// deeper after a `:` opening a block or a mapping, or one less after a Python statement
+/// ending its block
+pub fn next_line_indent(line: &str, file_type: FileType, unit: &str) -> String {
+    let mut indent = line[..line.len() - line.trim_start().len()].to_string();
+    let code = without_comment(line).trim();
+
+    if code.ends_with(':') {
+        indent.push_str(unit);
+    } else if file_type == FileType::Python
+        && BLOCK_ENDS.iter().any(|end| {
+            code.strip_prefix(end)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '(']))
+        })
+    {
+        dedent(&mut indent, unit);
+    }
+
+    indent
+}
+
+/// Removes a level of indentation from the start of `line`: one `unit`, or a tab, or as many
+/// spaces as there are up to the width of `unit`
+pub fn dedent(line: &mut String, unit: &str) {
+    let removed = if line.starts_with(unit) {
+        unit.len()
+    } else if line.starts_with('\t') {
+        1
+    } else {
+        line.chars()
+            .take(unit.len().max(1))
+            .take_while(|c| *c == ' ')
+            .count()
+    };
+    line.drain(..removed);
+}
+
+/// Returns `line` without the `#` comment ending it, if there is one outside of a string
+fn without_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return &line[..i],
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            _ => (),
+        }
+    }
+    line
+}
+
+/// Returns true if the indentation of `line` mixes tabs and spaces
+pub fn has_mixed_indent(line: &[char]) -> bool {
+    let indent = line.iter().take_while(|c| matches!(c, ' ' | '\t'));
+    let (mut tabs, mut spaces) = (false, false);
+    for c in indent {
+        tabs |= *c == '\t';
+        spaces |= *c == ' ';
+    }
+    tabs && spaces
+}
+
+/// Indents `lines` one `unit` deeper for each bracket they are nested in. The first line keeps
+/// its indentation and the others are indented from it, blank lines are emptied.
+///
+/// Lines starting with closing brackets are indented like the line that opened them, and the
+/// brackets opened on a single line only indent the lines after it once.
+pub fn reindent(lines: &mut [String], unit: &str) {
+    let base = match lines.first() {
+        Some(first) => first[..first.len() - first.trim_start().len()].to_string(),
+        None => return,
+    };
+
+    // The depth of the lines inside each open bracket
+    let mut open: Vec<usize> = vec![];
+    for line in lines.iter_mut() {
+        let text = line.trim().to_string();
+        if text.is_empty() {
+            line.clear();
+            continue;
+        }
+
+        let closing = text
+            .chars()
+            .take_while(|c| matches!(c, ')' | ']' | '}'))
+            .count();
+        let depth = match open.len().checked_sub(closing + 1) {
+            Some(i) => open[i],
+            None => 0,
+        };
+        *line = format!("{base}{}{text}", unit.repeat(depth));
+
+        for bracket in brackets(&text) {
+            if matches!(bracket, '(' | '[' | '{') {
+                open.push(depth + 1);
+            } else {
+                open.pop();
+            }
+        }
+    }
+}
+
+/// Returns the brackets of a line of code, leaving out those in strings, characters and comments
+fn brackets(line: &str) -> Vec<char> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut brackets = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '"' | '`' => {
+                let quote = chars[i];
+                i += 1;
+                while i < chars.len() && chars[i] != quote {
+                    i += if chars[i] == '\\' { 2 } else { 1 };
+                }
+            }
+            // A character like '{' or '\'', not a Rust lifetime
+            '\'' => match chars.get(i + 1) {
+                Some('\\') => {
+                    if let Some(end) = chars.iter().skip(i + 3).position(|c| *c == '\'') {
+                        i += end + 3;
+                    }
+                }
+                Some(_) if chars.get(i + 2) == Some(&'\'') => i += 2,
+                _ => (),
+            },
+            '/' if chars.get(i + 1) == Some(&'/') => break,
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
+                    i += 1;
+                }
+                i += 1;
+            }
+            c @ ('(' | ')' | '[' | ']' | '{' | '}') => brackets.push(c),
+            _ => (),
+        }
+        i += 1;
+    }
+
+    brackets
+}
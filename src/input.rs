@@ -0,0 +1,56 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
+
+/// Longest time between two clicks of a double or triple click
+pub const MULTI_CLICK_TIME: Duration = Duration::from_millis(400);
+
+/// The modifiers the editor tells apart, the others are dropped
+const KNOWN_MODIFIERS: KeyModifiers = KeyModifiers::SHIFT
+    .union(KeyModifiers::CONTROL)
+    .union(KeyModifiers::ALT);
+
+/// Returns `key` the way the editor expects it whatever the terminal reported: with only the
+/// Shift, Ctrl and Alt modifiers, Meta counting as Alt, and without the keypad and lock states.
+/// A held key acts like it is pressed again.
+pub fn normalize(mut key: KeyEvent) -> KeyEvent {
+    if key.kind == KeyEventKind::Repeat {
+        key.kind = KeyEventKind::Press;
+    }
+    if key.modifiers.contains(KeyModifiers::META) {
+        key.modifiers |= KeyModifiers::ALT;
+    }
+    key.modifiers &= KNOWN_MODIFIERS;
+    key.state = KeyEventState::NONE;
+
+    key
+}
+
+/// Tells single, double and triple clicks apart, by how soon each click follows the last one at
+/// the same cell
+#[derive(Debug, Default)]
+pub struct ClickCounter {
+    /// Cell and time of the last click
+    last: Option<(u16, u16, Instant)>,
+    /// Clicks made in a row so far
+    count: usize,
+}
+
+impl ClickCounter {
+    /// Counts a click at the cell `(column, row)` made at `at`, returns 1 for a single click,
+    /// 2 for a double click and 3 for a triple click. A fourth click starts over.
+    pub fn click(&mut self, column: u16, row: u16, at: Instant) -> usize {
+        let follows = self.last.is_some_and(|(last_column, last_row, last_at)| {
+            (last_column, last_row) == (column, row)
+                && at.saturating_duration_since(last_at) <= MULTI_CLICK_TIME
+        });
+
+        self.count = if follows && self.count < 3 {
+            self.count + 1
+        } else {
+            1
+        };
+        self.last = Some((column, row, at));
+        self.count
+    }
+}
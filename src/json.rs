@@ -0,0 +1,478 @@
+#![allow(dead_code)]
+use std::fmt::{self, Write};
+
+/// Arrays and objects nested deeper than this are refused rather than risk running out of stack
+pub const MAX_DEPTH: usize = 512;
+
+/// A minimal JSON value, just enough to talk to external tools.
+/// Object keys keep their original order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+/// Error produced while parsing JSON text.
+/// `line` and `column` are 0 based and point to the offending character.
+#[derive(Debug)]
+pub struct JsonError {
+    pub message: String,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message,
+            self.line + 1,
+            self.column + 1
+        )
+    }
+}
+
+impl JsonValue {
+    pub fn parse(text: &str) -> Result<Self, JsonError> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+            depth: 0,
+        };
+
+        parser.skip_whitespace();
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("Unexpected trailing characters"));
+        }
+
+        Ok(value)
+    }
+
+    /// Builds an object from `(key, value)` pairs
+    pub fn object(pairs: Vec<(&str, JsonValue)>) -> Self {
+        Self::Object(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub fn string(s: &str) -> Self {
+        Self::String(s.to_string())
+    }
+
+    /// Returns the value stored under `key` if `self` is an object
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            Self::Object(pairs) => pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_f64()
+            .filter(|n| *n >= 0.0 && n.fract() == 0.0)
+            .map(|n| n as usize)
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<JsonValue>> {
+        match self {
+            Self::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+}
+
+/// Serializes the value in its compact form
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Number(n) => write_number(f, *n),
+            Self::String(s) => write_string(f, s),
+            Self::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_char(']')
+            }
+            Self::Object(pairs) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+/// Pretty-prints JSON text, one value per line with nested ones indented by `indent`. Numbers
+/// and strings are kept as written. Fails where the text is not valid JSON.
+pub fn pretty(text: &str, indent: &str) -> Result<String, JsonError> {
+    reformat(text, Some(indent))
+}
+
+/// Puts JSON text on a single line, without any whitespace outside of strings
+pub fn minify(text: &str) -> Result<String, JsonError> {
+    reformat(text, None)
+}
+
+/// Lays out valid JSON text again, pretty-printed with `indent` or else minified
+fn reformat(text: &str, indent: Option<&str>) -> Result<String, JsonError> {
+    JsonValue::parse(text)?;
+
+    let new_line = |out: &mut String, depth: usize| {
+        if let Some(indent) = indent {
+            out.push('\n');
+            (0..depth).for_each(|_| out.push_str(indent));
+        }
+    };
+
+    let mut out = String::with_capacity(text.len());
+    let mut depth = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => {
+                out.push(ch);
+                while let Some(ch) = chars.next() {
+                    out.push(ch);
+                    match ch {
+                        '\\' => out.extend(chars.next()),
+                        '"' => break,
+                        _ => (),
+                    }
+                }
+            }
+            ' ' | '\t' | '\n' | '\r' => (),
+            '{' | '[' => {
+                out.push(ch);
+                while chars
+                    .next_if(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+                    .is_some()
+                {}
+
+                // Empty ones stay on their line
+                match chars.next_if(|c| matches!(c, '}' | ']')) {
+                    Some(close) => out.push(close),
+                    None => {
+                        depth += 1;
+                        new_line(&mut out, depth);
+                    }
+                }
+            }
+            '}' | ']' => {
+                depth -= 1;
+                new_line(&mut out, depth);
+                out.push(ch);
+            }
+            ',' => {
+                out.push(ch);
+                new_line(&mut out, depth);
+            }
+            ':' if indent.is_some() => out.push_str(": "),
+            _ => out.push(ch),
+        }
+    }
+
+    Ok(out)
+}
+
+fn write_number(f: &mut impl Write, n: f64) -> fmt::Result {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        write!(f, "{}", n as i64)
+    } else {
+        write!(f, "{n}")
+    }
+}
+
+fn write_string(f: &mut impl Write, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for ch in s.chars() {
+        match ch {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    /// Arrays and objects the parser is inside of
+    depth: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> JsonError {
+        let offset = self.pos.min(self.chars.len());
+        let before = &self.chars[..offset];
+        let line = before.iter().filter(|c| **c == '\n').count();
+        let column = before.iter().rev().take_while(|c| **c != '\n').count();
+
+        JsonError {
+            message: message.to_string(),
+            offset,
+            line,
+            column,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, ch: char) -> Result<(), JsonError> {
+        if self.peek() == Some(ch) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{ch}'")))
+        }
+    }
+
+    fn expect_word(&mut self, word: &str, value: JsonValue) -> Result<JsonValue, JsonError> {
+        for ch in word.chars() {
+            self.expect(ch)?;
+        }
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, JsonError> {
+        match self.peek() {
+            Some('n') => self.expect_word("null", JsonValue::Null),
+            Some('t') => self.expect_word("true", JsonValue::Bool(true)),
+            Some('f') => self.expect_word("false", JsonValue::Bool(false)),
+            Some('"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some(open @ ('[' | '{')) => {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error("Nested too deeply"));
+                }
+                self.depth += 1;
+                let value = match open {
+                    '[' => self.parse_array(),
+                    _ => self.parse_object(),
+                };
+                self.depth -= 1;
+                value
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(_) => Err(self.error("Unexpected character")),
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    /// Reads a number as JSON writes them: an optional minus, an integer part without leading
+    /// zeros, then an optional fraction and exponent, each with at least a digit
+    fn parse_number(&mut self) -> Result<JsonValue, JsonError> {
+        let start = self.pos;
+
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        if self.peek() == Some('0') {
+            self.pos += 1;
+            if matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+                return Err(self.error("Leading zeros are not allowed"));
+            }
+        } else {
+            self.parse_digits()?;
+        }
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            self.parse_digits()?;
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.pos += 1;
+            }
+            self.parse_digits()?;
+        }
+
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse::<f64>().map(JsonValue::Number).map_err(|_| {
+            self.pos = start;
+            self.error("Invalid number")
+        })
+    }
+
+    /// Skips one digit or more
+    fn parse_digits(&mut self) -> Result<(), JsonError> {
+        if !matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            return Err(self.error("Expected a digit"));
+        }
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .peek()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("Invalid unicode escape"))?;
+            code = code * 16 + digit;
+            self.pos += 1;
+        }
+        Ok(code)
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut s = String::new();
+
+        loop {
+            let Some(ch) = self.peek() else {
+                return Err(self.error("Unterminated string"));
+            };
+            self.pos += 1;
+
+            match ch {
+                '"' => return Ok(s),
+                '\\' => {
+                    let Some(escaped) = self.peek() else {
+                        return Err(self.error("Unterminated string"));
+                    };
+                    self.pos += 1;
+
+                    match escaped {
+                        '"' => s.push('"'),
+                        '\\' => s.push('\\'),
+                        '/' => s.push('/'),
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'n' => s.push('\n'),
+                        'r' => s.push('\r'),
+                        't' => s.push('\t'),
+                        'u' => {
+                            let mut code = self.parse_hex4()?;
+
+                            // Surrogate pairs encode characters outside the BMP
+                            if (0xD800..0xDC00).contains(&code)
+                                && self.chars.get(self.pos..self.pos + 2) == Some(&['\\', 'u'])
+                            {
+                                self.pos += 2;
+                                let low = self.parse_hex4()?;
+                                code = 0x10000
+                                    + ((code - 0xD800) << 10)
+                                    + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+
+                            s.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        }
+                        _ => {
+                            self.pos -= 1;
+                            return Err(self.error("Invalid escape sequence"));
+                        }
+                    }
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, JsonError> {
+        self.expect('[')?;
+        let mut items = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(items));
+        }
+
+        loop {
+            self.skip_whitespace();
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, JsonError> {
+        self.expect('{')?;
+        let mut pairs = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(pairs));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            pairs.push((key, self.parse_value()?));
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(pairs));
+                }
+                _ => return Err(self.error("Expected ',' or '}'")),
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::{collections::HashMap, fmt};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::config::{Config, ConfigValue};
+
+/// A key press, with letters always lower case and Shift only kept for them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn new(code: KeyCode, mut modifiers: KeyModifiers) -> Self {
+        let code = match code {
+            KeyCode::Char(c) if c.is_uppercase() => {
+                modifiers |= KeyModifiers::SHIFT;
+                KeyCode::Char(c.to_lowercase().next().unwrap_or(c))
+            }
+            // Shift is already part of symbols such as `?`
+            KeyCode::Char(c) if !c.is_alphabetic() => {
+                modifiers -= KeyModifiers::SHIFT;
+                KeyCode::Char(c)
+            }
+            code => code,
+        };
+
+        Self { code, modifiers }
+    }
+
+    pub fn from_event(event: &KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+
+    /// Parses a key written like `ctrl+k`, `alt+shift+x`, `space` or `f5`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = text.split('+').collect();
+        // `ctrl++` is Ctrl with the `+` key
+        if text.ends_with("++") {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+        let Some(name) = parts.pop() else {
+            return Err(format!("Invalid key : {text}"));
+        };
+
+        for modifier in parts {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("Invalid modifier `{modifier}` in {text}")),
+            };
+        }
+
+        let lower = name.to_lowercase();
+        let code = match lower.as_str() {
+            "space" => KeyCode::Char(' '),
+            "enter" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "esc" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            _ => match (name.chars().next(), name.chars().count()) {
+                (Some(c), 1) => KeyCode::Char(c),
+                (Some('f' | 'F'), _) => match lower[1..].parse::<u8>() {
+                    Ok(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => return Err(format!("Invalid key : {text}")),
+                },
+                _ => return Err(format!("Invalid key : {text}")),
+            },
+        };
+
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl+"),
+            (KeyModifiers::ALT, "alt+"),
+            (KeyModifiers::SHIFT, "shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{name}")?;
+            }
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            code => write!(f, "{}", format!("{code:?}").to_lowercase()),
+        }
+    }
+}
+
+/// What a sequence of keys leads to
+pub enum Lookup<'a> {
+    /// A command, written as in the command prompt
+    Command(&'a str),
+    /// More keys are needed, these are the ones that can follow
+    Prefix(&'a Keymap),
+    /// Nothing is bound to the sequence
+    Unbound,
+}
+
+enum Binding {
+    Command(String),
+    Prefix(Keymap),
+}
+
+/// Commands bound to sequences of keys, e.g. `ctrl+k ctrl+s`, stored as a trie
+#[derive(Default)]
+pub struct Keymap {
+    bindings: HashMap<Key, Binding>,
+}
+
+impl Keymap {
+    /// Reads the `[keys]` section of the config, where each key is a sequence of keys
+    /// separated by spaces and each value the command it runs.
+    /// `leader` in a sequence stands for the key set as `leader`.
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let mut keymap = Self::default();
+        let Some(section) = config.section("keys") else {
+            return Ok(keymap);
+        };
+
+        for (sequence, command) in section {
+            if sequence == "leader" {
+                continue;
+            }
+            let ConfigValue::String(command) = command else {
+                return Err(format!(
+                    "keys: the command of `{sequence}` must be a string"
+                ));
+            };
+
+            keymap.bind(&Self::parse_sequence(config, sequence)?, command)?;
+        }
+
+        Ok(keymap)
+    }
+
+    /// Parses a sequence of keys separated by spaces, with `leader` standing for the key set as
+    /// `leader` in the `[keys]` section of the config
+    pub fn parse_sequence(config: &Config, sequence: &str) -> Result<Vec<Key>, String> {
+        let leader = match config.get_str("keys", "leader") {
+            Some(leader) => Some(Key::parse(leader)?),
+            None => None,
+        };
+
+        sequence
+            .split_whitespace()
+            .map(|key| match (key, leader) {
+                ("leader", Some(leader)) => Ok(leader),
+                ("leader", None) => Err(format!("keys: `{sequence}` uses an unset leader")),
+                (key, _) => Key::parse(key).map_err(|e| format!("keys: {e}")),
+            })
+            .collect()
+    }
+
+    /// Binds `command` to the sequence `keys`
+    pub fn bind(&mut self, keys: &[Key], command: &str) -> Result<(), String> {
+        let describe = || {
+            keys.iter()
+                .map(Key::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        let Some((last, prefix)) = keys.split_last() else {
+            return Err("keys: empty key sequence".to_string());
+        };
+
+        let mut keymap = self;
+        for key in prefix {
+            let binding = keymap
+                .bindings
+                .entry(*key)
+                .or_insert_with(|| Binding::Prefix(Keymap::default()));
+            keymap = match binding {
+                Binding::Prefix(keymap) => keymap,
+                Binding::Command(_) => {
+                    return Err(format!("keys: `{}` starts with a bound key", describe()))
+                }
+            };
+        }
+
+        if let Some(Binding::Prefix(_)) = keymap.bindings.get(last) {
+            return Err(format!(
+                "keys: `{}` is the start of other sequences",
+                describe()
+            ));
+        }
+        keymap
+            .bindings
+            .insert(*last, Binding::Command(command.to_string()));
+
+        Ok(())
+    }
+
+    pub fn lookup(&self, keys: &[Key]) -> Lookup<'_> {
+        let mut keymap = self;
+
+        for (i, key) in keys.iter().enumerate() {
+            match keymap.bindings.get(key) {
+                Some(Binding::Command(command)) if i == keys.len() - 1 => {
+                    return Lookup::Command(command)
+                }
+                Some(Binding::Prefix(next)) => keymap = next,
+                _ => return Lookup::Unbound,
+            }
+        }
+
+        Lookup::Prefix(keymap)
+    }
+
+    /// Describes the keys that can be pressed next, e.g. `ctrl+s  sort`
+    pub fn continuations(&self) -> Vec<String> {
+        let mut items: Vec<(String, String)> = self
+            .bindings
+            .iter()
+            .map(|(key, binding)| {
+                let action = match binding {
+                    Binding::Command(command) => command.clone(),
+                    Binding::Prefix(keymap) => format!("+{} more", keymap.bindings.len()),
+                };
+                (key.to_string(), action)
+            })
+            .collect();
+        items.sort();
+
+        let width = items.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+        items
+            .into_iter()
+            .map(|(key, action)| format!("{key:width$}  {action}"))
+            .collect()
+    }
+}
@@ -0,0 +1,38 @@
+//! The core of tte: buffers of text with their lines, undo history and searches, and the display
+//! drawing them to any writer. The `tte` binary draws them to the terminal and turns key presses
+//! into edits, other tools (tests, benchmarks, fuzzers, another frontend) can drive them the same
+//! way.
+
+pub mod accessibility;
+pub mod buffer;
+pub mod clipboard;
+pub mod colors;
+pub mod completion;
+pub mod config;
+pub mod cursor_style;
+pub mod diff;
+pub mod display;
+pub mod filetype;
+pub mod grapheme;
+pub mod highlight;
+pub mod indent;
+pub mod json;
+pub mod lsp;
+pub mod markdown;
+pub mod paths;
+pub mod popup;
+pub mod position;
+pub mod project;
+pub mod printable;
+pub mod prompt;
+pub mod spans;
+pub mod start_screen;
+pub mod status_line;
+pub mod tab_line;
+pub mod undo;
+pub mod width;
+
+pub use buffer::{Buffer, Line};
+pub use filetype::FileType;
+pub use position::Position;
+pub use undo::UndoTree;
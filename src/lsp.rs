@@ -0,0 +1,777 @@
+#![allow(dead_code)]
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{json::JsonValue, paths::get_cache_dir, position::Position, printable::printable};
+
+/// Time the server has to answer `shutdown` and then to exit, before it is killed
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How the characters of a line are counted in the positions exchanged with the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PositionEncoding {
+    Utf8,
+    /// Used unless the server agrees on another
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// Encodings offered to the server, by preference. Counting characters as the buffers do
+    /// spares converting, UTF-16 is what every server supports.
+    pub const OFFERED: [Self; 2] = [Self::Utf32, Self::Utf16];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16 => "utf-16",
+            Self::Utf32 => "utf-32",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Utf8, Self::Utf16, Self::Utf32]
+            .into_iter()
+            .find(|encoding| encoding.name() == name)
+    }
+
+    fn units(self, ch: char) -> usize {
+        match self {
+            Self::Utf8 => ch.len_utf8(),
+            Self::Utf16 => ch.len_utf16(),
+            Self::Utf32 => 1,
+        }
+    }
+
+    /// Converts a column of `line`, counted in characters, into the units of the encoding.
+    /// Columns past the end of the line count one unit each.
+    pub fn to_units(self, line: &str, column: usize) -> usize {
+        let units: usize = line.chars().take(column).map(|ch| self.units(ch)).sum();
+        units + column.saturating_sub(line.chars().count())
+    }
+
+    /// Converts a count of units of the encoding on `line` into a column counted in
+    /// characters. A count falling inside a character is at the start of it.
+    pub fn to_column(self, line: &str, units: usize) -> usize {
+        let mut counted = 0;
+        for (column, ch) in line.chars().enumerate() {
+            let next = counted + self.units(ch);
+            if next > units {
+                return column;
+            }
+            counted = next;
+        }
+
+        line.chars().count() + (units - counted)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl Severity {
+    /// Returns the name the severity is shown with, e.g. `error`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Information => "info",
+            Self::Hint => "hint",
+        }
+    }
+}
+
+/// A problem reported by the language server
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub start: Position,
+    pub end: Position,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Things the language server told us about, ready to be applied to the editor state. The text
+/// in them is made `printable` already.
+pub enum LspEvent {
+    Diagnostics {
+        path: PathBuf,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Definition {
+        path: PathBuf,
+        position: Position,
+    },
+    Hover(String),
+    /// Something worth showing to the user in the status line
+    Message(String),
+}
+
+/// A connection to a language server process speaking JSON-RPC over stdio.
+///
+/// Messages from the server are read on a separate thread so the editor never blocks on it,
+/// call `poll` regularly to process them.
+pub struct LspClient {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<JsonValue>,
+    next_id: u64,
+    /// Method names of the requests waiting for a response
+    pending: HashMap<u64, &'static str>,
+    initialized: bool,
+    /// Notifications sent before the server finished initializing
+    queued: Vec<JsonValue>,
+    exited: bool,
+    /// How columns are counted, as agreed on with the server
+    encoding: PositionEncoding,
+    /// Lines of the open documents as last sent to the server, which its positions refer to
+    documents: HashMap<PathBuf, Vec<String>>,
+}
+
+/// Where a server writes its errors: `lsp/<program>.log` in the cache directory, started over
+/// each time the server is, or nowhere without a cache directory
+fn server_log(program: &str) -> Stdio {
+    let (Some(dir), Some(name)) = (get_cache_dir(), Path::new(program).file_name()) else {
+        return Stdio::null();
+    };
+    let dir = dir.join("lsp");
+    let log =
+        fs::create_dir_all(&dir).and_then(|_| File::create(dir.join(name).with_extension("log")));
+    match log {
+        Ok(file) => file.into(),
+        Err(_) => Stdio::null(),
+    }
+}
+
+impl LspClient {
+    /// Spawns `command` (program followed by its arguments) as a language server for the project in `root`
+    pub fn start(command: &str, root: &Path) -> io::Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Empty LSP command"))?;
+
+        let mut child = Command::new(program)
+            .args(parts)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(server_log(program))
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("stdin should be piped");
+        let stdout = child.stdout.take().expect("stdout should be piped");
+
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            while let Some(message) = read_message(&mut reader) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut client = Self {
+            child,
+            stdin,
+            messages,
+            next_id: 0,
+            pending: HashMap::new(),
+            initialized: false,
+            queued: vec![],
+            exited: false,
+            encoding: PositionEncoding::default(),
+            documents: HashMap::new(),
+        };
+
+        let root_uri = path_to_uri(root);
+        client.request(
+            "initialize",
+            JsonValue::object(vec![
+                ("processId", JsonValue::Number(std::process::id() as f64)),
+                ("rootUri", JsonValue::String(root_uri.clone())),
+                (
+                    "workspaceFolders",
+                    JsonValue::Array(vec![JsonValue::object(vec![
+                        ("uri", JsonValue::String(root_uri)),
+                        ("name", JsonValue::string("root")),
+                    ])]),
+                ),
+                (
+                    "capabilities",
+                    JsonValue::object(vec![
+                        (
+                            "general",
+                            JsonValue::object(vec![(
+                                "positionEncodings",
+                                JsonValue::Array(
+                                    PositionEncoding::OFFERED
+                                        .iter()
+                                        .map(|encoding| JsonValue::string(encoding.name()))
+                                        .collect(),
+                                ),
+                            )]),
+                        ),
+                        (
+                            "textDocument",
+                            JsonValue::object(vec![
+                                (
+                                    "hover",
+                                    JsonValue::object(vec![(
+                                        "contentFormat",
+                                        JsonValue::Array(vec![JsonValue::string("plaintext")]),
+                                    )]),
+                                ),
+                                ("definition", JsonValue::object(vec![])),
+                                ("publishDiagnostics", JsonValue::object(vec![])),
+                            ]),
+                        ),
+                    ]),
+                ),
+            ]),
+        )?;
+
+        Ok(client)
+    }
+
+    fn send(&mut self, message: &JsonValue) -> io::Result<()> {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.stdin.flush()
+    }
+
+    fn request(&mut self, method: &'static str, params: JsonValue) -> io::Result<()> {
+        self.next_id += 1;
+        self.pending.insert(self.next_id, method);
+
+        self.send(&JsonValue::object(vec![
+            ("jsonrpc", JsonValue::string("2.0")),
+            ("id", JsonValue::Number(self.next_id as f64)),
+            ("method", JsonValue::string(method)),
+            ("params", params),
+        ]))
+    }
+
+    fn notify(&mut self, method: &str, params: JsonValue) -> io::Result<()> {
+        let message = JsonValue::object(vec![
+            ("jsonrpc", JsonValue::string("2.0")),
+            ("method", JsonValue::string(method)),
+            ("params", params),
+        ]);
+
+        if self.initialized {
+            self.send(&message)
+        } else {
+            self.queued.push(message);
+            Ok(())
+        }
+    }
+
+    fn respond(&mut self, id: JsonValue, result: JsonValue) -> io::Result<()> {
+        self.send(&JsonValue::object(vec![
+            ("jsonrpc", JsonValue::string("2.0")),
+            ("id", id),
+            ("result", result),
+        ]))
+    }
+
+    pub fn did_open(
+        &mut self,
+        path: &Path,
+        language_id: &str,
+        version: usize,
+        text: &str,
+    ) -> io::Result<()> {
+        self.documents
+            .insert(absolute_path(path), document_lines(text));
+        self.notify(
+            "textDocument/didOpen",
+            JsonValue::object(vec![(
+                "textDocument",
+                JsonValue::object(vec![
+                    ("uri", JsonValue::String(path_to_uri(path))),
+                    ("languageId", JsonValue::string(language_id)),
+                    ("version", JsonValue::Number(version as f64)),
+                    ("text", JsonValue::string(text)),
+                ]),
+            )]),
+        )
+    }
+
+    /// Sends the full new content of the document
+    pub fn did_change(&mut self, path: &Path, version: usize, text: &str) -> io::Result<()> {
+        self.documents
+            .insert(absolute_path(path), document_lines(text));
+        self.notify(
+            "textDocument/didChange",
+            JsonValue::object(vec![
+                (
+                    "textDocument",
+                    JsonValue::object(vec![
+                        ("uri", JsonValue::String(path_to_uri(path))),
+                        ("version", JsonValue::Number(version as f64)),
+                    ]),
+                ),
+                (
+                    "contentChanges",
+                    JsonValue::Array(vec![JsonValue::object(vec![(
+                        "text",
+                        JsonValue::string(text),
+                    )])]),
+                ),
+            ]),
+        )
+    }
+
+    pub fn did_save(&mut self, path: &Path) -> io::Result<()> {
+        self.notify("textDocument/didSave", text_document(path))
+    }
+
+    pub fn did_close(&mut self, path: &Path) -> io::Result<()> {
+        self.documents.remove(&absolute_path(path));
+        self.notify("textDocument/didClose", text_document(path))
+    }
+
+    pub fn goto_definition(&mut self, path: &Path, position: Position) -> io::Result<()> {
+        let position = self.server_position(path, position);
+        self.request(
+            "textDocument/definition",
+            text_document_position(path, position),
+        )
+    }
+
+    pub fn hover(&mut self, path: &Path, position: Position) -> io::Result<()> {
+        let position = self.server_position(path, position);
+        self.request("textDocument/hover", text_document_position(path, position))
+    }
+
+    /// Lines of the document at `path` as the server knows it: as last sent if it is open,
+    /// else as saved
+    fn document(&self, path: &Path) -> Cow<'_, [String]> {
+        match self.documents.get(&absolute_path(path)) {
+            Some(lines) => Cow::Borrowed(lines),
+            None => Cow::Owned(document_lines(
+                &fs::read_to_string(path).unwrap_or_default(),
+            )),
+        }
+    }
+
+    /// Converts a position in a buffer into one counted in the units of the server
+    fn server_position(&self, path: &Path, position: Position) -> Position {
+        let document = self.document(path);
+        let line = document.get(position.line).map_or("", String::as_str);
+        Position::new(position.line, self.encoding.to_units(line, position.column))
+    }
+
+    /// Converts a position counted in the units of the server into one in a buffer
+    fn buffer_position(&self, document: &[String], position: Position) -> Position {
+        let line = document.get(position.line).map_or("", String::as_str);
+        Position::new(
+            position.line,
+            self.encoding.to_column(line, position.column),
+        )
+    }
+
+    /// Processes every message received from the server since the last call
+    pub fn poll(&mut self) -> Vec<LspEvent> {
+        let mut events = vec![];
+
+        loop {
+            let message = match self.messages.try_recv() {
+                Ok(message) => message,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if !self.exited {
+                        self.exited = true;
+                        events.push(LspEvent::Message("Language server exited".to_string()));
+                    }
+                    break;
+                }
+            };
+
+            let method = message.get("method").and_then(|m| m.as_str());
+            let id = message.get("id");
+
+            let result = match (method, id) {
+                // Request from the server, we do not support any of them but must still answer
+                (Some(method), Some(id)) => {
+                    let result = if method == "workspace/configuration" {
+                        let count = message
+                            .get("params")
+                            .and_then(|p| p.get("items"))
+                            .and_then(|i| i.as_array())
+                            .map_or(0, |i| i.len());
+                        JsonValue::Array(vec![JsonValue::Null; count])
+                    } else {
+                        JsonValue::Null
+                    };
+                    self.respond(id.clone(), result)
+                }
+                (Some(method), None) => {
+                    self.handle_notification(method, message.get("params"), &mut events);
+                    Ok(())
+                }
+                (None, Some(id)) => self.handle_response(id, &message, &mut events),
+                (None, None) => Ok(()),
+            };
+
+            if let Err(e) = result {
+                events.push(LspEvent::Message(format!("Language server error : {e}")));
+            }
+        }
+
+        events
+    }
+
+    fn handle_notification(
+        &mut self,
+        method: &str,
+        params: Option<&JsonValue>,
+        events: &mut Vec<LspEvent>,
+    ) {
+        let Some(params) = params else {
+            return;
+        };
+
+        match method {
+            "textDocument/publishDiagnostics" => {
+                let Some(path) = params.get("uri").and_then(|u| u.as_str()).map(uri_to_path) else {
+                    return;
+                };
+
+                let document = self.document(&path);
+                let diagnostics = params
+                    .get("diagnostics")
+                    .and_then(|d| d.as_array())
+                    .map(|d| d.iter().filter_map(parse_diagnostic).collect::<Vec<_>>())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|diagnostic| Diagnostic {
+                        start: self.buffer_position(&document, diagnostic.start),
+                        end: self.buffer_position(&document, diagnostic.end),
+                        ..diagnostic
+                    })
+                    .collect();
+
+                events.push(LspEvent::Diagnostics { path, diagnostics });
+            }
+            "window/showMessage" => {
+                if let Some(message) = params.get("message").and_then(|m| m.as_str()) {
+                    events.push(LspEvent::Message(printable(message)));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_response(
+        &mut self,
+        id: &JsonValue,
+        message: &JsonValue,
+        events: &mut Vec<LspEvent>,
+    ) -> io::Result<()> {
+        let Some(method) = id
+            .as_usize()
+            .and_then(|id| self.pending.remove(&(id as u64)))
+        else {
+            return Ok(());
+        };
+
+        if let Some(error) = message.get("error") {
+            let text = error
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("unknown error");
+            let text = printable(text);
+            events.push(LspEvent::Message(format!("{method} failed : {text}")));
+            return Ok(());
+        }
+
+        let result = message.get("result").unwrap_or(&JsonValue::Null);
+
+        match method {
+            "initialize" => {
+                self.initialized = true;
+                // Servers not telling which encoding they picked use UTF-16
+                self.encoding = result
+                    .get("capabilities")
+                    .and_then(|c| c.get("positionEncoding"))
+                    .and_then(|e| e.as_str())
+                    .and_then(PositionEncoding::from_name)
+                    .unwrap_or_default();
+                self.send(&JsonValue::object(vec![
+                    ("jsonrpc", JsonValue::string("2.0")),
+                    ("method", JsonValue::string("initialized")),
+                    ("params", JsonValue::object(vec![])),
+                ]))?;
+
+                for queued in std::mem::take(&mut self.queued) {
+                    self.send(&queued)?;
+                }
+            }
+            "textDocument/definition" => match parse_location(result) {
+                Some((path, position)) => {
+                    let position = self.buffer_position(&self.document(&path), position);
+                    events.push(LspEvent::Definition { path, position })
+                }
+                None => events.push(LspEvent::Message("No definition found".to_string())),
+            },
+            "textDocument/hover" => {
+                let text = result.get("contents").map(hover_text);
+                match text.map(|text| printable(&text)) {
+                    Some(text) if !text.trim().is_empty() => events.push(LspEvent::Hover(text)),
+                    _ => events.push(LspEvent::Message("No hover information".to_string())),
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Asks the server to exit, giving it a moment to do so, and makes sure the process is gone
+    pub fn shutdown(&mut self) {
+        if self.initialized && !self.exited {
+            let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+            if self.request("shutdown", JsonValue::Null).is_ok() {
+                // The server is told to exit once it answered
+                let id = self.next_id as usize;
+                while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                    match self.messages.recv_timeout(left) {
+                        Ok(message)
+                            if message.get("method").is_none()
+                                && message.get("id").and_then(|i| i.as_usize()) == Some(id) =>
+                        {
+                            break
+                        }
+                        Ok(_) => (),
+                        Err(_) => break,
+                    }
+                }
+                let _ = self.notify("exit", JsonValue::Null);
+            }
+
+            while Instant::now() < deadline {
+                match self.child.try_wait() {
+                    Ok(None) => thread::sleep(Duration::from_millis(10)),
+                    _ => break,
+                }
+            }
+            self.exited = true;
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Reads one `Content-Length` framed message, returns `None` once the stream is closed
+pub fn read_message(reader: &mut impl BufRead) -> Option<JsonValue> {
+    loop {
+        let mut content_length = None;
+
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).ok()? == 0 {
+                return None;
+            }
+
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("Content-Length") {
+                    content_length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+
+        let Some(length) = content_length else {
+            continue;
+        };
+
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).ok()?;
+
+        if let Ok(message) = JsonValue::parse(&String::from_utf8_lossy(&body)) {
+            return Some(message);
+        }
+    }
+}
+
+/// Makes `path` absolute, as in the URIs sent to the server
+fn absolute_path(path: &Path) -> PathBuf {
+    uri_to_path(&path_to_uri(path))
+}
+
+/// Splits a document into lines the way servers do, only on line breaks
+fn document_lines(text: &str) -> Vec<String> {
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+        .collect()
+}
+
+fn text_document(path: &Path) -> JsonValue {
+    JsonValue::object(vec![(
+        "textDocument",
+        JsonValue::object(vec![("uri", JsonValue::String(path_to_uri(path)))]),
+    )])
+}
+
+fn text_document_position(path: &Path, position: Position) -> JsonValue {
+    JsonValue::object(vec![
+        (
+            "textDocument",
+            JsonValue::object(vec![("uri", JsonValue::String(path_to_uri(path)))]),
+        ),
+        ("position", position_json(position)),
+    ])
+}
+
+fn position_json(position: Position) -> JsonValue {
+    JsonValue::object(vec![
+        ("line", JsonValue::Number(position.line as f64)),
+        ("character", JsonValue::Number(position.column as f64)),
+    ])
+}
+
+fn parse_position(value: &JsonValue) -> Option<Position> {
+    Some(Position::new(
+        value.get("line")?.as_usize()?,
+        value.get("character")?.as_usize()?,
+    ))
+}
+
+fn parse_diagnostic(value: &JsonValue) -> Option<Diagnostic> {
+    let range = value.get("range")?;
+    let severity = match value.get("severity").and_then(|s| s.as_usize()) {
+        Some(2) => Severity::Warning,
+        Some(3) => Severity::Information,
+        Some(4) => Severity::Hint,
+        _ => Severity::Error,
+    };
+
+    Some(Diagnostic {
+        start: parse_position(range.get("start")?)?,
+        end: parse_position(range.get("end")?)?,
+        severity,
+        message: printable(value.get("message")?.as_str()?),
+    })
+}
+
+/// Parses the first location out of a `Location`, `Location[]` or `LocationLink[]`
+fn parse_location(value: &JsonValue) -> Option<(PathBuf, Position)> {
+    let location = match value {
+        JsonValue::Array(items) => items.first()?,
+        other => other,
+    };
+
+    let uri = location
+        .get("uri")
+        .or_else(|| location.get("targetUri"))?
+        .as_str()?;
+    let range = location
+        .get("range")
+        .or_else(|| location.get("targetSelectionRange"))?;
+    let position = parse_position(range.get("start")?)?;
+
+    Some((uri_to_path(uri), position))
+}
+
+/// Extracts the text out of `MarkedString`, `MarkedString[]` or `MarkupContent`
+fn hover_text(contents: &JsonValue) -> String {
+    match contents {
+        JsonValue::String(s) => s.clone(),
+        JsonValue::Array(items) => items.iter().map(hover_text).collect::<Vec<_>>().join("\n"),
+        other => other
+            .get("value")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+    }
+}
+
+/// Converts a path to a `file://` URI, making it absolute first
+pub fn path_to_uri(path: &Path) -> String {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+
+    let mut uri = String::from("file://");
+    let path = absolute.to_string_lossy().replace('\\', "/");
+    if !path.starts_with('/') {
+        // Windows paths like `C:/...`
+        uri.push('/');
+    }
+
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    uri
+}
+
+pub fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+
+    let mut bytes = vec![];
+    let mut iter = path.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex: Vec<u8> = iter.by_ref().take(2).collect();
+            match std::str::from_utf8(&hex)
+                .ok()
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                Some(decoded) => bytes.push(decoded),
+                None => {
+                    bytes.push(b'%');
+                    bytes.extend(hex);
+                }
+            }
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    let path = String::from_utf8_lossy(&bytes).to_string();
+
+    // `/C:/...` on Windows
+    if cfg!(target_os = "windows") && path.len() > 2 && path.as_bytes()[2] == b':' {
+        PathBuf::from(&path[1..])
+    } else {
+        PathBuf::from(path)
+    }
+}
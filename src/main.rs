@@ -1,22 +1,69 @@
-mod buffer;
-mod display;
-mod status_line;
-mod util;
+mod action;
+mod build;
+mod case;
+mod clock;
+mod command;
+mod count;
+mod editor;
+mod encoding;
+mod events;
+mod format;
+mod history;
+mod input;
+mod keymap;
+mod modeline;
+mod outline;
+mod quickfix;
+mod recovery;
+mod reflow;
+#[cfg(test)]
+mod screen;
+mod script;
+mod session;
+mod settings;
+mod shell;
+mod signals;
+mod sort;
+mod spell;
+mod split;
+mod template;
+#[cfg(test)]
+mod tests;
+mod trash;
+mod unicode;
+
+// The editor core comes from the library, see lib.rs
+use tte_core::{
+    accessibility, buffer, clipboard, colors, completion, config, cursor_style, diff, display,
+    filetype, highlight, indent, json, lsp, markdown, paths, popup, position, printable, project,
+    prompt, start_screen, status_line, tab_line, undo, width,
+};
 
 use std::{
     env::args,
-    io::{self, stdout},
+    fs::{File, OpenOptions},
+    io::{self, stdin, stdout, IsTerminal, Read, Write},
     panic,
     process::exit,
+    time::{Duration, Instant},
 };
 
+use action::Action;
 use buffer::Buffer;
-use crossterm::{
-    cursor::SetCursorStyle,
-    event::{read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
-};
+use config::Config;
+use crossterm::event::{poll, read, Event};
 use display::Display;
-use status_line::StatusLine;
+use editor::Editor;
+use split::Split;
+
+/// How long to wait for terminal events before checking on background work
+const EVENT_POLL_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// The shortest time between two frames, so that bursts of events like key repeat are drawn once
+const FRAME_TIME: Duration = Duration::from_millis(1000 / 60);
+
+/// Name of the buffer holding what was piped into `tte -`
+const STDIN_BUFFER_NAME: &str = "[stdin]";
 
 fn main() {
     panic::set_hook(Box::new(|panic_info| {
@@ -26,6 +73,9 @@ fn main() {
         } else {
             println!("Panic occurred, but location is unknown.");
         }
+        if let Some(message) = panic_info.payload().downcast_ref::<String>() {
+            println!("{message}");
+        }
     }));
 
     if let Err(e) = run() {
@@ -36,169 +86,183 @@ fn main() {
 
 fn run() -> io::Result<()> {
     // TODO: Make this better
-    let args: Vec<String> = args().collect();
-    if args.len() > 2 {
-        eprintln!("USAGE: {} [filename]", args[0]);
+    let mut args: Vec<String> = args().collect();
+    let filter = args.iter().any(|arg| arg == "--filter");
+    let no_color = args.iter().any(|arg| arg == "--no-color");
+    let debug = args.iter().any(|arg| arg == "--debug");
+    args.retain(|arg| !["--filter", "--no-color", "--debug"].contains(&arg.as_str()));
+    if args.len() == 2 && args[1] == "--init-config" {
+        match Config::init() {
+            Ok(path) => println!("Wrote {}", path.display()),
+            Err(e) => {
+                eprintln!("ERROR : {e}");
+                exit(1);
+            }
+        }
+        return Ok(());
+    }
+    let diff_files = (args.get(1).map(String::as_str) == Some("--diff")).then(|| &args[2..]);
+    if args.len() > 2 && diff_files.is_none_or(|files| files.len() != 2) {
+        eprintln!(
+            "USAGE: {} [--filter] [--no-color] [--debug] [filename]",
+            args[0]
+        );
+        eprintln!("       {} --diff <left> <right>", args[0]);
+        eprintln!("       {} --init-config", args[0]);
         eprintln!("- If file is not provided, an empty buffer is opened.");
+        eprintln!("- If file is `-`, the buffer holds what is piped into tte.");
+        eprintln!("- With --filter, the buffer is printed to stdout on quit.");
+        eprintln!("- With --no-color, only the default colors of the terminal are used.");
+        eprintln!("- With --debug, the cursor, its line and the view are shown at the top right.");
+        eprintln!("- --init-config writes a commented default config into the config directory.");
         exit(1);
     }
 
-    let mut display = Display::new(stdout())?;
-    display.set_cursor_style(SetCursorStyle::BlinkingBar)?;
+    // Read before taking over the terminal, keys then come from the terminal itself
+    let piped = if args.get(1).map(String::as_str) == Some("-") {
+        if stdin().is_terminal() {
+            eprintln!("ERROR : Nothing piped into tte, e.g. `ls | tte -`");
+            exit(1);
+        }
+        Some(read_piped(stdin().lock())?)
+    } else {
+        None
+    };
 
-    let mut buffer = if args.len() == 1 {
-        Buffer::new(0, 0, display.width as usize, display.height as usize - 1)
+    // Stdout is left for the result in filter mode, the editor is drawn on the terminal
+    let out: Box<dyn Write> = if filter {
+        Box::new(open_terminal()?)
     } else {
-        Buffer::from_file(
-            &args[1],
-            0,
-            0,
-            display.width as usize,
-            display.height as usize - 1,
-        )
+        Box::new(stdout())
     };
+    let mut display = Display::new(out)?;
+    signals::install();
 
-    let mut status_line = if args.len() == 1 {
-        StatusLine::new(
-            0,
-            display.height,
-            display.width as usize,
-            1,
-            &buffer.file_name(),
-        )
+    let (width, height) = editor::buffer_size(display.width, display.height);
+    let mut buffer = if args.len() == 1 {
+        Buffer::new(0, 0, width, height)
+    } else if let Some(text) = &piped {
+        Buffer::scratch(STDIN_BUFFER_NAME, text, 0, 0, width, height)
     } else {
-        StatusLine::new(
-            0,
-            display.height,
-            display.width as usize,
-            1,
-            &buffer.file_name(),
-        )
+        Buffer::from_file(&args[1], 0, 0, width, height)
     };
 
-    loop {
-        display.begin_draw()?;
-
-        if let Ok(event) = read() {
-            match event {
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('q'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }) => break,
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('s'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }) => buffer.save(),
-                Event::Resize(w, h) => {
-                    display.resize(w, h);
-                    // Be sure to resize the buffer correctly or the rendering will messup.
-                    buffer.resize(w as usize, h as usize - 1);
-                    status_line.resize(w as usize, 1);
-                    status_line.move_to(0, h - 1);
-                }
+    // The left file is the active one at first, the right one is in the other pane
+    let mut split: Option<Split> = None;
+    if let Some([left, right]) = diff_files {
+        buffer = Buffer::from_file(left, 0, 0, 0, 0);
+        let other = Buffer::from_file(right, 0, 0, 0, 0);
+        let mut s = Split::new(other, false, true);
+        s.layout(&mut buffer, 0, 0, width, height);
+        split = Some(s);
+    }
 
-                Event::Key(KeyEvent {
-                    code: KeyCode::Left,
-                    modifiers: KeyModifiers::NONE,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => {
-                    buffer.move_cursor_left(1);
-                    buffer.scroll();
-                }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Right,
-                    modifiers: KeyModifiers::NONE,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => {
-                    buffer.move_cursor_right(1);
-                    buffer.scroll();
-                }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Up,
-                    modifiers: KeyModifiers::NONE,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => {
-                    buffer.move_cursor_up(1);
-                    buffer.scroll();
-                }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Down,
-                    modifiers: KeyModifiers::NONE,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => {
-                    buffer.move_cursor_down(1);
-                    buffer.scroll();
-                }
+    let mut editor = Editor::new(buffer, split, display.width, display.height);
+    if args.len() == 1 {
+        editor.show_start_screen();
+    }
+    if let Some(colors) = editor.color_support() {
+        display.set_color_support(colors);
+    }
+    display.set_terminal_background(editor.terminal_background());
+    display.set_screen_reader(editor.accessibility().screen_reader);
+    if no_color {
+        display.disable_colors();
+    }
+    if debug {
+        editor.set_debug(true);
+    }
+    if filter {
+        editor.set_filter(true);
+    }
 
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char(c),
-                    modifiers: KeyModifiers::NONE,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => {
-                    buffer.insert_ch(c);
-                }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char(c),
-                    modifiers: KeyModifiers::SHIFT,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => {
-                    buffer.insert_ch(c.to_ascii_uppercase());
-                }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Enter,
-                    modifiers: KeyModifiers::NONE,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => {
-                    buffer.insert_ch('\n');
-                }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Backspace,
-                    modifiers: KeyModifiers::NONE,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => {
-                    buffer.backspace();
+    let mut last_frame: Option<Instant> = None;
+    let mut recovered = Ok(vec![]);
+    'main: loop {
+        if signals::stop_requested() {
+            // Nobody is there to ask about the unsaved changes, copies of them are kept instead
+            recovered = match recovery::dir() {
+                Some(dir) => editor
+                    .save_recovery_copies(&dir)
+                    .map_err(|e| format!("Failed to keep the unsaved changes : {e}")),
+                None => Err("No data directory to keep the unsaved changes in".to_string()),
+            };
+            editor.apply(Action::ForceQuit);
+            break;
+        }
+
+        // Wait for the next frame when there is something to draw, otherwise sleep until input
+        // comes in, waking up now and then to keep up with the language server
+        let next_frame =
+            last_frame.map_or(Duration::ZERO, |at| FRAME_TIME.saturating_sub(at.elapsed()));
+        let timeout = if editor.needs_draw() {
+            next_frame
+        } else {
+            EVENT_POLL_TIMEOUT
+        };
+        let mut event = if poll(timeout)? { read().ok() } else { None };
+
+        // Handle everything that came in since the last frame, in order, before drawing it once
+        let batch_start = Instant::now();
+        loop {
+            if let Some(event) = event.take() {
+                if let Event::Resize(w, h) = event {
+                    display.resize(w, h);
                 }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Delete,
-                    modifiers: KeyModifiers::NONE,
-                    kind: KeyEventKind::Press,
-                    ..
-                }) => {
-                    buffer.delete();
+                editor.handle_event(event);
+                if editor.quit {
+                    break 'main;
                 }
+            }
+            // Without an edit, e.g. when waking up with no input, this leaves the lines as they are
+            editor.update();
 
-                _ => (),
+            // Input coming in faster than it is handled must not hold the frame back
+            if batch_start.elapsed() >= FRAME_TIME || !poll(Duration::ZERO)? {
+                break;
             }
+            event = read().ok();
         }
+        if editor.needs_draw() && last_frame.is_none_or(|at| at.elapsed() >= FRAME_TIME) {
+            display.begin_draw()?;
+            editor.draw(&mut display)?;
+            display.end_draw()?;
+            last_frame = Some(Instant::now());
+        }
+    }
 
-        // DEBUGGING STUFF
-        // display.print(format!("{event:?}"))?;
-
-        // display.move_cursor_to(30, 0)?;
-        // display.print(format!("{} ({:?}) -> {:?}", buffer.cursor_pos, buffer.data[buffer.cursor_pos], buffer.cursor_xy()))?;
-        // display.move_cursor_to(30, 1)?;
-        // display.print(format!("({}, {})", buffer.lines[0].start, buffer.lines[0].end))?;
-
-        // display.move_cursor_to(30, 0)?;
-        // display.print(format!(" Cursor {:?} | Terminal {:?} | Y Off {}", buffer.cursor_xy(), terminal::size()?, buffer.offset_y))?;
-
-        display.draw_status_line(&status_line)?;
-
-        buffer.recalculate_lines();
-        display.draw_buffer(&buffer)?; // Make sure to draw the active buffer the last to get the correct cursor position
-
-        display.end_draw()?;
+    // Give the terminal back before printing, in case stdout is it too
+    drop(display);
+    // The terminal may be gone already, there is nothing to do if these cannot be written
+    let mut err = io::stderr();
+    match recovered {
+        Ok(copies) => {
+            for copy in copies {
+                let _ = writeln!(err, "Unsaved changes kept in {}", copy.display());
+            }
+        }
+        Err(e) => {
+            let _ = writeln!(err, "ERROR : {e}");
+        }
+    }
+    if filter {
+        let mut out = stdout().lock();
+        out.write_all(editor.buffer.text().as_bytes())?;
+        out.flush()?;
     }
 
     Ok(())
 }
+
+/// Opens the terminal for writing whatever stdout is
+fn open_terminal() -> io::Result<File> {
+    let path = if cfg!(windows) { "CONOUT$" } else { "/dev/tty" };
+    OpenOptions::new().write(true).open(path)
+}
+
+/// Reads everything piped into tte, invalid UTF-8 becomes U+FFFD
+fn read_piped(mut input: impl Read) -> io::Result<String> {
+    let mut bytes = vec![];
+    input.read_to_end(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
@@ -0,0 +1,193 @@
+#![allow(dead_code)]
+
+use crate::highlight::{Highlight, HighlightKind};
+
+/// Highlights headings, emphasis, code, list bullets, links and block quotes
+pub fn highlight(data: &[char]) -> Vec<Highlight> {
+    let mut highlights = vec![];
+    let mut in_fence = false;
+    let mut line_start = 0;
+
+    while line_start <= data.len() {
+        let line_end = data[line_start..]
+            .iter()
+            .position(|c| *c == '\n')
+            .map_or(data.len(), |i| line_start + i);
+        let line = &data[line_start..line_end];
+
+        let indent = line.iter().take_while(|c| **c == ' ').count();
+        let content = &line[indent.min(line.len())..];
+        let mut add = |start: usize, end: usize, kind: HighlightKind| {
+            if start < end {
+                highlights.push(Highlight {
+                    start: line_start + start,
+                    end: line_start + end,
+                    kind,
+                });
+            }
+        };
+
+        if starts_with(content, "```") || starts_with(content, "~~~") {
+            // Fences are highlighted along with the code inside them
+            in_fence = !in_fence;
+            add(0, line.len(), HighlightKind::Code);
+        } else if in_fence {
+            add(0, line.len(), HighlightKind::Code);
+        } else if is_heading(content) {
+            add(0, line.len(), HighlightKind::Heading);
+        } else if content.first() == Some(&'>') {
+            add(0, line.len(), HighlightKind::Quote);
+        } else {
+            let marker = list_marker_len(content);
+            add(indent, indent + marker, HighlightKind::ListBullet);
+
+            for (start, end, kind) in inline_spans(line, indent + marker) {
+                add(start, end, kind);
+            }
+        }
+
+        line_start = line_end + 1;
+    }
+
+    highlights
+}
+
+fn starts_with(chars: &[char], prefix: &str) -> bool {
+    let prefix: Vec<char> = prefix.chars().collect();
+    chars.starts_with(&prefix)
+}
+
+/// `# Heading` up to `###### Heading`
+fn is_heading(content: &[char]) -> bool {
+    let hashes = content.iter().take_while(|c| **c == '#').count();
+    (1..=6).contains(&hashes) && content.get(hashes).is_none_or(|c| *c == ' ')
+}
+
+/// Length of the list marker at the start of `content`, including the space after it
+fn list_marker_len(content: &[char]) -> usize {
+    match content {
+        ['-' | '*' | '+', ' ', ..] => 2,
+        _ => {
+            let digits = content.iter().take_while(|c| c.is_ascii_digit()).count();
+            match content.get(digits..digits + 2) {
+                Some(['.' | ')', ' ']) if digits > 0 => digits + 2,
+                _ => 0,
+            }
+        }
+    }
+}
+
+/// Finds code spans, emphasis and links in `line`, starting at `from`
+fn inline_spans(line: &[char], from: usize) -> Vec<(usize, usize, HighlightKind)> {
+    let mut spans = vec![];
+    let mut i = from;
+
+    while i < line.len() {
+        match line[i] {
+            '`' => {
+                if let Some(close) = find(line, i + 1, &['`']) {
+                    spans.push((i, close + 1, HighlightKind::Code));
+                    i = close + 1;
+                    continue;
+                }
+            }
+            '*' | '_' => {
+                let marker = line[i];
+                let double = line.get(i + 1) == Some(&marker);
+                let width = if double { 2 } else { 1 };
+                let closing = if double {
+                    vec![marker, marker]
+                } else {
+                    vec![marker]
+                };
+
+                // `_` inside words, like snake_case, is not emphasis
+                let inside_word = marker == '_' && i > 0 && line[i - 1].is_alphanumeric();
+                let opens = line.get(i + width).is_some_and(|c| !c.is_whitespace());
+
+                if !inside_word && opens {
+                    if let Some(close) = find(line, i + width + 1, &closing) {
+                        spans.push((i, close + width, HighlightKind::Emphasis));
+                        i = close + width;
+                        continue;
+                    }
+                }
+            }
+            '[' => {
+                if let Some((_, end)) = link_at(line, i) {
+                    spans.push((i, end, HighlightKind::Link));
+                    i = end;
+                    continue;
+                }
+            }
+            _ => (),
+        }
+
+        i += 1;
+    }
+
+    spans
+}
+
+/// Index of the first occurrence of `pattern` in `line` at or after `from`
+fn find(line: &[char], from: usize, pattern: &[char]) -> Option<usize> {
+    (from..line.len()).find(|i| line[*i..].starts_with(pattern))
+}
+
+/// Parses a `[text](target)` link starting at `start`.
+/// Returns the target and the index after the closing parenthesis.
+fn link_at(line: &[char], start: usize) -> Option<(String, usize)> {
+    if line.get(start) != Some(&'[') {
+        return None;
+    }
+
+    let close_text = find(line, start + 1, &[']'])?;
+    if line.get(close_text + 1) != Some(&'(') {
+        return None;
+    }
+
+    let close_target = find(line, close_text + 2, &[')'])?;
+    let target: String = line[close_text + 2..close_target].iter().collect();
+
+    Some((target.trim().to_string(), close_target + 1))
+}
+
+/// Returns the link target or path under `idx`.
+/// Markdown links `[text](target)` give their target, otherwise the whitespace delimited word
+/// under the cursor is used, stripped of surrounding punctuation.
+pub fn link_target_at(data: &[char], idx: usize) -> Option<String> {
+    let line_start = data[..idx.min(data.len())]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |i| i + 1);
+    let line_end = data[line_start..]
+        .iter()
+        .position(|c| *c == '\n')
+        .map_or(data.len(), |i| line_start + i);
+    let line = &data[line_start..line_end];
+    let column = idx - line_start;
+
+    // Is the cursor inside a markdown link?
+    for start in (0..=column.min(line.len().saturating_sub(1))).rev() {
+        if let Some((target, end)) = link_at(line, start) {
+            if column < end {
+                return Some(target);
+            }
+        }
+    }
+
+    let is_path_char = |c: &char| !c.is_whitespace() && !"()[]<>\"'`,;".contains(*c);
+    let start = line[..column.min(line.len())]
+        .iter()
+        .rposition(|c| !is_path_char(c))
+        .map_or(0, |i| i + 1);
+    let end = line[column.min(line.len())..]
+        .iter()
+        .position(|c| !is_path_char(c))
+        .map_or(line.len(), |i| column + i);
+
+    let word: String = line[start..end].iter().collect();
+    let word = word.trim_end_matches(['.', ':', '!', '?']);
+
+    (!word.is_empty()).then(|| word.to_string())
+}
@@ -0,0 +1,94 @@
+/// Number of lines at the start and at the end of a file searched for vim modelines
+const MODELINE_LINES: usize = 5;
+
+/// Returns the settings set by the modelines of `text`, as `(name, value)` for the `set`
+/// command. Only the tab width, the use of tabs and the file type are understood, e.g.
+///
+/// ```text
+/// # vim: set ts=4 et ft=python :
+/// /* -*- mode: c; tab-width: 8; indent-tabs-mode: t -*- */
+/// ```
+pub fn parse(text: &str) -> Vec<(String, String)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut settings = vec![];
+
+    // Emacs only looks at the first line, or the second one after a shebang
+    for line in lines.iter().take(2) {
+        if let Some(found) = parse_emacs(line) {
+            settings.extend(found);
+            break;
+        }
+    }
+
+    let last = lines
+        .len()
+        .saturating_sub(MODELINE_LINES)
+        .max(MODELINE_LINES);
+    for line in lines
+        .iter()
+        .take(MODELINE_LINES)
+        .chain(lines.iter().skip(last))
+    {
+        settings.extend(parse_vim(line));
+    }
+
+    settings
+}
+
+/// Parses `vim: ts=4 et` or `vim: set ts=4 et :`, also after `vi:` and `ex:`
+fn parse_vim(line: &str) -> Vec<(String, String)> {
+    let Some(options) = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        let idx = line.find(marker)?;
+        // `ex:` and `vi:` are common in words, they need a space before them
+        let spaced = idx == 0 || line[..idx].ends_with(char::is_whitespace);
+        spaced.then(|| &line[idx + marker.len()..])
+    }) else {
+        return vec![];
+    };
+
+    // The `set` form ends at the next colon, the other one goes to the end of the line
+    let options = match options.trim_start().strip_prefix("set ") {
+        Some(rest) => rest.split(':').next().unwrap_or_default(),
+        None => options,
+    };
+
+    options
+        .split(|c: char| c.is_whitespace() || c == ':')
+        .filter_map(|option| {
+            let (name, value) = option.split_once('=').unwrap_or((option, ""));
+            let setting = match (name, value) {
+                ("ts" | "tabstop", width) => ("tab_width", width),
+                ("et" | "expandtab", "") => ("expand_tabs", "true"),
+                ("noet" | "noexpandtab", "") => ("expand_tabs", "false"),
+                ("ft" | "filetype", file_type) => ("filetype", file_type),
+                _ => return None,
+            };
+            Some((setting.0.to_string(), setting.1.to_string()))
+        })
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+}
+
+/// Parses `-*- mode: c; tab-width: 8; indent-tabs-mode: nil -*-`
+fn parse_emacs(line: &str) -> Option<Vec<(String, String)>> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (variables, _) = rest.split_once("-*-")?;
+
+    let settings = variables
+        .split(';')
+        .filter_map(|variable| {
+            let (name, value) = variable.split_once(':')?;
+            let value = value.trim();
+            let setting = match (name.trim().to_ascii_lowercase().as_str(), value) {
+                ("mode", mode) => ("filetype", mode.to_ascii_lowercase()),
+                ("tab-width", width) => ("tab_width", width.to_string()),
+                ("indent-tabs-mode", "nil") => ("expand_tabs", "true".to_string()),
+                ("indent-tabs-mode", _) => ("expand_tabs", "false".to_string()),
+                _ => return None,
+            };
+            Some((setting.0.to_string(), setting.1))
+        })
+        .collect();
+
+    Some(settings)
+}
@@ -0,0 +1,246 @@
+use crate::filetype::FileType;
+
+/// Words starting the definitions listed in the outline, per file type. Words before them like
+/// `pub` or `async` are skipped, see `QUALIFIERS`.
+fn definition_keywords(file_type: FileType) -> &'static [&'static str] {
+    match file_type {
+        FileType::Rust => &[
+            "fn",
+            "struct",
+            "enum",
+            "union",
+            "trait",
+            "impl",
+            "mod",
+            "type",
+            "const",
+            "static",
+            "macro_rules!",
+        ],
+        FileType::Python => &["def", "class"],
+        FileType::JavaScript | FileType::TypeScript => &[
+            "function",
+            "function*",
+            "class",
+            "interface",
+            "type",
+            "enum",
+            "namespace",
+        ],
+        FileType::Go => &["func", "type"],
+        FileType::C | FileType::Cpp => &["struct", "enum", "union", "class", "namespace"],
+        _ => &[],
+    }
+}
+
+/// Words that can come before the keyword of a definition
+const QUALIFIERS: [&str; 12] = [
+    "pub", "async", "unsafe", "extern", "default", "export", "declare", "abstract", "static",
+    "inline", "typedef", "const",
+];
+
+/// Words of C and C++ that look like a function call when a line starts with them
+const C_STATEMENTS: [&str; 8] = [
+    "if", "else", "for", "while", "switch", "return", "do", "sizeof",
+];
+
+/// A definition or heading of a buffer, listed by the outline picker
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    /// Line of the definition, 0 based
+    pub line: usize,
+    /// Column where the text of its line starts, in characters and 0 based
+    pub column: usize,
+    /// What the definition is shown as, e.g. `fn main` or `## Usage`
+    pub label: String,
+    /// How deep it is nested, from the indentation of its line or the level of a heading
+    pub depth: usize,
+}
+
+impl Symbol {
+    /// Describes the symbol for the picker, indented by its depth and with its line number
+    pub fn describe(&self) -> String {
+        format!(
+            "{}{}  :{}",
+            "  ".repeat(self.depth),
+            self.label,
+            self.line + 1
+        )
+    }
+}
+
+/// Finds the headings of Markdown text, or the definitions of code, one line at a time.
+/// It only goes by the start of the lines, so definitions split over several lines may be
+/// missed, but it is quick and needs no language server.
+pub fn symbols(text: &str, file_type: FileType) -> Vec<Symbol> {
+    let mut symbols = vec![];
+    let mut in_fence = false;
+
+    for (line, content) in text.lines().enumerate() {
+        let trimmed = content.trim_start();
+        let indent = content.len() - trimmed.len();
+
+        let symbol = match file_type {
+            FileType::Markdown => {
+                if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                    in_fence = !in_fence;
+                }
+                heading(trimmed).filter(|_| !in_fence)
+            }
+            FileType::Toml => table(trimmed).map(|label| (label, 0)),
+            FileType::Yaml if indent == 0 => yaml_key(trimmed).map(|label| (label, 0)),
+            FileType::C | FileType::Cpp if indent == 0 => definition(trimmed, file_type)
+                .or_else(|| c_function(trimmed))
+                .map(|label| (label, 0)),
+            _ => definition(trimmed, file_type).map(|label| (label, indent / 4)),
+        };
+
+        if let Some((label, depth)) = symbol {
+            symbols.push(Symbol {
+                line,
+                column: content[..indent].chars().count(),
+                label,
+                depth,
+            });
+        }
+    }
+
+    symbols
+}
+
+/// Returns a Markdown heading like `## Usage`, with its level counted from 0
+fn heading(line: &str) -> Option<(String, usize)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let title = line[level..]
+        .strip_prefix(' ')?
+        .trim()
+        .trim_end_matches('#')
+        .trim();
+    if level == 0 || level > 6 || title.is_empty() {
+        return None;
+    }
+
+    Some((format!("{} {title}", &line[..level]), level - 1))
+}
+
+/// Returns the header of a TOML table, like `[dependencies]`
+fn table(line: &str) -> Option<String> {
+    let end = line.find(']')?;
+    line.starts_with('[').then(|| line[..=end].to_string())
+}
+
+/// Returns the key of a YAML mapping entry, like `services:`
+fn yaml_key(line: &str) -> Option<String> {
+    let (key, _) = line.split_once(':')?;
+    let is_key = !key.is_empty()
+        && !key.starts_with(['#', '-', ' '])
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '"' | '\''));
+    is_key.then(|| key.to_string())
+}
+
+/// Returns a definition like `fn main` or `class Parser`, skipping the words before its keyword
+fn definition(line: &str, file_type: FileType) -> Option<String> {
+    let keywords = definition_keywords(file_type);
+    let mut words = line.split_whitespace().peekable();
+
+    while let Some(word) = words.next() {
+        // Words like `const` are qualifiers too, as in `const fn`
+        let keyword_follows = words.peek().is_some_and(|next| keywords.contains(next));
+        if !keywords.contains(&word) || keyword_follows {
+            // `pub(crate)`, `extern "C"`
+            let qualifier = word.split('(').next().unwrap_or(word);
+            if QUALIFIERS.contains(&qualifier) || word.starts_with('"') {
+                continue;
+            }
+            return None;
+        }
+
+        let rest = words.collect::<Vec<_>>().join(" ");
+        let name = match word {
+            // What is implemented is all the name there is, like `impl Display for Point`
+            "impl" => rest
+                .split(['{', ';'])
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+            _ => rest
+                .trim_start_matches("mut ")
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+                .collect(),
+        };
+        if name.is_empty() {
+            return None;
+        }
+
+        return Some(match word {
+            "impl" => format!("impl {name}"),
+            _ => format!("{} {name}", word.trim_end_matches('!')),
+        });
+    }
+
+    None
+}
+
+/// Returns the name of a C function defined on the line, like `main` for `int main(void) {`.
+/// Declarations ending with `;` are left out.
+fn c_function(line: &str) -> Option<String> {
+    let line = line.trim_end();
+    if line.ends_with(';') || line.starts_with(['#', '/', '*', '}']) {
+        return None;
+    }
+
+    let before = line[..line.find('(')?].trim_end();
+    let name = before
+        .rsplit(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':' || c == '~'))
+        .next()?;
+    // A return type has to come first, or it is a call or a statement
+    let has_type = before.len() > name.len();
+    if name.is_empty() || !has_type || C_STATEMENTS.contains(&name) {
+        return None;
+    }
+    let first = before.split_whitespace().next().unwrap_or_default();
+    if C_STATEMENTS.contains(&first) || first.contains('=') {
+        return None;
+    }
+
+    Some(name.to_string())
+}
+
+/// Scores how well `query` matches `text` when its characters are found in order, ignoring
+/// case, lower being better: the characters left between the matched ones count against it.
+/// Returns `None` if they are not all found.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<usize> {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    let mut score = 0;
+    let mut started = false;
+
+    for q in query.chars().flat_map(char::to_lowercase) {
+        if q.is_whitespace() {
+            continue;
+        }
+        let skipped = text.by_ref().position(|c| c == q)?;
+        // Where the first match starts does not matter
+        if started {
+            score += skipped;
+        }
+        started = true;
+    }
+
+    Some(score)
+}
+
+/// Returns the symbols matching `query`, the best matches first and in the order of the buffer
+/// otherwise
+pub fn filter<'a>(symbols: &'a [Symbol], query: &str) -> Vec<&'a Symbol> {
+    let mut matches: Vec<(usize, &Symbol)> = symbols
+        .iter()
+        .filter_map(|symbol| Some((fuzzy_score(query, &symbol.label)?, symbol)))
+        .collect();
+    matches.sort_by_key(|(score, _)| *score);
+
+    matches.into_iter().map(|(_, symbol)| symbol).collect()
+}
@@ -0,0 +1,208 @@
+//! Paths of files: the directories tte keeps its files in, the home directory and paths typed
+//! by the user.
+
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Name of the directories tte keeps its files in, inside the directories of the platform
+const APP_DIR_NAME: &str = "tte";
+
+/// The kinds of files tte keeps, each in a directory of its own
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DirKind {
+    /// Files written by the user, like the config and scripts
+    Config,
+    /// Files collected by the editor that are worth keeping, like the history of files
+    Data,
+    /// Files that can be deleted at any time and made again
+    Cache,
+}
+
+/// Returns the home directory of the user
+pub fn get_user_home_dir() -> Option<PathBuf> {
+    home_dir(cfg!(target_os = "windows"), |name| env::var_os(name))
+}
+
+/// Returns the home directory, reading the environment with `var`
+fn home_dir(windows: bool, var: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    if windows {
+        // On Windows, check the `USERPROFILE` or `HOMEDRIVE` + `HOMEPATH`
+        var("USERPROFILE")
+            .or_else(|| {
+                let mut home = var("HOMEDRIVE")?;
+                home.push(var("HOMEPATH")?);
+                Some(home)
+            })
+            .map(PathBuf::from)
+    } else {
+        // On Unix-like systems (Linux, macOS), check the `HOME` environment variable
+        var("HOME").map(PathBuf::from)
+    }
+}
+
+/// Returns the directory where the configuration files of tte are stored
+pub fn get_config_dir() -> Option<PathBuf> {
+    get_app_dir(DirKind::Config)
+}
+
+/// Returns the directory where tte keeps the data it collects, like the history of files
+pub fn get_data_dir() -> Option<PathBuf> {
+    get_app_dir(DirKind::Data)
+}
+
+/// Returns the directory where tte keeps files it can make again
+pub fn get_cache_dir() -> Option<PathBuf> {
+    get_app_dir(DirKind::Cache)
+}
+
+/// Returns the directory of tte for files of the given kind
+fn get_app_dir(kind: DirKind) -> Option<PathBuf> {
+    app_dir(kind, cfg!(target_os = "windows"), |name| env::var_os(name))
+}
+
+/// Resolves the directory of tte for files of the given kind, reading the environment with
+/// `var`. On Windows the config goes to `%APPDATA%\tte`, the data and the cache to the `data`
+/// and `cache` directories of `%LOCALAPPDATA%\tte`, side by side. Elsewhere they follow the XDG base directories: `$XDG_CONFIG_HOME`, `$XDG_DATA_HOME` and
+/// `$XDG_CACHE_HOME`, which default to `~/.config`, `~/.local/share` and `~/.cache`.
+pub fn app_dir(
+    kind: DirKind,
+    windows: bool,
+    var: impl Fn(&str) -> Option<OsString>,
+) -> Option<PathBuf> {
+    if windows {
+        let (name, default) = match kind {
+            DirKind::Config => ("APPDATA", ["AppData", "Roaming"]),
+            DirKind::Data | DirKind::Cache => ("LOCALAPPDATA", ["AppData", "Local"]),
+        };
+        let base = var(name)
+            .map(PathBuf::from)
+            .or_else(|| Some(home_dir(windows, &var)?.join(default[0]).join(default[1])))?;
+        let dir = base.join(APP_DIR_NAME);
+        return Some(match kind {
+            DirKind::Config => dir,
+            DirKind::Data => dir.join("data"),
+            DirKind::Cache => dir.join("cache"),
+        });
+    }
+
+    xdg_dir(kind, var).map(|base| base.join(APP_DIR_NAME))
+}
+
+/// Returns the XDG base directory shared by the programs of the user for files of the given
+/// kind, e.g. `~/.local/share` for data
+pub fn get_xdg_dir(kind: DirKind) -> Option<PathBuf> {
+    xdg_dir(kind, |name| env::var_os(name))
+}
+
+fn xdg_dir(kind: DirKind, var: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    let (name, default): (&str, &[&str]) = match kind {
+        DirKind::Config => ("XDG_CONFIG_HOME", &[".config"]),
+        DirKind::Data => ("XDG_DATA_HOME", &[".local", "share"]),
+        DirKind::Cache => ("XDG_CACHE_HOME", &[".cache"]),
+    };
+    // Relative paths are to be ignored, as the XDG spec says
+    match var(name).map(PathBuf::from).filter(|dir| dir.is_absolute()) {
+        Some(base) => Some(base),
+        None => Some(
+            default
+                .iter()
+                .fold(home_dir(false, &var)?, |dir, part| dir.join(part)),
+        ),
+    }
+}
+
+/// Replaces a leading `~` with the home directory of the user
+pub fn expand_home(path: &str) -> PathBuf {
+    let rest = match path.strip_prefix('~') {
+        Some("") => "",
+        Some(rest) if rest.starts_with(['/', '\\']) => &rest[1..],
+        _ => return PathBuf::from(path),
+    };
+
+    match get_user_home_dir() {
+        Some(home) => home.join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Shows `path` with the home directory of the user written as `~`
+pub fn shorten_home(path: &Path) -> String {
+    match get_user_home_dir().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// Makes `path` absolute from the current directory, without resolving links
+pub fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+
+    env::current_dir()
+        .map(|dir| dir.join(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Completes the last part of the path being typed in `input`.
+///
+/// Returns `input` extended with what all the matching entries of its directory start with,
+/// directories ending with a `/` once they are the only match, and the names of the matches.
+/// Hidden entries only match when the last part starts with a dot.
+pub fn complete(input: &str, directories_only: bool) -> (String, Vec<String>) {
+    let (dir, prefix) = match input.rfind(['/', '\\']) {
+        Some(idx) => input.split_at(idx + 1),
+        None => ("", input),
+    };
+    let read_from = if dir.is_empty() {
+        PathBuf::from(".")
+    } else {
+        expand_home(dir)
+    };
+
+    let Ok(entries) = fs::read_dir(read_from) else {
+        return (input.to_string(), vec![]);
+    };
+
+    let mut matches: Vec<(String, bool)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let is_dir = entry.path().is_dir();
+            let wanted = name.starts_with(prefix)
+                && (prefix.starts_with('.') || !name.starts_with('.'))
+                && (is_dir || !directories_only);
+            wanted.then_some((name, is_dir))
+        })
+        .collect();
+    matches.sort();
+
+    let Some((first, _)) = matches.first() else {
+        return (input.to_string(), vec![]);
+    };
+
+    let mut common = first.clone();
+    for (name, _) in &matches[1..] {
+        let shared = common
+            .chars()
+            .zip(name.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        common = common.chars().take(shared).collect();
+    }
+
+    let mut completed = format!("{dir}{common}");
+    if let [(_, true)] = matches.as_slice() {
+        completed.push('/');
+    }
+
+    (
+        completed,
+        matches.into_iter().map(|(name, _)| name).collect(),
+    )
+}
@@ -0,0 +1,111 @@
+#![allow(dead_code)]
+
+use crossterm::style::Color;
+
+/*
+    Color theme default
+    :root {
+        --popup-bg-color: rgb(50, 50, 50);              /* Popup Background */
+        --popup-fg-color: rgb(210, 210, 210);           /* Popup Foreground (light gray) */
+        --popup-selected-bg-color: rgb(255, 210, 85);   /* Selected item (warm yellow) */
+        --popup-selected-fg-color: rgb(30, 30, 30);     /* Selected item text */
+    }
+*/
+
+/// A small list drawn over the other components, e.g. for completions
+pub struct Popup {
+    /// The x position of the top left corner
+    pub x: u16,
+    /// The y position of the top left corner
+    pub y: u16,
+    /// The width of the popup
+    pub width: usize,
+    /// The height of the popup
+    pub height: usize,
+    /// Lines shown in the popup
+    pub items: Vec<String>,
+    /// Index of the highlighted line, if any
+    pub selected: Option<usize>,
+    /// Background color
+    pub bg_color: Color,
+    /// Foreground color
+    pub fg_color: Color,
+    /// Background color of the selected line
+    pub selected_bg_color: Color,
+    /// Foreground color of the selected line
+    pub selected_fg_color: Color,
+}
+
+impl Popup {
+    /// Creates a popup showing `items`, placed next to the `anchor` position (usually the cursor)
+    /// so that it stays within a screen of size `screen_width` x `screen_height`.
+    pub fn new(
+        items: Vec<String>,
+        selected: Option<usize>,
+        anchor: (u16, u16),
+        screen_width: u16,
+        screen_height: u16,
+    ) -> Self {
+        let padding = 1;
+        let content_width = items.iter().map(|s| s.chars().count()).max().unwrap_or(0);
+        let width = (content_width + 2 * padding).min(screen_width as usize);
+        let height = items.len().min(screen_height as usize);
+
+        let (anchor_x, anchor_y) = anchor;
+
+        // Prefer showing the popup below the anchor, but go above it if it doesn't fit
+        let y = if anchor_y as usize + 1 + height <= screen_height as usize {
+            anchor_y + 1
+        } else {
+            anchor_y.saturating_sub(height as u16)
+        };
+
+        let x = if anchor_x as usize + width <= screen_width as usize {
+            anchor_x
+        } else {
+            screen_width.saturating_sub(width as u16)
+        };
+
+        Self {
+            x,
+            y,
+            width,
+            height,
+            items,
+            selected,
+            bg_color: Color::Rgb {
+                r: 50,
+                g: 50,
+                b: 50,
+            },
+            fg_color: Color::Rgb {
+                r: 210,
+                g: 210,
+                b: 210,
+            },
+            selected_bg_color: Color::Rgb {
+                r: 255,
+                g: 210,
+                b: 85,
+            },
+            selected_fg_color: Color::Rgb {
+                r: 30,
+                g: 30,
+                b: 30,
+            },
+        }
+    }
+
+    /// Returns the text of row `idx`, padded or truncated to the popup width
+    pub fn get_row_text(&self, idx: usize) -> String {
+        let mut row = String::with_capacity(self.width);
+        row.push(' ');
+        row.extend(self.items[idx].chars().take(self.width.saturating_sub(2)));
+
+        while row.chars().count() < self.width {
+            row.push(' ');
+        }
+
+        row
+    }
+}
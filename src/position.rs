@@ -0,0 +1,20 @@
+//! Places in a buffer as a line and a column, rather than as an index into its characters
+
+/// A 0 based line and column, the column counting characters from the start of the line.
+/// Positions compare by line, then by column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub const fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+
+    /// The start of `line`
+    pub const fn line_start(line: usize) -> Self {
+        Self::new(line, 0)
+    }
+}
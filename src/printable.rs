@@ -0,0 +1,35 @@
+//! Text from other programs, commands and language servers, made safe to draw
+
+/// Removes from text another program wrote what a terminal would act on rather than show: escape
+/// sequences, like those setting colors or the title, and the control characters but line breaks
+/// and tabs. Drawn as they are, they would reach the terminal of the editor.
+pub fn printable(text: &str) -> String {
+    let mut printable = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\n' | '\t' => printable.push(ch),
+            // Control sequences, up to their final byte
+            '\u{1B}' if chars.next_if_eq(&'[').is_some() => {
+                chars.find(|c| ('@'..='~').contains(c));
+            }
+            '\u{9B}' => {
+                chars.find(|c| ('@'..='~').contains(c));
+            }
+            // Operating system commands, up to the bell or the string terminator
+            '\u{1B}' if chars.next_if_eq(&']').is_some() => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}'
+                        || c == '\u{9C}'
+                        || (c == '\u{1B}' && chars.next_if_eq(&'\\').is_some())
+                    {
+                        break;
+                    }
+                }
+            }
+            ch if ch.is_control() => (),
+            ch => printable.push(ch),
+        }
+    }
+    printable
+}
@@ -0,0 +1,53 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{Config, ConfigValue},
+    paths::absolute,
+};
+
+/// Files or directories found at the root of a project, unless `[project] markers` says otherwise
+pub const DEFAULT_ROOT_MARKERS: [&str; 2] = [".git", "Cargo.toml"];
+
+/// Returns the markers of a project root set in the config, e.g.
+///
+/// ```toml
+/// [project]
+/// markers = [".git", "package.json"]
+/// ```
+pub fn root_markers(config: &Config) -> Vec<String> {
+    match config.get("project", "markers") {
+        Some(ConfigValue::Array(values)) => values
+            .iter()
+            .filter_map(|value| match value {
+                ConfigValue::String(s) => Some(s.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => DEFAULT_ROOT_MARKERS.iter().map(|m| m.to_string()).collect(),
+    }
+}
+
+/// Returns the nearest directory holding `path` (itself included) that has one of `markers` in it
+pub fn find_root(path: &Path, markers: &[String]) -> Option<PathBuf> {
+    absolute(path)
+        .ancestors()
+        .find(|dir| markers.iter().any(|marker| dir.join(marker).exists()))
+        .map(Path::to_path_buf)
+}
+
+/// Returns the root of the project tte was started in, or the current directory outside of one
+pub fn current_root(config: &Config) -> Option<PathBuf> {
+    let dir = env::current_dir().ok()?;
+    find_root(&dir, &root_markers(config)).or(Some(dir))
+}
+
+/// Returns `path` relative to the root of its project, or `None` if it is not in one
+pub fn relative_path(path: &Path, markers: &[String]) -> Option<String> {
+    let path = absolute(path);
+    let root = find_root(path.parent()?, markers)?;
+    let relative = path.strip_prefix(root).ok()?;
+    Some(relative.display().to_string())
+}
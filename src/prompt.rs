@@ -0,0 +1,148 @@
+#![allow(dead_code)]
+
+use crossterm::{
+    event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    style::Color,
+};
+
+use crate::width::char_width;
+
+/*
+    Color theme default
+    :root {
+        --prompt-bg-color: rgb(40, 40, 40);    /* Prompt Background */
+        --prompt-fg-color: rgb(255, 210, 85);  /* Prompt Foreground (warm yellow) */
+    }
+*/
+
+/// What the prompt wants done after a key press
+pub enum PromptResult {
+    /// Still editing
+    Pending,
+    /// Enter was pressed, holds the input
+    Submit(String),
+    /// Esc was pressed
+    Cancel,
+}
+
+/// A single line input drawn over the status line, e.g. for commands
+pub struct Prompt {
+    /// The x position of the top left corner
+    pub x: u16,
+    /// The y position of the top left corner
+    pub y: u16,
+    /// The width of the prompt
+    pub width: usize,
+    /// Text shown before the input
+    pub label: String,
+    /// Text typed so far
+    pub input: Vec<char>,
+    /// Cursor position in the input
+    pub cursor_pos: usize,
+    /// Background color
+    pub bg_color: Color,
+    /// Foreground color
+    pub fg_color: Color,
+}
+
+impl Prompt {
+    pub fn new(x: u16, y: u16, width: usize, label: &str, input: &str) -> Self {
+        let input: Vec<char> = input.chars().collect();
+
+        Self {
+            x,
+            y,
+            width,
+            label: label.to_string(),
+            cursor_pos: input.len(),
+            input,
+            bg_color: Color::Rgb {
+                r: 40,
+                g: 40,
+                b: 40,
+            },
+            fg_color: Color::Rgb {
+                r: 255,
+                g: 210,
+                b: 85,
+            },
+        }
+    }
+
+    pub fn input(&self) -> String {
+        self.input.iter().collect()
+    }
+
+    /// Inserts text at the cursor, as pasted or composed by an input method. The input being
+    /// a single line, line breaks are left out.
+    pub fn insert_text(&mut self, text: &str) {
+        let chars: Vec<char> = text.chars().filter(|c| !matches!(c, '\n' | '\r')).collect();
+        let count = chars.len();
+        self.input.splice(self.cursor_pos..self.cursor_pos, chars);
+        self.cursor_pos += count;
+    }
+
+    pub fn handle_key(&mut self, key: &KeyEvent) -> PromptResult {
+        if key.kind != KeyEventKind::Press {
+            return PromptResult::Pending;
+        }
+
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, _) => return PromptResult::Cancel,
+            (KeyCode::Enter, _) => return PromptResult::Submit(self.input()),
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                self.input.insert(self.cursor_pos, c);
+                self.cursor_pos += 1;
+            }
+            (KeyCode::Backspace, _) if self.cursor_pos > 0 => {
+                self.cursor_pos -= 1;
+                self.input.remove(self.cursor_pos);
+            }
+            (KeyCode::Delete, _) if self.cursor_pos < self.input.len() => {
+                self.input.remove(self.cursor_pos);
+            }
+            (KeyCode::Left, _) => self.cursor_pos = self.cursor_pos.saturating_sub(1),
+            (KeyCode::Right, _) => self.cursor_pos = (self.cursor_pos + 1).min(self.input.len()),
+            (KeyCode::Home, _) => self.cursor_pos = 0,
+            (KeyCode::End, _) => self.cursor_pos = self.input.len(),
+            _ => (),
+        }
+
+        PromptResult::Pending
+    }
+
+    /// Returns the visible text, padded to the prompt width, and the x offset of the cursor in it
+    pub fn get_text(&self) -> (String, usize) {
+        let width_of = |chars: &[char]| chars.iter().map(|c| char_width(*c)).sum::<usize>();
+        let label_width = self.label.chars().map(char_width).sum::<usize>();
+        let input_width = self.width.saturating_sub(label_width + 1).max(1);
+
+        // Scroll the input so that the cursor stays visible
+        let mut offset = 0;
+        while offset < self.cursor_pos
+            && width_of(&self.input[offset..self.cursor_pos]) + 1 > input_width
+        {
+            offset += 1;
+        }
+
+        let mut line = self.label.clone();
+        let mut line_width = label_width;
+        for c in &self.input[offset..] {
+            if line_width + char_width(*c) > label_width + input_width {
+                break;
+            }
+            line.push(*c);
+            line_width += char_width(*c);
+        }
+
+        while line_width < self.width {
+            line.push(' ');
+            line_width += 1;
+        }
+
+        (
+            line,
+            label_width + width_of(&self.input[offset..self.cursor_pos]),
+        )
+    }
+}
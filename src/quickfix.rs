@@ -0,0 +1,183 @@
+use std::{fmt, path::PathBuf};
+
+/// Name of the scratch buffer holding the output of the build command
+pub const BUILD_BUFFER_NAME: &str = "[Build]";
+
+/// Name of the scratch buffer listing the entries of the quickfix list
+pub const QUICKFIX_BUFFER_NAME: &str = "[Quickfix]";
+
+/// Line of the quickfix buffer the entries start on, after the title and a blank line
+pub const FIRST_ENTRY_LINE: usize = 2;
+
+/// A `file:line:col` position found in compiler output.
+/// `line` and `column` are 0 based.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Finds the first `file:line` or `file:line:col` location in `text`, as printed by most compilers
+pub fn parse_location(text: &str) -> Option<Location> {
+    text.split_whitespace().find_map(|token| {
+        let token = token.trim_matches(|c: char| "()[]<>\"',".contains(c));
+        let parts: Vec<&str> = token.split(':').collect();
+        let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+
+        // Joining the parts before the line number keeps Windows drive letters in the path
+        let line_idx = (1..parts.len()).find(|i| is_number(parts[*i]))?;
+        let path = parts[..line_idx].join(":");
+        if path.is_empty() || !(path.contains('.') || path.contains('/')) {
+            return None;
+        }
+
+        let line: usize = parts[line_idx].parse().ok()?;
+        let column: usize = parts
+            .get(line_idx + 1)
+            .filter(|s| is_number(s))
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1);
+
+        Some(Location {
+            path: PathBuf::from(path),
+            line: line.saturating_sub(1),
+            column: column.saturating_sub(1),
+        })
+    })
+}
+
+/// Returns the location for line `line_idx` of `lines`.
+/// Compilers often print the location below the message, e.g. rustc's `--> src/main.rs:3:5`,
+/// so if the line has none the following lines are searched up to the next blank line.
+pub fn location_for_line(lines: &[&str], line_idx: usize) -> Option<Location> {
+    lines
+        .iter()
+        .skip(line_idx)
+        .enumerate()
+        .take_while(|(i, line)| *i == 0 || !line.trim().is_empty())
+        .find_map(|(_, line)| parse_location(line))
+}
+
+/// A place in a file with what was found there, e.g. a build error, a search match or a
+/// diagnostic of the language server. The quickfix list goes through them one after the other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub location: Location,
+    pub message: String,
+}
+
+impl Entry {
+    /// Parses an entry as written in the quickfix buffer, `path:line:col: message`
+    pub fn parse(text: &str) -> Option<Self> {
+        let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+
+        // The path may hold colons too, e.g. a Windows drive letter
+        text.match_indices(':').find_map(|(i, _)| {
+            let mut parts = text[i + 1..].splitn(3, ':');
+            let (line, column, message) = (parts.next()?, parts.next()?, parts.next()?);
+            if i == 0 || !is_number(line) || !is_number(column) {
+                return None;
+            }
+
+            Some(Self {
+                location: Location {
+                    path: PathBuf::from(&text[..i]),
+                    line: line.parse::<usize>().ok()?.saturating_sub(1),
+                    column: column.parse::<usize>().ok()?.saturating_sub(1),
+                },
+                message: message.trim().to_string(),
+            })
+        })
+    }
+}
+
+/// Writes the entry as `path:line:col: message`, with the line and column counted from 1
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Location { path, line, column } = &self.location;
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            path.display(),
+            line + 1,
+            column + 1,
+            self.message
+        )
+    }
+}
+
+/// Returns the text of the quickfix buffer listing `entries` under `title`
+pub fn list_text(title: &str, entries: &[Entry]) -> String {
+    let mut text = format!(
+        "{title}, press Enter on an entry to jump to it, F4 / Shift+F4 go through them\n\n"
+    );
+    for entry in entries {
+        text.push_str(&entry.to_string());
+        text.push('\n');
+    }
+    text
+}
+
+/// Finds the locations mentioned in compiler output, each with its message.
+/// Compilers often print the location below the message, e.g. rustc's `--> src/main.rs:3:5`,
+/// so a location alone on its line gets the first line of its block as message.
+pub fn build_entries(output: &str) -> Vec<Entry> {
+    let mut entries = vec![];
+    let mut block_start = None;
+
+    for line in output.lines() {
+        if line.trim().is_empty() {
+            block_start = None;
+            continue;
+        }
+        let block_start = *block_start.get_or_insert(line.trim());
+        let Some(location) = parse_location(line) else {
+            continue;
+        };
+
+        // What is left of the line once the token holding the location is taken out
+        let rest: Vec<&str> = line
+            .split_whitespace()
+            .filter(|token| parse_location(token).is_none() && *token != "-->")
+            .collect();
+        let message = match rest.join(" ") {
+            rest if rest.is_empty() => block_start.to_string(),
+            rest => rest,
+        };
+
+        entries.push(Entry { location, message });
+    }
+
+    entries
+}
+
+/// Reads the matches printed by `grep -n`, as `path:line:text`, or by tools also printing the
+/// column like `rg --vimgrep`, as `path:line:col:text`
+pub fn grep_entries(output: &str) -> Vec<Entry> {
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ':');
+            let (path, number, rest) = (parts.next()?, parts.next()?, parts.next()?);
+            if path.is_empty() || !is_number(number) {
+                return None;
+            }
+
+            let (column, text): (usize, &str) = match rest.split_once(':') {
+                Some((column, text)) if is_number(column) => (column.parse().ok()?, text),
+                _ => (1, rest),
+            };
+            Some(Entry {
+                location: Location {
+                    path: PathBuf::from(path.strip_prefix("./").unwrap_or(path)),
+                    line: number.parse::<usize>().ok()?.saturating_sub(1),
+                    column: column.saturating_sub(1),
+                },
+                message: text.trim().to_string(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,57 @@
+//! Copies of the buffers with unsaved changes, written when a signal stops tte, e.g. when its
+//! terminal is closed. Nobody is there to ask whether to save them then, and dropping them
+//! would lose the work.
+
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    buffer::Buffer,
+    history::{create_private_dir, path_name},
+    paths::get_data_dir,
+};
+
+/// Where the copies go, `recovery` in the data directory
+pub fn dir() -> Option<PathBuf> {
+    get_data_dir().map(|data| data.join("recovery"))
+}
+
+/// Writes the text of `buffers` into `dir`, in a directory only the user can open. Copies are
+/// named after the whole path of their file, or the name of the buffer without one, with a
+/// number added when a copy has the name already. Returns where the copies went.
+pub fn save_copies<'a>(
+    dir: &Path,
+    buffers: impl Iterator<Item = &'a Buffer>,
+) -> io::Result<Vec<PathBuf>> {
+    create_private_dir(dir)?;
+
+    let mut copies = vec![];
+    for buffer in buffers {
+        let name = match &buffer.file_path {
+            Some(path) => path_name(path),
+            None => buffer.file_name().replace(['/', '\\', ':'], "%"),
+        };
+
+        let mut number = 1;
+        let (path, mut file) = loop {
+            let path = match number {
+                1 => dir.join(&name),
+                _ => dir.join(format!("{name}.{number}")),
+            };
+            number += 1;
+            match File::create_new(&path) {
+                Ok(file) => break (path, file),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        };
+
+        file.write_all(buffer.text().as_bytes())?;
+        copies.push(path);
+    }
+
+    Ok(copies)
+}
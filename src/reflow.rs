@@ -0,0 +1,166 @@
+use crate::width::char_width;
+
+/// Comment leaders kept at the start of the lines of a reflowed paragraph of code, the longer
+/// ones first. `*` continues block comments.
+const CODE_LEADERS: [&str; 7] = ["///", "//!", "//", "#", "--", ";", "*"];
+
+/// Leaders kept at the start of the lines of a reflowed paragraph of prose, the quotes of
+/// Markdown and emails
+const PROSE_LEADERS: [&str; 1] = [">"];
+
+/// How the lines of a paragraph start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Leader {
+    /// Length in bytes of the indentation, the comment leader and the spaces after them
+    len: usize,
+    /// Length of the list item marker after it and the spaces after that, e.g. `- ` or `1. `
+    bullet: usize,
+}
+
+/// Rewraps the paragraphs of `lines` so that they fit in `width` columns, when their words do.
+/// Each paragraph keeps the indentation and comment leader of its lines, and list items keep
+/// their marker with the lines after it aligned on their text. Blank lines are kept as they are.
+///
+/// `code` tells whether the lines are code, where comment leaders like `//` or `#` are kept,
+/// rather than prose where `*` marks list items.
+pub fn reflow(lines: &mut Vec<String>, width: usize, tab_width: usize, code: bool) {
+    let mut reflowed = vec![];
+    let mut rest = &lines[..];
+
+    while let Some(first) = rest.first() {
+        if is_blank(first, code) {
+            reflowed.push(first.clone());
+            rest = &rest[1..];
+            continue;
+        }
+
+        let len = paragraph_len(rest, code);
+        reflowed.extend(reflow_paragraph(&rest[..len], width, tab_width, code));
+        rest = &rest[len..];
+    }
+
+    *lines = reflowed;
+}
+
+/// Returns the first and last lines of the paragraph around `line`, none if it is blank
+pub fn paragraph_around<S: AsRef<str>>(
+    lines: &[S],
+    line: usize,
+    code: bool,
+) -> Option<(usize, usize)> {
+    let text = lines.get(line)?.as_ref();
+    if is_blank(text, code) {
+        return None;
+    }
+
+    let mut first = line;
+    while first > 0 && continues(lines[first - 1].as_ref(), lines[first].as_ref(), code) {
+        first -= 1;
+    }
+    let mut last = line;
+    while last + 1 < lines.len() && continues(lines[last].as_ref(), lines[last + 1].as_ref(), code)
+    {
+        last += 1;
+    }
+
+    Some((first, last))
+}
+
+/// Returns how many of the first lines of `lines` are in the paragraph starting them
+fn paragraph_len<S: AsRef<str>>(lines: &[S], code: bool) -> usize {
+    1 + lines
+        .windows(2)
+        .take_while(|pair| continues(pair[0].as_ref(), pair[1].as_ref(), code))
+        .count()
+}
+
+/// Whether `next` continues the paragraph of `line`: it is not blank, has the same comment
+/// leader and does not start another list item
+fn continues(line: &str, next: &str, code: bool) -> bool {
+    let (leader, next_leader) = (leader(line, code), leader(next, code));
+    !is_blank(next, code)
+        && next_leader.bullet == 0
+        && line[..leader.len].trim() == next[..next_leader.len].trim()
+}
+
+/// Whether nothing but a leader is on `line`
+fn is_blank(line: &str, code: bool) -> bool {
+    let leader = leader(line, code);
+    line[leader.len + leader.bullet..].trim().is_empty()
+}
+
+/// Returns how `line` starts, comment leaders are only looked for in `code`
+fn leader(line: &str, code: bool) -> Leader {
+    let leaders: &[&str] = if code { &CODE_LEADERS } else { &PROSE_LEADERS };
+
+    let mut len = line.len() - line.trim_start().len();
+    if let Some(comment) = leaders.iter().find(|l| line[len..].starts_with(**l)) {
+        len += comment.len();
+        len = line.len() - line[len..].trim_start().len();
+    }
+
+    let rest = &line[len..];
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+    let marker = if rest.starts_with("- ")
+        || rest.starts_with("+ ")
+        || (!code && rest.starts_with("* "))
+    {
+        1
+    } else if digits > 0 && (rest[digits..].starts_with(". ") || rest[digits..].starts_with(") ")) {
+        digits + 1
+    } else {
+        0
+    };
+    let bullet = match marker {
+        0 => 0,
+        _ => rest.len() - rest[marker..].trim_start().len(),
+    };
+
+    Leader { len, bullet }
+}
+
+/// Rewraps the lines of a single paragraph to `width` columns
+fn reflow_paragraph(lines: &[String], width: usize, tab_width: usize, code: bool) -> Vec<String> {
+    let first = leader(&lines[0], code);
+    let first_leader = &lines[0][..first.len + first.bullet];
+    // The lines after a list item marker line up with its text
+    let continuation = match lines.get(1) {
+        Some(second) => second[..leader(second, code).len].to_string(),
+        None => lines[0][..first.len].to_string(),
+    };
+    let continuation = if first.bullet > 0 && continuation.len() <= first.len {
+        format!("{}{}", &lines[0][..first.len], " ".repeat(first.bullet))
+    } else {
+        continuation
+    };
+
+    let words = lines.iter().flat_map(|line| {
+        let leader = leader(line, code);
+        line[leader.len + leader.bullet..].split_whitespace()
+    });
+
+    let columns = |text: &str| -> usize {
+        text.chars()
+            .map(|c| if c == '\t' { tab_width } else { char_width(c) })
+            .sum()
+    };
+
+    let mut reflowed = vec![];
+    let mut line = first_leader.to_string();
+    let mut has_words = false;
+    for word in words {
+        if has_words && columns(&line) + 1 + columns(word) > width {
+            reflowed.push(line);
+            line = continuation.clone();
+            has_words = false;
+        }
+        if has_words {
+            line.push(' ');
+        }
+        line.push_str(word);
+        has_words = true;
+    }
+    reflowed.push(line);
+
+    reflowed
+}
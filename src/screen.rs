@@ -0,0 +1,259 @@
+use std::io::{self, Write};
+
+use crossterm::style::Color;
+
+use tte_core::width::char_width;
+
+/// Held by the cell covered by the second half of a wide character
+const WIDE_CONTINUATION: char = '\0';
+
+/// One cell of the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub ch: char,
+    pub fg_color: Color,
+    pub bg_color: Color,
+    pub underlined: bool,
+    pub reversed: bool,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            ch: ' ',
+            fg_color: Color::Reset,
+            bg_color: Color::Reset,
+            underlined: false,
+            reversed: false,
+        }
+    }
+}
+
+/// A terminal screen kept in memory, for drawing a `Display` without a terminal.
+/// The escape sequences written to it are applied to a grid of cells instead of being shown.
+pub struct Screen {
+    pub width: u16,
+    pub height: u16,
+    cells: Vec<Cell>,
+    /// Position of the cursor, where the next character is printed
+    pub cursor: (u16, u16),
+    pub cursor_visible: bool,
+    /// Style of the next printed characters
+    style: Cell,
+    /// Bytes of an escape sequence or character not completely written yet
+    pending: Vec<u8>,
+    /// The operating system commands written to the screen, e.g. `52;c;aGk=` to set the
+    /// clipboard
+    pub commands: Vec<String>,
+    /// How many times the bell was rung
+    pub bells: usize,
+}
+
+impl Screen {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::default(); width as usize * height as usize],
+            cursor: (0, 0),
+            cursor_visible: true,
+            style: Cell::default(),
+            pending: vec![],
+            commands: vec![],
+            bells: 0,
+        }
+    }
+
+    /// Changes the size of the screen, keeping what fits of its content
+    pub fn resize(&mut self, width: u16, height: u16) {
+        let mut cells = vec![Cell::default(); width as usize * height as usize];
+        for y in 0..height.min(self.height) {
+            for x in 0..width.min(self.width) {
+                cells[y as usize * width as usize + x as usize] = *self.cell(x, y);
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+        self.cells = cells;
+    }
+
+    pub fn cell(&self, x: u16, y: u16) -> &Cell {
+        &self.cells[y as usize * self.width as usize + x as usize]
+    }
+
+    /// Returns the characters of row `y`
+    pub fn row(&self, y: u16) -> String {
+        (0..self.width)
+            .map(|x| self.cell(x, y).ch)
+            .filter(|ch| *ch != WIDE_CONTINUATION)
+            .collect()
+    }
+
+    /// Returns the characters of every row, without the spaces at their end
+    pub fn lines(&self) -> Vec<String> {
+        (0..self.height)
+            .map(|y| self.row(y).trim_end().to_string())
+            .collect()
+    }
+
+    fn print(&mut self, ch: char) {
+        let (x, y) = self.cursor;
+        let width = char_width(ch) as u16;
+        // Combining marks are not kept
+        if width == 0 {
+            return;
+        }
+
+        // Lines do not wrap, what goes past the right edge is lost. The cells covered by
+        // the second half of a wide character are left out of the rows.
+        for (i, ch) in [ch, WIDE_CONTINUATION]
+            .into_iter()
+            .take(width as usize)
+            .enumerate()
+        {
+            let x = x.saturating_add(i as u16);
+            if x < self.width && y < self.height {
+                self.cells[y as usize * self.width as usize + x as usize] =
+                    Cell { ch, ..self.style };
+            }
+        }
+        self.cursor.0 = x.saturating_add(width);
+    }
+
+    /// Applies the escape sequence made of `params` and the final character `command`
+    fn apply(&mut self, params: &str, command: char) {
+        match command {
+            'H' => {
+                let mut numbers = params.split(';').map(|n| n.parse::<u16>().unwrap_or(1));
+                let y = numbers.next().unwrap_or(1);
+                let x = numbers.next().unwrap_or(1);
+                self.cursor = (x.saturating_sub(1), y.saturating_sub(1));
+            }
+            'J' if params == "2" => self.cells.fill(Cell::default()),
+            'l' if params == "?25" => self.cursor_visible = false,
+            'h' if params == "?25" => self.cursor_visible = true,
+            'm' => self.apply_style(params),
+            // Cursor styles and the like do not change the cells
+            _ => (),
+        }
+    }
+
+    fn apply_style(&mut self, params: &str) {
+        let numbers: Vec<u8> = params.split(';').filter_map(|n| n.parse().ok()).collect();
+        let mut numbers = numbers.into_iter();
+
+        while let Some(n) = numbers.next() {
+            match n {
+                0 => self.style = Cell::default(),
+                4 => self.style.underlined = true,
+                7 => self.style.reversed = true,
+                24 => self.style.underlined = false,
+                27 => self.style.reversed = false,
+                38 | 48 => {
+                    let color = match numbers.next() {
+                        Some(2) => Color::Rgb {
+                            r: numbers.next().unwrap_or_default(),
+                            g: numbers.next().unwrap_or_default(),
+                            b: numbers.next().unwrap_or_default(),
+                        },
+                        Some(5) => Color::AnsiValue(numbers.next().unwrap_or_default()),
+                        _ => Color::Reset,
+                    };
+                    if n == 38 {
+                        self.style.fg_color = color;
+                    } else {
+                        self.style.bg_color = color;
+                    }
+                }
+                39 => self.style.fg_color = Color::Reset,
+                49 => self.style.bg_color = Color::Reset,
+                _ => (),
+            }
+        }
+    }
+
+    /// Applies the complete sequences and characters of the pending bytes
+    fn flush_pending(&mut self) {
+        // Keep the start of a character cut between two writes for the next one
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        let text = String::from_utf8_lossy(&self.pending[..valid]).into_owned();
+
+        let mut consumed = 0;
+        let mut chars = text.char_indices();
+        while let Some((i, ch)) = chars.next() {
+            if ch == '\x07' {
+                self.bells += 1;
+                consumed = i + 1;
+                continue;
+            }
+            if ch != '\x1b' {
+                self.print(ch);
+                consumed = i + ch.len_utf8();
+                continue;
+            }
+
+            // `ESC [ params command`, stop if the sequence is not complete yet
+            let Some((_, kind)) = chars.next() else {
+                break;
+            };
+
+            // `ESC ] command BEL`, or ended by `ESC \`
+            if kind == ']' {
+                let mut command = String::new();
+                let mut end = None;
+                for (j, c) in chars.by_ref() {
+                    if c == '\x07' {
+                        end = Some(j + 1);
+                        break;
+                    }
+                    if c == '\\' && command.ends_with('\x1b') {
+                        command.pop();
+                        end = Some(j + 1);
+                        break;
+                    }
+                    command.push(c);
+                }
+                let Some(end) = end else {
+                    break;
+                };
+
+                self.commands.push(command);
+                consumed = end;
+                continue;
+            }
+            let mut params = String::new();
+            let mut command = None;
+            for (j, c) in chars.by_ref() {
+                if c.is_ascii_alphabetic() || c == '~' {
+                    command = Some((j, c));
+                    break;
+                }
+                params.push(c);
+            }
+            let Some((j, command)) = command else {
+                break;
+            };
+
+            self.apply(params.trim_end_matches(' '), command);
+            consumed = j + 1;
+        }
+
+        self.pending.drain(..consumed);
+    }
+}
+
+impl Write for Screen {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        self.flush_pending();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
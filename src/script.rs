@@ -0,0 +1,335 @@
+//! Scripts written by the user in Rhai, run as commands, on events and when the editor starts.
+//!
+//! A script runs on a thread of its own and gets to the editor through requests, answered one
+//! at a time by the thread of the editor. The script never holds on to the state of the editor,
+//! so it sees every change it makes, and those of the commands it runs, as soon as it is made.
+
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    path::Path,
+    rc::Rc,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rhai::{Dynamic, Engine, EvalAltResult, Map};
+
+use crate::{command::Command, paths::get_config_dir, position::Position};
+
+/// Name of the directory in the config directory holding the scripts
+pub const SCRIPTS_DIR_NAME: &str = "scripts";
+
+/// Extension of the script files, the file name without it is the name of the command
+pub const SCRIPT_EXTENSION: &str = "rhai";
+
+/// Name of the script run when the editor starts, e.g. to bind keys
+pub const INIT_SCRIPT_NAME: &str = "init";
+
+/// Time a script can run before it is stopped, so a script stuck in a loop does not freeze the
+/// editor
+const MAX_RUN_TIME: Duration = Duration::from_secs(1);
+
+/// Directions the cursor can be moved in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// What a script asks of the editor. Lines and columns are 0 based here, scripts count them
+/// from 1 as the status line does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// `line_count()`
+    LineCount,
+    /// `line(n)`, the text of a line without its line break
+    Line(usize),
+    /// `lines()`, the text of every line
+    Lines,
+    /// `cursor()`, a map with the `line` and `column` of the cursor
+    Cursor,
+    /// `selection()`, the selected text or an empty string
+    Selection,
+    /// `insert(text)` types the text at the cursor, replacing the selection
+    Insert(String),
+    /// `backspace([count])` deletes the characters before the cursor
+    Backspace(usize),
+    /// `delete([count])` deletes the characters after the cursor
+    Delete(usize),
+    /// `up([count])`, `down`, `left` and `right` move the cursor
+    Move(Direction, usize),
+    /// `move_to(line, [column])` moves the cursor
+    Goto(Position),
+    /// `message(text)` or `print(text)` shows the text in the status line
+    Message(String),
+    /// `run(command)` runs a command as entered in the prompt, e.g. `sort` or `!make`
+    Run(String),
+    /// `bind(keys, command)` binds a sequence of keys written as in `[keys]` to a command
+    Bind { keys: String, command: String },
+}
+
+/// What the editor answers to a request
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Done,
+    Number(usize),
+    Text(String),
+    Lines(Vec<String>),
+    Position(Position),
+}
+
+impl Reply {
+    fn into_dynamic(self) -> Dynamic {
+        match self {
+            Self::Done => Dynamic::UNIT,
+            Self::Number(n) => Dynamic::from(n as i64),
+            Self::Text(text) => Dynamic::from(text),
+            Self::Lines(lines) => {
+                Dynamic::from_array(lines.into_iter().map(Dynamic::from).collect())
+            }
+            Self::Position(position) => {
+                let mut map = Map::new();
+                map.insert("line".into(), Dynamic::from(position.line as i64 + 1));
+                map.insert("column".into(), Dynamic::from(position.column as i64 + 1));
+                Dynamic::from_map(map)
+            }
+        }
+    }
+}
+
+/// What scripts leave to the editor once they are done
+#[derive(Debug, Default)]
+pub struct ScriptOutcome {
+    /// Commands of the editor itself, like `wa` or `cd`, run after the script
+    pub commands: Vec<Command>,
+    /// Sequences of keys bound with `bind`, with the command each runs
+    pub keys: Vec<(String, String)>,
+    /// Message for the status line
+    pub message: Option<String>,
+    /// Whether another buffer became the active one
+    pub switched: bool,
+}
+
+/// A command written by the user in Rhai
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub name: String,
+    source: String,
+}
+
+impl Script {
+    /// Checks the syntax of the script, which is compiled again each time it runs
+    pub fn new(name: &str, source: &str) -> Result<Self, String> {
+        Engine::new()
+            .compile(source)
+            .map_err(|e| format!("{name}: {e}"))?;
+
+        Ok(Self {
+            name: name.to_string(),
+            source: source.to_string(),
+        })
+    }
+
+    /// Runs the script, answering its requests with `answer` until it is done
+    pub fn run(
+        &self,
+        mut answer: impl FnMut(Request) -> Result<Reply, String>,
+    ) -> Result<(), String> {
+        let (requests, editor_requests) = mpsc::channel();
+        let (editor_replies, replies) = mpsc::channel();
+
+        thread::scope(|scope| {
+            let script = scope.spawn(move || {
+                let engine = engine(Rc::new(EditorLink {
+                    requests,
+                    replies,
+                    waited: Cell::new(Duration::ZERO),
+                }));
+                engine.run(&self.source).map_err(|e| e.to_string())
+            });
+
+            // The requests stop once the script is done and its engine dropped
+            for request in editor_requests {
+                if editor_replies.send(answer(request)).is_err() {
+                    break;
+                }
+            }
+
+            match script.join() {
+                Ok(result) => result.map_err(|e| format!("{}: {e}", self.name)),
+                Err(_) => Err(format!("{}: the script crashed", self.name)),
+            }
+        })
+    }
+}
+
+/// The end of the script of the link to the editor
+struct EditorLink {
+    requests: Sender<Request>,
+    replies: Receiver<Result<Reply, String>>,
+    /// Time spent waiting for the editor to answer, e.g. while a command the script ran was
+    /// saving, which does not count as the script running
+    waited: Cell<Duration>,
+}
+
+impl EditorLink {
+    fn ask(&self, request: Request) -> Result<Dynamic, Box<EvalAltResult>> {
+        let asked = Instant::now();
+        self.requests
+            .send(request)
+            .map_err(|_| "The editor is gone")?;
+        let reply = self.replies.recv();
+        self.waited.set(self.waited.get() + asked.elapsed());
+
+        match reply {
+            Ok(reply) => Ok(reply?.into_dynamic()),
+            Err(_) => Err("The editor is gone".into()),
+        }
+    }
+}
+
+/// Converts a count given by a script
+fn count(n: i64) -> Result<usize, Box<EvalAltResult>> {
+    usize::try_from(n).map_err(|_| format!("{n} is not a count").into())
+}
+
+/// Converts a line or column given by a script, counted from 1
+fn index(n: i64) -> Result<usize, Box<EvalAltResult>> {
+    match usize::try_from(n) {
+        Ok(n) if n > 0 => Ok(n - 1),
+        _ => Err(format!("{n} is not a line or column, they start at 1").into()),
+    }
+}
+
+/// An engine running scripts with the functions of the editor
+fn engine(editor: Rc<EditorLink>) -> Engine {
+    let mut engine = Engine::new();
+    let started = Instant::now();
+    let link = editor.clone();
+    engine.on_progress(move |_| {
+        let running = started.elapsed().saturating_sub(link.waited.get());
+        (running > MAX_RUN_TIME).then(|| "The script ran for too long".into())
+    });
+
+    let link = editor.clone();
+    engine.register_fn("line_count", move || link.ask(Request::LineCount));
+    let link = editor.clone();
+    engine.register_fn("line", move |n: i64| link.ask(Request::Line(index(n)?)));
+    let link = editor.clone();
+    engine.register_fn("lines", move || link.ask(Request::Lines));
+    let link = editor.clone();
+    engine.register_fn("cursor", move || link.ask(Request::Cursor));
+    let link = editor.clone();
+    engine.register_fn("selection", move || link.ask(Request::Selection));
+
+    let link = editor.clone();
+    engine.register_fn("insert", move |text: &str| {
+        link.ask(Request::Insert(text.to_string()))
+    });
+    let link = editor.clone();
+    engine.register_fn("move_to", move |line: i64| {
+        link.ask(Request::Goto(Position::line_start(index(line)?)))
+    });
+    let link = editor.clone();
+    engine.register_fn("move_to", move |line: i64, column: i64| {
+        link.ask(Request::Goto(Position::new(index(line)?, index(column)?)))
+    });
+
+    type Counted = fn(usize) -> Request;
+    let counted: [(&str, Counted); 6] = [
+        ("backspace", Request::Backspace),
+        ("delete", Request::Delete),
+        ("up", |n| Request::Move(Direction::Up, n)),
+        ("down", |n| Request::Move(Direction::Down, n)),
+        ("left", |n| Request::Move(Direction::Left, n)),
+        ("right", |n| Request::Move(Direction::Right, n)),
+    ];
+    for (name, request) in counted {
+        let link = editor.clone();
+        engine.register_fn(name, move || link.ask(request(1)));
+        let link = editor.clone();
+        engine.register_fn(name, move |n: i64| link.ask(request(count(n)?)));
+    }
+
+    let link = editor.clone();
+    engine.register_fn("message", move |text: &str| {
+        link.ask(Request::Message(text.to_string()))
+    });
+    // Printing would write over the screen, the text goes to the status line instead
+    let link = editor.clone();
+    engine.on_print(move |text| {
+        let _ = link.ask(Request::Message(text.to_string()));
+    });
+    let link = editor.clone();
+    engine.register_fn("run", move |command: &str| {
+        link.ask(Request::Run(command.to_string()))
+    });
+    engine.register_fn("bind", move |keys: &str, command: &str| {
+        editor.ask(Request::Bind {
+            keys: keys.to_string(),
+            command: command.to_string(),
+        })
+    });
+
+    engine
+}
+
+/// Loads the scripts of the config directory by name, along with the errors of those that
+/// could not be loaded. A missing directory means no scripts.
+pub fn load_scripts() -> (HashMap<String, Script>, Vec<String>) {
+    match get_config_dir() {
+        Some(dir) => load_scripts_from(&dir.join(SCRIPTS_DIR_NAME)),
+        None => (HashMap::new(), vec![]),
+    }
+}
+
+/// Loads the scripts in `dir`. A script that cannot be read or compiled does not keep the
+/// others, `init` among them, from loading.
+pub fn load_scripts_from(dir: &Path) -> (HashMap<String, Script>, Vec<String>) {
+    let mut scripts = HashMap::new();
+    let mut errors = vec![];
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return (scripts, errors),
+        Err(e) => {
+            errors.push(format!("{}: {e}", dir.display()));
+            return (scripts, errors);
+        }
+    };
+
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                errors.push(format!("{}: {e}", dir.display()));
+                continue;
+            }
+        };
+        if path.extension().and_then(|e| e.to_str()) != Some(SCRIPT_EXTENSION) {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|n| n.to_str()) else {
+            continue;
+        };
+
+        let script = fs::read_to_string(&path)
+            .map_err(|e| format!("{}: {e}", path.display()))
+            .and_then(|text| Script::new(name, &text));
+        match script {
+            Ok(script) => {
+                scripts.insert(name.to_string(), script);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    // The same errors in the same order each time
+    errors.sort();
+
+    (scripts, errors)
+}
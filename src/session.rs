@@ -0,0 +1,193 @@
+#![allow(dead_code)]
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    buffer::Buffer,
+    json::JsonValue,
+    paths::{get_config_dir, get_data_dir},
+    position::Position,
+};
+
+/// Name of the file in the data directory holding the session
+pub const SESSION_FILE_NAME: &str = "session.json";
+
+/// How many recently opened files the session remembers
+pub const RECENT_FILES: usize = 10;
+
+/// State kept between runs of the editor, per file
+#[derive(Debug, Default)]
+pub struct Session {
+    /// Named marks of each file, by absolute path
+    pub marks: BTreeMap<String, BTreeMap<char, Position>>,
+    /// Absolute paths of the files opened last, the most recent first
+    pub recent: Vec<String>,
+    /// File the session was loaded from and is saved to, none for a session kept in memory
+    file: Option<PathBuf>,
+}
+
+impl Session {
+    pub fn path() -> Option<PathBuf> {
+        get_data_dir().map(|dir| dir.join(SESSION_FILE_NAME))
+    }
+
+    /// Loads the session file, a missing file gives an empty session.
+    /// A session kept in the config directory, where it used to be, is read instead until the
+    /// session is saved to the data directory.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        let read_from = match get_config_dir().map(|dir| dir.join(SESSION_FILE_NAME)) {
+            Some(old) if !path.exists() && old.exists() => old,
+            _ => path.clone(),
+        };
+        let mut session = match fs::read_to_string(&read_from) {
+            Ok(text) => {
+                let json =
+                    JsonValue::parse(&text).map_err(|e| format!("{}: {e}", read_from.display()))?;
+                Self::from_json(&json)
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(format!("{}: {e}", read_from.display())),
+        };
+        session.file = Some(path);
+
+        Ok(session)
+    }
+
+    fn from_json(json: &JsonValue) -> Self {
+        let mut session = Self::default();
+
+        if let Some(recent) = json.get("recent").and_then(JsonValue::as_array) {
+            session.recent = recent
+                .iter()
+                .filter_map(|path| Some(path.as_str()?.to_string()))
+                .take(RECENT_FILES)
+                .collect();
+        }
+
+        let Some(JsonValue::Object(files)) = json.get("files") else {
+            return session;
+        };
+
+        for (file, state) in files {
+            let Some(JsonValue::Object(marks)) = state.get("marks") else {
+                continue;
+            };
+
+            let marks = marks
+                .iter()
+                .filter_map(|(name, position)| {
+                    let name = name.chars().next()?;
+                    let position = position.as_array()?;
+                    let line = position.first()?.as_usize()?;
+                    let column = position.get(1)?.as_usize()?;
+                    Some((name, Position::new(line, column)))
+                })
+                .collect();
+
+            session.marks.insert(file.clone(), marks);
+        }
+
+        session
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let files = self
+            .marks
+            .iter()
+            .filter(|(_, marks)| !marks.is_empty())
+            .map(|(file, marks)| {
+                let marks = marks
+                    .iter()
+                    .map(|(name, position)| {
+                        let position = JsonValue::Array(vec![
+                            JsonValue::Number(position.line as f64),
+                            JsonValue::Number(position.column as f64),
+                        ]);
+                        (name.to_string(), position)
+                    })
+                    .collect();
+                (
+                    file.clone(),
+                    JsonValue::Object(vec![("marks".to_string(), JsonValue::Object(marks))]),
+                )
+            })
+            .collect();
+
+        let recent = self
+            .recent
+            .iter()
+            .map(|path| JsonValue::string(path))
+            .collect();
+
+        JsonValue::object(vec![
+            ("files", JsonValue::Object(files)),
+            ("recent", JsonValue::Array(recent)),
+        ])
+    }
+
+    /// Writes the session to the file it was loaded from, if any
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.file else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, self.to_json().to_string())
+    }
+
+    /// Key of the file of `buffer` in the session, `None` for buffers without a file
+    fn key(buffer: &Buffer) -> Option<String> {
+        let path = buffer.file_path.as_deref()?;
+        let path = fs::canonicalize(path).unwrap_or_else(|_| Path::new(path).to_path_buf());
+        Some(path.to_string_lossy().into_owned())
+    }
+
+    /// Puts the marks saved for the file of `buffer` into it
+    pub fn restore(&self, buffer: &mut Buffer) {
+        let Some(marks) = Self::key(buffer).and_then(|key| self.marks.get(&key)) else {
+            return;
+        };
+
+        for (name, position) in marks {
+            let idx = buffer.position_to_index(*position);
+            buffer.set_named_mark(*name, idx);
+        }
+    }
+
+    /// Remembers the current marks of `buffer`
+    pub fn update(&mut self, buffer: &Buffer) {
+        let Some(key) = Self::key(buffer) else {
+            return;
+        };
+
+        let marks = buffer
+            .named_marks
+            .iter()
+            .map(|(name, idx)| (*name, buffer.index_to_position(*idx)))
+            .collect();
+
+        self.marks.insert(key, marks);
+    }
+
+    /// Puts the file of `buffer` first in the recently opened files
+    pub fn opened(&mut self, buffer: &Buffer) {
+        let Some(key) = Self::key(buffer) else {
+            return;
+        };
+
+        self.recent.retain(|path| *path != key);
+        self.recent.insert(0, key);
+        self.recent.truncate(RECENT_FILES);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{
+    buffer::{Buffer, DEFAULT_TAB_WIDTH, DEFAULT_WRAP_MARKER},
+    completion::DEFAULT_WORD_SEPARATORS,
+    config::{Config, ConfigValue},
+    filetype::FileType,
+};
+
+/// Number of columns paragraphs are reflowed to, unless set otherwise
+pub const DEFAULT_TEXT_WIDTH: usize = 80;
+
+/// Names of the settings, in the config file and for the `set` command
+pub const SETTING_NAMES: [&str; 13] = [
+    "tab_width",
+    "expand_tabs",
+    "format_on_save",
+    "virtual_space",
+    "screen_columns",
+    "smart_home",
+    "keep_bom",
+    "text_width",
+    "wrap",
+    "wrap_marker",
+    "rainbow_brackets",
+    "indent_guides",
+    "word_separators",
+];
+
+/// How a buffer is edited, shown and saved.
+///
+/// The settings of a buffer are resolved from, in order, the `[editor]` section of the config,
+/// the defaults of its file type, the `[editor.<file type>]` section of the config, and what
+/// was set for the buffer with the `set` command. Later ones win.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Number of columns between tab stops
+    pub tab_width: usize,
+    /// Whether the Tab key inserts spaces instead of a tab
+    pub expand_tabs: bool,
+    /// Whether the buffer goes through the formatter of its file type before being saved
+    pub format_on_save: bool,
+    /// Whether the cursor can move past the end of lines
+    pub virtual_space: bool,
+    /// Whether up and down keep to the column on screen rather than counting characters
+    pub screen_columns: bool,
+    /// Whether Home and End stop at the first and last non-blank characters first
+    pub smart_home: bool,
+    /// Whether a byte order mark the file started with is written back on save
+    pub keep_bom: bool,
+    /// Number of columns paragraphs are reflowed to
+    pub text_width: usize,
+    /// Whether lines wider than the view are wrapped on screen rather than scrolled across
+    pub wrap: bool,
+    /// Drawn at the start of the rows a wrapped line goes on over, to tell them from lines
+    pub wrap_marker: String,
+    /// Whether brackets are colored by how deeply they are nested
+    pub rainbow_brackets: bool,
+    /// Whether faint vertical lines are drawn at each tab stop of the indentation
+    pub indent_guides: bool,
+    /// Characters splitting words besides whitespace, e.g. without `-` for `kebab-case` words
+    pub word_separators: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tab_width: DEFAULT_TAB_WIDTH,
+            expand_tabs: true,
+            format_on_save: false,
+            virtual_space: false,
+            screen_columns: true,
+            smart_home: true,
+            keep_bom: true,
+            text_width: DEFAULT_TEXT_WIDTH,
+            wrap: false,
+            wrap_marker: DEFAULT_WRAP_MARKER.to_string(),
+            rainbow_brackets: false,
+            indent_guides: false,
+            word_separators: DEFAULT_WORD_SEPARATORS.to_string(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn for_buffer(config: &Config, buffer: &Buffer) -> Self {
+        let mut settings = Self::default();
+
+        // Set before there was an `[editor]` section
+        if let Some(on_save) = config.get_bool("format", "on_save") {
+            settings.format_on_save = on_save;
+        }
+
+        settings.apply_section(config, "editor");
+        settings.apply_file_type(buffer.file_type);
+        settings.apply_section(config, &format!("editor.{}", buffer.file_type.name()));
+
+        // Those were checked by the `set` command
+        for (name, value) in &buffer.settings {
+            let _ = settings.set(name, value);
+        }
+
+        settings
+    }
+
+    /// Applies the settings found in a section of the config, invalid ones are left out
+    fn apply_section(&mut self, config: &Config, section: &str) {
+        for name in SETTING_NAMES {
+            let value = match config.get(section, name) {
+                Some(ConfigValue::Integer(i)) => i.to_string(),
+                Some(ConfigValue::Boolean(b)) => b.to_string(),
+                Some(ConfigValue::String(s)) => s.clone(),
+                _ => continue,
+            };
+            let _ = self.set(name, &value);
+        }
+    }
+
+    /// Applies the conventions of file types that insist on them
+    fn apply_file_type(&mut self, file_type: FileType) {
+        match file_type {
+            // gofmt indents with tabs
+            FileType::Go => self.expand_tabs = false,
+            FileType::Yaml | FileType::Json => self.tab_width = 2,
+            _ => (),
+        }
+    }
+
+    /// Sets the setting called `name` from its value written as text
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "tab_width" => {
+                self.tab_width = value
+                    .parse()
+                    .ok()
+                    .filter(|width| *width > 0)
+                    .ok_or_else(|| format!("Invalid tab width : {value}"))?;
+            }
+            "expand_tabs" => self.expand_tabs = parse_bool(value)?,
+            "format_on_save" => self.format_on_save = parse_bool(value)?,
+            "virtual_space" => self.virtual_space = parse_bool(value)?,
+            "screen_columns" => self.screen_columns = parse_bool(value)?,
+            "smart_home" => self.smart_home = parse_bool(value)?,
+            "keep_bom" => self.keep_bom = parse_bool(value)?,
+            "text_width" => {
+                self.text_width = value
+                    .parse()
+                    .ok()
+                    .filter(|width| *width > 0)
+                    .ok_or_else(|| format!("Invalid text width : {value}"))?;
+            }
+            "wrap" => self.wrap = parse_bool(value)?,
+            "wrap_marker" => self.wrap_marker = value.to_string(),
+            "rainbow_brackets" => self.rainbow_brackets = parse_bool(value)?,
+            "indent_guides" => self.indent_guides = parse_bool(value)?,
+            "word_separators" => self.word_separators = value.to_string(),
+            _ => return Err(format!("Unknown setting : {name}")),
+        }
+
+        Ok(())
+    }
+
+    /// Returns what a level of indentation is made of, a tab or `tab_width` spaces
+    pub fn indent_unit(&self) -> String {
+        if self.expand_tabs {
+            " ".repeat(self.tab_width)
+        } else {
+            "\t".to_string()
+        }
+    }
+
+    /// Describes the settings, e.g. `tab_width=4 expand_tabs=true`
+    pub fn describe(&self) -> String {
+        format!(
+            "tab_width={} expand_tabs={} format_on_save={} virtual_space={} screen_columns={} smart_home={} keep_bom={} text_width={} wrap={} wrap_marker={:?} rainbow_brackets={} indent_guides={} word_separators={}",
+            self.tab_width,
+            self.expand_tabs,
+            self.format_on_save,
+            self.virtual_space,
+            self.screen_columns,
+            self.smart_home,
+            self.keep_bom,
+            self.text_width,
+            self.wrap,
+            self.wrap_marker,
+            self.rainbow_brackets,
+            self.indent_guides,
+            self.word_separators
+        )
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "on" | "yes" => Ok(true),
+        "false" | "off" | "no" => Ok(false),
+        _ => Err(format!("Expected true or false, not {value}")),
+    }
+}
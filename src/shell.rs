@@ -0,0 +1,93 @@
+use std::{
+    io::{self, Write},
+    process::{Command, ExitStatus, Stdio},
+    thread,
+};
+
+pub use crate::printable::printable;
+
+/// The result of a finished shell command
+pub struct ShellOutput {
+    pub status: ExitStatus,
+    pub stdout: String,
+    /// Only ever shown, so made `printable` already
+    pub stderr: String,
+}
+
+impl ShellOutput {
+    /// stdout followed by stderr, as they would appear in a terminal, made `printable`
+    pub fn combined(&self) -> String {
+        let mut text = printable(&self.stdout);
+        if !self.stderr.is_empty() && !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&self.stderr);
+        text
+    }
+}
+
+/// Runs `command` through the system shell, feeding it `input` on stdin if given.
+/// Blocks until the command exits.
+pub fn run(command: &str, input: Option<&str>) -> io::Result<ShellOutput> {
+    let mut child = shell_command(command)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Write from another thread, a command producing a lot of output before reading
+    // all of its input would otherwise deadlock with us.
+    let writer = match (child.stdin.take(), input) {
+        (Some(mut stdin), Some(input)) => {
+            let input = input.to_string();
+            Some(thread::spawn(move || stdin.write_all(input.as_bytes())))
+        }
+        _ => None,
+    };
+
+    let output = child.wait_with_output()?;
+
+    if let Some(writer) = writer {
+        match writer.join() {
+            // The command may exit without reading everything
+            Ok(Err(e)) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e),
+            _ => (),
+        }
+    }
+
+    Ok(ShellOutput {
+        status: output.status,
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: printable(&String::from_utf8_lossy(&output.stderr)),
+    })
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.args(["/C", command]);
+    cmd
+}
+
+/// Quotes `text` as a single argument for the system shell
+#[cfg(windows)]
+pub fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// Quotes `text` as a single argument for the system shell
+#[cfg(not(windows))]
+pub fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", command]);
+    cmd
+}
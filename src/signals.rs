@@ -0,0 +1,45 @@
+//! Quitting cleanly when the process is told to stop, e.g. by `kill` or when the terminal is
+//! closed, so that the terminal is not left in raw mode.
+//!
+//! Only an atomic flag is set from the signal handler, which is safe there. The main loop sees
+//! it and quits the usual way, restoring the terminal as it goes.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+mod unix {
+    use std::os::raw::c_int;
+
+    // The same on Linux, macOS and the BSDs
+    pub const SIGHUP: c_int = 1;
+    pub const SIGINT: c_int = 2;
+    pub const SIGQUIT: c_int = 3;
+    pub const SIGTERM: c_int = 15;
+
+    extern "C" {
+        pub fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    }
+
+    pub extern "C" fn on_signal(_: c_int) {
+        super::STOP_REQUESTED.store(true, super::Ordering::SeqCst);
+    }
+}
+
+/// Catches the signals asking the process to stop. In raw mode `Ctrl + C` is a key press rather
+/// than a signal, they come from outside.
+pub fn install() {
+    #[cfg(unix)]
+    for signum in [unix::SIGHUP, unix::SIGINT, unix::SIGQUIT, unix::SIGTERM] {
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+        unsafe {
+            unix::signal(signum, unix::on_signal);
+        }
+    }
+}
+
+/// Whether a signal asked the process to stop since it started
+pub fn stop_requested() -> bool {
+    STOP_REQUESTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,62 @@
+use std::cmp::Ordering;
+
+/// Sorts `lines` in natural order, so that `item2` comes before `item10`
+pub fn sort_lines(lines: &mut [String], descending: bool) {
+    lines.sort_by(|a, b| {
+        let ordering = natural_cmp(a, b);
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+/// Compares two strings, taking runs of digits as whole numbers
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_chunks, mut b_chunks) = (chunks(a), chunks(b));
+
+    loop {
+        match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_chunk), Some(b_chunk)) => {
+                let ordering = match (number(a_chunk), number(b_chunk)) {
+                    // Without the leading zeros, a longer number is a bigger one
+                    (Some(a_digits), Some(b_digits)) => a_digits
+                        .len()
+                        .cmp(&b_digits.len())
+                        .then_with(|| a_digits.cmp(b_digits)),
+                    _ => a_chunk.cmp(b_chunk),
+                };
+
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+/// Splits `text` into runs of digits and runs of everything else
+fn chunks(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != first.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (chunk, remaining) = rest.split_at(end);
+        rest = remaining;
+        Some(chunk)
+    })
+}
+
+/// Returns the digits of `chunk` without the leading zeros, if it is a number
+fn number(chunk: &str) -> Option<&str> {
+    chunk
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then(|| chunk.trim_start_matches('0'))
+}
@@ -0,0 +1,226 @@
+use crossterm::style::Color;
+
+use crate::buffer::{Buffer, Line};
+
+/// How a piece of text is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub fg_color: Color,
+    pub bg_color: Color,
+    pub underlined: bool,
+}
+
+impl Style {
+    pub const fn new(fg_color: Color, bg_color: Color) -> Self {
+        Self {
+            fg_color,
+            bg_color,
+            underlined: false,
+        }
+    }
+
+    /// Paints over the style with `paint`
+    fn paint(&mut self, paint: Paint) {
+        match paint {
+            Paint::Foreground(color) => self.fg_color = color,
+            Paint::Background(color) => self.bg_color = color,
+            Paint::Underlined(color) => {
+                self.fg_color = color;
+                self.underlined = true;
+            }
+        }
+    }
+}
+
+/// What a layer changes of the style of the text it covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Paint {
+    Foreground(Color),
+    Background(Color),
+    /// Colored and underlined, e.g. for problems in the text
+    Underlined(Color),
+}
+
+/// A source of styles over the text of a buffer, e.g. syntax highlighting or the selection.
+/// Layers are painted one over the other, so a later layer wins where they overlap.
+pub struct Layer {
+    /// `[start, end)` ranges of indexes into the buffer with what they paint, sorted by start
+    ranges: Vec<(usize, usize, Paint)>,
+}
+
+impl Layer {
+    pub fn new(mut ranges: Vec<(usize, usize, Paint)>) -> Self {
+        ranges.sort_by_key(|(start, _, _)| *start);
+        Self { ranges }
+    }
+
+    /// A layer painting all of `ranges` the same
+    pub fn uniform(ranges: impl IntoIterator<Item = (usize, usize)>, paint: Paint) -> Self {
+        Self::new(
+            ranges
+                .into_iter()
+                .map(|(start, end)| (start, end, paint))
+                .collect(),
+        )
+    }
+
+    /// What the layer paints at `idx`. Of nested ranges, the innermost wins.
+    fn paint_at(&self, idx: usize) -> Option<Paint> {
+        let after = self.ranges.partition_point(|(start, _, _)| *start <= idx);
+        self.ranges[..after]
+            .iter()
+            .rev()
+            .find(|(_, end, _)| idx < *end)
+            .map(|(_, _, paint)| *paint)
+    }
+}
+
+/// A piece of a line drawn in a single style
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+    /// Columns the text takes on screen
+    pub width: usize,
+}
+
+/// Splits the part of `line` in the view of `buffer` into spans of the same style, starting
+/// from `base` and painted with each of `layers` in turn. `segment` is which of the rows of
+/// a wrapped line it is for, the text on it starts that many view widths into the line.
+/// Tabs are given as spaces up to the next tab stop, and so are wide characters cut by an edge
+/// of the view.
+pub fn line_spans(
+    buffer: &Buffer,
+    line: usize,
+    segment: usize,
+    layers: &[Layer],
+    base: Style,
+) -> Vec<Span> {
+    let view_start = buffer.offset_x + buffer.row_start_column(segment);
+    let view_end = view_start + buffer.text_width() - buffer.row_marker_width(segment);
+    // Long lines are gone through from the chunk the view starts in
+    let (from, mut column) = buffer.chunk_start(line, |_, column| column <= view_start);
+    let Line { end, .. } = buffer.lines[line];
+    // The only line of an empty buffer has no characters
+    let data = buffer.data.get(from..=end).unwrap_or(&[]);
+
+    let mut spans: Vec<Span> = vec![];
+    // Whether the start of the current grapheme cluster was drawn whole
+    let mut cluster_drawn = column == view_start;
+    for (i, ch) in data.iter().enumerate() {
+        if *ch == '\n' {
+            continue;
+        }
+
+        // Columns of the character within the view, a tab can be partly scrolled out
+        let first = column.max(view_start);
+        let width = buffer.width_at(from + i, column);
+        column += width;
+        let cells = if width == 0 {
+            // Drawn along with the start of its cluster
+            if !cluster_drawn {
+                continue;
+            }
+            0
+        } else {
+            if column <= view_start {
+                cluster_drawn = false;
+                continue;
+            }
+            if first >= view_end {
+                break;
+            }
+            let cells = column.min(view_end) - first;
+            cluster_drawn = cells == width;
+            cells
+        };
+
+        let mut style = base;
+        for paint in layers.iter().filter_map(|layer| layer.paint_at(from + i)) {
+            style.paint(paint);
+        }
+
+        let span = match spans.last_mut() {
+            Some(span) if span.style == style => span,
+            _ => {
+                spans.push(Span {
+                    text: String::new(),
+                    style,
+                    width: 0,
+                });
+                spans.last_mut().unwrap()
+            }
+        };
+        if *ch == '\t' || cells < width {
+            (0..cells).for_each(|_| span.text.push(' '));
+        } else {
+            span.text.push(*ch);
+        }
+        span.width += cells;
+    }
+
+    spans
+}
+
+/// Draws indent guides in `color` over the spaces of `spans` at the given columns of the view.
+/// Spans ending before the last guide, like those of a blank line inside a block, are padded
+/// with spaces in the `base` style first.
+///
+/// The guides are within the indentation, where every character takes a single column.
+pub fn add_guides(mut spans: Vec<Span>, columns: &[usize], color: Color, base: Style) -> Vec<Span> {
+    let Some(last) = columns.iter().max() else {
+        return spans;
+    };
+
+    let width: usize = spans.iter().map(|span| span.width).sum();
+    if width <= *last {
+        spans.push(Span {
+            text: " ".repeat(last + 1 - width),
+            style: base,
+            width: last + 1 - width,
+        });
+    }
+
+    let mut guided = vec![];
+    let mut column = 0;
+    for span in spans {
+        let end = column + span.width;
+        if !columns.iter().any(|c| (column..end).contains(c)) {
+            column = end;
+            guided.push(span);
+            continue;
+        }
+
+        let mut piece = Span {
+            text: String::new(),
+            style: span.style,
+            width: 0,
+        };
+        for ch in span.text.chars() {
+            if ch == ' ' && columns.contains(&column) {
+                if piece.width > 0 {
+                    guided.push(piece.clone());
+                    piece.text.clear();
+                    piece.width = 0;
+                }
+                guided.push(Span {
+                    text: "│".to_string(),
+                    style: Style {
+                        fg_color: color,
+                        ..span.style
+                    },
+                    width: 1,
+                });
+            } else {
+                piece.text.push(ch);
+                piece.width += 1;
+            }
+            column += 1;
+        }
+        if piece.width > 0 {
+            guided.push(piece);
+        }
+    }
+
+    guided
+}
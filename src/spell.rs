@@ -0,0 +1,285 @@
+#![allow(dead_code)]
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{buffer::Buffer, paths::get_config_dir};
+
+/// Places where a system word list is usually installed
+const SYSTEM_DICTIONARIES: [&str; 3] = [
+    "/usr/share/dict/words",
+    "/usr/share/dict/american-english",
+    "/usr/share/dict/british-english",
+];
+
+/// Name of the file in the config directory holding the words added by the user
+pub const PERSONAL_DICTIONARY_NAME: &str = "personal_dictionary.txt";
+
+/// Maximum number of suggestions offered for a misspelled word
+const MAX_SUGGESTIONS: usize = 10;
+
+/// Returns true if `ch` can be a part of a word for spell checking
+fn is_spell_char(ch: char) -> bool {
+    ch.is_alphabetic() || ch == '\''
+}
+
+/// A set of known words, made of a word list and the user's personal dictionary
+pub struct Dictionary {
+    words: HashSet<String>,
+    personal: HashSet<String>,
+    personal_path: Option<PathBuf>,
+}
+
+impl Dictionary {
+    /// Loads the word list at `path`, or the first system dictionary found if `path` is `None`
+    pub fn load(path: Option<&Path>) -> io::Result<Self> {
+        let text = match path {
+            Some(path) => fs::read_to_string(path)?,
+            None => SYSTEM_DICTIONARIES
+                .iter()
+                .find_map(|path| fs::read_to_string(path).ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No dictionary found"))?,
+        };
+
+        let personal_path = get_config_dir().map(|dir| dir.join(PERSONAL_DICTIONARY_NAME));
+        let personal = personal_path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| parse_words(&text))
+            .unwrap_or_default();
+
+        Ok(Self {
+            words: parse_words(&text),
+            personal,
+            personal_path,
+        })
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        let word = word.trim_matches('\'');
+        if word.is_empty() {
+            return true;
+        }
+
+        let lower = word.to_lowercase();
+        self.words.contains(word)
+            || self.words.contains(&lower)
+            || self.personal.contains(word)
+            || self.personal.contains(&lower)
+            // Possessives, e.g. "editor's"
+            || lower
+                .strip_suffix("'s")
+                .is_some_and(|stem| self.words.contains(stem) || self.personal.contains(stem))
+    }
+
+    /// Adds `word` to the personal dictionary, and saves it in the config directory
+    pub fn add_to_personal(&mut self, word: &str) -> io::Result<()> {
+        let word = word.trim_matches('\'').to_lowercase();
+        if !self.personal.insert(word.clone()) {
+            return Ok(());
+        }
+
+        let Some(path) = &self.personal_path else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No config directory for the personal dictionary",
+            ));
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{word}")
+    }
+
+    /// Returns the `[start, end)` ranges in `data` of every unknown word
+    pub fn misspelled_ranges(&self, data: &[char]) -> Vec<(usize, usize)> {
+        word_ranges(data)
+            .into_iter()
+            .filter(|(start, end)| {
+                let word: String = data[*start..*end].iter().collect();
+                !self.contains(&word)
+            })
+            .collect()
+    }
+
+    /// Returns known words close to `word`, closest first
+    pub fn suggestions(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let length = lower.chars().count();
+        let target: Vec<char> = lower.chars().collect();
+
+        let mut candidates: Vec<(usize, &String)> = self
+            .words
+            .iter()
+            .chain(self.personal.iter())
+            .filter(|candidate| candidate.chars().count().abs_diff(length) <= 2)
+            .filter_map(|candidate| {
+                let chars: Vec<char> = candidate.to_lowercase().chars().collect();
+                let distance = edit_distance(&target, &chars);
+                (distance <= 2).then_some((distance, candidate))
+            })
+            .collect();
+
+        candidates.sort();
+        candidates.dedup_by(|a, b| a.1.eq_ignore_ascii_case(b.1));
+
+        candidates
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, candidate)| match_case(word, candidate))
+            .collect()
+    }
+}
+
+fn parse_words(text: &str) -> HashSet<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Returns the `[start, end)` ranges of the words in `data`
+pub fn word_ranges(data: &[char]) -> Vec<(usize, usize)> {
+    let mut ranges = vec![];
+    let mut start = None;
+
+    for (i, ch) in data.iter().enumerate() {
+        match (is_spell_char(*ch), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                ranges.push((s, i));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    if let Some(s) = start {
+        ranges.push((s, data.len()));
+    }
+
+    // Words glued to digits or underscores are most likely identifiers, not prose
+    ranges
+        .into_iter()
+        .filter(|(s, e)| {
+            let glued = |ch: Option<&char>| ch.is_some_and(|c| c.is_numeric() || *c == '_');
+            !glued(s.checked_sub(1).and_then(|i| data.get(i))) && !glued(data.get(*e))
+        })
+        .collect()
+}
+
+/// Optimal string alignment distance between `a` and `b`
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+
+            rows[i][j] = best;
+        }
+    }
+
+    rows[a.len()][b.len()]
+}
+
+/// Applies the capitalization of `original` to `suggestion`
+fn match_case(original: &str, suggestion: &str) -> String {
+    let letters = original.chars().filter(|c| c.is_alphabetic());
+    if original.chars().count() > 1 && letters.clone().all(|c| c.is_uppercase()) {
+        suggestion.to_uppercase()
+    } else if original.chars().next().is_some_and(|c| c.is_uppercase()) {
+        let mut chars = suggestion.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    } else {
+        suggestion.to_string()
+    }
+}
+
+/// Cycles through the suggestions for a misspelled word, replacing it in place
+pub struct SuggestionCycle {
+    /// Index in the buffer where the word starts
+    pub start: usize,
+    /// The word as it was before any replacement
+    pub original: String,
+    pub suggestions: Vec<String>,
+    /// Index of the suggestion currently in the buffer, `None` if it still holds `original`
+    pub current: Option<usize>,
+}
+
+impl SuggestionCycle {
+    /// Starts cycling for the misspelled word under the cursor, if any
+    pub fn new(buffer: &Buffer, dictionary: &Dictionary) -> Option<Self> {
+        let (start, end) = buffer
+            .misspelled
+            .iter()
+            .find(|(start, end)| *start <= buffer.cursor_pos && buffer.cursor_pos <= *end)
+            .copied()?;
+
+        let original: String = buffer.data[start..end].iter().collect();
+        let suggestions = dictionary.suggestions(&original);
+
+        Some(Self {
+            start,
+            original,
+            suggestions,
+            current: None,
+        })
+    }
+
+    /// The text currently in the buffer for this word
+    fn current_text(&self) -> &str {
+        match self.current {
+            Some(i) => &self.suggestions[i],
+            None => &self.original,
+        }
+    }
+
+    /// Returns true if the buffer still holds the word this cycle last put there
+    pub fn is_current(&self, buffer: &Buffer) -> bool {
+        let text: Vec<char> = self.current_text().chars().collect();
+        buffer.data.get(self.start..self.start + text.len()) == Some(&text[..])
+            && buffer.cursor_pos >= self.start
+            && buffer.cursor_pos <= self.start + text.len()
+    }
+
+    /// Replaces the word with the next suggestion, going back to the original after the last one.
+    /// Returns the text now in the buffer.
+    pub fn next(&mut self, buffer: &mut Buffer) -> String {
+        let old_len = self.current_text().chars().count();
+
+        self.current = match self.current {
+            None if !self.suggestions.is_empty() => Some(0),
+            Some(i) if i + 1 < self.suggestions.len() => Some(i + 1),
+            _ => None,
+        };
+
+        let text = self.current_text().to_string();
+        buffer.replace_range(self.start, self.start + old_len, &text);
+        text
+    }
+}
@@ -0,0 +1,216 @@
+use std::mem;
+
+use crate::{
+    buffer::Buffer,
+    diff::{self, Hunk},
+    position::Position,
+};
+
+/// Two buffers side by side: the active buffer in one pane and `other` in the other one.
+/// When comparing, the changed lines of both sides are marked and they scroll together.
+pub struct Split {
+    /// The buffer of the inactive pane
+    pub other: Buffer,
+    /// Whether the active buffer is in the right pane
+    pub active_is_right: bool,
+    /// Compare the two buffers and keep them scrolled to matching lines
+    pub diff_mode: bool,
+    /// Changes from the left buffer to the right buffer
+    hunks: Vec<Hunk>,
+    /// Versions of the `(left, right)` buffers the hunks were computed for
+    diff_versions: Option<(usize, usize)>,
+}
+
+impl Split {
+    pub fn new(other: Buffer, active_is_right: bool, diff_mode: bool) -> Self {
+        Self {
+            other,
+            active_is_right,
+            diff_mode,
+            hunks: vec![],
+            diff_versions: None,
+        }
+    }
+
+    /// Places the two panes side by side in the given area, with a column between them
+    pub fn layout(&mut self, active: &mut Buffer, x: u16, y: u16, width: usize, height: usize) {
+        // Panes keep at least a column each, even if the terminal is too narrow for them
+        let left_width = (width.saturating_sub(1) / 2).max(1);
+        let right_x = x + left_width as u16 + 1;
+        let right_width = width.saturating_sub(left_width + 1).max(1);
+
+        let (left, right) = if self.active_is_right {
+            (&mut self.other, active)
+        } else {
+            (active, &mut self.other)
+        };
+
+        left.move_to(x, y);
+        left.resize(left_width, height);
+        right.move_to(right_x, y);
+        right.resize(right_width, height);
+    }
+
+    /// Column of the separator between the panes
+    pub fn separator_x(&self, active: &Buffer) -> u16 {
+        let left = if self.active_is_right {
+            &self.other
+        } else {
+            active
+        };
+        left.x + left.width as u16
+    }
+
+    /// Makes the other pane the active one
+    pub fn switch(&mut self, active: &mut Buffer) {
+        mem::swap(active, &mut self.other);
+        // Those follow the cursor, which is now in the other pane
+        self.other.occurrences.clear();
+        self.active_is_right = !self.active_is_right;
+    }
+
+    fn sides<'a>(&'a mut self, active: &'a mut Buffer) -> (&'a mut Buffer, &'a mut Buffer) {
+        if self.active_is_right {
+            (&mut self.other, active)
+        } else {
+            (active, &mut self.other)
+        }
+    }
+
+    /// Compares the buffers again if either changed, and marks their changed lines
+    pub fn update_diff(&mut self, active: &mut Buffer) {
+        if !self.diff_mode {
+            return;
+        }
+
+        let (left, right) = self.sides(active);
+        let versions = (left.version, right.version);
+        if self.diff_versions == Some(versions) {
+            return;
+        }
+
+        let (left, right) = self.sides(active);
+        let (left_text, right_text) = (left.text(), right.text());
+
+        let old: Vec<&str> = left_text.lines().collect();
+        let new: Vec<&str> = right_text.lines().collect();
+        let hunks = diff::diff_lines(&old, &new);
+
+        let (left, right) = self.sides(active);
+        left.changed_lines = hunks.iter().map(|h| (h.old_start, h.old_end)).collect();
+        right.changed_lines = hunks.iter().map(|h| (h.new_start, h.new_end)).collect();
+
+        self.hunks = hunks;
+        self.diff_versions = Some(versions);
+    }
+
+    /// Scrolls the other pane to the line matching the top line of the active pane
+    pub fn sync_scroll(&mut self, active: &Buffer) {
+        if !self.diff_mode {
+            return;
+        }
+
+        let hunks = if self.active_is_right {
+            invert(&self.hunks)
+        } else {
+            self.hunks.clone()
+        };
+
+        self.other.offset_y = diff::map_line(&hunks, active.offset_y);
+        self.other.offset_x = active.offset_x;
+    }
+
+    /// Copies the change under the cursor of the active pane to the other pane if `put`,
+    /// otherwise replaces it with the matching lines of the other pane
+    pub fn copy_hunk(&mut self, active: &mut Buffer, put: bool) -> Result<(), String> {
+        if !self.diff_mode {
+            return Err("Not comparing buffers".to_string());
+        }
+
+        let line = active.current_line();
+        let hunks = if self.active_is_right {
+            invert(&self.hunks)
+        } else {
+            self.hunks.clone()
+        };
+
+        // With the hunks seen from the active side, `old` is the active buffer
+        let hunk = hunks
+            .iter()
+            .find(|h| (h.old_start <= line && line < h.old_end) || h.old_start == line)
+            .copied()
+            .ok_or_else(|| "No change under the cursor".to_string())?;
+
+        let (source, target, source_lines, target_lines) = if put {
+            (
+                &*active,
+                &mut self.other,
+                (hunk.old_start, hunk.old_end),
+                (hunk.new_start, hunk.new_end),
+            )
+        } else {
+            (
+                &self.other,
+                &mut *active,
+                (hunk.new_start, hunk.new_end),
+                (hunk.old_start, hunk.old_end),
+            )
+        };
+
+        let (start, end) = line_span(source, source_lines);
+        let mut text: String = source.data[start..end].iter().collect();
+        let (mut target_start, target_end) = line_span(target, target_lines);
+
+        // The last line of a buffer has no newline, keep the line structure of the target
+        if target_end < target.data.len() {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+        } else if target.data.last() != Some(&'\n') {
+            if text.ends_with('\n') {
+                text.pop();
+            } else if text.is_empty() && target_start > 0 {
+                // Removing the last lines, so the newline before them goes too
+                target_start -= 1;
+            }
+        }
+
+        target.replace_range(target_start, target_end, &text);
+        target.commit_history();
+
+        if !put {
+            // The replaced lines may have been the last ones, where the cursor could be left
+            // past the end of the buffer
+            let line = hunk.old_start.min(active.line_count() - 1);
+            active.move_cursor_to_position(Position::line_start(line));
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the `[start, end)` range in `data` of the lines `[first, last)` of `buffer`
+fn line_span(buffer: &Buffer, (first, last): (usize, usize)) -> (usize, usize) {
+    let start = buffer
+        .lines
+        .get(first)
+        .map_or(buffer.data.len(), |l| l.start);
+    let end = buffer
+        .lines
+        .get(last)
+        .map_or(buffer.data.len(), |l| l.start);
+    (start, end.max(start))
+}
+
+/// The same changes, going from the new text to the old one
+fn invert(hunks: &[Hunk]) -> Vec<Hunk> {
+    hunks
+        .iter()
+        .map(|h| Hunk {
+            old_start: h.new_start,
+            old_end: h.new_end,
+            new_start: h.old_start,
+            new_end: h.old_end,
+        })
+        .collect()
+}
@@ -0,0 +1,104 @@
+use crossterm::style::Color;
+
+/*
+    Color theme default
+    :root {
+        --start-title-color: rgb(255, 210, 85); /* Name and version (warm yellow) */
+        --start-fg-color: rgb(150, 150, 150);   /* Everything else (gray) */
+    }
+*/
+
+/// Keybinds shown on the start screen
+const HINTS: [(&str, &str); 6] = [
+    ("Ctrl + o", "Open a file"),
+    ("Ctrl + s", "Save"),
+    ("Ctrl + p", "Run a command"),
+    ("Ctrl + z / Ctrl + y", "Undo / Redo"),
+    ("Alt + Enter", "Open the link or path under the cursor"),
+    ("Ctrl + q", "Quit"),
+];
+
+/// Shown over the empty buffer opened when tte starts without a file, until something is typed
+pub struct StartScreen {
+    /// Absolute paths of the files opened last, the most recent first
+    pub recent: Vec<String>,
+    /// Color of the name and version
+    pub title_color: Color,
+    /// Color of the other lines
+    pub fg_color: Color,
+}
+
+impl StartScreen {
+    pub fn new(recent: &[String]) -> Self {
+        Self {
+            recent: recent.to_vec(),
+            title_color: Color::Rgb {
+                r: 255,
+                g: 210,
+                b: 85,
+            },
+            fg_color: Color::Rgb {
+                r: 150,
+                g: 150,
+                b: 150,
+            },
+        }
+    }
+
+    /// Returns the lines of the start screen, the first one being the title
+    pub fn get_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("tte {}", env!("CARGO_PKG_VERSION")),
+            "A bare minimum terminal text editor".to_string(),
+            String::new(),
+        ];
+
+        if !self.recent.is_empty() {
+            lines.push("Recent files".to_string());
+            lines.extend(self.recent.iter().map(|path| format!("  {path}")));
+            lines.push(String::new());
+        }
+
+        let keys_width = HINTS.iter().map(|(keys, _)| keys.len()).max().unwrap_or(0);
+        lines.extend(
+            HINTS
+                .iter()
+                .map(|(keys, hint)| format!("{keys:keys_width$}  {hint}")),
+        );
+        lines.push(String::new());
+        lines.push("Start typing to edit a new file".to_string());
+
+        lines
+    }
+
+    /// Returns the rows to draw in a view of `width` x `height`, each exactly `width` wide.
+    /// The lines are centered as a block, what does not fit is cut.
+    pub fn get_rows(&self, width: usize, height: usize) -> Vec<String> {
+        let lines = self.get_lines();
+        let block_width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let left = width.saturating_sub(block_width) / 2;
+        let top = height.saturating_sub(lines.len()) / 2;
+
+        (0..height)
+            .map(|row| {
+                let line = row
+                    .checked_sub(top)
+                    .and_then(|i| lines.get(i))
+                    .map_or("", String::as_str);
+                let text: String = " "
+                    .repeat(left)
+                    .chars()
+                    .chain(line.chars())
+                    .take(width)
+                    .collect();
+                format!("{text:width$}")
+            })
+            .collect()
+    }
+
+    /// Row of the title in a view of `height` rows, if it is shown
+    pub fn title_row(&self, height: usize) -> Option<usize> {
+        let top = height.saturating_sub(self.get_lines().len()) / 2;
+        (top < height).then_some(top)
+    }
+}
@@ -20,8 +20,25 @@ pub struct StatusLine {
     pub width: usize,
     /// The height of the status line
     pub height: usize,
+    /// Input waiting for more keys, shown before the file name, e.g. `3×` for a count typed
+    /// before a command or `ctrl+k …` for the start of a key sequence
+    pub pending: Option<String>,
     /// Name of current active file
     pub filename: String,
+    /// Message shown after the file name, e.g. errors or diagnostics
+    pub message: Option<String>,
+    /// Word count shown at the right end, if enabled and there is room for it
+    pub counts: Option<String>,
+    /// Working directory shown before the counts, if enabled
+    pub directory: Option<String>,
+    /// How long the last frame took to draw, shown first at the right if enabled
+    pub frame_time: Option<String>,
+    /// Number of open buffers, shown before the directory if enabled
+    pub buffers: Option<String>,
+    /// Time of day shown at the right end, if enabled
+    pub clock: Option<String>,
+    /// Whether text cut short ends with `...` rather than an ellipsis, for screen readers
+    pub plain: bool,
     /// Background color
     pub bg_color: Color,
     /// Foreground color
@@ -35,7 +52,15 @@ impl StatusLine {
             y,
             width,
             height,
+            pending: None,
             filename: filename.to_string(),
+            message: None,
+            counts: None,
+            directory: None,
+            frame_time: None,
+            buffers: None,
+            clock: None,
+            plain: false,
             bg_color: Color::Rgb { r: 40, g: 40, b: 40 },
             fg_color: Color::Rgb { r: 210, g: 210, b: 210 },
         }
@@ -51,21 +76,107 @@ impl StatusLine {
         self.height = h;
     }
 
+    /// Returns the text of the status line, exactly `width` characters long.
+    /// When space is tight the frame time goes first, then the buffer count, the counts, the
+    /// directory and the clock, then the message is shortened, and the file name is cut last.
+    /// The pending input is always shown, it is what the next key press goes on from.
     pub fn get_text(&self) -> String {
         let padding = 1;
-        let content_width = self.filename.len();
+
+        // Too narrow to show anything between the paddings
+        if self.width <= 2 * padding {
+            return " ".repeat(self.width);
+        }
+        let room = self.width - 2 * padding;
+
+        let ellipsis = if self.plain { "..." } else { "…" };
+        let truncate = |text: &str, width: usize| truncate(text, width, ellipsis);
+
+        let mut content = match &self.pending {
+            Some(pending) => {
+                let pending = truncate(pending, room);
+                match room.saturating_sub(pending.chars().count() + 2) {
+                    0 => pending,
+                    left => format!("{pending}  {}", truncate(&self.filename, left)),
+                }
+            }
+            None => truncate(&self.filename, room),
+        };
+
+        if let Some(message) = &self.message {
+            let separator = " | ";
+            let left = room.saturating_sub(content.chars().count() + separator.len());
+            if left > 0 {
+                content.push_str(separator);
+                content.push_str(&truncate(message, left));
+            }
+        }
+
+        let content_width = content.chars().count();
+
+        // The segments at the right end in the order they are shown, with how much they
+        // matter: those mattering more are kept first, each if there is still room for it
+        let segments = [
+            (&self.frame_time, 0),
+            (&self.buffers, 1),
+            (&self.directory, 3),
+            (&self.counts, 2),
+            (&self.clock, 4),
+        ];
+        let mut by_importance: Vec<usize> = (0..segments.len()).collect();
+        by_importance.sort_by_key(|i| std::cmp::Reverse(segments[*i].1));
+
+        let join = |kept: &[bool]| {
+            let shown: Vec<&str> = segments
+                .iter()
+                .zip(kept)
+                .filter(|(_, kept)| **kept)
+                .filter_map(|((segment, _), _)| segment.as_deref())
+                .collect();
+            shown.join("  ")
+        };
+        let mut kept = [false; 5];
+        for i in by_importance {
+            kept[i] = segments[i].0.is_some();
+            // Leave at least a space between the content and what is at the right end
+            if content_width + 1 + join(&kept).chars().count() > room {
+                kept[i] = false;
+            }
+        }
+        let right = Some(join(&kept)).filter(|right| !right.is_empty());
+        let right_width = right.as_ref().map_or(0, |right| right.chars().count());
 
         let mut line = String::with_capacity(self.width);
         line.push(' ');
-        
-        line.push_str(&self.filename);
 
-        for _ in 0..(self.width - padding - content_width - padding) {
+        line.push_str(&content);
+
+        for _ in 0..(room - content_width - right_width) {
             line.push(' ');
         }
 
+        if let Some(right) = right {
+            line.push_str(&right);
+        }
+
         line.push(' ');
 
         line
     }
-}
\ No newline at end of file
+}
+
+/// Cuts `text` down to at most `width` characters, ending it with `ellipsis` if it was cut and
+/// there is room for it
+fn truncate(text: &str, width: usize, ellipsis: &str) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+
+    let ellipsis_width = ellipsis.chars().count();
+    if width < ellipsis_width {
+        return text.chars().take(width).collect();
+    }
+    let mut cut: String = text.chars().take(width - ellipsis_width).collect();
+    cut.push_str(ellipsis);
+    cut
+}
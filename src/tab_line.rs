@@ -0,0 +1,120 @@
+use crossterm::style::Color;
+
+/*
+    Color theme default
+    :root {
+        --tab-bg-color: rgb(40, 40, 40);          /* Tab line Background */
+        --tab-fg-color: rgb(150, 150, 150);       /* Other buffers (gray) */
+        --tab-active-bg-color: rgb(255, 210, 85); /* Active buffer (warm yellow) */
+        --tab-active-fg-color: rgb(30, 30, 30);   /* Active buffer text */
+    }
+*/
+
+/// One buffer in the tab line
+pub struct Tab {
+    /// Id of the buffer
+    pub id: usize,
+    pub name: String,
+    /// Whether the buffer has unsaved changes
+    pub modified: bool,
+}
+
+impl Tab {
+    fn text(&self) -> String {
+        if self.modified {
+            format!(" {} + ", self.name)
+        } else {
+            format!(" {} ", self.name)
+        }
+    }
+}
+
+/// The row at the top listing the open buffers, shown when there is more than one
+pub struct TabLine {
+    /// The y position of the line
+    pub y: u16,
+    /// The width of the tab line
+    pub width: usize,
+    /// Buffers in the order they were opened
+    pub tabs: Vec<Tab>,
+    /// Index of the active buffer in `tabs`
+    pub active: usize,
+    /// Background color
+    pub bg_color: Color,
+    /// Foreground color
+    pub fg_color: Color,
+    /// Background color of the active buffer
+    pub active_bg_color: Color,
+    /// Foreground color of the active buffer
+    pub active_fg_color: Color,
+}
+
+impl TabLine {
+    pub fn new(y: u16, width: usize, tabs: Vec<Tab>, active: usize) -> Self {
+        Self {
+            y,
+            width,
+            tabs,
+            active,
+            bg_color: Color::Rgb {
+                r: 40,
+                g: 40,
+                b: 40,
+            },
+            fg_color: Color::Rgb {
+                r: 150,
+                g: 150,
+                b: 150,
+            },
+            active_bg_color: Color::Rgb {
+                r: 255,
+                g: 210,
+                b: 85,
+            },
+            active_fg_color: Color::Rgb {
+                r: 30,
+                g: 30,
+                b: 30,
+            },
+        }
+    }
+
+    /// Returns the visible tabs as `(index, column, text)`, cut to the width of the line.
+    /// The first tabs are left out if the active one would not fit otherwise.
+    pub fn visible_tabs(&self) -> Vec<(usize, usize, String)> {
+        let texts: Vec<String> = self.tabs.iter().map(Tab::text).collect();
+
+        let mut first = 0;
+        while first < self.active
+            && texts[first..=self.active]
+                .iter()
+                .map(|t| t.chars().count())
+                .sum::<usize>()
+                > self.width
+        {
+            first += 1;
+        }
+
+        let mut visible = vec![];
+        let mut column = 0;
+        for (idx, text) in texts.into_iter().enumerate().skip(first) {
+            if column >= self.width {
+                break;
+            }
+            let text: String = text.chars().take(self.width - column).collect();
+            let width = text.chars().count();
+            visible.push((idx, column, text));
+            column += width;
+        }
+
+        visible
+    }
+
+    /// Returns the id of the buffer whose tab is at `column`
+    pub fn tab_at(&self, column: usize) -> Option<usize> {
+        self.visible_tabs()
+            .into_iter()
+            .find(|(_, start, text)| (*start..start + text.chars().count()).contains(&column))
+            .map(|(idx, _, _)| self.tabs[idx].id)
+    }
+}
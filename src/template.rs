@@ -0,0 +1,68 @@
+use crate::{buffer::Buffer, clock::DateTime, config::Config};
+
+/// Templates there are without setting any in the `[templates]` section of the config
+pub const DEFAULT_TEMPLATES: [(&str, &str); 5] = [
+    ("date", "%Y-%m-%d"),
+    ("time", "%H:%M"),
+    ("datetime", "%Y-%m-%d %H:%M"),
+    ("filename", "{filename}"),
+    ("path", "{path}"),
+];
+
+/// Returns the text of the template called `name`, with the `%` sequences of `DateTime::format`
+/// replaced by the date and time `now`, and `{filename}` and `{path}` by those of the file of
+/// `buffer`. Templates set in the config replace the default ones.
+pub fn expand(
+    config: &Config,
+    name: &str,
+    buffer: &Buffer,
+    now: DateTime,
+) -> Result<String, String> {
+    let template = config
+        .get_str("templates", name)
+        .or_else(|| {
+            DEFAULT_TEMPLATES
+                .iter()
+                .find(|(default, _)| *default == name)
+                .map(|(_, template)| *template)
+        })
+        .ok_or_else(|| {
+            format!("No template called {name}, set it in the `[templates]` section of the config")
+        })?;
+
+    let path = match &buffer.file_path {
+        Some(path) => path.display().to_string(),
+        None => buffer.file_name(),
+    };
+
+    // The date first, names of files may have a % in them
+    Ok(now
+        .format(template)
+        .replace("{filename}", &buffer.file_name())
+        .replace("{path}", &path))
+}
+
+/// Completes the name of a template starting with `prefix`, as far as the matching names agree
+pub fn complete(config: &Config, prefix: &str) -> String {
+    let names = DEFAULT_TEMPLATES.iter().map(|(name, _)| *name).chain(
+        config
+            .section("templates")
+            .into_iter()
+            .flat_map(|section| section.keys().map(String::as_str)),
+    );
+
+    let mut common: Option<String> = None;
+    for name in names.filter(|name| name.starts_with(prefix)) {
+        common = Some(match common {
+            None => name.to_string(),
+            Some(common) => common
+                .chars()
+                .zip(name.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(c, _)| c)
+                .collect(),
+        });
+    }
+
+    common.unwrap_or_else(|| prefix.to_string())
+}
@@ -0,0 +1,3839 @@
+//! Tests drawing the whole editor on a screen kept in memory and feeding it terminal events
+
+use std::collections::HashMap;
+
+use crossterm::{
+    event::{
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
+    style::Color,
+};
+
+use crate::{
+    action::Action,
+    buffer::Buffer,
+    case::Case,
+    clock::DateTime,
+    colors::ColorSupport,
+    command::Command,
+    config::{self, Config, ConfigWatch},
+    cursor_style::{CursorShape, CursorStyle, CursorStyles},
+    display::Display,
+    editor::{write_part, Editor},
+    encoding::Encoding,
+    events::{EditorEvent, EventBus, EventContext},
+    filetype::FileType,
+    highlight::HighlightKind,
+    history::{self, FileHistory},
+    keymap::{Key, Keymap},
+    lsp::{Diagnostic, Severity},
+    modeline, outline,
+    paths::{self, app_dir, DirKind},
+    position::Position,
+    project,
+    quickfix::{self, Entry, Location},
+    screen::Screen,
+    script::{self, Reply, Script, ScriptOutcome},
+    session::Session,
+    settings::Settings,
+    shell,
+    spell::{word_ranges, Dictionary},
+    split::Split,
+    start_screen::StartScreen,
+    status_line::StatusLine,
+    tab_line::{Tab, TabLine},
+    trash::{self, Trash},
+};
+use tte_core::spans::{line_spans, Layer, Paint, Span, Style};
+
+/// The editor drawn on an in-memory screen, updated like the main loop does
+struct Terminal {
+    editor: Editor,
+    display: Display<Screen>,
+}
+
+/// Settings keeping the tests away from the user's dictionary, clipboards, history of files and
+/// trash, read before those of each test. Copies go to the screen through OSC 52.
+const TEST_CONFIG: &str = "[spell]\nenabled = false\n[clipboard]\nprimary = false\nosc52 = true\n\
+                           [history]\nenabled = false\n[editor]\ntrash = false\n";
+
+impl Terminal {
+    /// Opens a scratch buffer holding `text` on a screen of the given size
+    fn new(text: &str, width: u16, height: u16) -> Self {
+        let buffer = Buffer::scratch("test", text, 0, 0, width as usize, height as usize - 1);
+        Self::with_config(buffer, "", width, height)
+    }
+
+    /// Opens `buffer` with the settings of `config`, on top of `TEST_CONFIG`
+    fn with_config(buffer: Buffer, config: &str, width: u16, height: u16) -> Self {
+        Self::start(buffer, None, config, HashMap::new(), width, height)
+    }
+
+    fn with_split(buffer: Buffer, split: Option<Split>, width: u16, height: u16) -> Self {
+        Self::start(buffer, split, "", HashMap::new(), width, height)
+    }
+
+    /// Opens `buffer` with scripts by name, `init` runs before the first frame
+    fn with_scripts(
+        buffer: Buffer,
+        config: &str,
+        scripts: HashMap<String, Script>,
+        width: u16,
+        height: u16,
+    ) -> Self {
+        Self::start(buffer, None, config, scripts, width, height)
+    }
+
+    fn start(
+        buffer: Buffer,
+        split: Option<Split>,
+        config: &str,
+        scripts: HashMap<String, Script>,
+        width: u16,
+        height: u16,
+    ) -> Self {
+        let config = Config::parse(&format!("{TEST_CONFIG}{config}")).unwrap();
+        let editor = Editor::with_config(
+            buffer,
+            split,
+            width,
+            height,
+            config,
+            scripts,
+            Session::default(),
+        );
+
+        let mut terminal = Self {
+            editor,
+            display: Display::headless(Screen::new(width, height), width, height),
+        };
+        terminal.draw();
+        terminal
+    }
+
+    fn draw(&mut self) {
+        self.editor.update();
+        self.display.begin_draw().unwrap();
+        self.editor.draw(&mut self.display).unwrap();
+        self.display.end_draw().unwrap();
+    }
+
+    fn send(&mut self, event: Event) {
+        if let Event::Resize(w, h) = event {
+            self.display.resize(w, h);
+            self.display.output().resize(w, h);
+        }
+        self.editor.handle_event(event);
+        self.draw();
+    }
+
+    fn press(&mut self, code: KeyCode, times: usize) {
+        self.press_with(code, KeyModifiers::NONE, times);
+    }
+
+    fn press_with(&mut self, code: KeyCode, modifiers: KeyModifiers, times: usize) {
+        for _ in 0..times {
+            self.send(Event::Key(KeyEvent::new(code, modifiers)));
+        }
+    }
+
+    fn type_text(&mut self, text: &str) {
+        for ch in text.chars() {
+            self.press(KeyCode::Char(ch), 1);
+        }
+    }
+
+    fn screen(&mut self) -> &Screen {
+        self.display.output()
+    }
+
+    fn lines(&mut self) -> Vec<String> {
+        self.screen().lines()
+    }
+}
+
+/// Returns `count` numbered lines
+fn numbered_lines(count: usize) -> String {
+    (1..=count).map(|n| format!("line {n}\n")).collect()
+}
+
+#[test]
+fn draws_the_buffer_and_the_status_line() {
+    let mut terminal = Terminal::new("hello\nworld\n", 20, 5);
+
+    let lines = terminal.lines();
+    assert_eq!(lines[0], "hello");
+    assert_eq!(lines[1], "world");
+    assert_eq!(lines[4].trim(), "test");
+    assert_eq!(terminal.screen().cursor, (0, 0));
+    assert!(terminal.screen().cursor_visible);
+}
+
+#[test]
+fn typed_text_shows_up_at_the_cursor() {
+    let mut terminal = Terminal::new("world\n", 20, 5);
+
+    terminal.type_text("hello ");
+
+    assert_eq!(terminal.lines()[0], "hello world");
+    assert_eq!(terminal.screen().cursor, (6, 0));
+}
+
+#[test]
+fn scrolls_down_to_keep_the_cursor_visible() {
+    let mut terminal = Terminal::new(&numbered_lines(20), 20, 5);
+
+    terminal.press(KeyCode::Down, 6);
+
+    let lines = terminal.lines();
+    assert_eq!(lines[..4], ["line 4", "line 5", "line 6", "line 7"]);
+    assert_eq!(terminal.screen().cursor, (0, 3));
+
+    terminal.press(KeyCode::Up, 5);
+
+    let lines = terminal.lines();
+    assert_eq!(lines[..4], ["line 2", "line 3", "line 4", "line 5"]);
+    assert_eq!(terminal.screen().cursor, (0, 0));
+}
+
+#[test]
+fn scrolls_right_on_long_lines() {
+    let mut terminal = Terminal::new("0123456789abcdefghij\n", 10, 3);
+
+    terminal.press(KeyCode::Right, 12);
+
+    let row = terminal.lines()[0].clone();
+    assert!(row.contains('c'), "{row:?}");
+    assert!(!row.starts_with('0'), "{row:?}");
+    let (x, y) = terminal.screen().cursor;
+    assert_eq!(y, 0);
+    assert_eq!(terminal.screen().cell(x, y).ch, 'c');
+}
+
+#[test]
+fn resizing_moves_the_status_line_and_shrinks_the_buffer() {
+    let mut terminal = Terminal::new(&numbered_lines(20), 30, 10);
+
+    terminal.send(Event::Resize(20, 4));
+
+    let lines = terminal.lines();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[..3], ["line 1", "line 2", "line 3"]);
+    assert_eq!(lines[3].trim(), "test");
+    assert_eq!(terminal.editor.buffer.height, 3);
+
+    // The cursor has to stay within the smaller buffer
+    terminal.press(KeyCode::Down, 4);
+    assert_eq!(terminal.screen().cursor, (0, 2));
+    assert_eq!(terminal.lines()[2], "line 5");
+}
+
+#[test]
+fn growing_the_terminal_shows_more_lines() {
+    let mut terminal = Terminal::new(&numbered_lines(20), 20, 4);
+
+    terminal.send(Event::Resize(20, 8));
+
+    let lines = terminal.lines();
+    assert_eq!(lines[6], "line 7");
+    assert_eq!(lines[7].trim(), "test");
+}
+
+#[test]
+fn draws_buffers_at_their_origin() {
+    let mut display = Display::headless(Screen::new(20, 6), 20, 6);
+    let mut buffer = Buffer::scratch("offset", "abc\ndef\n", 5, 2, 10, 3);
+    buffer.move_cursor_right(1);
+    buffer.move_cursor_down(1);
+
+    display.draw_buffer(&buffer).unwrap();
+
+    let screen = display.output();
+    assert_eq!(screen.row(0).trim(), "");
+    assert_eq!(screen.row(1).trim(), "");
+    assert_eq!(screen.row(2), format!("{:5}{:15}", "", "abc"));
+    assert_eq!(screen.row(3), format!("{:5}{:15}", "", "def"));
+    assert_eq!(screen.cursor, (6, 3));
+    // The padding stops at the right edge of the buffer
+    assert_eq!(screen.cell(14, 2).bg_color, buffer.bg_color);
+    assert_eq!(screen.cell(15, 2).bg_color, Color::Reset);
+}
+
+#[test]
+fn draws_both_panes_of_a_split() {
+    let mut left = Buffer::scratch("left", "same\nold\n", 0, 0, 0, 0);
+    let right = Buffer::scratch("right", "same\nnew\n", 0, 0, 0, 0);
+    let mut split = Split::new(right, false, false);
+    split.layout(&mut left, 0, 0, 21, 5);
+
+    let mut terminal = Terminal::with_split(left, Some(split), 21, 6);
+
+    let lines = terminal.lines();
+    assert_eq!(lines[0], format!("{:10}│same", "same"));
+    assert_eq!(lines[1], format!("{:10}│new", "old"));
+
+    // The other pane becomes the active one
+    terminal.press_with(KeyCode::Char('w'), KeyModifiers::ALT, 1);
+    terminal.type_text("x");
+
+    assert_eq!(terminal.lines()[0], format!("{:10}│xsame", "same"));
+    assert_eq!(terminal.screen().cursor, (12, 0));
+}
+
+#[test]
+fn arrows_ignore_unknown_modifiers() {
+    let mut terminal = Terminal::new("abc\ndef\n", 20, 5);
+
+    terminal.press_with(KeyCode::Down, KeyModifiers::SUPER, 1);
+    terminal.press_with(KeyCode::Right, KeyModifiers::HYPER, 2);
+
+    assert_eq!(terminal.screen().cursor, (2, 1));
+}
+
+#[test]
+fn ctrl_and_alt_arrows_move_over_words() {
+    let mut terminal = Terminal::new("one two_2 three\n", 30, 5);
+
+    terminal.press_with(KeyCode::Right, KeyModifiers::CONTROL, 2);
+    assert_eq!(terminal.screen().cursor, (9, 0));
+
+    terminal.press_with(KeyCode::Left, KeyModifiers::ALT, 1);
+    assert_eq!(terminal.screen().cursor, (4, 0));
+
+    terminal.press_with(
+        KeyCode::Right,
+        KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        1,
+    );
+    assert_eq!(
+        terminal.editor.buffer.selected_text().as_deref(),
+        Some("two_2")
+    );
+
+    // The last word of a buffer without a final line break is moved past and selected whole
+    let mut terminal = Terminal::new("one two", 30, 5);
+    terminal.press_with(KeyCode::Right, KeyModifiers::CONTROL, 3);
+    assert_eq!(terminal.editor.buffer.cursor_pos, 7);
+    terminal.press(KeyCode::Left, 3);
+    terminal.press_with(
+        KeyCode::Right,
+        KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        1,
+    );
+    assert_eq!(
+        terminal.editor.buffer.selected_text().as_deref(),
+        Some("two")
+    );
+}
+
+#[test]
+fn ctrl_arrows_scroll_the_view() {
+    let mut terminal = Terminal::new(&numbered_lines(20), 20, 5);
+
+    terminal.press_with(KeyCode::Down, KeyModifiers::CONTROL, 3);
+
+    assert_eq!(terminal.lines()[0], "line 4");
+    // The cursor is pushed down with the top of the view
+    assert_eq!(terminal.screen().cursor, (0, 0));
+    assert_eq!(terminal.editor.buffer.current_line(), 3);
+
+    terminal.press_with(KeyCode::Up, KeyModifiers::ALT, 1);
+
+    assert_eq!(terminal.lines()[0], "line 3");
+    assert_eq!(terminal.screen().cursor, (0, 1));
+}
+
+#[test]
+fn long_file_names_are_cut_to_fit_the_status_line() {
+    let buffer = Buffer::scratch("a_very_long_file_name.rs", "text\n", 0, 0, 12, 3);
+    let mut terminal = Terminal::with_config(buffer, "", 12, 4);
+
+    assert_eq!(terminal.screen().row(3), " a_very_lo… ");
+    assert_eq!(terminal.screen().row(3).chars().count(), 12);
+
+    // Even narrower after the start, down to nothing but padding
+    terminal.send(Event::Resize(4, 4));
+    assert_eq!(terminal.screen().row(3), " a… ");
+    terminal.send(Event::Resize(2, 4));
+    assert_eq!(terminal.screen().row(3), "  ");
+}
+
+#[test]
+fn pending_input_is_shown_before_the_file_name() {
+    let mut terminal = Terminal::new("one two\n", 40, 4);
+
+    terminal.press_with(KeyCode::Char('1'), KeyModifiers::ALT, 1);
+    terminal.press_with(KeyCode::Char('2'), KeyModifiers::ALT, 1);
+    assert!(terminal.lines()[3].starts_with(" 12×  test"));
+    terminal.press(KeyCode::Right, 1);
+    assert!(terminal.lines()[3].starts_with(" test"));
+
+    // The keys of a sequence wait for the next one
+    let buffer = Buffer::scratch("test", "one two\n", 0, 0, 40, 3);
+    let config = "[keys]\n\"ctrl+k ctrl+d\" = \"count\"\n";
+    let mut terminal = Terminal::with_config(buffer, config, 40, 4);
+    terminal.press_with(KeyCode::Char('k'), KeyModifiers::CONTROL, 1);
+    assert!(terminal.lines()[3].starts_with(" ctrl+k …  test"));
+    terminal.press_with(KeyCode::Char('d'), KeyModifiers::CONTROL, 1);
+    assert!(terminal.lines()[3].starts_with(" test | Buffer: "));
+
+    // Kept when nothing else fits
+    let mut status_line = StatusLine::new(0, 0, 8, 1, "main.rs");
+    status_line.pending = Some("3×".to_string());
+    assert_eq!(status_line.get_text(), " 3×  m… ");
+    status_line.resize(5, 1);
+    assert_eq!(status_line.get_text(), " 3×  ");
+}
+
+#[test]
+fn keys_are_parsed_with_their_modifiers() {
+    let key = |text| Key::parse(text).map(|key| key.to_string());
+    assert_eq!(key("Ctrl+k"), Ok("ctrl+k".to_string()));
+    // An upper case letter is typed with Shift
+    assert_eq!(key("ctrl+K"), Ok("ctrl+shift+k".to_string()));
+    assert_eq!(key("alt+X"), Ok("alt+shift+x".to_string()));
+    assert_eq!(key("ctrl++"), Ok("ctrl++".to_string()));
+    // Shift is part of the symbol
+    assert_eq!(key("shift+?"), Ok("?".to_string()));
+    assert_eq!(key("space"), Ok("space".to_string()));
+    assert_eq!(key("f12"), Ok("f12".to_string()));
+    assert_eq!(key("f13"), Err("Invalid key : f13".to_string()));
+    assert_eq!(
+        key("hyper+a"),
+        Err("Invalid modifier `hyper` in hyper+a".to_string())
+    );
+
+    // A bound key cannot also start a sequence
+    let keys = |sequence: &str| {
+        let keys: Result<Vec<Key>, String> = sequence.split(' ').map(Key::parse).collect();
+        keys.unwrap()
+    };
+    let mut keymap = Keymap::default();
+    keymap.bind(&keys("ctrl+k ctrl+s"), "sort").unwrap();
+    assert_eq!(
+        keymap.bind(&keys("ctrl+k"), "count"),
+        Err("keys: `ctrl+k` is the start of other sequences".to_string())
+    );
+    assert_eq!(
+        keymap.bind(&keys("ctrl+k ctrl+s ctrl+x"), "count"),
+        Err("keys: `ctrl+k ctrl+s ctrl+x` starts with a bound key".to_string())
+    );
+
+    let config = Config::parse("[keys]\n\"leader s\" = \"sort\"\n").unwrap();
+    assert_eq!(
+        Keymap::from_config(&config).err(),
+        Some("keys: `leader s` uses an unset leader".to_string())
+    );
+}
+
+#[test]
+fn the_leader_key_starts_sequences_and_shows_what_follows() {
+    let buffer = Buffer::scratch("test", "b\na\n", 0, 0, 40, 5);
+    let config =
+        "[keys]\nleader = \"space\"\n\"leader s\" = \"sort\"\n\"leader c w\" = \"count\"\n";
+    let mut terminal = Terminal::with_config(buffer, config, 40, 6);
+
+    terminal.type_text(" ");
+    let lines = terminal.lines();
+    assert!(lines[5].starts_with(" space …  test"));
+    assert!(lines.iter().any(|line| line.contains("c  +1 more")));
+    assert!(lines.iter().any(|line| line.contains("s  sort")));
+    terminal.type_text("s");
+    assert_eq!(terminal.editor.buffer.text(), "a\nb\n");
+
+    terminal.type_text(" c");
+    assert!(terminal
+        .lines()
+        .iter()
+        .any(|line| line.contains("w  count")));
+    terminal.type_text("w");
+    assert!(terminal.lines()[5].starts_with(" test | Buffer: "));
+
+    // A key that does not continue the sequence ends it without being typed
+    terminal.type_text(" cx");
+    assert!(!terminal.lines()[5].starts_with(" space"));
+    assert_eq!(terminal.editor.buffer.text(), "a\nb\n");
+    // Keys starting no sequence are typed
+    terminal.type_text("x");
+    assert_eq!(terminal.editor.buffer.text(), "xa\nb\n");
+}
+
+#[test]
+fn the_message_gives_way_before_the_file_name() {
+    let mut status_line = StatusLine::new(0, 0, 20, 1, "main.rs");
+    status_line.message = Some("Unknown command : nosuch".to_string());
+    status_line.counts = Some("3 words".to_string());
+
+    assert_eq!(status_line.get_text(), " main.rs | Unknown… ");
+
+    status_line.resize(11, 1);
+    assert_eq!(status_line.get_text(), " main.rs   ");
+
+    status_line.resize(50, 1);
+    assert_eq!(
+        status_line.get_text(),
+        format!(" {:41}3 words ", "main.rs | Unknown command : nosuch")
+    );
+}
+
+#[test]
+fn the_counts_give_way_before_the_directory() {
+    let mut status_line = StatusLine::new(0, 0, 30, 1, "main.rs");
+    status_line.directory = Some("~/src".to_string());
+    status_line.counts = Some("3 words".to_string());
+
+    assert_eq!(
+        status_line.get_text(),
+        format!(" {:14}~/src  3 words ", "main.rs")
+    );
+
+    status_line.resize(16, 1);
+    assert_eq!(status_line.get_text(), format!(" {:9}~/src ", "main.rs"));
+}
+
+#[test]
+fn the_frame_time_gives_way_first() {
+    let mut status_line = StatusLine::new(0, 0, 30, 1, "main.rs");
+    status_line.counts = Some("3 words".to_string());
+    status_line.frame_time = Some("1.2ms".to_string());
+
+    assert_eq!(
+        status_line.get_text(),
+        format!(" {:14}1.2ms  3 words ", "main.rs")
+    );
+
+    status_line.resize(20, 1);
+    assert_eq!(status_line.get_text(), format!(" {:11}3 words ", "main.rs"));
+}
+
+#[test]
+fn the_clock_stays_longest_at_the_right_end() {
+    let mut status_line = StatusLine::new(0, 0, 42, 1, "main.rs");
+    status_line.frame_time = Some("1.2ms".to_string());
+    status_line.buffers = Some("2 buffers".to_string());
+    status_line.counts = Some("3 words".to_string());
+    status_line.clock = Some("12:30".to_string());
+
+    assert_eq!(
+        status_line.get_text(),
+        format!(" {:8}1.2ms  2 buffers  3 words  12:30 ", "main.rs")
+    );
+
+    status_line.resize(30, 1);
+    assert_eq!(
+        status_line.get_text(),
+        format!(" {:14}3 words  12:30 ", "main.rs")
+    );
+
+    status_line.resize(15, 1);
+    assert_eq!(status_line.get_text(), format!(" {:8}12:30 ", "main.rs"));
+
+    // Each is turned on by itself in the config, a format string sets how the clock reads
+    let buffer = Buffer::scratch("test", "one two\n", 0, 0, 40, 3);
+    let config = "[status]\nbuffers = true\nclock = \"noon\"\n";
+    let mut terminal = Terminal::with_config(buffer, config, 40, 4);
+    terminal.draw();
+    assert_eq!(terminal.lines()[3], format!(" {:24}1 buffer  noon", "test"));
+
+    terminal.editor.apply(Action::Run(Command::Scratch));
+    terminal.draw();
+    assert!(terminal.lines()[3].ends_with("2 buffers  noon"));
+}
+
+#[test]
+fn screen_readers_get_messages_on_their_own_line_and_errors_rung() {
+    let buffer = Buffer::scratch("test", "one\n", 0, 0, 40, 4);
+    let config = "[accessibility]\nscreen_reader = true\nbell = true\nnotify = true\n";
+    let mut terminal = Terminal::with_config(buffer, config, 40, 5);
+    terminal
+        .display
+        .set_screen_reader(terminal.editor.accessibility().screen_reader);
+    terminal.draw();
+    // No marks past the end of the buffer
+    assert_eq!(terminal.lines(), ["one", "", "", " test", ""]);
+
+    terminal
+        .editor
+        .apply(Action::RunText("nonsense".to_string()));
+    terminal.draw();
+    assert_eq!(
+        terminal.lines(),
+        ["one", "", "", " test", "Unknown command : nonsense"]
+    );
+    assert_eq!(terminal.screen().bells, 1);
+    assert!(terminal
+        .display
+        .output()
+        .commands
+        .contains(&"9;Unknown command : nonsense".to_string()));
+
+    // Only errors are announced
+    terminal.editor.apply(Action::Run(Command::Count));
+    terminal.draw();
+    assert!(terminal.lines()[4].starts_with("Buffer: "));
+    assert_eq!(terminal.screen().bells, 1);
+}
+
+#[test]
+fn counts_cover_the_buffer_and_the_selection() {
+    let buffer = Buffer::scratch("test", "one two\nthree\nfour", 0, 0, 120, 3);
+    let mut terminal = Terminal::with_config(buffer, "[status]\ncounts = true\n", 120, 4);
+    terminal.draw();
+    assert!(terminal.lines()[3].trim_end().ends_with(" 4 words"));
+
+    // The last line counts without a line break
+    terminal.editor.apply(Action::RunText("count".to_string()));
+    terminal.draw();
+    assert!(terminal.lines()[3].starts_with(" test | Buffer: 3 lines, 4 words, 18 characters "));
+
+    terminal.press(KeyCode::Right, 4);
+    terminal.press_with(KeyCode::Down, KeyModifiers::SHIFT, 1);
+    assert!(terminal.lines()[3].trim_end().ends_with(" 2 of 4 words"));
+    terminal.editor.apply(Action::RunText("count".to_string()));
+    terminal.draw();
+    assert!(terminal.lines()[3].starts_with(
+        " test | Buffer: 3 lines, 4 words, 18 characters | Selection: 2 lines, 2 words, 8 characters "
+    ));
+}
+
+#[test]
+fn a_single_row_is_left_to_the_buffer() {
+    let mut terminal = Terminal::new(&numbered_lines(20), 20, 5);
+
+    terminal.send(Event::Resize(20, 1));
+    assert_eq!(terminal.lines(), ["line 1"]);
+    assert_eq!(terminal.editor.buffer.height, 1);
+
+    terminal.press(KeyCode::Down, 2);
+    assert_eq!(terminal.lines(), ["line 3"]);
+
+    // Nothing fits, but editing goes on until the terminal grows again
+    terminal.send(Event::Resize(0, 0));
+    terminal.type_text("ab");
+    assert_eq!(terminal.editor.buffer.width, 1);
+    assert_eq!(terminal.editor.buffer.height, 1);
+
+    terminal.send(Event::Resize(20, 5));
+    let lines = terminal.lines();
+    assert!(lines[..4].contains(&"abline 3".to_string()), "{lines:?}");
+    assert_eq!(lines[4].trim(), "test");
+}
+
+#[test]
+fn resizing_keeps_the_cursor_in_view() {
+    let mut terminal = Terminal::new(&numbered_lines(20), 20, 10);
+    terminal.press(KeyCode::Down, 8);
+
+    terminal.send(Event::Resize(20, 4));
+
+    assert_eq!(terminal.lines()[..3], ["line 7", "line 8", "line 9"]);
+    assert_eq!(terminal.screen().cursor, (0, 2));
+
+    // The top line stays where it is when there is room for more
+    terminal.send(Event::Resize(20, 8));
+
+    assert_eq!(terminal.lines()[0], "line 7");
+    assert_eq!(terminal.screen().cursor, (0, 2));
+}
+
+#[test]
+fn widening_shows_the_start_of_long_lines_again() {
+    let mut terminal = Terminal::new("0123456789abcdefghij\n", 10, 3);
+    terminal.press(KeyCode::Right, 12);
+    assert!(!terminal.lines()[0].starts_with('0'));
+
+    terminal.send(Event::Resize(30, 3));
+
+    assert_eq!(terminal.lines()[0], "0123456789abcdefghij");
+    assert_eq!(terminal.screen().cursor, (12, 0));
+}
+
+#[test]
+fn the_start_screen_goes_away_when_typing() {
+    let mut terminal = Terminal::new("", 50, 20);
+    terminal.editor.show_start_screen();
+    terminal.draw();
+
+    let lines = terminal.lines();
+    assert!(lines.iter().any(|l| l.contains("tte ")), "{lines:?}");
+    assert!(lines.iter().any(|l| l.contains("Quit")), "{lines:?}");
+
+    // Keys that do not type keep it
+    terminal.press(KeyCode::Right, 1);
+    assert!(terminal.lines().iter().any(|l| l.contains("Quit")));
+
+    terminal.type_text("hi");
+
+    let lines = terminal.lines();
+    assert_eq!(lines[0], "hi");
+    assert!(lines[1..19].iter().all(|l| l == "~"), "{lines:?}");
+}
+
+#[test]
+fn the_start_screen_lists_the_recent_files() {
+    let start = StartScreen::new(&["/home/me/notes.md".to_string()]);
+
+    let lines = start.get_lines();
+    assert_eq!(lines[0], format!("tte {}", env!("CARGO_PKG_VERSION")));
+    assert!(lines.contains(&"  /home/me/notes.md".to_string()));
+
+    // Every row fills the view, even when it is too small for the lines
+    let rows = start.get_rows(10, 3);
+    assert_eq!(rows.len(), 3);
+    assert!(rows.iter().all(|row| row.chars().count() == 10));
+}
+
+/// Returns a path in a new directory of the system's temporary directory
+fn temp_path(test: &str, file: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("tte-{test}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir.join(file)
+}
+
+#[test]
+fn saving_a_new_file_asks_to_create_its_directory() {
+    let path = temp_path("new-file", "sub/new.txt");
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 60, 4);
+    let mut terminal = Terminal::with_config(buffer, "", 60, 5);
+
+    assert_eq!(terminal.lines()[4].trim(), "new.txt [New File]");
+
+    terminal.type_text("hi");
+    terminal.press_with(KeyCode::Char('s'), KeyModifiers::CONTROL, 1);
+    assert!(terminal.lines()[4].starts_with("Create directory"));
+    assert!(!path.exists());
+
+    terminal.type_text("y");
+    terminal.press(KeyCode::Enter, 1);
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "hi");
+    assert_eq!(terminal.lines()[4].trim(), "new.txt");
+
+    std::fs::remove_dir_all(path.parent().unwrap().parent().unwrap()).unwrap();
+}
+
+#[test]
+fn saving_over_a_directory_fails() {
+    let path = temp_path("over-directory", "file.txt");
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 200, 4);
+    let mut terminal = Terminal::with_config(buffer, "", 200, 5);
+
+    // The file became a directory after opening it
+    std::fs::create_dir_all(&path).unwrap();
+    terminal.type_text("hi");
+    terminal.press_with(KeyCode::Char('s'), KeyModifiers::CONTROL, 1);
+
+    let status = terminal.lines()[4].clone();
+    assert!(status.contains("is a directory"), "{status:?}");
+    assert!(terminal.editor.buffer.modified);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn a_dropped_file_is_opened_when_agreed_to() {
+    let path = temp_path("dropped", "my notes.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "notes\n").unwrap();
+    let escaped = path.to_str().unwrap().replace(' ', "\\ ");
+
+    let buffer = Buffer::scratch("test", "", 0, 0, 200, 4);
+    let config = "[editor]\nopen_dropped_files = true\n";
+    let mut terminal = Terminal::with_config(buffer, config, 200, 5);
+
+    // Answering no pastes the path
+    terminal.send(Event::Paste(escaped.clone()));
+    assert!(terminal.lines()[4].starts_with(&format!("Open {} ?", path.display())));
+    terminal.type_text("n");
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.editor.buffer.text(), escaped);
+
+    terminal.send(Event::Paste(format!("'{}'", path.display())));
+    terminal.type_text("y");
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.editor.buffer.file_path.as_ref(), Some(&path));
+    assert_eq!(terminal.lines()[1], "notes");
+
+    // Text that does not name a file is pasted right away
+    terminal.send(Event::Paste("/no/such/file.txt".to_string()));
+    assert_eq!(terminal.editor.buffer.text(), "/no/such/file.txtnotes\n");
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn the_tab_line_lists_the_open_buffers() {
+    let mut terminal = Terminal::new("first\n", 30, 6);
+    assert_eq!(terminal.lines()[0], "first");
+
+    let second = Buffer::scratch("other", "second\n", 0, 0, 30, 5);
+    terminal.editor.hidden_buffers.push(second);
+    terminal.type_text("x");
+
+    let lines = terminal.lines();
+    assert_eq!(lines[0], " test +  other");
+    assert_eq!(lines[1], "xfirst");
+    assert_eq!(lines[5].trim(), "test");
+    assert_eq!(terminal.screen().cursor, (1, 1));
+    assert_eq!(terminal.editor.buffer.height, 4);
+
+    // The active tab is highlighted
+    let active = terminal.screen().cell(1, 0).bg_color;
+    assert_ne!(active, terminal.screen().cell(10, 0).bg_color);
+
+    terminal.press_with(KeyCode::PageDown, KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.lines()[1], "second");
+    assert_eq!(terminal.screen().cell(10, 0).bg_color, active);
+
+    // Going around to the first buffer again
+    terminal.press_with(KeyCode::PageDown, KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.lines()[1], "xfirst");
+}
+
+#[test]
+fn clicking_a_tab_switches_to_its_buffer() {
+    let mut terminal = Terminal::new("first\n", 30, 6);
+    let second = Buffer::scratch("other", "second\n", 0, 0, 30, 5);
+    terminal.editor.hidden_buffers.push(second);
+    terminal.draw();
+
+    let click = |column| {
+        Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row: 0,
+            modifiers: KeyModifiers::NONE,
+        })
+    };
+
+    terminal.send(click(9));
+    assert_eq!(terminal.lines()[1], "second");
+    assert_eq!(terminal.lines()[5].trim(), "other");
+
+    terminal.send(click(2));
+    assert_eq!(terminal.lines()[1], "first");
+
+    // Closing the other buffer takes the tab line away
+    terminal.editor.hidden_buffers.clear();
+    terminal.draw();
+    assert_eq!(terminal.lines()[0], "first");
+    assert_eq!(terminal.editor.buffer.height, 5);
+}
+
+#[test]
+fn the_active_tab_stays_visible_on_narrow_terminals() {
+    let tabs = ["one", "two", "three"]
+        .iter()
+        .enumerate()
+        .map(|(id, name)| Tab {
+            id,
+            name: name.to_string(),
+            modified: false,
+        })
+        .collect();
+    let tab_line = TabLine::new(0, 12, tabs, 2);
+
+    let visible = tab_line.visible_tabs();
+    assert_eq!(visible[0], (1, 0, " two ".to_string()));
+    assert_eq!(visible[1], (2, 5, " three ".to_string()));
+    assert_eq!(tab_line.tab_at(6), Some(2));
+    assert_eq!(tab_line.tab_at(12), None);
+}
+
+#[test]
+fn closing_buffers_asks_about_unsaved_changes() {
+    let mut terminal = Terminal::new("first\n", 40, 6);
+    for name in ["second", "third"] {
+        let buffer = Buffer::scratch(name, &format!("{name}\n"), 0, 0, 40, 5);
+        terminal.editor.hidden_buffers.push(buffer);
+    }
+    terminal.type_text("x");
+
+    terminal.press_with(KeyCode::Char('w'), KeyModifiers::CONTROL, 1);
+    assert!(terminal.lines()[5].starts_with("Discard changes to test ?"));
+
+    // Keeping the changes keeps the buffer
+    terminal.type_text("n");
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.lines()[1], "xfirst");
+
+    terminal.press_with(KeyCode::Char('w'), KeyModifiers::CONTROL, 1);
+    terminal.type_text("y");
+    terminal.press(KeyCode::Enter, 1);
+
+    // The next buffer takes its place
+    assert_eq!(terminal.lines()[0], " second  third");
+    assert_eq!(terminal.lines()[1], "second");
+
+    // Unmodified buffers close right away, the last one quits
+    terminal.press_with(KeyCode::Char('w'), KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.lines()[0], "third");
+    assert!(!terminal.editor.quit);
+
+    terminal.press_with(KeyCode::Char('w'), KeyModifiers::CONTROL, 1);
+    assert!(terminal.editor.quit);
+}
+
+#[test]
+fn the_buffer_list_switches_to_and_closes_buffers() {
+    let mut terminal = Terminal::new("first\n", 50, 10);
+    let first = terminal.editor.buffer.id;
+    terminal.editor.apply(Action::Run(Command::Scratch));
+    terminal.type_text("notes");
+    let scratch = terminal.editor.buffer.id;
+    terminal.editor.apply(Action::Run(Command::Buffers));
+    terminal.draw();
+
+    let lines = terminal.lines();
+    assert_eq!(lines[3], format!("{first:>4}   test"));
+    assert_eq!(lines[4], format!("{scratch:>4} + NO NAME"));
+
+    // The modified scratch buffer stays open
+    terminal.press(KeyCode::Down, 1);
+    terminal.press(KeyCode::Char('d'), 1);
+    assert_eq!(terminal.lines()[4], format!("{scratch:>4} + NO NAME"));
+
+    terminal.press(KeyCode::Up, 1);
+    terminal.press(KeyCode::Char('d'), 1);
+    let lines = terminal.lines();
+    assert_eq!(lines[3], format!("{scratch:>4} + NO NAME"));
+    assert_eq!(lines[4], "");
+
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.lines()[0], "notes");
+    assert!(terminal.editor.hidden_buffers.is_empty());
+}
+
+#[test]
+fn tabs_take_the_columns_up_to_the_next_tab_stop() {
+    let mut terminal = Terminal::new("\tab\ncd\tef\n", 20, 5);
+
+    assert_eq!(terminal.lines()[..2], ["    ab", "cd  ef"]);
+    terminal.press(KeyCode::Right, 1);
+    assert_eq!(terminal.screen().cursor, (4, 0));
+
+    terminal.editor.apply(Action::Run(Command::Set(Some((
+        "tab_width".to_string(),
+        "8".to_string(),
+    )))));
+    terminal.draw();
+
+    assert_eq!(terminal.lines()[..2], ["        ab", "cd      ef"]);
+    assert_eq!(terminal.screen().cursor, (8, 0));
+}
+
+#[test]
+fn the_tab_key_follows_the_settings_of_the_buffer() {
+    let mut terminal = Terminal::new("x\n", 40, 5);
+
+    terminal.press(KeyCode::Tab, 1);
+    assert_eq!(terminal.editor.buffer.text(), "    x\n");
+
+    terminal.type_text("y");
+    terminal.press(KeyCode::Tab, 1);
+    assert_eq!(terminal.editor.buffer.text(), "    y   x\n");
+
+    terminal
+        .editor
+        .apply(Action::RunText("set expand_tabs false".to_string()));
+    terminal.press(KeyCode::Tab, 1);
+    assert_eq!(terminal.editor.buffer.text(), "    y   \tx\n");
+
+    // Invalid values are refused
+    terminal
+        .editor
+        .apply(Action::RunText("set tab_width zero".to_string()));
+    terminal.draw();
+    assert_eq!(
+        terminal.lines()[4].trim(),
+        "test | Invalid tab width : zero"
+    );
+}
+
+#[test]
+fn settings_are_resolved_from_the_config_then_the_buffer() {
+    let config = Config::parse(
+        "[editor]\ntab_width = 8\n[editor.go]\ntab_width = 3\n[format]\non_save = true\n",
+    )
+    .unwrap();
+    let mut buffer = Buffer::scratch("main.go", "", 0, 0, 20, 5);
+
+    assert_eq!(Settings::for_buffer(&config, &buffer).tab_width, 8);
+
+    buffer.file_type = FileType::Go;
+    let settings = Settings::for_buffer(&config, &buffer);
+    assert_eq!(settings.tab_width, 3);
+    assert!(!settings.expand_tabs);
+    assert!(settings.format_on_save);
+
+    buffer
+        .settings
+        .push(("tab_width".to_string(), "2".to_string()));
+    assert_eq!(Settings::for_buffer(&config, &buffer).tab_width, 2);
+}
+
+#[test]
+fn modelines_are_parsed_from_vim_and_emacs_forms() {
+    let pairs = |items: &[(&str, &str)]| {
+        items
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        modeline::parse("# vim: ts=8 noet ft=python\nx = 1\n"),
+        pairs(&[
+            ("tab_width", "8"),
+            ("expand_tabs", "false"),
+            ("filetype", "python")
+        ])
+    );
+    assert_eq!(
+        modeline::parse("a\nb\nc\nd\ne\nf\n/* vim: set sw=2 ts=2 et : */\n"),
+        pairs(&[("tab_width", "2"), ("expand_tabs", "true")])
+    );
+    assert_eq!(
+        modeline::parse("#!/bin/sh\n# -*- mode: Shell; tab-width: 3; indent-tabs-mode: t -*-\n"),
+        pairs(&[
+            ("filetype", "shell"),
+            ("tab_width", "3"),
+            ("expand_tabs", "false")
+        ])
+    );
+    // Not a modeline, the marker is inside a word
+    assert!(modeline::parse("see index: ts=8\n").is_empty());
+}
+
+#[test]
+fn modelines_apply_only_when_enabled() {
+    let path = temp_path("modeline", "tabs.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "\tindented\n// vim: ts=2 noet\n").unwrap();
+
+    let open = |config: &str| {
+        let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 40, 4);
+        let terminal = Terminal::with_config(buffer, config, 40, 5);
+        Settings::for_buffer(&Config::parse(config).unwrap(), &terminal.editor.buffer)
+    };
+
+    let settings = open("");
+    assert_eq!(settings.tab_width, 4);
+    assert!(settings.expand_tabs);
+
+    let settings = open("[editor]\nmodelines = true\n");
+    assert_eq!(settings.tab_width, 2);
+    assert!(!settings.expand_tabs);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn project_roots_are_found_from_their_markers() {
+    let path = temp_path("project-root", "src/deep/main.rs");
+    let root = path.parent().unwrap().parent().unwrap().parent().unwrap();
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(root.join("Cargo.toml"), "").unwrap();
+
+    let defaults = project::root_markers(&Config::parse("").unwrap());
+    assert_eq!(project::find_root(&path, &defaults).as_deref(), Some(root));
+    assert_eq!(
+        project::relative_path(&path, &defaults).as_deref(),
+        Some("src/deep/main.rs")
+    );
+
+    // Configured markers replace the default ones
+    std::fs::write(root.join("src/package.json"), "").unwrap();
+    let markers =
+        project::root_markers(&Config::parse("[project]\nmarkers = [\"package.json\"]\n").unwrap());
+    assert_eq!(
+        project::relative_path(&path, &markers).as_deref(),
+        Some("deep/main.rs")
+    );
+    assert_eq!(project::relative_path(&path, &["none".to_string()]), None);
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn files_in_a_project_show_their_relative_path() {
+    let path = temp_path("project-status", "src/lib.rs");
+    let root = path.parent().unwrap().parent().unwrap();
+    std::fs::create_dir_all(root.join(".git")).unwrap();
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "").unwrap();
+
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 60, 4);
+    let mut terminal = Terminal::with_config(buffer, "", 60, 5);
+    assert_eq!(terminal.lines()[4].trim(), "src/lib.rs");
+
+    let id = terminal.editor.buffer.id;
+    terminal.editor.apply(Action::Run(Command::Buffers));
+    terminal.draw();
+    assert_eq!(terminal.lines()[3], format!("{id:>4}   src/lib.rs"));
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn words_are_completed_from_every_open_buffer() {
+    let mut left = Buffer::scratch("left", "ap\n", 0, 0, 0, 0);
+    let right = Buffer::scratch("right", "apple\n", 0, 0, 0, 0);
+    let mut split = Split::new(right, false, false);
+    split.layout(&mut left, 0, 0, 40, 5);
+    let mut terminal = Terminal::with_split(left, Some(split), 40, 6);
+    terminal
+        .editor
+        .hidden_buffers
+        .push(Buffer::scratch("hidden", "apricot\n", 0, 0, 40, 5));
+
+    // The words of the other pane and of the hidden buffer are both offered
+    terminal.press(KeyCode::End, 1);
+    terminal.editor.apply(Action::Complete);
+    terminal.press(KeyCode::Down, 1);
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.editor.buffer.text(), "apricot\n");
+}
+
+#[test]
+fn paths_are_completed_from_their_directory() {
+    let dir = temp_path("complete", "");
+    std::fs::create_dir_all(dir.join("alpha")).unwrap();
+    std::fs::write(dir.join("alpine.txt"), "").unwrap();
+    std::fs::write(dir.join(".alpaca"), "").unwrap();
+    let dir = dir.to_str().unwrap();
+
+    let (completed, matches) = paths::complete(&format!("{dir}al"), false);
+    assert_eq!(completed, format!("{dir}alp"));
+    assert_eq!(matches, ["alpha", "alpine.txt"]);
+
+    // A single directory gets its slash
+    let (completed, _) = paths::complete(&format!("{dir}al"), true);
+    assert_eq!(completed, format!("{dir}alpha/"));
+
+    let (completed, matches) = paths::complete(&format!("{dir}.al"), false);
+    assert_eq!(completed, format!("{dir}.alpaca"));
+    assert_eq!(matches, [".alpaca"]);
+
+    assert_eq!(
+        Command::parse("cd ~/src").unwrap(),
+        Command::ChangeDirectory(Some("~/src".to_string()))
+    );
+    assert_eq!(
+        Command::parse("cd").unwrap(),
+        Command::ChangeDirectory(None)
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cd_changes_the_working_directory_with_completion() {
+    let dir = temp_path("cd", "");
+    std::fs::create_dir_all(dir.join("target-dir")).unwrap();
+    let before = std::env::current_dir().unwrap();
+    let mut terminal = Terminal::new("", 60, 5);
+
+    terminal
+        .editor
+        .apply(Action::CommandPrompt(format!("cd {}tar", dir.display())));
+    terminal.press(KeyCode::Tab, 1);
+    terminal.draw();
+    assert!(terminal.lines()[4].trim_end().ends_with("target-dir/"));
+
+    terminal.press(KeyCode::Enter, 1);
+    let now = std::env::current_dir().unwrap();
+    std::env::set_current_dir(&before).unwrap();
+
+    assert_eq!(now, dir.join("target-dir").canonicalize().unwrap());
+    assert!(terminal.lines()[4].contains("Working directory : "));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn ctrl_o_opens_a_completed_path_in_a_new_buffer() {
+    let dir = temp_path("open", "");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("notes.md"), "# Notes\n").unwrap();
+    let mut terminal = Terminal::new("first\n", 60, 5);
+    let first = terminal.editor.buffer.id;
+
+    terminal.press_with(KeyCode::Char('o'), KeyModifiers::CONTROL, 1);
+    terminal.type_text(&format!("{}no", dir.display()));
+    terminal.press(KeyCode::Tab, 1);
+    assert_eq!(
+        terminal.lines()[4].trim_end(),
+        format!("Open : {}notes.md", dir.display())
+    );
+
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.editor.buffer.text(), "# Notes\n");
+    assert_eq!(terminal.editor.hidden_buffers[0].id, first);
+
+    // The home directory is written as ~
+    let home = paths::get_user_home_dir().unwrap();
+    assert_eq!(paths::expand_home("~/notes.md"), home.join("notes.md"));
+    assert_eq!(paths::shorten_home(&home.join("src")), "~/src");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn diff_shows_the_unsaved_changes() {
+    let path = temp_path("diff", "notes.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 40, 5);
+    let mut terminal = Terminal::with_config(buffer, "", 40, 6);
+
+    terminal.editor.apply(Action::RunText("diff".to_string()));
+    terminal.draw();
+    assert_eq!(terminal.lines()[5].trim(), "notes.txt | No unsaved changes");
+
+    terminal.press(KeyCode::Down, 1);
+    terminal.press(KeyCode::End, 1);
+    terminal.type_text("!");
+    let source = terminal.editor.buffer.id;
+    terminal.editor.apply(Action::RunText("diff".to_string()));
+    let name = path.display();
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        format!(
+            "--- {name} (saved)\n+++ {name} (buffer)\n@@ -1,3 +1,3 @@\n one\n-two\n+two!\n three\n"
+        )
+    );
+    assert_eq!(terminal.editor.buffer.file_type, FileType::Diff);
+    assert_eq!(terminal.editor.buffer.source, Some(source));
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn read_inserts_a_file_at_the_cursor_as_one_edit() {
+    let path = temp_path("read", "part.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "one\r\ntwo\n").unwrap();
+    let mut terminal = Terminal::new("start\nend\n", 40, 6);
+    terminal.press(KeyCode::Down, 1);
+
+    terminal
+        .editor
+        .apply(Action::RunText(format!("r {}", path.display())));
+    terminal.draw();
+    assert_eq!(terminal.editor.buffer.text(), "start\none\ntwo\nend\n");
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(3, 0)
+    );
+
+    terminal.press_with(KeyCode::Char('z'), KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.editor.buffer.text(), "start\nend\n");
+
+    // Other encodings are converted
+    std::fs::write(&path, [0x63, 0x61, 0x66, 0xE9]).unwrap();
+    terminal.editor.apply(Action::RunText(format!(
+        "read ++enc=latin1 {}",
+        path.display()
+    )));
+    assert_eq!(terminal.editor.buffer.text(), "start\ncaféend\n");
+
+    assert_eq!(
+        Encoding::Utf16Le.decode(&[0xFF, 0xFE, 0x68, 0x00, 0x69, 0x00]),
+        "hi"
+    );
+    assert_eq!(
+        Command::parse("r ++enc=ebcdic x"),
+        Err("Unknown encoding : ebcdic".to_string())
+    );
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn write_saves_a_range_or_the_selection_to_another_file() {
+    let path = temp_path("write", "part.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let mut terminal = Terminal::new("one\ntwo\nthree\nfour\n", 60, 6);
+
+    terminal
+        .editor
+        .apply(Action::RunText(format!("2,3w {}", path.display())));
+    terminal.draw();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "two\nthree\n");
+    assert!(terminal.lines()[5].contains("Wrote 2 lines to"));
+
+    // Existing files are only replaced with w!
+    terminal.press_with(KeyCode::Right, KeyModifiers::SHIFT, 3);
+    terminal
+        .editor
+        .apply(Action::RunText(format!("w {}", path.display())));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "two\nthree\n");
+    terminal
+        .editor
+        .apply(Action::RunText(format!("w! {}", path.display())));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "one");
+    assert!(!terminal.editor.buffer.modified);
+
+    assert_eq!(
+        Command::parse("0,2w x"),
+        Err("Invalid line range : 0,2".to_string())
+    );
+    assert!(Command::parse("write").is_err());
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn piped_text_is_read_as_utf8() {
+    let text = crate::read_piped(&b"caf\xC3\xA9\r\n\xFF\n"[..]).unwrap();
+    assert_eq!(text, "caf\u{e9}\r\n\u{fffd}\n");
+
+    // Line endings are left to the buffer
+    let buffer = Buffer::scratch("[stdin]", &text, 0, 0, 20, 4);
+    assert_eq!(buffer.text(), "caf\u{e9}\n\u{fffd}\n");
+}
+
+#[test]
+fn ctrl_l_catches_up_with_a_missed_resize() {
+    let mut terminal = Terminal::new("text\n", 20, 4);
+
+    // The terminal grew without telling
+    terminal.display.resize(30, 6);
+    terminal.display.output().resize(30, 6);
+    terminal.draw();
+    assert_eq!(terminal.lines()[5], "");
+
+    terminal.press_with(KeyCode::Char('l'), KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.lines()[0], "text");
+    assert_eq!(terminal.lines()[5].trim(), "test");
+    assert_eq!(terminal.editor.buffer.height, 5);
+}
+
+#[test]
+fn insert_toggles_overwrite_mode_and_its_cursor() {
+    let mut terminal = Terminal::new("abc\n", 20, 4);
+    let styles = CursorStyles::default();
+    assert_eq!(terminal.editor.cursor_style(), styles.insert);
+
+    terminal.press(KeyCode::Insert, 1);
+    terminal.type_text("xy");
+    assert_eq!(terminal.editor.buffer.text(), "xyc\n");
+    assert_eq!(terminal.editor.cursor_style(), styles.overwrite);
+
+    terminal.press_with(KeyCode::Char('p'), KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.editor.cursor_style(), styles.prompt);
+    terminal.press(KeyCode::Esc, 1);
+
+    terminal.press(KeyCode::Insert, 1);
+    terminal.type_text("z");
+    assert_eq!(terminal.editor.buffer.text(), "xyzc\n");
+}
+
+#[test]
+fn cursor_styles_are_read_from_the_config() {
+    let config = Config::parse("[cursor]\noverwrite = \"steady-underline\"\n").unwrap();
+    let styles = CursorStyles::from_config(&config).unwrap();
+    assert_eq!(
+        styles.overwrite,
+        CursorStyle::new(CursorShape::Underline, false)
+    );
+    assert_eq!(styles.insert, CursorStyles::default().insert);
+
+    let config = Config::parse("[cursor]\ninsert = \"triangle\"\n").unwrap();
+    assert_eq!(
+        CursorStyles::from_config(&config),
+        Err("Unknown cursor style for insert : triangle".to_string())
+    );
+}
+
+#[test]
+fn color_support_is_detected_from_the_environment() {
+    let detect = ColorSupport::from_env;
+    assert_eq!(
+        detect("1", "truecolor", "xterm-256color"),
+        ColorSupport::NoColor
+    );
+    assert_eq!(detect("", "", "dumb"), ColorSupport::NoColor);
+    if !cfg!(windows) {
+        assert_eq!(detect("", "truecolor", "xterm"), ColorSupport::TrueColor);
+        assert_eq!(detect("", "", "xterm-256color"), ColorSupport::Ansi256);
+        assert_eq!(detect("", "", "xterm"), ColorSupport::Ansi16);
+    }
+}
+
+#[test]
+fn dumb_terminals_get_no_colors_titles_or_clipboard() {
+    let mut terminal = Terminal::new("hello\n", 40, 5);
+    terminal.display.set_dumb();
+    terminal.display.output().commands.clear();
+
+    terminal.press_with(KeyCode::Right, KeyModifiers::SHIFT, 2);
+    terminal.press_with(KeyCode::Char('c'), KeyModifiers::CONTROL, 1);
+    terminal.type_text("x");
+    terminal.draw();
+
+    assert_eq!(terminal.lines()[0], "xllo");
+    assert!(terminal.display.output().commands.is_empty());
+    let screen = terminal.screen();
+    for x in 0..40 {
+        assert_eq!(screen.cell(x, 0).fg_color, Color::Reset);
+        assert_eq!(screen.cell(x, 4).bg_color, Color::Reset);
+    }
+    assert!(screen.cell(1, 4).reversed);
+}
+
+#[test]
+fn theme_colors_are_converted_to_the_palette() {
+    let rgb = |r, g, b| Color::Rgb { r, g, b };
+    let colors = ColorSupport::Ansi256;
+    assert_eq!(colors.convert(rgb(255, 0, 0)), Color::AnsiValue(196));
+    assert_eq!(colors.convert(rgb(40, 40, 40)), Color::AnsiValue(235));
+    assert_eq!(colors.convert(Color::Reset), Color::Reset);
+
+    let colors = ColorSupport::Ansi16;
+    assert_eq!(colors.convert(rgb(250, 10, 10)), Color::Red);
+    assert_eq!(colors.convert(rgb(20, 20, 30)), Color::Black);
+
+    assert_eq!(ColorSupport::NoColor.convert(rgb(1, 2, 3)), Color::Reset);
+    assert_eq!(ColorSupport::TrueColor.convert(rgb(1, 2, 3)), rgb(1, 2, 3));
+}
+
+#[test]
+fn without_colors_the_status_line_is_reversed() {
+    let mut terminal = Terminal::new("text\n", 20, 4);
+    terminal.display.set_color_support(ColorSupport::NoColor);
+    terminal.draw();
+
+    let text = terminal.screen().cell(0, 0);
+    assert_eq!((text.fg_color, text.bg_color), (Color::Reset, Color::Reset));
+    assert!(!text.reversed);
+    let status = terminal.screen().cell(0, 3);
+    assert_eq!(
+        (status.fg_color, status.bg_color),
+        (Color::Reset, Color::Reset)
+    );
+    assert!(status.reversed);
+}
+
+#[test]
+fn buffers_can_be_drawn_on_the_terminal_background() {
+    let mut terminal = Terminal::new("text\n", 20, 4);
+    terminal.display.set_terminal_background(true);
+    terminal.draw();
+
+    assert_eq!(terminal.screen().cell(0, 0).bg_color, Color::Reset);
+    assert_eq!(terminal.screen().cell(10, 1).bg_color, Color::Reset);
+    assert_ne!(terminal.screen().cell(0, 3).bg_color, Color::Reset);
+
+    terminal.press_with(KeyCode::Right, KeyModifiers::SHIFT, 2);
+    assert_ne!(terminal.screen().cell(0, 0).bg_color, Color::Reset);
+    assert_eq!(terminal.screen().cell(2, 0).bg_color, Color::Reset);
+}
+
+#[test]
+fn virtual_space_lets_the_cursor_past_the_end_of_lines() {
+    let mut terminal = Terminal::new("ab\nlonger line\n", 20, 5);
+    terminal
+        .editor
+        .apply(Action::RunText("set virtual_space on".to_string()));
+    terminal.draw();
+
+    terminal.press(KeyCode::Right, 5);
+    assert_eq!(terminal.screen().cursor, (5, 0));
+    assert_eq!(terminal.editor.buffer.text(), "ab\nlonger line\n");
+
+    // The column is kept across lines, shorter or not
+    terminal.press(KeyCode::Down, 1);
+    assert_eq!(terminal.screen().cursor, (5, 1));
+    terminal.press(KeyCode::Up, 1);
+    assert_eq!(terminal.screen().cursor, (5, 0));
+
+    terminal.press(KeyCode::Backspace, 1);
+    terminal.type_text("|");
+    assert_eq!(terminal.editor.buffer.text(), "ab  |\nlonger line\n");
+    assert_eq!(terminal.screen().cursor, (5, 0));
+
+    terminal
+        .editor
+        .apply(Action::RunText("set virtual_space off".to_string()));
+    terminal.draw();
+    terminal.press(KeyCode::Right, 2);
+    assert_eq!(terminal.screen().cursor, (1, 1));
+}
+
+#[test]
+fn rows_past_the_last_line_are_marked() {
+    let mut terminal = Terminal::new("one\ntwo\nthree\n", 20, 6);
+    assert_eq!(terminal.lines()[..5], ["one", "two", "three", "", "~"]);
+
+    // Deleted lines do not stay on the screen
+    terminal.press(KeyCode::Down, 1);
+    terminal.press_with(KeyCode::Right, KeyModifiers::SHIFT, 10);
+    terminal.press(KeyCode::Delete, 1);
+    assert_eq!(terminal.lines()[..5], ["one", "", "~", "~", "~"]);
+    assert_eq!(terminal.editor.buffer.text(), "one\n");
+
+    let empty = Terminal::new("", 20, 4).lines();
+    assert_eq!(empty[..3], ["", "~", "~"]);
+}
+
+#[test]
+fn shortened_lines_are_cleared_to_the_edge_of_their_pane() {
+    let mut left = Buffer::scratch("left", "left\n", 0, 0, 0, 0);
+    let right = Buffer::scratch("right", "a\tlong line\n", 0, 0, 0, 0);
+    let mut split = Split::new(right, false, false);
+    split.layout(&mut left, 0, 0, 21, 5);
+    let mut terminal = Terminal::with_split(left, Some(split), 21, 6);
+
+    terminal.press_with(KeyCode::Char('w'), KeyModifiers::ALT, 1);
+    assert_eq!(terminal.lines()[0], format!("{:10}│a   long l", "left"));
+
+    terminal.press_with(KeyCode::Right, KeyModifiers::SHIFT, 20);
+    terminal.press(KeyCode::Delete, 1);
+    terminal.type_text("\t\tb");
+    assert_eq!(terminal.lines()[0], format!("{:10}│        b", "left"));
+
+    terminal.press(KeyCode::Backspace, 3);
+    assert_eq!(terminal.lines()[0], format!("{:10}│", "left"));
+    assert_eq!(terminal.screen().cell(20, 0).ch, ' ');
+}
+
+#[test]
+fn the_gutter_stays_within_a_narrow_pane() {
+    let mut left = Buffer::scratch("left", "abc\n", 0, 0, 0, 0);
+    let right = Buffer::scratch("right", "def\n", 0, 0, 0, 0);
+    left.gutter_width = 3;
+    let mut split = Split::new(right, false, false);
+    split.layout(&mut left, 0, 0, 5, 3);
+    let mut terminal = Terminal::with_split(left, Some(split), 5, 4);
+
+    assert_eq!(terminal.lines()[0], "  │de");
+}
+
+#[test]
+fn wide_characters_take_two_columns() {
+    let mut terminal = Terminal::new("日本語xy\nab日本語\n", 7, 4);
+    // A character cut by the edge of the view is left blank
+    assert_eq!(terminal.lines()[..2], ["日本語x", "ab日本"]);
+
+    terminal.press(KeyCode::Right, 4);
+    assert_eq!(terminal.screen().cursor, (6, 0));
+    assert_eq!(terminal.lines()[..2], [" 本語xy", "b日本語"]);
+
+    terminal.press(KeyCode::Left, 2);
+    assert_eq!(terminal.screen().cursor, (3, 0));
+    // Down keeps to the column on screen, not to the number of characters
+    terminal.press(KeyCode::Down, 1);
+    assert_eq!(terminal.screen().cursor, (3, 1));
+}
+
+#[test]
+fn very_long_lines_are_drawn_from_the_chunk_in_view() {
+    // Each piece takes 8 columns: the tab goes up to column 4 and the wide characters take two
+    let line = "x\t日本".repeat(2000);
+    let mut terminal = Terminal::new(&format!("{line}\n{line}"), 60, 4);
+    assert!(terminal.lines()[3].contains("`set wrap on`"));
+
+    terminal.press(KeyCode::End, 1);
+    assert_eq!(terminal.editor.buffer.offset_x, 16000 - 59);
+    assert_eq!(terminal.lines()[0], format!(" 本{}", "x   日本".repeat(7)));
+    assert_eq!(terminal.screen().cursor, (59, 0));
+
+    // Down keeps to the column on screen far into the next line
+    terminal.press(KeyCode::Down, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(1, 8000)
+    );
+    terminal.press(KeyCode::Left, 5);
+    assert_eq!(terminal.screen().cursor, (49, 1));
+}
+
+#[test]
+fn long_lines_wrap_on_screen_when_asked() {
+    let mut terminal = Terminal::new("0123456789abcdefghijklmno\nend\n", 10, 6);
+
+    terminal
+        .editor
+        .apply(Action::RunText("set wrap on".to_string()));
+    terminal.draw();
+    // The rows the line goes on over start with the wrap marker
+    assert_eq!(
+        terminal.lines()[..4],
+        ["0123456789", "↪ abcdefgh", "↪ ijklmno", "end"]
+    );
+
+    terminal.press(KeyCode::End, 1);
+    assert_eq!(terminal.screen().cursor, (9, 2));
+    terminal.press(KeyCode::Left, 7);
+    assert_eq!(terminal.screen().cursor, (2, 2));
+    terminal.press(KeyCode::Left, 1);
+    assert_eq!(terminal.screen().cursor, (9, 1));
+    terminal.press(KeyCode::Down, 1);
+    assert_eq!(terminal.screen().cursor, (3, 3));
+
+    // Clicking the marker goes to the first character after it
+    terminal.send(Event::Mouse(MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column: 0,
+        row: 2,
+        modifiers: KeyModifiers::NONE,
+    }));
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 18)
+    );
+
+    terminal
+        .editor
+        .apply(Action::RunText("set wrap_marker >".to_string()));
+    terminal.draw();
+    assert_eq!(
+        terminal.lines()[..4],
+        ["0123456789", ">abcdefghi", ">jklmno", "end"]
+    );
+
+    // The rows of the wrapped line take the room of lines further down
+    terminal.press(KeyCode::Down, 1);
+    terminal.send(Event::Resize(10, 3));
+    assert_eq!(terminal.lines()[..2], [">jklmno", "end"]);
+    assert_eq!(terminal.screen().cursor, (3, 1));
+
+    terminal
+        .editor
+        .apply(Action::RunText("set wrap off".to_string()));
+    terminal.draw();
+    assert_eq!(terminal.lines()[0], "end");
+    // Back to scrolling across
+    terminal.press(KeyCode::Up, 1);
+    assert_eq!(terminal.lines()[..2], ["9abcdefghi", ""]);
+}
+
+#[test]
+fn up_and_down_keep_to_the_column_on_screen() {
+    let mut terminal = Terminal::new("\tx = 1\n    y = 2\nz\nlast line", 20, 6);
+
+    // The tab takes the 4 columns of the spaces below it
+    terminal.press(KeyCode::Right, 1);
+    terminal.press(KeyCode::Down, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(1, 4)
+    );
+
+    // Through a short line and back to the goal column
+    terminal.press(KeyCode::Right, 2);
+    terminal.press(KeyCode::Down, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(2, 1)
+    );
+    terminal.press(KeyCode::Down, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(3, 6)
+    );
+
+    // The end of the last line is reachable, even without a line break after it
+    terminal.press(KeyCode::Up, 3);
+    terminal.press(KeyCode::Right, 3);
+    terminal.press(KeyCode::Down, 3);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(3, 9)
+    );
+    assert_eq!(terminal.editor.buffer.line_length(3), 9);
+
+    // Counting characters instead, as before
+    terminal
+        .editor
+        .apply(Action::RunText("set screen_columns off".to_string()));
+    terminal.draw();
+    terminal
+        .editor
+        .buffer
+        .move_cursor_to_position(Position::new(0, 1));
+    terminal.press(KeyCode::Down, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(1, 1)
+    );
+}
+
+#[test]
+fn pasted_text_is_inserted_as_one_edit() {
+    let mut terminal = Terminal::new("ab\n", 20, 6);
+    terminal.press(KeyCode::Right, 1);
+
+    terminal.send(Event::Paste("日本\r\nx".to_string()));
+    assert_eq!(terminal.editor.buffer.text(), "a日本\nxb\n");
+    assert_eq!(terminal.screen().cursor, (1, 1));
+
+    terminal.press_with(KeyCode::Char('z'), KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.editor.buffer.text(), "ab\n");
+
+    // In the prompt, on a single line
+    terminal.press_with(KeyCode::Char('p'), KeyModifiers::CONTROL, 1);
+    terminal.send(Event::Paste("한국\n어".to_string()));
+    assert!(
+        terminal.lines()[5].ends_with("한국어"),
+        "{:?}",
+        terminal.lines()
+    );
+}
+
+#[test]
+fn quitting_asks_to_save_the_modified_buffers() {
+    let path = temp_path("quit", "a.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "a\n").unwrap();
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 60, 4);
+    let mut terminal = Terminal::with_config(buffer, "", 60, 5);
+
+    terminal.type_text("x");
+    terminal.press_with(KeyCode::Char('q'), KeyModifiers::CONTROL, 1);
+    assert!(!terminal.editor.quit);
+    assert!(terminal.lines()[4].starts_with("Save changes to 1 buffer before quitting"));
+
+    // Esc stays in the editor, no throws the changes away
+    terminal.press(KeyCode::Esc, 1);
+    assert!(!terminal.editor.quit);
+    terminal.press_with(KeyCode::Char('q'), KeyModifiers::CONTROL, 1);
+    terminal.type_text("n");
+    terminal.press(KeyCode::Enter, 1);
+    assert!(terminal.editor.quit);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\n");
+}
+
+#[test]
+fn save_all_reports_the_buffers_it_could_not_save() {
+    let path = temp_path("save-all", "a.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let missing = path.with_file_name("missing/b.txt");
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 60, 4);
+    let mut terminal = Terminal::with_config(buffer, "", 60, 5);
+    let mut hidden = Buffer::from_file(missing.to_str().unwrap(), 0, 0, 60, 4);
+    hidden.insert_ch('b');
+    terminal.editor.hidden_buffers.push(hidden);
+
+    terminal.type_text("a");
+    terminal.editor.apply(Action::RunText("wqa".to_string()));
+    terminal.draw();
+    assert!(!terminal.editor.quit);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "a");
+    assert!(
+        terminal.lines()[4].contains("Not saved : b.txt"),
+        "{:?}",
+        terminal.lines()
+    );
+
+    std::fs::create_dir_all(missing.parent().unwrap()).unwrap();
+    terminal.editor.apply(Action::RunText("wqa".to_string()));
+    assert!(terminal.editor.quit);
+    assert_eq!(std::fs::read_to_string(&missing).unwrap(), "b");
+}
+
+#[test]
+fn quitting_asks_to_save_modified_scratch_buffers() {
+    let mut terminal = Terminal::new("", 60, 5);
+    terminal.type_text("notes");
+    terminal.press_with(KeyCode::Char('q'), KeyModifiers::CONTROL, 1);
+    assert!(terminal.lines()[4].starts_with("Save changes to 1 buffer before quitting"));
+
+    // They cannot be saved, the editor stays open to keep them
+    terminal.type_text("y");
+    terminal.press(KeyCode::Enter, 1);
+    assert!(!terminal.editor.quit);
+    assert!(terminal.lines()[4].contains("Not saved : test (no file)"));
+
+    terminal.press_with(KeyCode::Char('q'), KeyModifiers::CONTROL, 1);
+    terminal.type_text("n");
+    terminal.press(KeyCode::Enter, 1);
+    assert!(terminal.editor.quit);
+
+    // Unless they are printed when quitting
+    let mut terminal = Terminal::new("", 60, 5);
+    terminal.editor.set_filter(true);
+    terminal.type_text("notes");
+    terminal.press_with(KeyCode::Char('q'), KeyModifiers::CONTROL, 1);
+    assert!(terminal.editor.quit);
+}
+
+#[test]
+fn quitting_remembers_the_marks_of_the_other_pane() {
+    let path = temp_path("quit-split", "right.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "one\ntwo\n").unwrap();
+
+    let mut left = Buffer::scratch("left", "", 0, 0, 0, 0);
+    let mut right = Buffer::from_file(path.to_str().unwrap(), 0, 0, 0, 0);
+    right.set_named_mark('a', 4);
+    let mut split = Split::new(right, false, false);
+    split.layout(&mut left, 0, 0, 40, 5);
+    let mut terminal = Terminal::with_split(left, Some(split), 40, 6);
+
+    terminal.editor.apply(Action::ForceQuit);
+    let key = std::fs::canonicalize(&path).unwrap();
+    let marks = &terminal.editor.session.marks[key.to_str().unwrap()];
+    assert_eq!(marks[&'a'], Position::line_start(1));
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn unsaved_changes_are_copied_before_quitting_without_asking() {
+    let path = temp_path("recovery", "notes.txt");
+    let dir = path.parent().unwrap().join("recovery");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "saved\n").unwrap();
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 40, 4);
+    let mut terminal = Terminal::with_config(buffer, "", 40, 5);
+    terminal.type_text("new ");
+    terminal
+        .editor
+        .apply(Action::RunText("scratch".to_string()));
+    terminal.type_text("draft");
+    // Left as they are
+    terminal
+        .editor
+        .apply(Action::RunText("scratch".to_string()));
+
+    let copies = terminal.editor.save_recovery_copies(&dir).unwrap();
+    let read = |copies: &[std::path::PathBuf]| -> Vec<String> {
+        copies
+            .iter()
+            .map(|copy| std::fs::read_to_string(copy).unwrap())
+            .collect()
+    };
+    assert_eq!(read(&copies), ["new saved\n", "draft"]);
+    assert_eq!(copies[0], dir.join(history::path_name(&path)));
+
+    // Earlier copies are kept
+    let again = terminal.editor.save_recovery_copies(&dir).unwrap();
+    assert_eq!(read(&again), ["new saved\n", "draft"]);
+    assert_eq!(
+        again[0],
+        dir.join(format!("{}.2", history::path_name(&path)))
+    );
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 4);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn ctrl_shift_q_quits_without_asking() {
+    let mut terminal = Terminal::new("", 20, 4);
+    terminal.editor.buffer.file_path = Some(temp_path("force-quit", "a.txt"));
+    terminal.type_text("x");
+
+    terminal.press_with(
+        KeyCode::Char('Q'),
+        KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        1,
+    );
+    assert!(terminal.editor.quit);
+}
+
+#[test]
+fn frames_are_only_drawn_after_a_change() {
+    let mut terminal = Terminal::new("abc\n", 20, 4);
+    assert!(!terminal.editor.needs_draw());
+
+    // Nothing happening in the background leaves the screen as it is
+    terminal.editor.update();
+    assert!(!terminal.editor.needs_draw());
+
+    // A burst of events is drawn once
+    terminal.editor.handle_event(Event::Key(KeyEvent::new(
+        KeyCode::Right,
+        KeyModifiers::NONE,
+    )));
+    terminal.editor.handle_event(Event::Key(KeyEvent::new(
+        KeyCode::Right,
+        KeyModifiers::NONE,
+    )));
+    terminal.editor.update();
+    assert!(terminal.editor.needs_draw());
+    terminal.draw();
+    assert!(!terminal.editor.needs_draw());
+    let x = terminal.editor.buffer.x;
+    assert_eq!(terminal.screen().cursor.0, x + 2);
+}
+
+#[test]
+fn a_burst_of_events_is_handled_in_order_before_one_frame() {
+    let mut terminal = Terminal::new("", 20, 5);
+
+    let keys = "ab\ncd"
+        .chars()
+        .map(|ch| match ch {
+            '\n' => KeyCode::Enter,
+            ch => KeyCode::Char(ch),
+        })
+        .chain([KeyCode::Up, KeyCode::End, KeyCode::Char('x')]);
+    for code in keys {
+        terminal
+            .editor
+            .handle_event(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+        terminal.editor.update();
+    }
+    assert_eq!(terminal.lines()[0], "");
+
+    terminal.draw();
+    assert_eq!(terminal.lines()[0], "abx");
+    assert_eq!(terminal.lines()[1], "cd");
+}
+
+#[test]
+fn the_debug_overlay_describes_the_cursor_and_the_last_event() {
+    let mut terminal = Terminal::new("abc\ndef\n", 60, 8);
+
+    terminal.press(KeyCode::Down, 1);
+    terminal.press_with(
+        KeyCode::Char('d'),
+        KeyModifiers::CONTROL | KeyModifiers::ALT,
+        1,
+    );
+    let lines = terminal.lines();
+    assert!(lines[1].starts_with("def"));
+    assert!(lines[1].ends_with(" Cursor 4 Some('d')"));
+    assert!(lines[3].contains(" Line 1 : 4..=7 of 3"));
+    assert!(lines[5].contains(" Event Key(KeyEvent { code: Char('d'),"));
+    // The cursor stays in the buffer
+    assert_eq!(terminal.screen().cursor.1, 1);
+
+    terminal.press_with(
+        KeyCode::Char('d'),
+        KeyModifiers::CONTROL | KeyModifiers::ALT,
+        1,
+    );
+    assert_eq!(terminal.lines()[1], "def");
+}
+
+#[test]
+fn later_layers_paint_over_earlier_ones() {
+    let buffer = Buffer::scratch("spans", "let x = 1;\n", 0, 0, 20, 3);
+    let (red, blue, gray) = (Color::Red, Color::Blue, Color::DarkGrey);
+    let layers = [
+        Layer::new(vec![(0, 3, Paint::Foreground(blue))]),
+        Layer::uniform([(2, 5)], Paint::Background(gray)),
+        Layer::uniform([(4, 5)], Paint::Underlined(red)),
+    ];
+    let base = Style::new(Color::White, Color::Black);
+
+    let span = |text: &str, fg_color, bg_color, underlined| Span {
+        text: text.to_string(),
+        style: Style {
+            fg_color,
+            bg_color,
+            underlined,
+        },
+        width: text.len(),
+    };
+    assert_eq!(
+        line_spans(&buffer, 0, 0, &layers, base),
+        [
+            span("le", blue, Color::Black, false),
+            span("t", blue, gray, false),
+            span(" ", Color::White, gray, false),
+            span("x", red, gray, true),
+            span(" = 1;", Color::White, Color::Black, false),
+        ]
+    );
+}
+
+#[test]
+fn home_and_end_stop_at_the_non_blank_characters_first() {
+    let mut terminal = Terminal::new("    let x = 1;  \nnext\n", 30, 5);
+    terminal.press(KeyCode::Right, 6);
+
+    terminal.press(KeyCode::Home, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 4)
+    );
+    terminal.press(KeyCode::Home, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 0)
+    );
+    terminal.press(KeyCode::Home, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 4)
+    );
+
+    terminal.press(KeyCode::End, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 14)
+    );
+    terminal.press_with(KeyCode::End, KeyModifiers::SHIFT, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 16)
+    );
+    assert_eq!(
+        terminal.editor.buffer.selected_text().as_deref(),
+        Some("  ")
+    );
+
+    // Straight to the start and end without it
+    terminal
+        .editor
+        .apply(Action::RunText("set smart_home off".to_string()));
+    terminal.draw();
+    terminal.press(KeyCode::Home, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 0)
+    );
+    terminal.press(KeyCode::End, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 16)
+    );
+}
+
+#[test]
+fn block_motions_move_past_paragraphs_indentation_and_braces() {
+    let text = "fn a() {\n    if x {\n        y();\n    }\n\n    z();\n}\n\nlast\n";
+    let mut terminal = Terminal::new(text, 50, 12);
+    let position = |terminal: &Terminal| terminal.editor.buffer.cursor_position();
+
+    // Paragraphs end at blank lines
+    terminal.press_with(KeyCode::Char('}'), KeyModifiers::ALT, 1);
+    assert_eq!(position(&terminal), Position::new(4, 0));
+    terminal.press_with(
+        KeyCode::Char('}'),
+        KeyModifiers::ALT | KeyModifiers::SHIFT,
+        1,
+    );
+    assert_eq!(position(&terminal), Position::new(7, 0));
+    terminal.press_with(KeyCode::Char('{'), KeyModifiers::ALT, 1);
+    assert_eq!(position(&terminal), Position::new(4, 0));
+    terminal.press_with(KeyCode::Char('{'), KeyModifiers::ALT, 1);
+    assert_eq!(position(&terminal), Position::new(0, 0));
+
+    // Indentation blocks go on to the line introducing them, and take in blank lines
+    terminal
+        .editor
+        .buffer
+        .move_cursor_to_position(Position::new(2, 8));
+    terminal.press_with(KeyCode::Char('('), KeyModifiers::ALT, 1);
+    assert_eq!(position(&terminal), Position::new(1, 4));
+    terminal.press_with(KeyCode::Char(')'), KeyModifiers::ALT, 1);
+    assert_eq!(position(&terminal), Position::new(5, 4));
+
+    // Blocks of braces skip the nested ones
+    terminal
+        .editor
+        .buffer
+        .move_cursor_to_position(Position::new(2, 8));
+    terminal.press_with(KeyCode::Char('<'), KeyModifiers::ALT, 1);
+    assert_eq!(position(&terminal), Position::new(1, 9));
+    terminal.press_with(KeyCode::Char('<'), KeyModifiers::ALT, 1);
+    assert_eq!(position(&terminal), Position::new(0, 7));
+    terminal.press_with(KeyCode::Char('>'), KeyModifiers::ALT, 1);
+    assert_eq!(position(&terminal), Position::new(6, 0));
+
+    terminal
+        .editor
+        .buffer
+        .move_cursor_to_position(Position::new(8, 0));
+    terminal.press_with(KeyCode::Char('<'), KeyModifiers::ALT, 1);
+    assert_eq!(position(&terminal), Position::new(8, 0));
+    assert!(terminal.lines()[11].contains("Not in a block of braces"));
+}
+
+#[test]
+fn ctrl_home_and_end_go_to_the_start_and_end_of_the_buffer() {
+    let text: Vec<String> = (1..=30).map(|n| n.to_string()).collect();
+    let mut terminal = Terminal::new(&text.join("\n"), 20, 6);
+
+    // Scrolled past the end, the last page is brought back to fill the view
+    terminal.press_with(KeyCode::Down, KeyModifiers::CONTROL, 40);
+    assert_eq!(terminal.lines()[0], "30");
+    terminal.press_with(KeyCode::End, KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.lines()[..5], ["26", "27", "28", "29", "30"]);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(29, 2)
+    );
+
+    terminal.press_with(
+        KeyCode::Home,
+        KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        1,
+    );
+    assert_eq!(terminal.lines()[..2], ["1", "2"]);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 0)
+    );
+    assert_eq!(
+        terminal.editor.buffer.selected_text(),
+        Some(text.join("\n"))
+    );
+}
+
+#[test]
+fn the_last_edit_is_repeated_at_the_cursor() {
+    let mut terminal = Terminal::new("a\nb\nc\nd\n", 30, 8);
+
+    terminal.press_with(KeyCode::Char('r'), KeyModifiers::ALT, 1);
+    assert!(terminal.lines()[7].contains("No edit to repeat"));
+
+    // What was typed and deleted since the cursor last moved
+    terminal.type_text("x!");
+    terminal.press(KeyCode::Backspace, 1);
+    terminal.press(KeyCode::Down, 1);
+    terminal.press(KeyCode::Home, 1);
+    terminal.press_with(KeyCode::Char('r'), KeyModifiers::ALT, 1);
+    assert_eq!(terminal.lines()[..4], ["xa", "xb", "c", "d"]);
+
+    // With a count
+    terminal.press(KeyCode::Down, 1);
+    terminal.press(KeyCode::Home, 1);
+    terminal.press_with(KeyCode::Char('2'), KeyModifiers::ALT, 1);
+    terminal.press_with(KeyCode::Char('r'), KeyModifiers::ALT, 1);
+    assert_eq!(terminal.lines()[..4], ["xa", "xb", "xxc", "d"]);
+
+    // Commands rewriting the text
+    terminal.press_with(KeyCode::Char('p'), KeyModifiers::CONTROL, 1);
+    terminal.type_text("upper");
+    terminal.press(KeyCode::Enter, 1);
+    terminal.press(KeyCode::Down, 1);
+    terminal.press_with(KeyCode::Char('r'), KeyModifiers::ALT, 1);
+    assert_eq!(terminal.lines()[..4], ["xa", "xb", "XXC", "D"]);
+}
+
+#[test]
+fn counts_are_capped_and_stop_once_nothing_changes() {
+    let mut terminal = Terminal::new("one two\nthree\n", 30, 4);
+
+    terminal.press_with(KeyCode::Char('9'), KeyModifiers::ALT, 30);
+    assert!(
+        terminal.lines()[3].starts_with(" 100000×"),
+        "{:?}",
+        terminal.lines()
+    );
+    terminal.press_with(KeyCode::Right, KeyModifiers::CONTROL, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(2, 0)
+    );
+    terminal.press_with(KeyCode::Char('9'), KeyModifiers::ALT, 6);
+    terminal.press(KeyCode::Left, 1);
+    assert_eq!(terminal.editor.buffer.cursor_pos, 0);
+
+    // Deleting until there is nothing left to delete
+    terminal.press(KeyCode::Delete, 1);
+    terminal.press_with(KeyCode::Char('9'), KeyModifiers::ALT, 6);
+    terminal.press_with(KeyCode::Char('r'), KeyModifiers::ALT, 1);
+    assert_eq!(terminal.editor.buffer.text(), "");
+
+    // Counts past the lines of the buffer go to its first or last line
+    let mut buffer = Buffer::scratch("test", "a\nb\n", 0, 0, 30, 3);
+    buffer.move_cursor_down(usize::MAX);
+    assert_eq!(buffer.current_line(), 2);
+    buffer.move_cursor_up(usize::MAX);
+    assert_eq!(buffer.current_line(), 0);
+}
+
+#[test]
+fn a_byte_order_mark_is_hidden_and_kept_on_save() {
+    let path = temp_path("bom", "bom.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "\u{FEFF}h\u{e9}llo\n").unwrap();
+
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 60, 4);
+    let mut terminal = Terminal::with_config(buffer, "", 60, 5);
+    assert_eq!(terminal.lines()[0], "h\u{e9}llo");
+    assert_eq!(terminal.lines()[4].trim(), "bom.txt [BOM]");
+
+    terminal.type_text("x");
+    terminal.press_with(KeyCode::Char('s'), KeyModifiers::CONTROL, 1);
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(saved, "\u{FEFF}xh\u{e9}llo\n");
+
+    // Removed on the next save without the setting
+    terminal
+        .editor
+        .apply(Action::RunText("set keep_bom off".to_string()));
+    terminal.draw();
+    assert!(terminal.lines()[4].starts_with(" bom.txt |"));
+    terminal.type_text("y");
+    terminal.press_with(KeyCode::Char('s'), KeyModifiers::CONTROL, 1);
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(saved, "xyh\u{e9}llo\n");
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn offsets_in_bytes_are_shown_and_jumped_to() {
+    let mut terminal = Terminal::new("a\u{f1}b\nc\n", 60, 5);
+    terminal.press(KeyCode::Right, 2);
+
+    terminal.editor.apply(Action::RunText("offset".to_string()));
+    terminal.draw();
+    assert!(terminal.lines()[4].contains("Offset 3 (0x3) of 7 bytes"));
+
+    terminal
+        .editor
+        .apply(Action::RunText("offset 5".to_string()));
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(1, 0)
+    );
+
+    // Within a character goes to its start
+    terminal
+        .editor
+        .apply(Action::RunText("offset 0x2".to_string()));
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 1)
+    );
+
+    terminal
+        .editor
+        .apply(Action::RunText("offset 99".to_string()));
+    terminal.draw();
+    assert!(terminal.lines()[4].contains("Offset 99 is past the end of the file at 7"));
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 1)
+    );
+}
+
+#[test]
+fn middle_click_pastes_the_last_selection_where_it_is_clicked() {
+    let mut terminal = Terminal::new("one two\nthree\n", 30, 5);
+    let middle_click = |column, row| {
+        Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Middle),
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        })
+    };
+
+    terminal.press_with(KeyCode::Right, KeyModifiers::SHIFT, 3);
+    terminal.press(KeyCode::Esc, 1);
+
+    // Past the end of the line, and not on a line at all
+    terminal.send(middle_click(20, 1));
+    terminal.send(middle_click(0, 3));
+    assert_eq!(terminal.editor.buffer.text(), "one two\nthreeone\n");
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(1, 8)
+    );
+
+    // The selection made since is pasted next
+    terminal.press_with(KeyCode::Left, KeyModifiers::SHIFT, 2);
+    terminal.send(middle_click(4, 0));
+    assert_eq!(terminal.editor.buffer.text(), "one netwo\nthreeone\n");
+}
+
+#[test]
+fn double_and_triple_clicks_select_words_and_lines() {
+    let mut terminal = Terminal::new("one two three\nfour five\n", 30, 5);
+    let mouse = |kind, column, row| {
+        Event::Mouse(MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        })
+    };
+    let down = MouseEventKind::Down(MouseButton::Left);
+    let selected = |terminal: &Terminal| terminal.editor.buffer.selected_text();
+
+    terminal.send(mouse(down, 5, 0));
+    assert_eq!(terminal.editor.buffer.cursor_pos, 5);
+    assert_eq!(selected(&terminal), None);
+
+    terminal.send(mouse(down, 5, 0));
+    assert_eq!(selected(&terminal).as_deref(), Some("two"));
+
+    // Dragging goes on a word at a time
+    terminal.send(mouse(MouseEventKind::Drag(MouseButton::Left), 1, 1));
+    assert_eq!(selected(&terminal).as_deref(), Some("two three\nfour"));
+    terminal.send(mouse(MouseEventKind::Drag(MouseButton::Left), 1, 0));
+    assert_eq!(selected(&terminal).as_deref(), Some("one two"));
+    terminal.send(mouse(MouseEventKind::Up(MouseButton::Left), 1, 0));
+
+    terminal.send(mouse(down, 2, 1));
+    terminal.send(mouse(down, 2, 1));
+    terminal.send(mouse(down, 2, 1));
+    assert_eq!(selected(&terminal).as_deref(), Some("four five\n"));
+}
+
+#[test]
+fn copying_goes_through_the_terminal_without_a_clipboard_tool() {
+    let mut terminal = Terminal::new("hello wörld\n", 60, 5);
+    let copied = |terminal: &mut Terminal| -> Vec<String> {
+        let commands = &terminal.display.output().commands;
+        commands
+            .iter()
+            .filter_map(|command| command.strip_prefix("52;c;"))
+            .map(str::to_string)
+            .collect()
+    };
+
+    terminal.press_with(KeyCode::Char('c'), KeyModifiers::CONTROL, 1);
+    assert!(terminal.lines()[4].contains("Nothing selected to copy, Ctrl + q quits"));
+
+    terminal.press_with(KeyCode::Right, KeyModifiers::SHIFT, 5);
+    terminal.press_with(KeyCode::Char('c'), KeyModifiers::CONTROL, 1);
+    terminal.draw();
+    assert!(terminal.lines()[4].contains("Copied 5 characters"));
+    assert_eq!(copied(&mut terminal), ["aGVsbG8="]);
+    // Written once, the sequence leaves the screen alone
+    terminal.draw();
+    assert_eq!(copied(&mut terminal).len(), 1);
+    assert_eq!(terminal.lines()[0], "hello wörld");
+
+    // Too much for the limit in the config
+    let buffer = Buffer::scratch("test", "hello wörld\n", 0, 0, 60, 4);
+    let mut terminal = Terminal::with_config(buffer, "[clipboard]\nosc52_limit = 8\n", 60, 5);
+    terminal.press_with(KeyCode::Right, KeyModifiers::SHIFT, 8);
+    terminal.press_with(KeyCode::Char('c'), KeyModifiers::CONTROL, 1);
+    assert!(terminal.lines()[4].contains("Too much text to copy through the terminal"));
+    assert!(copied(&mut terminal).is_empty());
+}
+
+#[test]
+fn the_window_title_names_the_active_buffer() {
+    let mut terminal = Terminal::new("text\n", 40, 5);
+    let titles = |terminal: &mut Terminal| -> Vec<String> {
+        let commands = &terminal.display.output().commands;
+        commands
+            .iter()
+            .filter_map(|command| command.strip_prefix("0;"))
+            .map(str::to_string)
+            .collect()
+    };
+    assert_eq!(titles(&mut terminal), ["test — tte"]);
+
+    // Set again only when it changes
+    terminal.type_text("ab");
+    terminal.draw();
+    assert_eq!(titles(&mut terminal), ["test — tte", "test + — tte"]);
+
+    terminal.editor.apply(Action::Run(Command::Scratch));
+    terminal.draw();
+    assert_eq!(titles(&mut terminal)[2..], ["NO NAME — tte"]);
+}
+
+#[test]
+fn focus_changes_save_the_buffers_and_reload_the_files() {
+    let path = temp_path("focus", "notes.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "one\n").unwrap();
+    // Another program writing to the file, later than anything the editor did
+    let change = |text: &str, seconds: u64| {
+        std::fs::write(&path, text).unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(seconds);
+        file.set_modified(later).unwrap();
+    };
+
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 80, 4);
+    let config = "[editor]\nsave_on_focus_lost = true\n";
+    let mut terminal = Terminal::with_config(buffer, config, 80, 5);
+
+    terminal.type_text("x");
+    terminal.send(Event::FocusLost);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "xone\n");
+    assert!(terminal.lines()[4].contains("Saved 1 buffer"));
+
+    // Nothing to lose, the buffer follows the file
+    change("two\n", 2);
+    terminal.send(Event::FocusGained);
+    assert_eq!(terminal.editor.buffer.text(), "two\n");
+    assert!(!terminal.editor.buffer.modified);
+    assert!(terminal.lines()[4].contains("Reloaded notes.txt"));
+
+    // Changes in the buffer are kept until asked to discard them
+    terminal.type_text("y");
+    change("three\n", 4);
+    terminal.send(Event::FocusGained);
+    assert!(terminal.editor.buffer.modified);
+    assert!(terminal.lines()[4].contains("Changed on disk"));
+
+    terminal.editor.apply(Action::Run(Command::Reload));
+    assert_eq!(terminal.editor.buffer.text(), "three\n");
+    assert!(!terminal.editor.buffer.modified);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn releasing_a_key_keeps_the_message_it_showed() {
+    let path = temp_path("release", "notes.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "one\n").unwrap();
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 80, 4);
+    let mut terminal = Terminal::with_config(buffer, "", 80, 5);
+
+    terminal.type_text("x");
+    terminal.press_with(KeyCode::Char('p'), KeyModifiers::CONTROL, 1);
+    terminal.type_text("wa");
+    terminal.press(KeyCode::Enter, 1);
+    assert!(terminal.lines()[4].contains("Saved 1 buffer"));
+
+    // The terminal reports the key going up after the key press that saved
+    let mut release = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+    release.kind = KeyEventKind::Release;
+    terminal.send(Event::Key(release));
+    assert!(terminal.lines()[4].contains("Saved 1 buffer"));
+
+    // The next key press hides it
+    terminal.press(KeyCode::Right, 1);
+    assert!(!terminal.lines()[4].contains("Saved 1 buffer"));
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn saved_versions_are_kept_in_the_history_and_restored() {
+    let path = temp_path("history", "notes.txt");
+    let dir = path.parent().unwrap().to_path_buf();
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(&path, "one\n").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    }
+
+    let config = format!(
+        "[history]\nenabled = true\ndirectory = \"{}\"\nkeep = 2\n",
+        dir.join("history").display()
+    );
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 80, 9);
+    let mut terminal = Terminal::with_config(buffer, &config, 80, 10);
+    let save = |terminal: &mut Terminal, text: &str| {
+        terminal.type_text(text);
+        terminal.press_with(KeyCode::Char('s'), KeyModifiers::CONTROL, 1);
+    };
+
+    // Saving the same text again keeps no new version, the oldest ones go past `keep`
+    save(&mut terminal, "a");
+    save(&mut terminal, "");
+    save(&mut terminal, "b");
+    save(&mut terminal, "c");
+    let history = FileHistory::for_file(&Config::parse(&config).unwrap(), &path)
+        .unwrap()
+        .unwrap();
+    let snapshots = history.snapshots().unwrap();
+    assert_eq!(snapshots.len(), 2);
+
+    // Only the user can open the history, and read the versions of a file only they can read
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode(&snapshots[0].path) & 0o777, 0o600);
+        assert_eq!(mode(snapshots[0].path.parent().unwrap()) & 0o777, 0o700);
+    }
+
+    // At least one version is kept
+    let no_versions = config.replace("keep = 2", "keep = 0");
+    assert_eq!(
+        FileHistory::for_file(&Config::parse(&no_versions).unwrap(), &path).err(),
+        Some("history: `keep` must be at least 1, not 0".to_string())
+    );
+
+    terminal.editor.apply(Action::Run(Command::History));
+    terminal.draw();
+    // Below the tab line
+    let lines = terminal.lines();
+    assert!(lines[1].starts_with("History of notes.txt"), "{lines:?}");
+    assert!(lines[3].trim_start().starts_with("1  "), "{lines:?}");
+    assert!(lines[4].trim_start().starts_with("2  "), "{lines:?}");
+    assert!(lines[4].ends_with("+1 -1"), "{lines:?}");
+
+    // Enter compares the version with the buffer
+    terminal.press(KeyCode::Down, 1);
+    terminal.press(KeyCode::Enter, 1);
+    let lines = terminal.lines();
+    assert!(lines.contains(&"-abone".to_string()), "{lines:?}");
+    assert!(lines.contains(&"+abcone".to_string()), "{lines:?}");
+
+    // r restores it as one edit
+    terminal.press_with(KeyCode::PageUp, KeyModifiers::CONTROL, 1);
+    terminal.press(KeyCode::Char('r'), 1);
+    assert_eq!(terminal.editor.buffer.text(), "abone\n");
+    assert!(terminal.editor.buffer.modified);
+    assert!(terminal.lines()[9].contains("Restored the version of"));
+    terminal.press_with(KeyCode::Char('z'), KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.editor.buffer.text(), "abcone\n");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn programs_cannot_reach_the_terminal_through_their_output() {
+    assert_eq!(
+        shell::printable(
+            "\x1b[1;31merror\x1b[0m: \x1b]0;title\x07bad\x1b]8;;x\x1b\\\r\n\tat\x08\u{9b}2Jx\x7f\n"
+        ),
+        "error: bad\n\tatx\n"
+    );
+
+    // What a command printed is shown in a buffer
+    #[cfg(unix)]
+    {
+        let mut terminal = Terminal::new("", 40, 5);
+        let command = r"!printf '\033[31mred\033[0m\033]0;title\007\r\n'";
+        terminal.editor.apply(Action::RunText(command.to_string()));
+        assert_eq!(terminal.editor.buffer.text().trim_end(), "red");
+
+        // And the lines found by grep
+        let config =
+            "[grep]\ncommand = \"printf 'Cargo.toml:2:\\\\033[31mred\\\\033[0m\\\\n'; true\"\n";
+        let buffer = Buffer::scratch("test", "", 0, 0, 40, 4);
+        let mut terminal = Terminal::with_config(buffer, config, 40, 5);
+        terminal
+            .editor
+            .apply(Action::RunText("grep red".to_string()));
+        terminal.draw();
+        assert!(terminal
+            .editor
+            .buffer
+            .text()
+            .contains("Cargo.toml:2:1: red\n"));
+        terminal.editor.apply(Action::StepEntry { forward: true });
+        terminal.draw();
+        assert!(terminal.lines()[4].ends_with("Entry 1 of 1 : red"));
+    }
+}
+
+#[test]
+fn reindent_follows_brackets_or_the_configured_indenter() {
+    let text = "fn main() {\nlet v = vec![\n1,\n    2,\n];\n        if v.is_empty() { // {\n\
+                println!(\"{{\");\n  } else {\n let c = '}';\n}\n\n  }\n";
+    let mut terminal = Terminal::new(text, 40, 14);
+    terminal.editor.buffer.file_type = FileType::Rust;
+    terminal.press(KeyCode::Down, 5);
+
+    terminal.editor.apply(Action::Run(Command::Reindent));
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "fn main() {\n    let v = vec![\n        1,\n        2,\n    ];\n    if v.is_empty() { // {\n\
+         \x20       println!(\"{{\");\n    } else {\n        let c = '}';\n    }\n\n}\n"
+    );
+    // Still on its line, at its first character
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(5, 4)
+    );
+
+    // One edit, undone at once
+    terminal.press_with(KeyCode::Char('z'), KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.editor.buffer.text(), text);
+
+    // Languages without brackets need an indenter
+    terminal.editor.buffer.file_type = FileType::Python;
+    terminal.editor.apply(Action::Run(Command::Reindent));
+    terminal.draw();
+    let lines = terminal.lines();
+    assert!(lines[13].contains("No indentation rules for"), "{lines:?}");
+
+    let buffer = Buffer::scratch("test", "a\n  b\nc\n", 0, 0, 40, 4);
+    let config = "[indent]\ntext = \"sed 's/^ */> /'\"\n";
+    let mut terminal = Terminal::with_config(buffer, config, 40, 5);
+    terminal.editor.buffer.file_type = FileType::Text;
+    terminal.press_with(KeyCode::Down, KeyModifiers::SHIFT, 2);
+    terminal.editor.apply(Action::Run(Command::Reindent));
+    assert_eq!(terminal.editor.buffer.text(), "> a\n> b\nc\n");
+}
+
+#[test]
+fn dates_are_formatted_like_strftime() {
+    // 2024-02-29 13:05:09 UTC, a Thursday
+    let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_709_211_909);
+    let date = DateTime::utc(time);
+
+    assert_eq!(date.format("%F %T"), "2024-02-29 13:05:09");
+    assert_eq!(
+        date.format("%a %e %b %y, %I:%M %p %z"),
+        "Thu 29 Feb 24, 01:05 PM +0000"
+    );
+    assert_eq!(date.format("%A %B 100%% %Q"), "Thursday February 100% %Q");
+    assert_eq!(
+        DateTime::utc(std::time::UNIX_EPOCH).format("%F"),
+        "1970-01-01"
+    );
+}
+
+#[test]
+fn insert_types_templates_at_the_cursor() {
+    let buffer = Buffer::scratch("notes", "\n", 0, 0, 40, 4);
+    let config = "[templates]\nsign = \"-- {filename}\\n\"\nstamp = \"%%Y\"\n";
+    let mut terminal = Terminal::with_config(buffer, config, 40, 5);
+
+    terminal
+        .editor
+        .apply(Action::RunText("insert sign".to_string()));
+    terminal
+        .editor
+        .apply(Action::RunText("insert stamp".to_string()));
+    assert_eq!(terminal.editor.buffer.text(), "-- notes\n%Y\n");
+
+    terminal
+        .editor
+        .apply(Action::RunText("insert date".to_string()));
+    let text = terminal.editor.buffer.text();
+    let date = text.lines().nth(1).unwrap();
+    assert_eq!(date.len(), "%Y2024-01-31".len(), "{date}");
+
+    terminal
+        .editor
+        .apply(Action::RunText("insert nothing".to_string()));
+    terminal.draw();
+    assert!(terminal.lines()[4].contains("No template called nothing"));
+
+    // Tab completes the name of the template in the command prompt
+    terminal.press_with(KeyCode::Char('p'), KeyModifiers::CONTROL, 1);
+    terminal.type_text("insert da");
+    terminal.press(KeyCode::Tab, 1);
+    assert!(terminal.lines()[4].contains("insert date"));
+}
+
+#[test]
+fn sort_and_uniq_change_the_selected_lines_in_one_edit() {
+    let mut terminal = Terminal::new("b\nitem10\nitem2\nitem2\na\n", 40, 6);
+    let select_middle = |terminal: &mut Terminal| {
+        terminal.press_with(KeyCode::Home, KeyModifiers::CONTROL, 1);
+        terminal.press(KeyCode::Down, 1);
+        terminal.press_with(KeyCode::Down, KeyModifiers::SHIFT, 3);
+    };
+
+    // Numbers are compared by their value
+    select_middle(&mut terminal);
+    terminal.editor.apply(Action::RunText("sort".to_string()));
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "b\nitem2\nitem2\nitem10\na\n"
+    );
+    select_middle(&mut terminal);
+    terminal.editor.apply(Action::RunText("uniq".to_string()));
+    assert_eq!(terminal.editor.buffer.text(), "b\nitem2\nitem10\na\n");
+    terminal.press_with(KeyCode::Char('z'), KeyModifiers::CONTROL, 1);
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "b\nitem2\nitem2\nitem10\na\n"
+    );
+
+    // Without a selection, all the lines
+    terminal.press(KeyCode::Esc, 1);
+    terminal
+        .editor
+        .apply(Action::RunText("sort desc".to_string()));
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "item10\nitem2\nitem2\nb\na\n"
+    );
+    terminal.press_with(KeyCode::Char('z'), KeyModifiers::CONTROL, 1);
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "b\nitem2\nitem2\nitem10\na\n"
+    );
+
+    select_middle(&mut terminal);
+    terminal.editor.apply(Action::RunText("sort".to_string()));
+    terminal.draw();
+    assert_eq!(terminal.lines()[5].trim(), "test | Already sorted");
+}
+
+#[test]
+fn characters_are_inserted_by_codepoint_or_picked_by_name() {
+    let mut terminal = Terminal::new("", 60, 6);
+    terminal.press_with(
+        KeyCode::Char('U'),
+        KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        1,
+    );
+    terminal.type_text("e9");
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.editor.buffer.text(), "é");
+
+    for (input, error) in [
+        ("zz", "Not a hexadecimal codepoint : zz"),
+        ("110000", "Not a valid character : U+110000"),
+    ] {
+        terminal.press_with(KeyCode::Char('u'), KeyModifiers::CONTROL, 1);
+        terminal.type_text(input);
+        terminal.press(KeyCode::Enter, 1);
+        terminal.draw();
+        assert_eq!(terminal.lines()[5].trim(), format!("test | {error}"));
+    }
+
+    // The arrows go through the matches of the picker, en and em dash, up to the last one
+    terminal.editor.apply(Action::RunText("char".to_string()));
+    terminal.type_text("dash");
+    terminal.press(KeyCode::Down, 3);
+    terminal.press(KeyCode::Enter, 1);
+    // Codepoints are searched too
+    terminal.editor.apply(Action::RunText("char".to_string()));
+    terminal.type_text("U+2603");
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.editor.buffer.text(), "é—☃");
+
+    terminal.editor.apply(Action::RunText("char".to_string()));
+    terminal.type_text("no such name");
+    terminal.press(KeyCode::Enter, 1);
+    terminal.draw();
+    assert_eq!(terminal.lines()[5].trim(), "test | No matching character");
+    assert_eq!(terminal.editor.buffer.text(), "é—☃");
+}
+
+#[test]
+fn picked_characters_are_typed_for_the_hooks_and_repeat() {
+    let scripts = scripts(&[("mark", r#"insert("!");"#)]);
+    let buffer = Buffer::scratch("test", "", 0, 0, 40, 3);
+    let config = "[hooks]\non_insert = \"mark\"\n";
+    let mut terminal = Terminal::with_scripts(buffer, config, scripts, 40, 4);
+
+    terminal.press_with(KeyCode::Char('u'), KeyModifiers::CONTROL, 1);
+    terminal.type_text("e9");
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.editor.buffer.text(), "é!");
+
+    terminal.press_with(KeyCode::Char('p'), KeyModifiers::CONTROL, 1);
+    terminal.type_text("char");
+    terminal.press(KeyCode::Enter, 1);
+    terminal.type_text("U+2603");
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.editor.buffer.text(), "é!☃!");
+    terminal.press_with(KeyCode::Char('r'), KeyModifiers::ALT, 1);
+    assert_eq!(terminal.editor.buffer.text(), "é!☃!☃!");
+}
+
+#[test]
+fn unknown_words_are_marked_and_replaced_by_suggestions() {
+    let path = temp_path("spell", "words.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "the\nquick\nquack\nfox\neditor\n").unwrap();
+
+    let dictionary = Dictionary::load(Some(&path)).unwrap();
+    // Any case, and possessives
+    assert!(dictionary.contains("The") && dictionary.contains("editor's"));
+    assert!(!dictionary.contains("fax"));
+    assert_eq!(dictionary.suggestions("Quikc"), ["Quick", "Quack"]);
+    assert_eq!(dictionary.suggestions("FOXX"), ["FOX"]);
+    // Words glued to digits or underscores are identifiers
+    let data: Vec<char> = "the qiuck fox2 snake_case it's".chars().collect();
+    assert_eq!(word_ranges(&data), [(0, 3), (4, 9), (26, 30)]);
+    assert_eq!(dictionary.misspelled_ranges(&data), [(4, 9), (26, 30)]);
+
+    let mut buffer = Buffer::scratch("notes.txt", "the qiuck fox\n", 0, 0, 60, 3);
+    buffer.file_type = FileType::Text;
+    let config = format!(
+        "[spell]\nenabled = true\ndictionary = \"{}\"\n",
+        path.display()
+    );
+    let mut terminal = Terminal::with_config(buffer, &config, 60, 4);
+    terminal.draw();
+    assert_eq!(terminal.editor.buffer.misspelled, [(4, 9)]);
+
+    // F7 goes through the suggestions, then back to the word as it was
+    terminal.press(KeyCode::Right, 5);
+    terminal.press(KeyCode::F(7), 1);
+    assert_eq!(terminal.editor.buffer.text(), "the quick fox\n");
+    assert_eq!(terminal.lines()[3].trim(), "notes.txt | quick (1/2)");
+    terminal.press(KeyCode::F(7), 2);
+    assert_eq!(terminal.editor.buffer.text(), "the qiuck fox\n");
+    assert_eq!(terminal.lines()[3].trim(), "notes.txt | qiuck (original)");
+
+    terminal.press(KeyCode::Home, 1);
+    terminal.press(KeyCode::F(7), 1);
+    assert_eq!(
+        terminal.lines()[3].trim(),
+        "notes.txt | No misspelled word under the cursor"
+    );
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn alt_enter_opens_the_file_a_link_points_to() {
+    let path = temp_path("follow-link", "notes.md");
+    std::fs::create_dir_all(path.parent().unwrap().join("docs")).unwrap();
+    std::fs::write(
+        &path,
+        "[next](docs/next.md#intro)\nhttps://example.com\nmissing.md\n",
+    )
+    .unwrap();
+    std::fs::write(path.parent().unwrap().join("docs/next.md"), "# Next\n").unwrap();
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 60, 4);
+    let mut terminal = Terminal::with_config(buffer, "", 60, 5);
+
+    // Relative to the directory of the file
+    terminal.press(KeyCode::Right, 2);
+    terminal.press_with(KeyCode::Enter, KeyModifiers::ALT, 1);
+    assert_eq!(terminal.editor.buffer.file_name(), "next.md");
+    assert_eq!(terminal.editor.buffer.text(), "# Next\n");
+
+    terminal.press_with(KeyCode::PageUp, KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.editor.buffer.file_name(), "notes.md");
+    terminal.press(KeyCode::Down, 1);
+    terminal.press_with(KeyCode::Enter, KeyModifiers::ALT, 1);
+    assert_eq!(
+        terminal.lines()[4].trim(),
+        "notes.md | Cannot open https://example.com"
+    );
+    terminal.press(KeyCode::Down, 1);
+    terminal.press_with(KeyCode::Enter, KeyModifiers::ALT, 1);
+    assert!(terminal.lines()[4].starts_with(" notes.md | No such file : "));
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn formatters_change_only_the_lines_they_rewrite() {
+    let path = temp_path("format", "notes.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "b\n  a\nc\n").unwrap();
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 60, 4);
+    let config = "[editor]\nformat_on_save = true\n[format]\ntext = \"sed 's/^ *//'\"\n";
+    let mut terminal = Terminal::with_config(buffer, config, 60, 5);
+
+    terminal.press(KeyCode::Down, 2);
+    terminal.press(KeyCode::End, 1);
+    terminal.editor.apply(Action::RunText("format".to_string()));
+    assert_eq!(terminal.editor.buffer.text(), "b\na\nc\n");
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(2, 1)
+    );
+    terminal.press_with(KeyCode::Char('z'), KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.editor.buffer.text(), "b\n  a\nc\n");
+
+    // Before saving
+    terminal.type_text("  ");
+    terminal.press_with(KeyCode::Char('s'), KeyModifiers::CONTROL, 1);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "b\na\nc  \n");
+
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 60, 4);
+    let mut terminal = Terminal::with_config(buffer, "[format]\ntext = \"false\"\n", 60, 5);
+    terminal.editor.apply(Action::RunText("format".to_string()));
+    terminal.draw();
+    assert!(terminal.lines()[4].starts_with(" notes.txt | `false` failed ("));
+
+    // Line breaks written as CRLF are no changes
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 60, 4);
+    let config = "[format]\ntext = \"sed 's/$/\\\\r/'\"\n";
+    let mut terminal = Terminal::with_config(buffer, config, 60, 5);
+    terminal.editor.apply(Action::RunText("format".to_string()));
+    assert_eq!(terminal.editor.buffer.text(), "b\na\nc  \n");
+    assert!(!terminal.editor.buffer.modified);
+
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 60, 4);
+    let mut terminal = Terminal::with_config(buffer, "", 60, 5);
+    terminal.editor.apply(Action::RunText("format".to_string()));
+    terminal.draw();
+    assert!(terminal.lines()[4].starts_with(" notes.txt | No formatter for text"));
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn case_commands_rewrite_the_selection_or_the_word() {
+    let text = "parseHTTPRequest foo_bar straße";
+    for (case, expected) in [
+        (Case::Upper, "PARSEHTTPREQUEST FOO_BAR STRASSE"),
+        (Case::Lower, "parsehttprequest foo_bar straße"),
+        (Case::Title, "Parsehttprequest Foo_Bar Straße"),
+        (Case::Snake, "parse_http_request foo_bar straße"),
+        (Case::Camel, "parseHttpRequest fooBar straße"),
+    ] {
+        assert_eq!(case.apply(text), expected, "{case:?}");
+    }
+
+    // The word under the cursor, even as it gets longer
+    let mut terminal = Terminal::new("straße fooBar\n", 40, 4);
+    terminal.press(KeyCode::Right, 2);
+    terminal.press_with(KeyCode::Char('u'), KeyModifiers::ALT, 1);
+    assert_eq!(terminal.editor.buffer.text(), "STRASSE fooBar\n");
+    assert_eq!(terminal.editor.buffer.cursor_pos, 2);
+    terminal.press_with(KeyCode::Char('l'), KeyModifiers::ALT, 1);
+    assert_eq!(terminal.editor.buffer.text(), "strasse fooBar\n");
+
+    // The selection stays on the rewritten text
+    terminal.press_with(KeyCode::Char('z'), KeyModifiers::CONTROL, 2);
+    terminal.press(KeyCode::Home, 1);
+    terminal.press_with(KeyCode::End, KeyModifiers::SHIFT, 1);
+    terminal.press_with(KeyCode::Char('u'), KeyModifiers::ALT, 1);
+    assert_eq!(
+        terminal.editor.buffer.selected_text().as_deref(),
+        Some("STRASSE FOOBAR")
+    );
+    assert_eq!(terminal.editor.buffer.cursor_pos, 14);
+    for (command, expected) in [
+        ("lower", "strasse foobar"),
+        ("title", "Strasse Foobar"),
+        ("camel", "strasse foobar"),
+    ] {
+        terminal.editor.apply(Action::RunText(command.to_string()));
+        assert_eq!(
+            terminal.editor.buffer.selected_text().as_deref(),
+            Some(expected)
+        );
+    }
+
+    terminal.press_with(KeyCode::Char('z'), KeyModifiers::CONTROL, 4);
+    terminal.press(KeyCode::Esc, 1);
+    terminal.press(KeyCode::End, 1);
+    terminal.press_with(KeyCode::Left, KeyModifiers::CONTROL, 1);
+    terminal.editor.apply(Action::RunText("snake".to_string()));
+    assert_eq!(terminal.editor.buffer.text(), "straße foo_bar\n");
+}
+
+#[test]
+fn json_is_pretty_printed_and_minified() {
+    let text = "{\"a\": [1, 2.50, {}], \"b\" :{\"c\":\"x, y\\\"\"}}\n";
+    let mut terminal = Terminal::new(text, 40, 12);
+    terminal.editor.buffer.file_type = FileType::Json;
+
+    terminal.editor.apply(Action::RunText("json".to_string()));
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "{\n  \"a\": [\n    1,\n    2.50,\n    {}\n  ],\n  \"b\": {\n    \"c\": \"x, y\\\"\"\n  }\n}\n"
+    );
+
+    terminal
+        .editor
+        .apply(Action::RunText("json minify".to_string()));
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "{\"a\":[1,2.50,{}],\"b\":{\"c\":\"x, y\\\"\"}}\n"
+    );
+
+    // Mistakes are pointed at
+    let mut terminal = Terminal::new("{\n  \"a\": 1\n  \"b\": 2\n}\n", 80, 6);
+    terminal.editor.buffer.file_type = FileType::Json;
+    terminal.editor.apply(Action::RunText("json".to_string()));
+    terminal.draw();
+    let lines = terminal.lines();
+    assert!(
+        lines[5].contains("Invalid JSON : Expected ',' or '}' at line 3, column 3"),
+        "{lines:?}"
+    );
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(2, 2)
+    );
+}
+
+#[test]
+fn reflow_rewraps_paragraphs_keeping_their_leaders() {
+    let text = "    // one two three four five six\n    // seven eight\n    //\n    // nine\n";
+    let mut terminal = Terminal::new(text, 40, 8);
+    terminal.editor.buffer.file_type = FileType::Rust;
+    terminal
+        .editor
+        .apply(Action::RunText("set text_width 20".to_string()));
+
+    terminal.press_with(KeyCode::Char('q'), KeyModifiers::ALT, 1);
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "    // one two three\n    // four five six\n    // seven eight\n    //\n    // nine\n"
+    );
+
+    // Joined back as one edit, with the blank comment line kept
+    terminal.press_with(KeyCode::Char('z'), KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.editor.buffer.text(), text);
+    terminal
+        .editor
+        .apply(Action::RunText("set text_width 80".to_string()));
+    terminal.press_with(KeyCode::Down, KeyModifiers::SHIFT, 4);
+    terminal.editor.apply(Action::Run(Command::Reflow));
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "    // one two three four five six seven eight\n    //\n    // nine\n"
+    );
+
+    // List items keep their marker, the lines after it line up with their text
+    let text = "Some words to wrap here\n\n- an item\n  that goes on\n* another item\n";
+    let mut terminal = Terminal::new(text, 40, 8);
+    terminal.editor.buffer.file_type = FileType::Markdown;
+    terminal
+        .editor
+        .apply(Action::RunText("set text_width 12".to_string()));
+    terminal.press_with(KeyCode::End, KeyModifiers::CONTROL | KeyModifiers::SHIFT, 1);
+    terminal.editor.apply(Action::Run(Command::Reflow));
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "Some words\nto wrap here\n\n- an item\n  that goes\n  on\n* another\n  item\n"
+    );
+}
+
+#[test]
+fn compiler_output_is_read_into_quickfix_entries() {
+    let output = "error[E0425]: cannot find value `x` in this scope\n --> src/main.rs:3:5\n  |\n\nsrc/lib.rs:10:2: warning: unused variable\n";
+    let entries = quickfix::build_entries(output);
+    assert_eq!(
+        entries,
+        vec![
+            Entry {
+                location: Location {
+                    path: "src/main.rs".into(),
+                    line: 2,
+                    column: 4,
+                },
+                message: "error[E0425]: cannot find value `x` in this scope".to_string(),
+            },
+            Entry {
+                location: Location {
+                    path: "src/lib.rs".into(),
+                    line: 9,
+                    column: 1,
+                },
+                message: "warning: unused variable".to_string(),
+            },
+        ]
+    );
+
+    // Entries are written as they are read back from the list
+    assert_eq!(
+        entries[1].to_string(),
+        "src/lib.rs:10:2: warning: unused variable"
+    );
+    assert_eq!(
+        Entry::parse(&entries[1].to_string()).as_ref(),
+        Some(&entries[1])
+    );
+
+    let matches = quickfix::grep_entries("./a.txt:4:let x = 1;\nb.txt:2:7:x: y\n");
+    assert_eq!(matches[0].location.path, std::path::PathBuf::from("a.txt"));
+    assert_eq!(
+        (matches[0].location.line, matches[0].location.column),
+        (3, 0)
+    );
+    assert_eq!(matches[1].message, "x: y");
+    assert_eq!(
+        (matches[1].location.line, matches[1].location.column),
+        (1, 6)
+    );
+}
+
+#[test]
+fn f4_goes_through_the_quickfix_list() {
+    let first = temp_path("quickfix", "first.txt");
+    std::fs::create_dir_all(first.parent().unwrap()).unwrap();
+    std::fs::write(&first, "one\ntwo\nthree\n").unwrap();
+
+    let mut buffer = Buffer::from_file(first.to_str().unwrap(), 0, 0, 80, 4);
+    let diagnostic = |line, column, severity, message: &str| Diagnostic {
+        start: Position::new(line, column),
+        end: Position::new(line, column + 1),
+        severity,
+        message: message.to_string(),
+    };
+    buffer.diagnostics = vec![
+        diagnostic(2, 1, Severity::Warning, "later"),
+        diagnostic(1, 0, Severity::Error, "sooner\nmore"),
+    ];
+    let mut terminal = Terminal::with_config(buffer, "", 80, 5);
+
+    terminal.press(KeyCode::F(4), 1);
+    assert!(terminal.lines()[4].contains("No quickfix list"));
+
+    terminal
+        .editor
+        .apply(Action::RunText("diagnostics".to_string()));
+    terminal.draw();
+    let text = terminal.editor.buffer.text();
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines[0].starts_with("2 diagnostics"));
+    assert!(lines[2].ends_with("first.txt:2:1: error: sooner"));
+    assert!(lines[3].ends_with("first.txt:3:2: warning: later"));
+
+    // Enter on an entry jumps to it, F4 goes on from there
+    terminal.press(KeyCode::Down, 3);
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(
+        terminal.editor.buffer.file_path.as_deref(),
+        Some(first.as_path())
+    );
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(2, 1)
+    );
+    terminal.press(KeyCode::F(4), 1);
+    assert!(terminal.lines()[4].contains("No next entry in the quickfix list"));
+
+    terminal.press_with(KeyCode::F(4), KeyModifiers::SHIFT, 1);
+    assert!(terminal.lines()[4].contains("Entry 1 of 2 : error: sooner"));
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(1, 0)
+    );
+
+    // The list comes back with its cursor on the last entry gone to
+    terminal
+        .editor
+        .apply(Action::RunText("quickfix".to_string()));
+    assert!(terminal.editor.buffer.text().starts_with("2 diagnostics"));
+    assert_eq!(terminal.editor.buffer.current_line(), 2);
+
+    std::fs::remove_dir_all(first.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn definitions_and_headings_make_the_outline() {
+    let labels = |text: &str, file_type| -> Vec<String> {
+        outline::symbols(text, file_type)
+            .into_iter()
+            .map(|symbol| symbol.describe())
+            .collect()
+    };
+
+    let rust = "pub(crate) struct Point {\n    x: i32,\n}\n\nimpl fmt::Display for Point {\n    pub const fn new() -> Self {}\n}\n\nconst MAX: usize = 3;\nlet x = 1;\n";
+    assert_eq!(
+        labels(rust, FileType::Rust),
+        [
+            "struct Point  :1",
+            "impl fmt::Display for Point  :5",
+            "  fn new  :6",
+            "const MAX  :9"
+        ]
+    );
+
+    let python = "class Parser:\n    async def parse(self):\n        pass\n";
+    assert_eq!(
+        labels(python, FileType::Python),
+        ["class Parser  :1", "  def parse  :2"]
+    );
+
+    let c = "#include <stdio.h>\nstatic int add(int a, int b) {\n    return add(a, b);\n}\nint decl(void);\nstruct node {\n";
+    assert_eq!(labels(c, FileType::C), ["add  :2", "struct node  :6"]);
+
+    // Code blocks hold no headings
+    let markdown = "# Title\n\n```sh\n# not a heading\n```\n\n## Usage\n";
+    assert_eq!(
+        labels(markdown, FileType::Markdown),
+        ["# Title  :1", "  ## Usage  :7"]
+    );
+
+    // Letters found in order, the closer together the better
+    assert_eq!(outline::fuzzy_score("po", "struct Point"), Some(0));
+    assert_eq!(outline::fuzzy_score("sp", "struct Point"), Some(6));
+    assert_eq!(outline::fuzzy_score("tp", "fn main"), None);
+}
+
+#[test]
+fn alt_o_jumps_to_a_symbol_picked_by_its_letters() {
+    let text = "fn main() {}\n\nstruct Parser;\n\nimpl Parser {\n    fn parse() {}\n}\n";
+    let mut terminal = Terminal::new(text, 60, 8);
+    terminal.editor.buffer.file_type = FileType::Rust;
+
+    terminal.press_with(KeyCode::Char('o'), KeyModifiers::ALT, 1);
+    assert!(terminal.lines()[7].starts_with("Symbol :"));
+    assert!(terminal
+        .lines()
+        .iter()
+        .any(|line| line.contains("struct Parser  :3")));
+
+    // Matches as good as each other keep the order of the buffer
+    terminal.type_text("prs");
+    assert!(!terminal
+        .lines()
+        .iter()
+        .any(|line| line.contains("fn main  :1")));
+    terminal.press(KeyCode::Down, 1);
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(5, 4)
+    );
+
+    terminal.press_with(KeyCode::Char('o'), KeyModifiers::ALT, 1);
+    terminal.type_text("main");
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 0)
+    );
+
+    let mut terminal = Terminal::new("just text\n", 60, 8);
+    terminal.press_with(KeyCode::Char('o'), KeyModifiers::ALT, 1);
+    assert!(terminal.lines()[7].contains("No symbols in this buffer"));
+}
+
+#[test]
+fn rainbow_brackets_color_brackets_by_depth() {
+    let text = "f(a[b{c}], \"(\", ')') // (\n";
+    let mut terminal = Terminal::new(text, 40, 4);
+    terminal.editor.buffer.file_type = FileType::Rust;
+    terminal.draw();
+    assert_eq!(terminal.editor.buffer.highlight_at(1), None);
+
+    terminal
+        .editor
+        .apply(Action::RunText("set rainbow_brackets on".to_string()));
+    terminal.draw();
+    let depth_at = |terminal: &Terminal, idx| match terminal.editor.buffer.highlight_at(idx) {
+        Some(HighlightKind::Bracket(depth)) => Some(depth),
+        _ => None,
+    };
+    let depths: Vec<Option<usize>> = (0..text.len()).map(|i| depth_at(&terminal, i)).collect();
+    assert_eq!(depths[1], Some(0));
+    assert_eq!(depths[3], Some(1));
+    assert_eq!(depths[5], Some(2));
+    assert_eq!(depths[7], Some(2));
+    assert_eq!(depths[8], Some(1));
+    assert_eq!(depths[19], Some(0));
+
+    // Brackets in strings, character literals and comments are left alone
+    assert_eq!(depths.iter().flatten().count(), 6);
+
+    let (x, _) = terminal.screen().cursor;
+    let bracket = terminal.screen().cell(x + 1, 0).fg_color;
+    assert_ne!(bracket, terminal.screen().cell(x + 3, 0).fg_color);
+    assert_eq!(bracket, terminal.screen().cell(x + 19, 0).fg_color);
+}
+
+#[test]
+fn indent_guides_mark_the_tab_stops_of_the_indentation() {
+    let text = "def f():\n    if x:\n        y()\n\n        z()\n";
+    let mut terminal = Terminal::new(text, 30, 7);
+    assert!(!terminal.lines().iter().any(|line| line.contains('│')));
+
+    terminal
+        .editor
+        .apply(Action::RunText("set indent_guides on".to_string()));
+    terminal.draw();
+    let lines = terminal.lines();
+    let (x, _) = terminal.screen().cursor;
+    let guides = |line: &str| -> String { line.chars().skip(x as usize).take(9).collect() };
+    assert_eq!(guides(&lines[0]), "def f():");
+    assert_eq!(guides(&lines[1]), "│   if x:");
+    assert_eq!(guides(&lines[2]), "│   │   y");
+    // The blank line is within the block around it
+    assert_eq!(guides(&lines[3]), "│   │");
+    assert_eq!(guides(&lines[4]), "│   │   z");
+
+    let guide = terminal.screen().cell(x + 4, 2).fg_color;
+    assert_ne!(guide, terminal.screen().cell(x + 8, 2).fg_color);
+}
+
+#[test]
+fn word_separators_decide_where_words_end() {
+    let mut terminal = Terminal::new("kebab-case snake_case\n", 40, 4);
+    terminal.press_with(KeyCode::Right, KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.editor.buffer.cursor_pos, 5);
+
+    // `-` joins words and `_` splits them
+    terminal
+        .editor
+        .apply(Action::RunText("set word_separators _.,".to_string()));
+    terminal.draw();
+    terminal.press(KeyCode::Home, 1);
+    terminal.press_with(KeyCode::Right, KeyModifiers::CONTROL, 2);
+    assert_eq!(terminal.editor.buffer.cursor_pos, 16);
+
+    terminal.press(KeyCode::Home, 1);
+    terminal.press(KeyCode::F(6), 1);
+    assert!(terminal.lines()[3].starts_with("Rename kebab-case to : "));
+}
+
+#[test]
+fn rename_changes_only_the_whole_words() {
+    let mut terminal = Terminal::new("foo = foobar(foo) + barfoo + foo_1;\nfoo\n", 40, 4);
+    terminal.press(KeyCode::F(6), 1);
+    assert!(terminal.lines()[3].starts_with("Rename foo to : "));
+    assert_eq!(
+        terminal.editor.buffer.occurrences,
+        [(0, 3), (13, 16), (36, 39)]
+    );
+
+    terminal.press(KeyCode::Backspace, 3);
+    terminal.type_text("baz");
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "baz = foobar(baz) + barfoo + foo_1;\nbaz\n"
+    );
+    assert_eq!(terminal.lines()[3].trim(), "test | Renamed 3 occurrences");
+
+    // All in one edit
+    terminal.press_with(KeyCode::Char('z'), KeyModifiers::CONTROL, 1);
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "foo = foobar(foo) + barfoo + foo_1;\nfoo\n"
+    );
+}
+
+#[test]
+fn python_and_yaml_lines_are_broken_at_their_block_indentation() {
+    let mut terminal = Terminal::new("", 40, 8);
+    terminal.editor.buffer.file_type = FileType::Python;
+    terminal.type_text("def f(x):  # comment");
+    terminal.press(KeyCode::Enter, 1);
+    terminal.type_text("if x:");
+    terminal.press(KeyCode::Enter, 1);
+    terminal.type_text("return x");
+    terminal.press(KeyCode::Enter, 1);
+    terminal.type_text("y = 1");
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(
+        terminal.editor.buffer.text(),
+        "def f(x):  # comment\n    if x:\n        return x\n    y = 1\n    "
+    );
+
+    // Shift + Tab takes a level off the line, Tab puts it back on the selected lines
+    terminal.type_text("z");
+    terminal.press(KeyCode::BackTab, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(4, 1)
+    );
+    terminal.press_with(KeyCode::Up, KeyModifiers::SHIFT, 1);
+    terminal.press(KeyCode::Tab, 1);
+    assert!(terminal
+        .editor
+        .buffer
+        .text()
+        .ends_with("\n        y = 1\n    z"));
+    terminal.press(KeyCode::BackTab, 2);
+    assert!(terminal.editor.buffer.text().ends_with("\ny = 1\nz"));
+
+    let mut terminal = Terminal::new("", 40, 8);
+    terminal.editor.buffer.file_type = FileType::Yaml;
+    terminal.type_text("services:");
+    terminal.press(KeyCode::Enter, 1);
+    terminal.type_text("web: x");
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.editor.buffer.text(), "services:\n  web: x\n  ");
+
+    // Other file types start new lines at the margin
+    let mut terminal = Terminal::new("", 40, 8);
+    terminal.type_text("a:");
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.editor.buffer.text(), "a:\n");
+}
+
+#[test]
+fn indentation_mixing_tabs_and_spaces_is_marked() {
+    let mut terminal = Terminal::new("if x:\n \tmixed\n\ttabs\n", 40, 5);
+    terminal.editor.buffer.file_type = FileType::Python;
+    terminal.draw();
+
+    let (x, _) = terminal.screen().cursor;
+    let plain = terminal.screen().cell(x, 2).bg_color;
+    assert_ne!(terminal.screen().cell(x, 1).bg_color, plain);
+    assert_eq!(terminal.screen().cell(x + 4, 1).bg_color, plain);
+}
+
+#[test]
+fn config_files_are_read_again_when_they_change() {
+    let path = temp_path("config-watch", ".tte.toml");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let mut watch = ConfigWatch::new(vec![path.clone()]);
+    assert!(!watch.check());
+
+    // Created, then changed
+    std::fs::write(&path, "[editor]\ntab_width = 2\n").unwrap();
+    assert!(watch.check());
+    assert!(!watch.check());
+    let file = std::fs::File::options().write(true).open(&path).unwrap();
+    file.set_modified(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap();
+    assert!(watch.check());
+
+    // Removed
+    std::fs::remove_file(&path).unwrap();
+    assert!(watch.check());
+    assert!(!watch.poll());
+}
+
+#[test]
+fn a_changed_config_applies_without_restarting() {
+    let mut terminal = Terminal::new("one two\n", 40, 4);
+
+    let changed = format!("{TEST_CONFIG}[status]\ncounts = true\n[keys]\n\"ctrl+g\" = \"upper\"\n");
+    terminal.editor.set_config(Config::parse(&changed).unwrap());
+    terminal.draw();
+    assert!(terminal.lines()[3].ends_with("2 words"));
+    terminal.press_with(KeyCode::Char('g'), KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.editor.buffer.text(), "ONE two\n");
+
+    // Bindings with errors leave those there were
+    let broken =
+        format!("{TEST_CONFIG}[keys]\n\"ctrl+g\" = \"lower\"\n\"ctrl+nosuch\" = \"lower\"\n");
+    terminal.editor.set_config(Config::parse(&broken).unwrap());
+    terminal.draw();
+    assert!(terminal.lines()[3].contains("Config error"));
+    assert!(!terminal.lines()[3].ends_with("words"));
+    terminal.press_with(KeyCode::Char('g'), KeyModifiers::CONTROL, 1);
+    assert_eq!(terminal.editor.buffer.text(), "ONE two\n");
+}
+
+#[test]
+fn the_default_config_is_all_commented_out_and_valid() {
+    let path = temp_path("init-config", "tte/config.toml");
+    Config::write_default(&path).unwrap();
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert!(Config::parse(&written).unwrap().section("editor").is_none());
+    assert!(Config::write_default(&path)
+        .unwrap_err()
+        .ends_with("already exists"));
+
+    // Settings are commented out right before their name, explanations after a space
+    let uncommented: String = written
+        .lines()
+        .map(|line| match line.strip_prefix('#') {
+            Some(setting) if !setting.starts_with(' ') => format!("{setting}\n"),
+            _ => format!("{line}\n"),
+        })
+        .collect();
+    let config = Config::parse(&uncommented).unwrap();
+    assert_eq!(config.get_int("editor", "tab_width"), Some(4));
+    assert_eq!(
+        Settings::default().word_separators,
+        config.get_str("editor", "word_separators").unwrap()
+    );
+    assert!(Keymap::from_config(&config).is_ok());
+    assert!(CursorStyles::from_config(&config).is_ok());
+    assert!(ColorSupport::from_name(config.get_str("theme", "colors").unwrap()).is_some());
+}
+
+#[test]
+fn files_go_to_the_directories_of_the_platform() {
+    use std::{ffi::OsString, path::PathBuf};
+
+    let env = |vars: &'static [(&str, &str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| OsString::from(value))
+        }
+    };
+
+    let home = env(&[("HOME", "/home/ada")]);
+    assert_eq!(
+        app_dir(DirKind::Config, false, home),
+        Some(PathBuf::from("/home/ada/.config/tte"))
+    );
+    assert_eq!(
+        app_dir(DirKind::Data, false, home),
+        Some(PathBuf::from("/home/ada/.local/share/tte"))
+    );
+    assert_eq!(
+        app_dir(DirKind::Cache, false, home),
+        Some(PathBuf::from("/home/ada/.cache/tte"))
+    );
+
+    // XDG directories are followed when they are absolute
+    let xdg = env(&[
+        ("HOME", "/home/ada"),
+        ("XDG_CONFIG_HOME", "/etc/ada"),
+        ("XDG_CACHE_HOME", "cache"),
+    ]);
+    assert_eq!(
+        app_dir(DirKind::Config, false, xdg),
+        Some(PathBuf::from("/etc/ada/tte"))
+    );
+    assert_eq!(
+        app_dir(DirKind::Cache, false, xdg),
+        Some(PathBuf::from("/home/ada/.cache/tte"))
+    );
+
+    let windows = env(&[("USERPROFILE", "/Users/ada"), ("APPDATA", "/Roaming")]);
+    assert_eq!(
+        app_dir(DirKind::Config, true, windows),
+        Some(PathBuf::from("/Roaming/tte"))
+    );
+    assert_eq!(
+        app_dir(DirKind::Cache, true, windows),
+        Some(PathBuf::from("/Users/ada/AppData/Local/tte/cache"))
+    );
+    assert_eq!(
+        app_dir(DirKind::Data, true, windows),
+        Some(PathBuf::from("/Users/ada/AppData/Local/tte/data"))
+    );
+    assert_eq!(app_dir(DirKind::Data, false, env(&[])), None);
+}
+
+#[test]
+#[cfg(unix)]
+fn replaced_files_are_copied_to_the_trash_and_written_in_place() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let path = temp_path("trash", "notes.txt");
+    let dir = path.parent().unwrap();
+    let trash = Trash::freedesktop(dir.join("Trash"));
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(&path, "first\n").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    let link = dir.join("link.txt");
+    symlink(&path, &link).unwrap();
+
+    // Through the link, which stays one, and keeping the permissions of the file
+    let kept = trash::replace_file(&link, "second\n", Some(&trash))
+        .unwrap()
+        .unwrap();
+    assert!(std::fs::symlink_metadata(&link).unwrap().is_symlink());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "second\n");
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    assert_eq!(kept, dir.join("Trash/files/notes.txt"));
+    assert_eq!(std::fs::read_to_string(&kept).unwrap(), "first\n");
+    let info = std::fs::read_to_string(dir.join("Trash/info/notes.txt.trashinfo")).unwrap();
+    let target = std::fs::canonicalize(&path).unwrap();
+    assert!(info.starts_with(&format!("[Trash Info]\nPath={}\n", target.display())));
+    assert!(info.contains("\nDeletionDate="));
+
+    // Names already in the trash are kept apart
+    let kept = trash::replace_file(&path, "third\n", Some(&trash))
+        .unwrap()
+        .unwrap();
+    assert_eq!(kept, dir.join("Trash/files/notes.txt.2"));
+    assert_eq!(std::fs::read_to_string(kept).unwrap(), "second\n");
+
+    // New files have nothing to keep
+    let new = dir.join("new.txt");
+    assert_eq!(trash::replace_file(&new, "new\n", Some(&trash)), Ok(None));
+
+    // `w!` copies the file it replaces to the trash
+    let buffer = Buffer::scratch("test", "from the buffer\n", 0, 0, 40, 4);
+    let written = path.display().to_string();
+    assert!(write_part(&buffer, None, &written, false, true, Some(trash.clone())).is_err());
+    let message = write_part(&buffer, None, &written, true, true, Some(trash.clone())).unwrap();
+    assert!(message.ends_with("the file replaced is in the trash"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "from the buffer\n");
+    assert_eq!(
+        std::fs::read_to_string(dir.join("Trash/files/notes.txt.3")).unwrap(),
+        "third\n"
+    );
+    let message = write_part(&buffer, None, &written, true, true, None).unwrap();
+    assert!(message.ends_with("there is no trash to keep the file replaced"));
+    let message = write_part(&buffer, None, &written, true, false, Some(trash)).unwrap();
+    assert!(message.ends_with(&written));
+    assert!(!dir.join("Trash/files/notes.txt.4").exists());
+}
+
+#[test]
+fn a_project_config_runs_programs_only_once_trusted() {
+    let project = "[editor]\ntab_width = 2\n[lsp]\nrust = \"evil\"\n[hooks]\non_open = \"evil\"\n\
+                   [history]\ndirectory = \"~/.ssh\"\n";
+    let mut config = Config::parse("[lsp]\npython = \"pylsp\"\n").unwrap();
+    config.merge_project(Config::parse(project).unwrap(), false);
+    assert_eq!(config.get_int("editor", "tab_width"), Some(2));
+    assert_eq!(config.get_str("lsp", "rust"), None);
+    assert_eq!(config.get_str("lsp", "python"), Some("pylsp"));
+    assert_eq!(config.get_str("hooks", "on_open"), None);
+    // Nor does it write versions of the files where it likes
+    assert_eq!(config.get_str("history", "directory"), None);
+    assert_eq!(config.untrusted(), ["history", "hooks", "lsp"]);
+
+    let list = temp_path("trust", "trusted_projects");
+    let root = list.parent().unwrap().join("project");
+    assert!(!config::is_trusted(&root, &list));
+    config::trust(&root, &list).unwrap();
+    config::trust(&root, &list).unwrap();
+    assert!(config::is_trusted(&root, &list));
+    assert!(!config::is_trusted(&root.join("sub"), &list));
+    assert_eq!(std::fs::read_to_string(&list).unwrap().lines().count(), 1);
+
+    let mut config = Config::default();
+    config.merge_project(Config::parse(project).unwrap(), true);
+    assert_eq!(config.get_str("lsp", "rust"), Some("evil"));
+    assert!(config.untrusted().is_empty());
+}
+
+/// Scripts by name, for `Terminal::with_scripts`
+fn scripts(sources: &[(&str, &str)]) -> HashMap<String, Script> {
+    sources
+        .iter()
+        .map(|(name, source)| (name.to_string(), Script::new(name, source).unwrap()))
+        .collect()
+}
+
+#[test]
+fn scripts_read_and_edit_the_buffer_and_bind_keys() {
+    let scripts = scripts(&[
+        ("init", r#"bind("ctrl+k t", "total");"#),
+        (
+            "total",
+            r#"
+            let sum = 0;
+            for line in lines() {
+                if line != "" { sum += parse_int(line); }
+            }
+            move_to(line_count());
+            insert("total " + sum);
+            let at = cursor();
+            message(`${line(1)} at ${at.line}:${at.column}, "${selection()}" selected`);
+            "#,
+        ),
+        ("broken", "left(-1);"),
+        ("looping", "loop { up(); }"),
+    ]);
+    let buffer = Buffer::scratch("test", "3\n1\n2\n", 0, 0, 80, 5);
+    let mut terminal = Terminal::with_scripts(buffer, "", scripts, 80, 6);
+
+    // `init` bound the keys when the editor started
+    terminal.press_with(KeyCode::Char('k'), KeyModifiers::CONTROL, 1);
+    terminal.press(KeyCode::Char('t'), 1);
+    assert_eq!(terminal.editor.buffer.text(), "3\n1\n2\ntotal 6");
+    assert!(
+        terminal.lines()[5].contains(r#"3 at 4:8, "" selected"#),
+        "{:?}",
+        terminal.lines()
+    );
+
+    terminal.editor.apply(Action::RunText("broken".to_string()));
+    terminal.draw();
+    assert!(
+        terminal.lines()[5].contains("broken: Runtime error: -1 is not a count"),
+        "{:?}",
+        terminal.lines()
+    );
+
+    // A script stuck in a loop is stopped
+    terminal
+        .editor
+        .apply(Action::RunText("looping".to_string()));
+    terminal.draw();
+    assert!(
+        terminal.lines()[5].contains("looping: Script terminated"),
+        "{:?}",
+        terminal.lines()
+    );
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 1)
+    );
+}
+
+#[test]
+fn a_broken_script_does_not_keep_the_others_from_loading() {
+    let dir = temp_path("scripts", "scripts");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("init.rhai"), r#"bind("ctrl+k t", "total");"#).unwrap();
+    std::fs::write(dir.join("broken.rhai"), "let = ;").unwrap();
+    std::fs::write(dir.join("total.rhai"), r#"insert("total");"#).unwrap();
+
+    let (scripts, errors) = script::load_scripts_from(&dir);
+    let mut names: Vec<_> = scripts.keys().map(String::as_str).collect();
+    names.sort();
+    assert_eq!(names, ["init", "total"]);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with("broken: "), "{errors:?}");
+}
+
+#[test]
+fn waiting_for_the_editor_does_not_count_as_running() {
+    let source = "line_count(); line_count(); line_count(); let n = 0; for i in 0..100 { n += i; }";
+    let script = Script::new("slow", source).unwrap();
+    let result = script.run(|_| {
+        std::thread::sleep(std::time::Duration::from_millis(400));
+        Ok(Reply::Number(1))
+    });
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn events_go_to_every_handler_in_the_order_they_subscribed() {
+    fn type_a(event: &EditorEvent, cx: &mut EventContext) -> Result<(), String> {
+        if *event == EditorEvent::CursorMoved {
+            cx.buffer.insert_ch('a');
+        }
+        Ok(())
+    }
+    fn fail(event: &EditorEvent, _: &mut EventContext) -> Result<(), String> {
+        Err(format!("Failed on {event:?}"))
+    }
+    fn type_b(event: &EditorEvent, cx: &mut EventContext) -> Result<(), String> {
+        if let EditorEvent::CharInserted(ch) = *event {
+            cx.buffer.insert_ch(ch);
+        }
+        cx.buffer.insert_ch('b');
+        Ok(())
+    }
+
+    let mut events = EventBus::default();
+    events.subscribe(type_a);
+    events.subscribe(fail);
+    events.subscribe(type_b);
+
+    let config = Config::default();
+    let mut buffer = Buffer::scratch("test", "", 0, 0, 40, 4);
+    let mut message = None;
+    let (mut hidden, mut build, mut split) = (vec![], None, None);
+    let (mut session, mut language_servers) = (Session::default(), HashMap::new());
+    let (scripts, mut outcome) = (HashMap::new(), ScriptOutcome::default());
+    let mut cx = EventContext {
+        config: &config,
+        buffer: &mut buffer,
+        hidden: &mut hidden,
+        build: &mut build,
+        split: &mut split,
+        session: &mut session,
+        language_servers: &mut language_servers,
+        scripts: &scripts,
+        run_hooks: true,
+        outcome: &mut outcome,
+        message: &mut message,
+    };
+
+    // A failing handler does not stop the ones after it
+    events.emit(EditorEvent::CursorMoved, &mut cx);
+    assert_eq!(cx.buffer.text(), "ab");
+    assert_eq!(cx.message.as_deref(), Some("Failed on CursorMoved"));
+    events.emit(EditorEvent::CharInserted('x'), &mut cx);
+    assert_eq!(cx.buffer.text(), "abxb");
+    assert_eq!(cx.message.as_deref(), Some("Failed on CharInserted('x')"));
+}
+
+#[test]
+fn hooks_keep_the_buffer_they_switch_to_and_do_not_run_again_from_their_commands() {
+    let path = temp_path("save-hook", "a.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "a").unwrap();
+    let scripts = scripts(&[
+        ("stamp", r#"move_to(1, 2); insert("."); run("wa");"#),
+        ("notes", r#"run("scratch"); insert("notes");"#),
+    ]);
+    let buffer = Buffer::from_file(path.to_str().unwrap(), 0, 0, 40, 4);
+    let config = "[hooks]\non_save = \"stamp\"\non_open = \"notes\"\n";
+    let mut terminal = Terminal::with_scripts(buffer, config, scripts, 40, 5);
+
+    // The scratch buffer `on_open` switched to is the one shown
+    assert_eq!(terminal.lines()[1], "notes");
+
+    // Saving from the `on_save` hook saves once, without running the hook again
+    terminal.editor.apply(Action::NextBuffer);
+    terminal.type_text("b");
+    terminal.press_with(KeyCode::Char('s'), KeyModifiers::CONTROL, 1);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "b.a");
+    assert!(!terminal.editor.buffer.modified);
+}
+
+/// Two blocks, the second with a block inside it
+const FOLDS_TEXT: &str =
+    "fn a() {\n    one\n    two\n}\nfn b() {\n    if x {\n        three\n    }\n}\n";
+
+#[test]
+fn the_cursor_moves_over_closed_folds() {
+    let mut terminal = Terminal::new(FOLDS_TEXT, 30, 8);
+    terminal.press_with(KeyCode::Char('f'), KeyModifiers::ALT, 1);
+    assert_eq!(terminal.lines()[0], "  +fn a() { ... 2 lines");
+    assert_eq!(terminal.lines()[1], "   }");
+
+    // Down and up skip the hidden lines
+    terminal.press(KeyCode::Down, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::line_start(3)
+    );
+    assert_eq!(terminal.editor.buffer.cursor_xy(), (3, 1));
+    terminal.press(KeyCode::Up, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::line_start(0)
+    );
+
+    // Left and right go from the end of the header to the line after the fold
+    terminal.press(KeyCode::Down, 1);
+    terminal.press(KeyCode::Left, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::new(0, 8)
+    );
+    terminal.press(KeyCode::Right, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::line_start(3)
+    );
+}
+
+#[test]
+fn nested_folds_open_one_at_a_time() {
+    let mut terminal = Terminal::new(FOLDS_TEXT, 30, 8);
+    terminal
+        .editor
+        .buffer
+        .move_cursor_to_position(Position::line_start(5));
+    terminal.press_with(KeyCode::Char('f'), KeyModifiers::ALT, 1);
+    terminal.press(KeyCode::Up, 1);
+    terminal.press_with(KeyCode::Char('f'), KeyModifiers::ALT, 1);
+    assert_eq!(terminal.lines()[4], "  +fn b() { ... 3 lines");
+    assert_eq!(terminal.lines()[5], "   }");
+
+    // Opening the outer fold leaves the inner one closed
+    terminal.press_with(KeyCode::Char('f'), KeyModifiers::ALT, 1);
+    assert_eq!(terminal.lines()[5], "  +    if x { ... 1 line");
+    assert_eq!(terminal.lines()[6], "       }");
+    terminal.press(KeyCode::Down, 2);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::line_start(7)
+    );
+}
+
+#[test]
+fn folds_move_with_the_edits_around_them() {
+    let mut terminal = Terminal::new(FOLDS_TEXT, 30, 8);
+    terminal.press_with(KeyCode::Char('f'), KeyModifiers::ALT, 1);
+
+    // A line inserted before the fold pushes it down
+    terminal.press(KeyCode::Enter, 1);
+    assert_eq!(terminal.editor.buffer.folded_under(1), Some(2));
+    assert_eq!(terminal.lines()[1], "  +fn a() { ... 2 lines");
+
+    // Typing on the header keeps it folded
+    terminal.type_text("// a\n");
+    assert_eq!(terminal.editor.buffer.folded_under(2), Some(2));
+    assert_eq!(terminal.lines()[2], "  +fn a() { ... 2 lines");
+
+    // A line added inside it is hidden with the others
+    let buffer = &mut terminal.editor.buffer;
+    buffer.move_cursor_to_position(Position::line_start(3));
+    buffer.insert_str("    zero\n");
+    buffer.move_cursor_to_position(Position::line_start(2));
+    terminal.draw();
+    assert_eq!(terminal.editor.buffer.folded_under(2), Some(3));
+    assert_eq!(terminal.lines()[2], "  +fn a() { ... 3 lines");
+    assert_eq!(terminal.lines()[3], "   }");
+}
+
+#[test]
+fn wrapped_lines_and_folds_share_the_rows() {
+    let text = "a long line that wraps around\nfn b() {\n    one\n    two\n}\nend\n";
+    let buffer = Buffer::scratch("test", text, 0, 0, 20, 5);
+    let mut terminal = Terminal::with_config(buffer, "[editor]\nwrap = true\n", 20, 6);
+    terminal.press(KeyCode::Down, 1);
+    terminal.press_with(KeyCode::Char('f'), KeyModifiers::ALT, 1);
+    assert_eq!(
+        terminal.lines()[..5],
+        [
+            "   a long line that",
+            "   \u{21aa} wraps around",
+            "  +fn b() { ... 2 li",
+            "   }",
+            "   end",
+        ]
+    );
+
+    // The header is on the row after the wrapped line, the line after the fold below it
+    assert_eq!(terminal.editor.buffer.cursor_xy(), (3, 2));
+    terminal.press(KeyCode::Down, 1);
+    assert_eq!(
+        terminal.editor.buffer.cursor_position(),
+        Position::line_start(4)
+    );
+    assert_eq!(terminal.editor.buffer.cursor_xy(), (3, 3));
+}
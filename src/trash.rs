@@ -0,0 +1,141 @@
+//! Keeping the files tte replaces in the trash of the desktop rather than losing them.
+//!
+//! On Linux and the BSDs the trash follows the Freedesktop trash spec: the file goes to
+//! `Trash/files` in the data directory of the user, with a `.trashinfo` file in `Trash/info`
+//! telling where it came from so file managers can restore it. On macOS it goes to `~/.Trash`.
+//! Windows keeps its Recycle Bin behind system calls, so there is no trash there.
+
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    clock::DateTime,
+    lsp::path_to_uri,
+    paths::{absolute, get_user_home_dir, get_xdg_dir, DirKind},
+};
+
+/// A trash directory files are copied to before being replaced
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trash {
+    dir: PathBuf,
+    /// Whether the trash is laid out the Freedesktop way, with an info file for each file
+    freedesktop: bool,
+}
+
+impl Trash {
+    /// The trash of the user, `None` on platforms where tte cannot reach it
+    pub fn of_user() -> Option<Self> {
+        if cfg!(target_os = "windows") {
+            None
+        } else if cfg!(target_os = "macos") {
+            get_user_home_dir().map(|home| Self {
+                dir: home.join(".Trash"),
+                freedesktop: false,
+            })
+        } else {
+            get_xdg_dir(DirKind::Data).map(|dir| Self::freedesktop(dir.join("Trash")))
+        }
+    }
+
+    /// A trash at `dir` following the Freedesktop trash spec
+    pub fn freedesktop(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            freedesktop: true,
+        }
+    }
+
+    /// Copies the file at `path` into the trash, leaving it where it is, and returns where the
+    /// copy went. The name is kept unless a file of the trash has it already, then a number is
+    /// added to it.
+    pub fn keep_copy(&self, path: &Path, deleted_at: &DateTime) -> io::Result<PathBuf> {
+        let path = absolute(path);
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "not a file"))?
+            .to_string_lossy()
+            .into_owned();
+        let files = if self.freedesktop {
+            self.dir.join("files")
+        } else {
+            self.dir.clone()
+        };
+        fs::create_dir_all(&files)?;
+
+        let mut number = 1;
+        loop {
+            let trashed_name = match number {
+                1 => name.clone(),
+                _ => format!("{name}.{number}"),
+            };
+            number += 1;
+            let trashed = files.join(&trashed_name);
+            if trashed.exists() {
+                continue;
+            }
+            if !self.freedesktop {
+                fs::copy(&path, &trashed)?;
+                return Ok(trashed);
+            }
+
+            // Creating the info file first reserves the name, as the spec asks
+            let info = self.dir.join("info");
+            fs::create_dir_all(&info)?;
+            let info_path = info.join(format!("{trashed_name}.trashinfo"));
+            let mut info_file = match File::create_new(&info_path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            };
+
+            let uri = path_to_uri(&path);
+            let copied = write!(
+                info_file,
+                "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                uri.trim_start_matches("file://"),
+                deleted_at.format("%FT%T")
+            )
+            .and_then(|_| fs::copy(&path, &trashed));
+            if let Err(e) = copied {
+                let _ = fs::remove_file(&info_path);
+                return Err(e);
+            }
+
+            return Ok(trashed);
+        }
+    }
+}
+
+/// Replaces the contents of the file at `path` with `text`, keeping a copy of what it held in
+/// `trash` first. The file is written in place, through a link if it is one, so it keeps its
+/// permissions and owner. Returns where the copy went.
+pub fn replace_file(
+    path: &Path,
+    text: &str,
+    trash: Option<&Trash>,
+) -> Result<Option<PathBuf>, String> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    let kept = match trash {
+        Some(trash) if target.is_file() => Some(
+            trash
+                .keep_copy(&target, &DateTime::now())
+                .map_err(|e| format!("Failed to copy {} to the trash : {e}", path.display()))?,
+        ),
+        _ => None,
+    };
+
+    fs::write(&target, text).map_err(|e| match &kept {
+        Some(kept) => format!(
+            "Failed to write {} : {e}, it was kept at {}",
+            path.display(),
+            kept.display()
+        ),
+        None => format!("Failed to write {} : {e}", path.display()),
+    })?;
+
+    Ok(kept)
+}
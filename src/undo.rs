@@ -0,0 +1,261 @@
+#![allow(dead_code)]
+use std::time::{Duration, SystemTime};
+
+/// Start of the name of the scratch buffers browsing an undo tree
+pub const UNDO_BUFFER_PREFIX: &str = "[Undo]";
+
+/// A single change to the buffer data: `removed` was replaced by `inserted` at `start`
+#[derive(Debug, Clone)]
+pub struct Edit {
+    pub start: usize,
+    pub removed: Vec<char>,
+    pub inserted: Vec<char>,
+}
+
+impl Edit {
+    /// The edit undoing this one
+    pub fn inverse(&self) -> Self {
+        Self {
+            start: self.start,
+            removed: self.inserted.clone(),
+            inserted: self.removed.clone(),
+        }
+    }
+
+    /// Returns the typed character if this edit only inserts one character
+    fn typed_char(&self) -> Option<char> {
+        match (self.removed.as_slice(), self.inserted.as_slice()) {
+            ([], [ch]) => Some(*ch),
+            _ => None,
+        }
+    }
+}
+
+/// A state of the buffer, reached from its parent by applying `edits`
+pub struct UndoNode {
+    pub parent: usize,
+    pub children: Vec<usize>,
+    pub edits: Vec<Edit>,
+    /// Cursor position before the edits were applied
+    pub cursor_before: usize,
+    /// Cursor position after the edits were applied
+    pub cursor_after: usize,
+    /// When the node was last changed
+    pub time: SystemTime,
+    /// The child redo goes to, the branch that was visited last
+    redo_child: Option<usize>,
+}
+
+/// History of the changes to a buffer.
+/// Undoing and then editing starts a new branch instead of discarding the undone changes,
+/// so every state the buffer was ever in can be reached again.
+pub struct UndoTree {
+    /// Node 0 is the state the buffer was loaded in
+    pub nodes: Vec<UndoNode>,
+    /// The node matching the current buffer data
+    pub current: usize,
+    /// Edits made since the last commit
+    pending: Vec<Edit>,
+    /// Cursor position before the first pending edit
+    pending_cursor: usize,
+}
+
+/// Typing less than this long after the previous key press joins the same undo step
+const TYPING_TIMEOUT: Duration = Duration::from_secs(2);
+
+impl Default for UndoTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UndoTree {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![UndoNode {
+                parent: 0,
+                children: vec![],
+                edits: vec![],
+                cursor_before: 0,
+                cursor_after: 0,
+                time: SystemTime::now(),
+                redo_child: None,
+            }],
+            current: 0,
+            pending: vec![],
+            pending_cursor: 0,
+        }
+    }
+
+    /// Records an edit already applied to the buffer, `cursor` is the position before it
+    pub fn record(&mut self, edit: Edit, cursor: usize) {
+        if self.pending.is_empty() {
+            self.pending_cursor = cursor;
+        }
+        self.pending.push(edit);
+    }
+
+    /// Turns the pending edits into one undo step. `cursor` is the position after them.
+    /// Consecutive typing is joined into a single step, up to the end of a word.
+    pub fn commit(&mut self, cursor: usize) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let edits = std::mem::take(&mut self.pending);
+
+        if self.continues_typing(&edits) {
+            let node = &mut self.nodes[self.current];
+            node.edits.extend(edits);
+            node.cursor_after = cursor;
+            node.time = SystemTime::now();
+            return;
+        }
+
+        let id = self.nodes.len();
+        self.nodes.push(UndoNode {
+            parent: self.current,
+            children: vec![],
+            edits,
+            cursor_before: self.pending_cursor,
+            cursor_after: cursor,
+            time: SystemTime::now(),
+            redo_child: None,
+        });
+
+        let parent = &mut self.nodes[self.current];
+        parent.children.push(id);
+        parent.redo_child = Some(id);
+
+        self.current = id;
+    }
+
+    /// Returns true if `edits` just types the next character after the current node's typing
+    fn continues_typing(&self, edits: &[Edit]) -> bool {
+        let node = &self.nodes[self.current];
+        let ([edit], Some(last)) = (edits, node.edits.last()) else {
+            return false;
+        };
+
+        self.current != 0
+            && node.children.is_empty()
+            && node.time.elapsed().is_ok_and(|t| t < TYPING_TIMEOUT)
+            && edit.typed_char().is_some()
+            && last.typed_char().is_some_and(|c| !c.is_whitespace())
+            && edit.start == last.start + 1
+    }
+
+    /// Moves to the parent state.
+    /// Returns the edits to apply to the buffer, in order, and the cursor position to restore.
+    pub fn undo(&mut self) -> Option<(Vec<Edit>, usize)> {
+        if self.current == 0 {
+            return None;
+        }
+
+        let node = &self.nodes[self.current];
+        let edits = node.edits.iter().rev().map(Edit::inverse).collect();
+        let cursor = node.cursor_before;
+
+        let child = self.current;
+        self.current = node.parent;
+        self.nodes[self.current].redo_child = Some(child);
+
+        Some((edits, cursor))
+    }
+
+    /// Moves to the child state visited last.
+    /// Returns the edits to apply to the buffer, in order, and the cursor position to restore.
+    pub fn redo(&mut self) -> Option<(Vec<Edit>, usize)> {
+        let child = self.nodes[self.current].redo_child?;
+        self.current = child;
+
+        let node = &self.nodes[child];
+        Some((node.edits.clone(), node.cursor_after))
+    }
+
+    /// Moves to the state `target`, undoing up to the closest common state and redoing down from it.
+    /// Returns the edits to apply to the buffer, in order, and the cursor position to restore.
+    pub fn jump(&mut self, target: usize) -> Option<(Vec<Edit>, usize)> {
+        if target >= self.nodes.len() || target == self.current {
+            return None;
+        }
+
+        let target_path = self.path_from_root(target);
+        let mut edits = vec![];
+        let mut cursor = self.nodes[self.current].cursor_after;
+
+        while !target_path.contains(&self.current) {
+            let (undone, c) = self.undo()?;
+            edits.extend(undone);
+            cursor = c;
+        }
+
+        let common = target_path.iter().position(|n| *n == self.current)?;
+        for node in &target_path[common + 1..] {
+            self.nodes[self.current].redo_child = Some(*node);
+            let (redone, c) = self.redo()?;
+            edits.extend(redone);
+            cursor = c;
+        }
+
+        Some((edits, cursor))
+    }
+
+    /// Nodes from the root down to `node`, both included
+    fn path_from_root(&self, mut node: usize) -> Vec<usize> {
+        let mut path = vec![node];
+        while node != 0 {
+            node = self.nodes[node].parent;
+            path.push(node);
+        }
+        path.reverse();
+        path
+    }
+
+    /// One line per state, depth first, with branches indented below the state they start from.
+    /// Each line starts with the node id, `*` marks the current state.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![];
+        let mut stack = vec![(0, 0, false)];
+
+        while let Some((id, depth, branch)) = stack.pop() {
+            let node = &self.nodes[id];
+            let marker = if id == self.current { '*' } else { ' ' };
+            let mut summary = if id == 0 {
+                "original".to_string()
+            } else {
+                let inserted: usize = node.edits.iter().map(|e| e.inserted.len()).sum();
+                let removed: usize = node.edits.iter().map(|e| e.removed.len()).sum();
+                format!("+{inserted} -{removed}")
+            };
+            if branch {
+                summary.push_str(&format!("  (branch from {})", node.parent));
+            }
+
+            lines.push(format!(
+                "{id:>4} {marker} {}{}  {summary}",
+                "  ".repeat(depth),
+                format_age(node.time),
+            ));
+
+            // The first child continues the current branch, the others are drawn indented
+            for (i, child) in node.children.iter().enumerate().rev() {
+                stack.push((*child, depth + usize::from(i > 0), i > 0));
+            }
+        }
+
+        lines
+    }
+}
+
+/// Formats how long ago `time` was, e.g. `5m ago`
+pub fn format_age(time: SystemTime) -> String {
+    let secs = time.elapsed().map(|d| d.as_secs()).unwrap_or(0);
+
+    match secs {
+        0..60 => format!("{secs}s ago"),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
@@ -0,0 +1,293 @@
+/// Characters offered by the character picker, with their Unicode names
+pub const CHARACTERS: &[(char, &str)] = &[
+    // Punctuation
+    ('\u{00A0}', "NO-BREAK SPACE"),
+    ('\u{00A1}', "INVERTED EXCLAMATION MARK"),
+    ('\u{00BF}', "INVERTED QUESTION MARK"),
+    ('\u{00A7}', "SECTION SIGN"),
+    ('\u{00B6}', "PILCROW SIGN"),
+    ('\u{00B7}', "MIDDLE DOT"),
+    ('\u{00AB}', "LEFT-POINTING DOUBLE ANGLE QUOTATION MARK"),
+    ('\u{00BB}', "RIGHT-POINTING DOUBLE ANGLE QUOTATION MARK"),
+    ('\u{2010}', "HYPHEN"),
+    ('\u{2013}', "EN DASH"),
+    ('\u{2014}', "EM DASH"),
+    ('\u{2018}', "LEFT SINGLE QUOTATION MARK"),
+    ('\u{2019}', "RIGHT SINGLE QUOTATION MARK"),
+    ('\u{201C}', "LEFT DOUBLE QUOTATION MARK"),
+    ('\u{201D}', "RIGHT DOUBLE QUOTATION MARK"),
+    ('\u{2020}', "DAGGER"),
+    ('\u{2021}', "DOUBLE DAGGER"),
+    ('\u{2022}', "BULLET"),
+    ('\u{2026}', "HORIZONTAL ELLIPSIS"),
+    ('\u{2030}', "PER MILLE SIGN"),
+    ('\u{2032}', "PRIME"),
+    ('\u{2033}', "DOUBLE PRIME"),
+    ('\u{203D}', "INTERROBANG"),
+    // Symbols
+    ('\u{00A9}', "COPYRIGHT SIGN"),
+    ('\u{00AE}', "REGISTERED SIGN"),
+    ('\u{2122}', "TRADE MARK SIGN"),
+    ('\u{00B0}', "DEGREE SIGN"),
+    ('\u{2103}', "DEGREE CELSIUS"),
+    ('\u{2109}', "DEGREE FAHRENHEIT"),
+    ('\u{2116}', "NUMERO SIGN"),
+    ('\u{2605}', "BLACK STAR"),
+    ('\u{2606}', "WHITE STAR"),
+    ('\u{2665}', "BLACK HEART SUIT"),
+    ('\u{2660}', "BLACK SPADE SUIT"),
+    ('\u{2663}', "BLACK CLUB SUIT"),
+    ('\u{2666}', "BLACK DIAMOND SUIT"),
+    ('\u{2713}', "CHECK MARK"),
+    ('\u{2714}', "HEAVY CHECK MARK"),
+    ('\u{2717}', "BALLOT X"),
+    ('\u{2718}', "HEAVY BALLOT X"),
+    ('\u{2610}', "BALLOT BOX"),
+    ('\u{2611}', "BALLOT BOX WITH CHECK"),
+    ('\u{2612}', "BALLOT BOX WITH X"),
+    ('\u{263A}', "WHITE SMILING FACE"),
+    ('\u{2600}', "BLACK SUN WITH RAYS"),
+    ('\u{2601}', "CLOUD"),
+    ('\u{2602}', "UMBRELLA"),
+    ('\u{2603}', "SNOWMAN"),
+    ('\u{26A0}', "WARNING SIGN"),
+    ('\u{26A1}', "HIGH VOLTAGE SIGN"),
+    ('\u{2318}', "PLACE OF INTEREST SIGN"),
+    ('\u{2325}', "OPTION KEY"),
+    ('\u{21E7}', "UPWARDS WHITE ARROW"),
+    ('\u{238B}', "BROKEN CIRCLE WITH NORTHWEST ARROW"),
+    ('\u{23CE}', "RETURN SYMBOL"),
+    ('\u{232B}', "ERASE TO THE LEFT"),
+    // Currency
+    ('\u{00A2}', "CENT SIGN"),
+    ('\u{00A3}', "POUND SIGN"),
+    ('\u{00A5}', "YEN SIGN"),
+    ('\u{20AC}', "EURO SIGN"),
+    ('\u{20B9}', "INDIAN RUPEE SIGN"),
+    ('\u{20BD}', "RUBLE SIGN"),
+    ('\u{20A9}', "WON SIGN"),
+    ('\u{20BF}', "BITCOIN SIGN"),
+    // Arrows
+    ('\u{2190}', "LEFTWARDS ARROW"),
+    ('\u{2191}', "UPWARDS ARROW"),
+    ('\u{2192}', "RIGHTWARDS ARROW"),
+    ('\u{2193}', "DOWNWARDS ARROW"),
+    ('\u{2194}', "LEFT RIGHT ARROW"),
+    ('\u{2195}', "UP DOWN ARROW"),
+    ('\u{21B5}', "DOWNWARDS ARROW WITH CORNER LEFTWARDS"),
+    ('\u{21D0}', "LEFTWARDS DOUBLE ARROW"),
+    ('\u{21D2}', "RIGHTWARDS DOUBLE ARROW"),
+    ('\u{21D4}', "LEFT RIGHT DOUBLE ARROW"),
+    ('\u{27F6}', "LONG RIGHTWARDS ARROW"),
+    // Math
+    ('\u{00B1}', "PLUS-MINUS SIGN"),
+    ('\u{00D7}', "MULTIPLICATION SIGN"),
+    ('\u{00F7}', "DIVISION SIGN"),
+    ('\u{00AC}', "NOT SIGN"),
+    ('\u{00B2}', "SUPERSCRIPT TWO"),
+    ('\u{00B3}', "SUPERSCRIPT THREE"),
+    ('\u{00B9}', "SUPERSCRIPT ONE"),
+    ('\u{00BC}', "VULGAR FRACTION ONE QUARTER"),
+    ('\u{00BD}', "VULGAR FRACTION ONE HALF"),
+    ('\u{00BE}', "VULGAR FRACTION THREE QUARTERS"),
+    ('\u{2200}', "FOR ALL"),
+    ('\u{2202}', "PARTIAL DIFFERENTIAL"),
+    ('\u{2203}', "THERE EXISTS"),
+    ('\u{2205}', "EMPTY SET"),
+    ('\u{2207}', "NABLA"),
+    ('\u{2208}', "ELEMENT OF"),
+    ('\u{2209}', "NOT AN ELEMENT OF"),
+    ('\u{220F}', "N-ARY PRODUCT"),
+    ('\u{2211}', "N-ARY SUMMATION"),
+    ('\u{2212}', "MINUS SIGN"),
+    ('\u{2218}', "RING OPERATOR"),
+    ('\u{221A}', "SQUARE ROOT"),
+    ('\u{221D}', "PROPORTIONAL TO"),
+    ('\u{221E}', "INFINITY"),
+    ('\u{2227}', "LOGICAL AND"),
+    ('\u{2228}', "LOGICAL OR"),
+    ('\u{2229}', "INTERSECTION"),
+    ('\u{222A}', "UNION"),
+    ('\u{222B}', "INTEGRAL"),
+    ('\u{2248}', "ALMOST EQUAL TO"),
+    ('\u{2260}', "NOT EQUAL TO"),
+    ('\u{2261}', "IDENTICAL TO"),
+    ('\u{2264}', "LESS-THAN OR EQUAL TO"),
+    ('\u{2265}', "GREATER-THAN OR EQUAL TO"),
+    ('\u{2282}', "SUBSET OF"),
+    ('\u{2283}', "SUPERSET OF"),
+    ('\u{2286}', "SUBSET OF OR EQUAL TO"),
+    ('\u{2287}', "SUPERSET OF OR EQUAL TO"),
+    ('\u{2295}', "CIRCLED PLUS"),
+    ('\u{2297}', "CIRCLED TIMES"),
+    ('\u{22A5}', "UP TACK"),
+    ('\u{22C5}', "DOT OPERATOR"),
+    ('\u{2308}', "LEFT CEILING"),
+    ('\u{2309}', "RIGHT CEILING"),
+    ('\u{230A}', "LEFT FLOOR"),
+    ('\u{230B}', "RIGHT FLOOR"),
+    ('\u{27E8}', "MATHEMATICAL LEFT ANGLE BRACKET"),
+    ('\u{27E9}', "MATHEMATICAL RIGHT ANGLE BRACKET"),
+    ('\u{2115}', "DOUBLE-STRUCK CAPITAL N"),
+    ('\u{211A}', "DOUBLE-STRUCK CAPITAL Q"),
+    ('\u{211D}', "DOUBLE-STRUCK CAPITAL R"),
+    ('\u{2124}', "DOUBLE-STRUCK CAPITAL Z"),
+    ('\u{2102}', "DOUBLE-STRUCK CAPITAL C"),
+    // Greek
+    ('\u{03B1}', "GREEK SMALL LETTER ALPHA"),
+    ('\u{03B2}', "GREEK SMALL LETTER BETA"),
+    ('\u{03B3}', "GREEK SMALL LETTER GAMMA"),
+    ('\u{03B4}', "GREEK SMALL LETTER DELTA"),
+    ('\u{03B5}', "GREEK SMALL LETTER EPSILON"),
+    ('\u{03B6}', "GREEK SMALL LETTER ZETA"),
+    ('\u{03B7}', "GREEK SMALL LETTER ETA"),
+    ('\u{03B8}', "GREEK SMALL LETTER THETA"),
+    ('\u{03B9}', "GREEK SMALL LETTER IOTA"),
+    ('\u{03BA}', "GREEK SMALL LETTER KAPPA"),
+    ('\u{03BB}', "GREEK SMALL LETTER LAMDA"),
+    ('\u{03BC}', "GREEK SMALL LETTER MU"),
+    ('\u{03BD}', "GREEK SMALL LETTER NU"),
+    ('\u{03BE}', "GREEK SMALL LETTER XI"),
+    ('\u{03BF}', "GREEK SMALL LETTER OMICRON"),
+    ('\u{03C0}', "GREEK SMALL LETTER PI"),
+    ('\u{03C1}', "GREEK SMALL LETTER RHO"),
+    ('\u{03C3}', "GREEK SMALL LETTER SIGMA"),
+    ('\u{03C4}', "GREEK SMALL LETTER TAU"),
+    ('\u{03C5}', "GREEK SMALL LETTER UPSILON"),
+    ('\u{03C6}', "GREEK SMALL LETTER PHI"),
+    ('\u{03C7}', "GREEK SMALL LETTER CHI"),
+    ('\u{03C8}', "GREEK SMALL LETTER PSI"),
+    ('\u{03C9}', "GREEK SMALL LETTER OMEGA"),
+    ('\u{0393}', "GREEK CAPITAL LETTER GAMMA"),
+    ('\u{0394}', "GREEK CAPITAL LETTER DELTA"),
+    ('\u{0398}', "GREEK CAPITAL LETTER THETA"),
+    ('\u{039B}', "GREEK CAPITAL LETTER LAMDA"),
+    ('\u{03A0}', "GREEK CAPITAL LETTER PI"),
+    ('\u{03A3}', "GREEK CAPITAL LETTER SIGMA"),
+    ('\u{03A6}', "GREEK CAPITAL LETTER PHI"),
+    ('\u{03A8}', "GREEK CAPITAL LETTER PSI"),
+    ('\u{03A9}', "GREEK CAPITAL LETTER OMEGA"),
+    // Latin letters with diacritics
+    ('\u{00E0}', "LATIN SMALL LETTER A WITH GRAVE"),
+    ('\u{00E1}', "LATIN SMALL LETTER A WITH ACUTE"),
+    ('\u{00E2}', "LATIN SMALL LETTER A WITH CIRCUMFLEX"),
+    ('\u{00E3}', "LATIN SMALL LETTER A WITH TILDE"),
+    ('\u{00E4}', "LATIN SMALL LETTER A WITH DIAERESIS"),
+    ('\u{00E5}', "LATIN SMALL LETTER A WITH RING ABOVE"),
+    ('\u{00E6}', "LATIN SMALL LETTER AE"),
+    ('\u{00E7}', "LATIN SMALL LETTER C WITH CEDILLA"),
+    ('\u{00E8}', "LATIN SMALL LETTER E WITH GRAVE"),
+    ('\u{00E9}', "LATIN SMALL LETTER E WITH ACUTE"),
+    ('\u{00EA}', "LATIN SMALL LETTER E WITH CIRCUMFLEX"),
+    ('\u{00EB}', "LATIN SMALL LETTER E WITH DIAERESIS"),
+    ('\u{00ED}', "LATIN SMALL LETTER I WITH ACUTE"),
+    ('\u{00EF}', "LATIN SMALL LETTER I WITH DIAERESIS"),
+    ('\u{00F1}', "LATIN SMALL LETTER N WITH TILDE"),
+    ('\u{00F3}', "LATIN SMALL LETTER O WITH ACUTE"),
+    ('\u{00F4}', "LATIN SMALL LETTER O WITH CIRCUMFLEX"),
+    ('\u{00F6}', "LATIN SMALL LETTER O WITH DIAERESIS"),
+    ('\u{00F8}', "LATIN SMALL LETTER O WITH STROKE"),
+    ('\u{0153}', "LATIN SMALL LIGATURE OE"),
+    ('\u{00DF}', "LATIN SMALL LETTER SHARP S"),
+    ('\u{00FA}', "LATIN SMALL LETTER U WITH ACUTE"),
+    ('\u{00FC}', "LATIN SMALL LETTER U WITH DIAERESIS"),
+    ('\u{00FD}', "LATIN SMALL LETTER Y WITH ACUTE"),
+    ('\u{0107}', "LATIN SMALL LETTER C WITH ACUTE"),
+    ('\u{010D}', "LATIN SMALL LETTER C WITH CARON"),
+    ('\u{0142}', "LATIN SMALL LETTER L WITH STROKE"),
+    ('\u{0161}', "LATIN SMALL LETTER S WITH CARON"),
+    ('\u{017E}', "LATIN SMALL LETTER Z WITH CARON"),
+    ('\u{00C0}', "LATIN CAPITAL LETTER A WITH GRAVE"),
+    ('\u{00C4}', "LATIN CAPITAL LETTER A WITH DIAERESIS"),
+    ('\u{00C5}', "LATIN CAPITAL LETTER A WITH RING ABOVE"),
+    ('\u{00C6}', "LATIN CAPITAL LETTER AE"),
+    ('\u{00C7}', "LATIN CAPITAL LETTER C WITH CEDILLA"),
+    ('\u{00C9}', "LATIN CAPITAL LETTER E WITH ACUTE"),
+    ('\u{00D1}', "LATIN CAPITAL LETTER N WITH TILDE"),
+    ('\u{00D6}', "LATIN CAPITAL LETTER O WITH DIAERESIS"),
+    ('\u{00D8}', "LATIN CAPITAL LETTER O WITH STROKE"),
+    ('\u{00DC}', "LATIN CAPITAL LETTER U WITH DIAERESIS"),
+    // Box drawing
+    ('\u{2500}', "BOX DRAWINGS LIGHT HORIZONTAL"),
+    ('\u{2502}', "BOX DRAWINGS LIGHT VERTICAL"),
+    ('\u{250C}', "BOX DRAWINGS LIGHT DOWN AND RIGHT"),
+    ('\u{2510}', "BOX DRAWINGS LIGHT DOWN AND LEFT"),
+    ('\u{2514}', "BOX DRAWINGS LIGHT UP AND RIGHT"),
+    ('\u{2518}', "BOX DRAWINGS LIGHT UP AND LEFT"),
+    ('\u{251C}', "BOX DRAWINGS LIGHT VERTICAL AND RIGHT"),
+    ('\u{2524}', "BOX DRAWINGS LIGHT VERTICAL AND LEFT"),
+    ('\u{252C}', "BOX DRAWINGS LIGHT DOWN AND HORIZONTAL"),
+    ('\u{2534}', "BOX DRAWINGS LIGHT UP AND HORIZONTAL"),
+    ('\u{253C}', "BOX DRAWINGS LIGHT VERTICAL AND HORIZONTAL"),
+    ('\u{2588}', "FULL BLOCK"),
+    ('\u{2591}', "LIGHT SHADE"),
+    ('\u{2592}', "MEDIUM SHADE"),
+    ('\u{2593}', "DARK SHADE"),
+    ('\u{25A0}', "BLACK SQUARE"),
+    ('\u{25A1}', "WHITE SQUARE"),
+    ('\u{25B2}', "BLACK UP-POINTING TRIANGLE"),
+    ('\u{25B6}', "BLACK RIGHT-POINTING TRIANGLE"),
+    ('\u{25BC}', "BLACK DOWN-POINTING TRIANGLE"),
+    ('\u{25C0}', "BLACK LEFT-POINTING TRIANGLE"),
+    ('\u{25CB}', "WHITE CIRCLE"),
+    ('\u{25CF}', "BLACK CIRCLE"),
+    // Emoji
+    ('\u{1F600}', "GRINNING FACE"),
+    ('\u{1F602}', "FACE WITH TEARS OF JOY"),
+    ('\u{1F609}', "WINKING FACE"),
+    ('\u{1F60D}', "SMILING FACE WITH HEART-SHAPED EYES"),
+    ('\u{1F622}', "CRYING FACE"),
+    ('\u{1F44D}', "THUMBS UP SIGN"),
+    ('\u{1F44E}', "THUMBS DOWN SIGN"),
+    ('\u{1F44B}', "WAVING HAND SIGN"),
+    ('\u{1F64F}', "PERSON WITH FOLDED HANDS"),
+    ('\u{1F389}', "PARTY POPPER"),
+    ('\u{1F525}', "FIRE"),
+    ('\u{1F680}', "ROCKET"),
+    ('\u{1F41B}', "BUG"),
+    ('\u{1F4A1}', "ELECTRIC LIGHT BULB"),
+    ('\u{1F4DD}', "MEMO"),
+    ('\u{2705}', "WHITE HEAVY CHECK MARK"),
+    ('\u{274C}', "CROSS MARK"),
+    ('\u{2764}', "HEAVY BLACK HEART"),
+];
+
+/// Returns the characters whose name contains every word of `query`, ignoring case.
+/// A query starting with `U+` looks up that codepoint instead.
+pub fn search(query: &str) -> Vec<(char, &'static str)> {
+    if let Some(hex) = query
+        .strip_prefix("U+")
+        .or_else(|| query.strip_prefix("u+"))
+    {
+        return parse_codepoint(hex)
+            .map(|ch| {
+                let name = CHARACTERS
+                    .iter()
+                    .find(|(c, _)| *c == ch)
+                    .map_or("", |(_, name)| name);
+                vec![(ch, name)]
+            })
+            .unwrap_or_default();
+    }
+
+    let words: Vec<String> = query.split_whitespace().map(str::to_uppercase).collect();
+
+    CHARACTERS
+        .iter()
+        .filter(|(_, name)| words.iter().all(|word| name.contains(word.as_str())))
+        .copied()
+        .collect()
+}
+
+/// Parses a hexadecimal codepoint, e.g. `e9` for `é`
+pub fn parse_codepoint(hex: &str) -> Result<char, String> {
+    let hex = hex.trim();
+    let value =
+        u32::from_str_radix(hex, 16).map_err(|_| format!("Not a hexadecimal codepoint : {hex}"))?;
+    char::from_u32(value).ok_or_else(|| format!("Not a valid character : U+{value:04X}"))
+}
+
+/// Describes `ch` for the picker, e.g. `é  U+00E9  LATIN SMALL LETTER E WITH ACUTE`
+pub fn describe(ch: char, name: &str) -> String {
+    format!("{ch}  U+{:04X}  {name}", ch as u32)
+}
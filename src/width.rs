@@ -0,0 +1,12 @@
+use unicode_width::UnicodeWidthChar;
+
+/// Returns the number of columns `ch` takes on a terminal, tabs aside: two for the ideographs
+/// of East Asian scripts and for emoji, none for combining marks drawn over the character before,
+/// as the Unicode East Asian Width and general categories give them. Control characters take one.
+pub fn char_width(ch: char) -> usize {
+    // A regional indicator starts the pair of them drawn as a flag
+    if ('\u{1F1E6}'..='\u{1F1FF}').contains(&ch) {
+        return 2;
+    }
+    ch.width().unwrap_or(1)
+}
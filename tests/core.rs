@@ -0,0 +1,695 @@
+//! Drives the editor core through the library, without the terminal
+
+use std::{
+    env, fs,
+    io::{self, BufReader, Read},
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+use tte_core::{
+    diff::{diff_lines, map_line, unified, Hunk},
+    highlight::HighlightKind,
+    json::{JsonValue, MAX_DEPTH},
+    lsp::{read_message, LspClient, LspEvent, PositionEncoding},
+    markdown,
+    width::char_width,
+    Buffer, Position,
+};
+
+#[test]
+fn edits_update_the_lines() {
+    let mut buffer = Buffer::scratch("core", "one\ntwo\n", 0, 0, 80, 24);
+    buffer.move_cursor_down(1);
+    buffer.insert_ch('x');
+    buffer.insert_ch('\n');
+    buffer.recalculate_lines();
+
+    assert_eq!(buffer.text(), "one\nx\ntwo\n");
+    assert_eq!(buffer.lines.len(), 4);
+    assert_eq!(buffer.cursor_position(), Position::new(2, 0));
+    assert!(buffer.lines[3].is_empty());
+}
+
+#[test]
+fn undo_and_redo_restore_the_text() {
+    let mut buffer = Buffer::scratch("core", "abc\n", 0, 0, 80, 24);
+    buffer.move_cursor_right(3);
+    "def".chars().for_each(|ch| buffer.insert_ch(ch));
+    buffer.commit_history();
+
+    assert!(buffer.undo());
+    assert_eq!(buffer.text(), "abc\n");
+    assert!(buffer.redo());
+    assert_eq!(buffer.text(), "abcdef\n");
+    assert!(!buffer.redo());
+}
+
+/// Types `text` a key press at a time, each one ending its edit as the editor does
+fn type_text(buffer: &mut Buffer, text: &str) {
+    for ch in text.chars() {
+        buffer.insert_ch(ch);
+        buffer.commit_history();
+    }
+}
+
+#[test]
+fn typing_is_undone_a_word_at_a_time() {
+    let mut buffer = Buffer::scratch("core", "", 0, 0, 80, 24);
+    type_text(&mut buffer, "one two  three");
+
+    // A step goes up to the first whitespace after a word, more whitespace is a step of its own
+    for text in ["one two  ", "one two ", "one ", ""] {
+        assert!(buffer.undo());
+        assert_eq!(buffer.text(), text);
+    }
+    assert!(!buffer.undo());
+    assert_eq!(buffer.history.nodes.len(), 5);
+}
+
+#[test]
+fn editing_after_undo_starts_a_branch() {
+    let mut buffer = Buffer::scratch("core", "", 0, 0, 80, 24);
+    type_text(&mut buffer, "one two");
+    let two = buffer.history.current;
+    buffer.undo();
+    type_text(&mut buffer, "three");
+    let three = buffer.history.current;
+
+    // Both branches start from the state before them
+    let parent = buffer.history.nodes[three].parent;
+    assert_eq!(buffer.history.nodes[parent].children, [two, three]);
+    assert_eq!(buffer.text(), "one three");
+
+    // Jumping goes to the exact text and cursor of the other branch, and back
+    assert!(buffer.undo_jump(two));
+    assert_eq!(buffer.text(), "one two");
+    assert_eq!(buffer.cursor_pos, 7);
+    assert!(buffer.undo_jump(three));
+    assert_eq!(buffer.text(), "one three");
+    assert_eq!(buffer.cursor_pos, 9);
+    assert!(!buffer.undo_jump(three));
+    assert!(buffer.undo_jump(0));
+    assert_eq!(buffer.text(), "");
+    assert_eq!(buffer.cursor_pos, 0);
+}
+
+#[test]
+fn redo_follows_the_branch_visited_last() {
+    let mut buffer = Buffer::scratch("core", "", 0, 0, 80, 24);
+    type_text(&mut buffer, "one two");
+    let two = buffer.history.current;
+    buffer.undo();
+    type_text(&mut buffer, "three");
+
+    // The new branch is the last one visited
+    buffer.undo();
+    assert!(buffer.redo());
+    assert_eq!(buffer.text(), "one three");
+
+    // Until the other one is visited again
+    buffer.undo_jump(two);
+    buffer.undo();
+    assert_eq!(buffer.text(), "one ");
+    assert!(buffer.redo());
+    assert_eq!(buffer.text(), "one two");
+    assert_eq!(buffer.cursor_pos, 7);
+    assert!(!buffer.redo());
+}
+
+#[test]
+fn finds_the_occurrences_of_the_word_under_the_cursor() {
+    let buffer = Buffer::scratch("core", "foo bar foo_bar foo\n", 0, 0, 80, 24);
+
+    assert_eq!(buffer.word_at_cursor(), Some((0, 3)));
+    assert_eq!(buffer.word_occurrences(), vec![(0, 3), (16, 19)]);
+}
+
+#[test]
+fn overwriting_stops_at_the_end_of_the_line() {
+    let mut buffer = Buffer::scratch("core", "ab\ncd\n", 0, 0, 80, 24);
+    "xyz".chars().for_each(|ch| buffer.overwrite_ch(ch));
+    buffer.commit_history();
+
+    assert_eq!(buffer.text(), "xyz\ncd\n");
+    assert!(buffer.undo());
+    assert_eq!(buffer.text(), "ab\ncd\n");
+}
+
+#[test]
+fn bookmarks_follow_the_edits_around_them() {
+    let mut buffer = Buffer::scratch("core", "zero\none\ntwo\nthree\n", 0, 0, 80, 24);
+    buffer.move_cursor_to_position(Position::line_start(2));
+    buffer.toggle_bookmark();
+    let lines = |buffer: &mut Buffer| {
+        buffer.update_lines();
+        buffer.bookmark_lines()
+    };
+    assert_eq!(lines(&mut buffer), [2]);
+
+    // Lines added before the bookmark move it down
+    buffer.replace_range(0, 0, "new\n");
+    assert_eq!(lines(&mut buffer), [3]);
+    // A line broken at the bookmark keeps it on its text
+    buffer.update_lines();
+    buffer.move_cursor_to_position(Position::line_start(3));
+    type_text(&mut buffer, "x\n");
+    assert_eq!(lines(&mut buffer), [4]);
+    // Edits after it leave it
+    buffer.move_cursor_to_position(Position::new(4, 3));
+    type_text(&mut buffer, "!");
+    assert_eq!(lines(&mut buffer), [4]);
+    assert_eq!(buffer.text(), "new\nzero\none\nx\ntwo!\nthree\n");
+
+    // Lines removed before it move it up
+    buffer.replace_range(0, 4, "");
+    assert_eq!(lines(&mut buffer), [3]);
+    // Removing the text around it leaves it where the removed text started
+    buffer.replace_range(9, 13, "");
+    assert_eq!(buffer.text(), "zero\none\no!\nthree\n");
+    assert_eq!(lines(&mut buffer), [2]);
+
+    buffer.move_cursor_to_position(Position::new(2, 1));
+    buffer.toggle_bookmark();
+    assert_eq!(lines(&mut buffer), []);
+}
+
+#[test]
+fn named_marks_follow_the_edits_around_them() {
+    let mut buffer = Buffer::scratch("core", "hello world\n", 0, 0, 80, 24);
+    buffer.set_named_mark('a', 6);
+
+    // Text inserted before the mark, or at it, moves it along
+    buffer.replace_range(0, 0, ">> ");
+    assert_eq!(buffer.named_mark('a'), Some(9));
+    buffer.replace_range(9, 9, "big ");
+    assert_eq!(buffer.named_mark('a'), Some(13));
+    // Text inserted after it leaves it
+    buffer.replace_range(14, 14, "X");
+    assert_eq!(buffer.named_mark('a'), Some(13));
+    assert_eq!(buffer.text(), ">> hello big wXorld\n");
+
+    // Removing text before it moves it back
+    buffer.replace_range(0, 3, "");
+    assert_eq!(buffer.named_mark('a'), Some(10));
+    // Removing the text after it leaves it
+    buffer.replace_range(10, 12, "");
+    assert_eq!(buffer.named_mark('a'), Some(10));
+    // Removing the text around it leaves it where the removed text started
+    buffer.replace_range(6, 12, "");
+    assert_eq!(buffer.text(), "hello ld\n");
+    assert_eq!(buffer.named_mark('a'), Some(6));
+
+    // Typing and deleting before it
+    buffer.move_cursor_to_position(Position::new(0, 0));
+    type_text(&mut buffer, "ab");
+    assert_eq!(buffer.named_mark('a'), Some(8));
+    buffer.backspace();
+    assert_eq!(buffer.named_mark('a'), Some(7));
+    buffer.delete();
+    assert_eq!(buffer.named_mark('a'), Some(6));
+    assert_eq!(buffer.named_mark('b'), None);
+}
+
+#[test]
+fn wide_characters_and_combining_marks_have_their_width() {
+    let buffer = Buffer::scratch("core", "漢字e\u{301}😀!\n", 0, 0, 80, 24);
+
+    assert_eq!(buffer.display_column(2), 4);
+    assert_eq!(buffer.display_column(4), 5);
+    assert_eq!(buffer.display_column(5), 7);
+}
+
+#[test]
+fn the_cursor_moves_over_grapheme_clusters() {
+    // e with a combining acute accent, a family emoji, a flag
+    let text = "e\u{301}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{1F1EB}\u{1F1F7}!\n";
+    let mut buffer = Buffer::scratch("core", text, 0, 0, 80, 24);
+
+    let mut stops = vec![buffer.cursor_pos];
+    for _ in 0..4 {
+        buffer.move_cursor_right(1);
+        stops.push(buffer.cursor_pos);
+    }
+    assert_eq!(stops, [0, 2, 7, 9, 10]);
+    assert_eq!(buffer.display_column(10), 6);
+
+    buffer.move_cursor_left(2);
+    assert_eq!(buffer.cursor_pos, 7);
+    buffer.backspace();
+    buffer.commit_history();
+    assert_eq!(buffer.text(), "e\u{301}\u{1F1EB}\u{1F1F7}!\n");
+    buffer.move_cursor_left(1);
+    buffer.delete();
+    assert_eq!(buffer.text(), "\u{1F1EB}\u{1F1F7}!\n");
+
+    buffer.commit_history();
+    assert!(buffer.undo());
+    assert_eq!(buffer.text(), "e\u{301}\u{1F1EB}\u{1F1F7}!\n");
+}
+
+#[test]
+fn hangul_jamo_and_indic_conjuncts_are_single_clusters() {
+    // The leading, vowel and trailing jamo of 각, drawn on the two columns of the first
+    assert_eq!(
+        ['\u{1100}', '\u{1161}', '\u{11A8}', '\u{1160}', '\u{11FF}'].map(char_width),
+        [2, 0, 0, 0, 0]
+    );
+    let mut buffer = Buffer::scratch("core", "\u{1100}\u{1161}\u{11A8}\u{1100}!\n", 0, 0, 80, 24);
+    buffer.move_cursor_right(1);
+    assert_eq!(buffer.cursor_pos, 3);
+    buffer.move_cursor_right(1);
+    assert_eq!(buffer.cursor_pos, 4);
+    assert_eq!(buffer.display_column(4), 4);
+
+    // नमस्ते: न, म, then स्ते joined by the virama, and क्षि with its vowel sign
+    let text = "\u{928}\u{92E}\u{938}\u{94D}\u{924}\u{947} \u{915}\u{94D}\u{937}\u{93F}\n";
+    let mut buffer = Buffer::scratch("core", text, 0, 0, 80, 24);
+    let mut stops = vec![buffer.cursor_pos];
+    for _ in 0..5 {
+        buffer.move_cursor_right(1);
+        stops.push(buffer.cursor_pos);
+    }
+    assert_eq!(stops, [0, 1, 2, 6, 7, 11]);
+    assert_eq!(buffer.display_column(11), 5);
+
+    buffer.backspace();
+    assert_eq!(
+        buffer.text(),
+        "\u{928}\u{92E}\u{938}\u{94D}\u{924}\u{947} \n"
+    );
+}
+
+#[test]
+fn positions_convert_to_indexes_and_byte_offsets() {
+    let buffer = Buffer::scratch("core", "héllo\nwörld\n", 0, 0, 80, 24);
+
+    let position = Position::new(1, 2);
+    assert_eq!(buffer.position_to_index(position), 8);
+    assert_eq!(buffer.index_to_position(8), position);
+    assert!(Position::new(0, 9) < position);
+
+    // The accented letters take two bytes each
+    assert_eq!(buffer.byte_offset(position), 10);
+    assert_eq!(buffer.byte_offset_to_position(10), position);
+    // Inside a character is at its start
+    assert_eq!(buffer.byte_offset_to_position(9), Position::new(1, 1));
+    assert_eq!(buffer.byte_offset_to_position(2), Position::new(0, 1));
+
+    // Clamped to the line and to the buffer
+    assert_eq!(buffer.position_to_index(Position::new(0, 40)), 5);
+    assert_eq!(buffer.byte_offset(Position::new(9, 0)), 14);
+    assert_eq!(buffer.byte_offset_to_position(100), Position::new(2, 0));
+}
+
+#[test]
+fn selections_are_set_and_read_as_positions() {
+    let mut buffer = Buffer::scratch("core", "one\ntwo\nthree\n", 0, 0, 80, 24);
+    assert_eq!(buffer.selection_positions(), None);
+    assert_eq!(buffer.selected_lines(), (0, 3));
+
+    buffer.select_positions(Position::new(0, 1), Position::new(1, 2));
+    assert_eq!(buffer.selected_text().as_deref(), Some("ne\ntw"));
+    assert_eq!(buffer.cursor_position(), Position::new(1, 2));
+    assert_eq!(buffer.selected_lines(), (0, 1));
+
+    // Backwards, and ending at the start of a line which is then left out
+    buffer.select_positions(Position::line_start(2), Position::new(0, 40));
+    assert_eq!(
+        buffer.selection_positions(),
+        Some((Position::new(0, 3), Position::line_start(2)))
+    );
+    assert_eq!(buffer.selected_lines(), (0, 1));
+
+    // Home and end go by the columns of the cursor line
+    buffer.clear_selection();
+    buffer.move_cursor_to_position(Position::new(2, 3));
+    buffer.move_cursor_home();
+    assert_eq!(buffer.cursor_position(), Position::line_start(2));
+    buffer.move_cursor_end();
+    assert_eq!(buffer.cursor_position(), Position::new(2, 5));
+}
+
+#[test]
+fn a_final_line_break_is_followed_by_an_empty_line() {
+    for (text, count) in [("one\ntwo\n", 3), ("one\ntwo", 2), ("", 1)] {
+        let mut buffer = Buffer::scratch("core", text, 0, 0, 80, 24);
+        assert_eq!(buffer.line_count(), count, "{text:?}");
+
+        // Down goes as far as the last line and no further, where the cursor is drawn
+        buffer.move_cursor_down(10);
+        assert_eq!(buffer.current_line(), count - 1, "{text:?}");
+        assert_eq!(buffer.cursor_xy().1, count as isize - 1, "{text:?}");
+    }
+
+    // At the end of a last line without a line break
+    let mut buffer = Buffer::scratch("core", "one\ntwo", 0, 0, 80, 24);
+    buffer.move_cursor_to_position(Position::new(1, 3));
+    assert_eq!(buffer.cursor_xy(), (3, 1));
+}
+
+#[test]
+fn diffs_leave_out_the_common_start_and_end() {
+    let hunk = |old_start, old_end, new_start, new_end| Hunk {
+        old_start,
+        old_end,
+        new_start,
+        new_end,
+    };
+    assert_eq!(diff_lines(&["a", "b"], &["a", "b"]), []);
+    assert_eq!(
+        diff_lines(&["a", "b", "c", "d"], &["a", "x", "c", "d"]),
+        [hunk(1, 2, 1, 2)]
+    );
+    assert_eq!(
+        diff_lines(&["a", "b"], &["a", "x", "b"]),
+        [hunk(1, 1, 1, 2)]
+    );
+    // A repeated line is only counted once, by the start
+    assert_eq!(diff_lines(&["a", "a"], &["a"]), [hunk(1, 2, 1, 1)]);
+    assert_eq!(
+        diff_lines(&["a", "b", "c", "d", "e"], &["a", "x", "c", "y", "e"]),
+        [hunk(1, 2, 1, 2), hunk(3, 4, 3, 4)]
+    );
+
+    // Too many lines changed to compare them all are one change
+    let old: Vec<usize> = (0..2100).collect();
+    let new: Vec<usize> = (0..2100).map(|i| i + (i % 2) * 10_000).collect();
+    assert_eq!(diff_lines(&old, &new), [hunk(1, 2100, 1, 2100)]);
+}
+
+#[test]
+fn lines_are_mapped_through_the_changes() {
+    // Lines 1 and 2 removed
+    let hunks = diff_lines(&["a", "b", "c", "d"], &["a", "d"]);
+    let mapped: Vec<usize> = (0..4).map(|line| map_line(&hunks, line)).collect();
+    assert_eq!(mapped, [0, 1, 1, 1]);
+
+    // Line 1 replaced by three lines, those after it move down
+    let hunks = diff_lines(&["a", "b", "c"], &["a", "x", "y", "z", "c"]);
+    let mapped: Vec<usize> = (0..3).map(|line| map_line(&hunks, line)).collect();
+    assert_eq!(mapped, [0, 1, 4]);
+}
+
+#[test]
+fn unified_diffs_group_the_changes_sharing_context() {
+    let old: Vec<String> = (1..=10).map(|n| n.to_string()).collect();
+    let mut new = old.clone();
+    new[1] = "two".to_string();
+    new[8] = "nine".to_string();
+    let old: Vec<&str> = old.iter().map(String::as_str).collect();
+    let new: Vec<&str> = new.iter().map(String::as_str).collect();
+
+    assert_eq!(
+        unified(&old, &new, "a", "b", 1),
+        "--- a\n+++ b\n@@ -1,3 +1,3 @@\n 1\n-2\n+two\n 3\n@@ -8,3 +8,3 @@\n 8\n-9\n+nine\n 10\n"
+    );
+    // With more context, the changes are shown together
+    let text = unified(&old, &new, "a", "b", 3);
+    assert_eq!(text.lines().filter(|l| l.starts_with("@@")).count(), 1);
+    assert!(text.contains("@@ -1,10 +1,10 @@\n"));
+
+    // The header counts the lines of each side
+    assert_eq!(
+        unified(&["a", "b"], &["a", "b", "c"], "a", "b", 3),
+        "--- a\n+++ b\n@@ -1,2 +1,3 @@\n a\n b\n+c\n"
+    );
+}
+
+#[test]
+fn markdown_is_highlighted_by_block_and_inline_spans() {
+    let text = "# Title\n#tag\n- item with `code` and **bold**\n1. see [docs](docs/a.md#x)\n\
+                > quote\n```\n# not a heading\n```\nsnake_case _em_\n";
+    let data: Vec<char> = text.chars().collect();
+    let highlights: Vec<(String, HighlightKind)> = markdown::highlight(&data)
+        .into_iter()
+        .map(|h| (data[h.start..h.end].iter().collect(), h.kind))
+        .collect();
+    let expected = [
+        ("# Title", HighlightKind::Heading),
+        ("- ", HighlightKind::ListBullet),
+        ("`code`", HighlightKind::Code),
+        ("**bold**", HighlightKind::Emphasis),
+        ("1. ", HighlightKind::ListBullet),
+        ("[docs](docs/a.md#x)", HighlightKind::Link),
+        ("> quote", HighlightKind::Quote),
+        ("```", HighlightKind::Code),
+        ("# not a heading", HighlightKind::Code),
+        ("```", HighlightKind::Code),
+        ("_em_", HighlightKind::Emphasis),
+    ];
+    let expected: Vec<(String, HighlightKind)> = expected
+        .into_iter()
+        .map(|(text, kind)| (text.to_string(), kind))
+        .collect();
+    assert_eq!(highlights, expected);
+
+    // Links give their target, anything else the path under the cursor
+    let data: Vec<char> = "see [the docs](docs/a.md#x) or src/main.rs.\n"
+        .chars()
+        .collect();
+    let target = |idx| markdown::link_target_at(&data, idx);
+    assert_eq!(target(6).as_deref(), Some("docs/a.md#x"));
+    assert_eq!(target(20).as_deref(), Some("docs/a.md#x"));
+    assert_eq!(target(1).as_deref(), Some("see"));
+    assert_eq!(target(35).as_deref(), Some("src/main.rs"));
+    assert_eq!(target(27), None);
+}
+
+#[test]
+fn json_numbers_follow_the_grammar_and_nesting_is_limited() {
+    for (text, number) in [
+        ("0", 0.0),
+        ("-0.5", -0.5),
+        ("10e2", 1000.0),
+        ("1.5E-1", 0.15),
+    ] {
+        assert_eq!(JsonValue::parse(text).unwrap(), JsonValue::Number(number));
+    }
+    for (text, error) in [
+        ("01", "Leading zeros are not allowed at line 1, column 2"),
+        ("[1.]", "Expected a digit at line 1, column 4"),
+        ("-", "Expected a digit at line 1, column 2"),
+        ("1e+", "Expected a digit at line 1, column 4"),
+        ("-.5", "Expected a digit at line 1, column 2"),
+        ("+1", "Unexpected character at line 1, column 1"),
+        (
+            "1.5.2",
+            "Unexpected trailing characters at line 1, column 4",
+        ),
+    ] {
+        assert_eq!(JsonValue::parse(text).unwrap_err().to_string(), error);
+    }
+
+    let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    assert!(JsonValue::parse(&nested(MAX_DEPTH)).is_ok());
+    let error = JsonValue::parse(&nested(MAX_DEPTH + 1)).unwrap_err();
+    assert_eq!(error.message, "Nested too deeply");
+    assert_eq!(error.offset, MAX_DEPTH);
+    // Far too deep to parse by recursion
+    assert!(JsonValue::parse(&"{\"a\":[".repeat(1_000_000)).is_err());
+}
+
+#[test]
+fn language_server_positions_convert_between_encodings() {
+    // `é` takes two bytes and one UTF-16 unit, `😀` four bytes and two units
+    let line = "é😀x";
+    for (encoding, units) in [
+        (PositionEncoding::Utf8, [0, 2, 6, 7]),
+        (PositionEncoding::Utf16, [0, 1, 3, 4]),
+        (PositionEncoding::Utf32, [0, 1, 2, 3]),
+    ] {
+        for (column, units) in units.into_iter().enumerate() {
+            assert_eq!(encoding.to_units(line, column), units, "{encoding:?}");
+            assert_eq!(encoding.to_column(line, units), column, "{encoding:?}");
+        }
+        assert_eq!(PositionEncoding::from_name(encoding.name()), Some(encoding));
+    }
+
+    // Inside a character is at its start, past the end of the line a unit is a column
+    assert_eq!(PositionEncoding::Utf16.to_column(line, 2), 1);
+    assert_eq!(PositionEncoding::Utf8.to_column(line, 4), 1);
+    assert_eq!(PositionEncoding::Utf16.to_column(line, 6), 5);
+    assert_eq!(PositionEncoding::Utf16.to_units(line, 5), 6);
+}
+
+/// Points the cache directory, where the language servers log, to one of the tests. The tests
+/// starting servers run side by side, they share it.
+#[cfg(unix)]
+fn cache_dir() -> PathBuf {
+    let cache = env::temp_dir().join(format!("tte-test-{}-cache", std::process::id()));
+    env::set_var("XDG_CACHE_HOME", &cache);
+    cache
+}
+
+#[test]
+#[cfg(unix)]
+fn language_server_errors_go_to_a_log_in_the_cache_directory() {
+    let cache = cache_dir();
+
+    // Not a server, it only complains and exits. Dropping the client would kill it.
+    let _server = LspClient::start("ls /no/such/file", &env::temp_dir());
+    let log = cache.join("tte/lsp/ls.log");
+    let start = Instant::now();
+    while fs::read_to_string(&log).unwrap_or_default().is_empty() {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "nothing in {}",
+            log.display()
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(fs::read_to_string(&log).unwrap().contains("/no/such/file"));
+
+    fs::remove_dir_all(cache).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn language_server_text_cannot_reach_the_terminal() {
+    let dir = env::temp_dir().join(format!("tte-test-{}-lsp-text", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    cache_dir();
+    let body = r#"{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{
+        "uri":"file:///tmp/main.rs","diagnostics":[{"range":{
+        "start":{"line":0,"character":0},"end":{"line":0,"character":1}},
+        "message":"unused \u001b[31mvariable\u001b]0;title\u0007\u001b[2J"}]}}"#;
+    fs::write(
+        dir.join("messages"),
+        format!("Content-Length: {}\r\n\r\n{body}", body.len()),
+    )
+    .unwrap();
+    // The server sends the messages and reads whatever it is sent
+    let script = dir.join("server.sh");
+    fs::write(&script, "cat messages\ncat > /dev/null\n").unwrap();
+
+    let mut server = LspClient::start(&format!("sh {}", script.display()), &dir).unwrap();
+    let start = Instant::now();
+    let diagnostics = loop {
+        let events = server.poll();
+        if let Some(LspEvent::Diagnostics { diagnostics, .. }) = events.into_iter().next() {
+            break diagnostics;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5), "no diagnostics");
+        thread::sleep(Duration::from_millis(10));
+    };
+    assert_eq!(diagnostics[0].message, "unused variable");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// Hands out a few bytes at a time, as a pipe the server writes to in pieces
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.0.len()).min(3);
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn language_server_messages_are_read_by_their_content_length() {
+    // The length is in bytes, `é` takes two
+    let body = r#"{"id":1,"result":"é"}"#;
+    let stream = format!(
+        "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n\
+         {body}content-length:  {}\r\n\r\n{body}\
+         Content-Length: 5\r\n\r\nnope!\
+         Content-Length: 2\r\n\r\n{{}}",
+        body.len(),
+        body.len()
+    );
+    let mut reader = BufReader::with_capacity(4, Trickle(stream.as_bytes()));
+    for _ in 0..2 {
+        let message = read_message(&mut reader).unwrap();
+        assert_eq!(message.get("result").and_then(|r| r.as_str()), Some("é"));
+    }
+
+    // A body that is not JSON is skipped
+    assert_eq!(read_message(&mut reader), Some(JsonValue::object(vec![])));
+    assert_eq!(read_message(&mut reader), None);
+
+    // So is a message without a length, a stream closed inside a body ends the messages
+    let mut reader = BufReader::new(Trickle(
+        b"Content-Type: text\r\n\r\nContent-Length: 2\r\n\r\n[]Content-Length: 10\r\n\r\n{}",
+    ));
+    assert_eq!(read_message(&mut reader), Some(JsonValue::Array(vec![])));
+    assert_eq!(read_message(&mut reader), None);
+}
+
+/// Characters typed by the random edits: line breaks, tabs, wide characters and joiners.
+/// The edits are the same as those of the fuzz target in `fuzz/`.
+const EDIT_CHARS: [char; 8] = ['a', ' ', '\n', '\t', 'é', '日', '\u{301}', '\u{200D}'];
+
+/// Applies the edit or move numbered `op`, with `arg` picking its details
+fn apply_edit(buffer: &mut Buffer, op: u8, arg: u8) {
+    let ch = EDIT_CHARS[arg as usize % EDIT_CHARS.len()];
+    let count = arg as usize % 4;
+    match op % 20 {
+        0 => buffer.insert_ch(ch),
+        1 => buffer.insert_str(&format!("{ch}x\n{ch}")),
+        2 => buffer.backspace(),
+        3 => buffer.delete(),
+        4 => buffer.move_cursor_right(count),
+        5 => buffer.move_cursor_left(count),
+        6 => buffer.move_cursor_up(count),
+        7 => buffer.move_cursor_down(count),
+        8 if arg < 128 => buffer.move_cursor_word_left(count),
+        8 => buffer.move_cursor_word_right(count),
+        9 if arg < 128 => buffer.select(),
+        9 => buffer.clear_selection(),
+        10 => {
+            buffer.delete_selection();
+        }
+        11 => {
+            buffer.commit_history();
+            buffer.undo();
+        }
+        12 => {
+            buffer.redo();
+        }
+        13 => buffer.resize(arg as usize % 30, arg as usize / 30),
+        14 => buffer.overwrite_ch(ch),
+        15 => buffer.virtual_space = !buffer.virtual_space,
+        16 => {
+            buffer.toggle_fold();
+        }
+        17 => buffer.toggle_bookmark(),
+        18 => buffer.move_cursor_to_position(Position::new(arg as usize % 8, arg as usize / 8)),
+        _ => buffer.scroll_view(arg as i8 as isize % 5),
+    }
+}
+
+#[test]
+fn random_edits_keep_the_buffer_consistent() {
+    // Xorshift, seeded so that failures can be replayed
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    for round in 0..200 {
+        let mut buffer = Buffer::scratch("core", "fn main() {\n\tlet x = 1;\n}\n", 0, 0, 20, 5);
+        let mut edits = vec![];
+        for _ in 0..200 {
+            let (op, arg) = (next() as u8, (next() >> 8) as u8);
+            edits.push((op % 20, arg));
+            apply_edit(&mut buffer, op, arg);
+            buffer.recalculate_lines();
+            buffer.scroll();
+            if let Err(e) = buffer.check_invariants() {
+                panic!(
+                    "{e} in round {round} ({}x{}) after {edits:?}",
+                    buffer.width, buffer.height
+                );
+            }
+        }
+    }
+}